@@ -0,0 +1,36 @@
+-- Add migration script here
+CREATE TABLE Discounts (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    kind TEXT NOT NULL CHECK (kind IN ('percent', 'fixed')),
+    value INTEGER NOT NULL,
+    scope TEXT NOT NULL CHECK (scope IN ('line', 'bill')),
+    stackable INTEGER DEFAULT (FALSE) NOT NULL,
+    requires_manager INTEGER DEFAULT (FALSE) NOT NULL
+);
+
+CREATE TABLE AppliedDiscounts (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    table_session_id INTEGER NOT NULL,
+    discount_id INTEGER,
+    order_id INTEGER,
+    item_id INTEGER,
+    name TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    value INTEGER NOT NULL,
+    scope TEXT NOT NULL,
+    stackable INTEGER NOT NULL,
+    reason TEXT NOT NULL,
+    applied_by INTEGER NOT NULL,
+    timestamp TEXT DEFAULT (DateTime('now')) NOT NULL,
+    FOREIGN KEY(table_session_id) REFERENCES TableSessions(id),
+    FOREIGN KEY(discount_id) REFERENCES Discounts(id),
+    FOREIGN KEY(order_id) REFERENCES Orders(id),
+    FOREIGN KEY(item_id) REFERENCES Items(id),
+    FOREIGN KEY(applied_by) REFERENCES Staff(id)
+);
+
+INSERT INTO Discounts ('name', 'kind', 'value', 'scope', 'stackable', 'requires_manager') VALUES
+	('Staff meal', 'percent', 10, 'bill', FALSE, FALSE),
+	('Birthday dessert', 'percent', 100, 'line', FALSE, FALSE),
+	('500 yen coupon', 'fixed', 500, 'bill', TRUE, FALSE);
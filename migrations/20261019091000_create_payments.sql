@@ -0,0 +1,22 @@
+-- Add migration script here
+ALTER TABLE Orders ADD COLUMN staff_id INTEGER REFERENCES Staff(id);
+
+CREATE TABLE Payments (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    table_session_id INTEGER NOT NULL,
+    staff_id INTEGER NOT NULL,
+    amount INTEGER NOT NULL,
+    tip INTEGER DEFAULT (0) NOT NULL,
+    method TEXT NOT NULL,
+    timestamp TEXT DEFAULT (DateTime('now')) NOT NULL,
+    FOREIGN KEY(table_session_id) REFERENCES TableSessions(id),
+    FOREIGN KEY(staff_id) REFERENCES Staff(id)
+);
+
+CREATE TABLE Shifts (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    staff_id INTEGER NOT NULL,
+    clock_in TEXT DEFAULT (DateTime('now')) NOT NULL,
+    clock_out TEXT,
+    FOREIGN KEY(staff_id) REFERENCES Staff(id)
+);
@@ -0,0 +1,13 @@
+-- Add migration script here
+ALTER TABLE OrderItems ADD COLUMN course TEXT CHECK (course IN ('starter', 'main', 'dessert'));
+ALTER TABLE OrderItems ADD COLUMN fired_at TEXT;
+
+UPDATE OrderItems SET fired_at = (SELECT timestamp FROM Orders WHERE Orders.id = OrderItems.order_id);
+
+CREATE TABLE SessionCourses (
+    table_session_id INTEGER NOT NULL,
+    course TEXT NOT NULL CHECK (course IN ('starter', 'main', 'dessert')),
+    fired_at TEXT,
+    PRIMARY KEY(table_session_id, course),
+    FOREIGN KEY(table_session_id) REFERENCES TableSessions(id)
+);
@@ -0,0 +1,34 @@
+-- Add migration script here
+CREATE TABLE Webhooks (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    url TEXT NOT NULL,
+    secret TEXT NOT NULL,
+    events TEXT NOT NULL,
+    active BOOLEAN DEFAULT TRUE NOT NULL,
+    created TEXT DEFAULT (DateTime('now')) NOT NULL
+);
+
+CREATE TABLE WebhookDeliveries (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    webhook_id INTEGER NOT NULL,
+    event_id INTEGER NOT NULL,
+    event_type TEXT NOT NULL,
+    attempt INTEGER NOT NULL,
+    status_code INTEGER,
+    error TEXT,
+    succeeded BOOLEAN NOT NULL,
+    timestamp TEXT DEFAULT (DateTime('now')) NOT NULL,
+    FOREIGN KEY(webhook_id) REFERENCES Webhooks(id)
+);
+
+CREATE TABLE WebhookDeadLetters (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    webhook_id INTEGER NOT NULL,
+    event_id INTEGER NOT NULL,
+    event_type TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    attempts INTEGER NOT NULL,
+    last_error TEXT NOT NULL,
+    timestamp TEXT DEFAULT (DateTime('now')) NOT NULL,
+    FOREIGN KEY(webhook_id) REFERENCES Webhooks(id)
+);
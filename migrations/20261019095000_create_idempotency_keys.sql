@@ -0,0 +1,9 @@
+-- Add migration script here
+CREATE TABLE IdempotencyKeys (
+    key TEXT NOT NULL,
+    scope TEXT NOT NULL,
+    request_hash TEXT NOT NULL,
+    response TEXT,
+    created TEXT DEFAULT (DateTime('now')) NOT NULL,
+    PRIMARY KEY(key, scope)
+);
@@ -0,0 +1,4 @@
+-- Add migration script here
+-- Tables that already have several active sessions are merged first, see
+-- db::repair_duplicate_sessions.
+CREATE UNIQUE INDEX OneActiveSessionPerTable ON TableSessions(table_nr) WHERE active = TRUE;
@@ -0,0 +1,15 @@
+-- Add migration script here
+-- Tables asking for staff: calling a server, the bill or water. Signals
+-- raised by guests have no raised_by.
+CREATE TABLE Signals (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    table_session_id INTEGER NOT NULL,
+    kind TEXT NOT NULL CHECK (kind IN ('call_server', 'request_bill', 'need_water')),
+    raised_at TEXT NOT NULL,
+    raised_by INTEGER,
+    acknowledged_at TEXT,
+    acknowledged_by INTEGER,
+    FOREIGN KEY(table_session_id) REFERENCES TableSessions(id),
+    FOREIGN KEY(raised_by) REFERENCES Staff(id),
+    FOREIGN KEY(acknowledged_by) REFERENCES Staff(id)
+);
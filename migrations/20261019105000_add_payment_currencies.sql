@@ -0,0 +1,4 @@
+-- Add migration script here
+-- The currency of the amount and tip; payments from before there were
+-- currencies have none and count in the bill's.
+ALTER TABLE Payments ADD COLUMN currency TEXT;
@@ -0,0 +1,3 @@
+-- Add migration script here
+-- Webhooks registered before locations existed belong to the first one.
+ALTER TABLE Webhooks ADD COLUMN location_id INTEGER DEFAULT 1 NOT NULL;
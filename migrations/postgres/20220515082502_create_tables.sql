@@ -0,0 +1,34 @@
+-- Add migration script here
+-- Postgres counterpart of the SQLite migration with the same version. Ids are
+-- 64 bit like SQLite's, and timestamps text in the same format.
+CREATE TABLE TableSessions (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    table_nr BIGINT NOT NULL,
+    customers BIGINT NOT NULL,
+    session_start TEXT DEFAULT (to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS')) NOT NULL,
+    session_end TEXT,
+    active BOOLEAN DEFAULT TRUE NOT NULL
+);
+
+CREATE TABLE Items (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    preparation_time BIGINT NOT NULL,
+    price_yen BIGINT NOT NULL,
+    name TEXT NOT NULL
+);
+
+CREATE TABLE Orders (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    table_session_id BIGINT NOT NULL,
+    timestamp TEXT DEFAULT (to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS')) NOT NULL,
+    FOREIGN KEY(table_session_id) REFERENCES TableSessions(id)
+);
+
+CREATE TABLE OrderItems (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    item_id BIGINT NOT NULL,
+    order_id BIGINT NOT NULL,
+    amount BIGINT NOT NULL,
+    FOREIGN KEY(item_id) REFERENCES Items(id),
+    FOREIGN KEY(order_id) REFERENCES Orders(id)
+);
@@ -0,0 +1,12 @@
+-- Add migration script here
+INSERT INTO Items (preparation_time, name, price_yen) VALUES
+	(328, 'Bresaola and sausage spaghetti', 2972),
+	(1374, 'Spinach and squash risotto', 576),
+	(749, 'Tea and banana bread', 1119),
+	(1023, 'Bean and ginger cake', 2509),
+	(1266, 'Crayfish and gruyere toastie', 774),
+	(344, 'Strawberry and cider jam', 2315),
+	(659, 'Sausage and pigeon wontons', 494),
+	(939, 'Honey and prune cupcakes', 1762),
+	(1496, 'Fish and cod crumble', 2893),
+	(434, 'Blueberry and banana cupcakes', 2462);
\ No newline at end of file
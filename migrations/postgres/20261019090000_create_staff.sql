@@ -0,0 +1,11 @@
+-- Add migration script here
+CREATE TABLE Staff (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    name TEXT NOT NULL,
+    role TEXT NOT NULL CHECK (role IN ('server', 'manager')),
+    token TEXT NOT NULL UNIQUE
+);
+
+INSERT INTO Staff (name, role, token) VALUES
+	('Floor manager', 'manager', 'dev-manager-token'),
+	('Floor server', 'server', 'dev-server-token');
@@ -0,0 +1,36 @@
+-- Add migration script here
+CREATE TABLE Discounts (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    name TEXT NOT NULL,
+    kind TEXT NOT NULL CHECK (kind IN ('percent', 'fixed')),
+    value BIGINT NOT NULL,
+    scope TEXT NOT NULL CHECK (scope IN ('line', 'bill')),
+    stackable BOOLEAN DEFAULT FALSE NOT NULL,
+    requires_manager BOOLEAN DEFAULT FALSE NOT NULL
+);
+
+CREATE TABLE AppliedDiscounts (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    table_session_id BIGINT NOT NULL,
+    discount_id BIGINT,
+    order_id BIGINT,
+    item_id BIGINT,
+    name TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    value BIGINT NOT NULL,
+    scope TEXT NOT NULL,
+    stackable BOOLEAN NOT NULL,
+    reason TEXT NOT NULL,
+    applied_by BIGINT NOT NULL,
+    timestamp TEXT DEFAULT (to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS')) NOT NULL,
+    FOREIGN KEY(table_session_id) REFERENCES TableSessions(id),
+    FOREIGN KEY(discount_id) REFERENCES Discounts(id),
+    FOREIGN KEY(order_id) REFERENCES Orders(id),
+    FOREIGN KEY(item_id) REFERENCES Items(id),
+    FOREIGN KEY(applied_by) REFERENCES Staff(id)
+);
+
+INSERT INTO Discounts (name, kind, value, scope, stackable, requires_manager) VALUES
+	('Staff meal', 'percent', 10, 'bill', FALSE, FALSE),
+	('Birthday dessert', 'percent', 100, 'line', FALSE, FALSE),
+	('500 yen coupon', 'fixed', 500, 'bill', TRUE, FALSE);
@@ -0,0 +1,22 @@
+-- Add migration script here
+ALTER TABLE Orders ADD COLUMN staff_id BIGINT REFERENCES Staff(id);
+
+CREATE TABLE Payments (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    table_session_id BIGINT NOT NULL,
+    staff_id BIGINT NOT NULL,
+    amount BIGINT NOT NULL,
+    tip BIGINT DEFAULT 0 NOT NULL,
+    method TEXT NOT NULL,
+    timestamp TEXT DEFAULT (to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS')) NOT NULL,
+    FOREIGN KEY(table_session_id) REFERENCES TableSessions(id),
+    FOREIGN KEY(staff_id) REFERENCES Staff(id)
+);
+
+CREATE TABLE Shifts (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    staff_id BIGINT NOT NULL,
+    clock_in TEXT DEFAULT (to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS')) NOT NULL,
+    clock_out TEXT,
+    FOREIGN KEY(staff_id) REFERENCES Staff(id)
+);
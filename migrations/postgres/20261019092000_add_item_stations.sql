@@ -0,0 +1,14 @@
+-- Add migration script here
+ALTER TABLE Items ADD COLUMN station TEXT DEFAULT 'grill' NOT NULL CHECK (station IN ('grill', 'fryer', 'bar', 'dessert'));
+
+UPDATE Items SET station = 'dessert' WHERE name IN (
+	'Tea and banana bread',
+	'Bean and ginger cake',
+	'Strawberry and cider jam',
+	'Honey and prune cupcakes',
+	'Blueberry and banana cupcakes'
+);
+UPDATE Items SET station = 'fryer' WHERE name IN (
+	'Sausage and pigeon wontons',
+	'Crayfish and gruyere toastie'
+);
@@ -0,0 +1,34 @@
+-- Add migration script here
+CREATE TABLE Webhooks (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    url TEXT NOT NULL,
+    secret TEXT NOT NULL,
+    events TEXT NOT NULL,
+    active BOOLEAN DEFAULT TRUE NOT NULL,
+    created TEXT DEFAULT (to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS')) NOT NULL
+);
+
+CREATE TABLE WebhookDeliveries (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    webhook_id BIGINT NOT NULL,
+    event_id BIGINT NOT NULL,
+    event_type TEXT NOT NULL,
+    attempt BIGINT NOT NULL,
+    status_code BIGINT,
+    error TEXT,
+    succeeded BOOLEAN NOT NULL,
+    timestamp TEXT DEFAULT (to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS')) NOT NULL,
+    FOREIGN KEY(webhook_id) REFERENCES Webhooks(id)
+);
+
+CREATE TABLE WebhookDeadLetters (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    webhook_id BIGINT NOT NULL,
+    event_id BIGINT NOT NULL,
+    event_type TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    attempts BIGINT NOT NULL,
+    last_error TEXT NOT NULL,
+    timestamp TEXT DEFAULT (to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS')) NOT NULL,
+    FOREIGN KEY(webhook_id) REFERENCES Webhooks(id)
+);
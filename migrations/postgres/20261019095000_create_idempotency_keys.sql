@@ -0,0 +1,9 @@
+-- Add migration script here
+CREATE TABLE IdempotencyKeys (
+    key TEXT NOT NULL,
+    scope TEXT NOT NULL,
+    request_hash TEXT NOT NULL,
+    response TEXT,
+    created TEXT DEFAULT (to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS')) NOT NULL,
+    PRIMARY KEY(key, scope)
+);
@@ -0,0 +1,3 @@
+-- Add migration script here
+ALTER TABLE Orders ADD COLUMN version BIGINT DEFAULT 1 NOT NULL;
+ALTER TABLE TableSessions ADD COLUMN version BIGINT DEFAULT 1 NOT NULL;
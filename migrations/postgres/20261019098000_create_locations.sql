@@ -0,0 +1,21 @@
+-- Add migration script here
+-- Everything so far belongs to the first location. Staff without a location
+-- work at all of them.
+CREATE TABLE Locations (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    slug TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL
+);
+
+INSERT INTO Locations (slug, name) VALUES ('main', 'Main');
+
+ALTER TABLE Items ADD COLUMN location_id BIGINT DEFAULT 1 NOT NULL;
+ALTER TABLE TableSessions ADD COLUMN location_id BIGINT DEFAULT 1 NOT NULL;
+ALTER TABLE Staff ADD COLUMN location_id BIGINT;
+UPDATE Staff SET location_id = 1;
+
+INSERT INTO Staff (name, role, token, location_id) VALUES
+	('Owner', 'manager', 'dev-owner-token', NULL);
+
+DROP INDEX OneActiveSessionPerTable;
+CREATE UNIQUE INDEX OneActiveSessionPerTable ON TableSessions(location_id, table_nr) WHERE active = TRUE;
@@ -0,0 +1,5 @@
+-- Add migration script here
+CREATE TABLE Settings (
+    id BIGINT PRIMARY KEY CHECK (id = 1),
+    settings TEXT NOT NULL
+);
@@ -0,0 +1,3 @@
+-- Add migration script here
+ALTER TABLE Items RENAME COLUMN price_yen TO price;
+ALTER TABLE Items ADD COLUMN currency TEXT DEFAULT 'JPY' NOT NULL;
@@ -0,0 +1,41 @@
+-- Add migration script here
+-- Names of menu items in the languages guests read, by locale (ja, zh, ko).
+CREATE TABLE ItemTranslations (
+    item_id BIGINT NOT NULL,
+    locale TEXT NOT NULL,
+    name TEXT NOT NULL,
+    PRIMARY KEY(item_id, locale),
+    FOREIGN KEY(item_id) REFERENCES Items(id)
+);
+
+INSERT INTO ItemTranslations (item_id, locale, name) VALUES
+	(1, 'ja', 'ブレザオラとソーセージのスパゲッティ'),
+	(1, 'zh', '风干牛肉香肠意大利面'),
+	(1, 'ko', '브레사올라 소시지 스파게티'),
+	(2, 'ja', 'ほうれん草とかぼちゃのリゾット'),
+	(2, 'zh', '菠菜南瓜烩饭'),
+	(2, 'ko', '시금치 단호박 리소토'),
+	(3, 'ja', '紅茶とバナナブレッド'),
+	(3, 'zh', '红茶配香蕉面包'),
+	(3, 'ko', '홍차와 바나나 브레드'),
+	(4, 'ja', '豆と生姜のケーキ'),
+	(4, 'zh', '豆子生姜蛋糕'),
+	(4, 'ko', '콩 생강 케이크'),
+	(5, 'ja', 'ザリガニとグリュイエールのトースティー'),
+	(5, 'zh', '小龙虾格鲁耶尔奶酪烤三明治'),
+	(5, 'ko', '가재 그뤼에르 토스티'),
+	(6, 'ja', 'いちごとシードルのジャム'),
+	(6, 'zh', '草莓苹果酒果酱'),
+	(6, 'ko', '딸기 사과주 잼'),
+	(7, 'ja', 'ソーセージと鳩のワンタン'),
+	(7, 'zh', '香肠鸽肉馄饨'),
+	(7, 'ko', '소시지 비둘기 완탕'),
+	(8, 'ja', 'はちみつとプルーンのカップケーキ'),
+	(8, 'zh', '蜂蜜西梅纸杯蛋糕'),
+	(8, 'ko', '꿀 자두 컵케이크'),
+	(9, 'ja', '魚とタラのクランブル'),
+	(9, 'zh', '鱼肉鳕鱼酥皮烤'),
+	(9, 'ko', '생선 대구 크럼블'),
+	(10, 'ja', 'ブルーベリーとバナナのカップケーキ'),
+	(10, 'zh', '蓝莓香蕉纸杯蛋糕'),
+	(10, 'ko', '블루베리 바나나 컵케이크');
@@ -0,0 +1,13 @@
+-- Add migration script here
+-- Orders guests placed with a table token. Pending ones wait for staff to
+-- approve them before they become an order of the session.
+CREATE TABLE GuestOrders (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    table_session_id BIGINT NOT NULL,
+    items TEXT NOT NULL,
+    status TEXT NOT NULL CHECK (status IN ('pending', 'placed', 'rejected')),
+    order_id BIGINT,
+    timestamp TEXT NOT NULL,
+    FOREIGN KEY(table_session_id) REFERENCES TableSessions(id),
+    FOREIGN KEY(order_id) REFERENCES Orders(id)
+);
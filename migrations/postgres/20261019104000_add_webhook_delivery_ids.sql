@@ -0,0 +1,5 @@
+-- Add migration script here
+-- Sent as X-Webhook-Id, the same for every attempt of a payload so receivers
+-- can tell retries apart from new events.
+ALTER TABLE WebhookDeliveries ADD COLUMN delivery_id TEXT DEFAULT '' NOT NULL;
+ALTER TABLE WebhookDeadLetters ADD COLUMN delivery_id TEXT DEFAULT '' NOT NULL;
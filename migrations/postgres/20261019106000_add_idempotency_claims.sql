@@ -0,0 +1,4 @@
+-- Add migration script here
+-- When the request still running with the key took it; claims from before
+-- there were claim times have none and can be taken over.
+ALTER TABLE IdempotencyKeys ADD COLUMN claimed TEXT;
@@ -0,0 +1,8 @@
+-- Add migration script here
+-- A table has at most one open signal of each kind. Open duplicates raised
+-- before this count as acknowledged along with the first one.
+UPDATE Signals
+SET acknowledged_at = raised_at
+WHERE acknowledged_at IS NULL
+AND id NOT IN (SELECT MIN(id) FROM Signals WHERE acknowledged_at IS NULL GROUP BY table_session_id, kind);
+CREATE UNIQUE INDEX Signals_open ON Signals (table_session_id, kind) WHERE acknowledged_at IS NULL;
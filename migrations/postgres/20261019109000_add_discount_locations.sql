@@ -0,0 +1,3 @@
+-- Add migration script here
+-- Discounts from before locations belong to the first one.
+ALTER TABLE Discounts ADD COLUMN location_id BIGINT DEFAULT 1 NOT NULL;
@@ -0,0 +1,76 @@
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket_db_pools::Connection;
+use log::error;
+use crate::db;
+use crate::models;
+
+/// Resolves the staff member making the request from an
+/// `Authorization: Bearer <token>` header. Staff of one location are
+/// forbidden at the others.
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for models::Staff {
+	type Error = ();
+
+	async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+		let token = match req.headers().get_one("Authorization").and_then(|value| value.strip_prefix("Bearer ")) {
+			Some(token) => token,
+			None => return Outcome::Failure((Status::Unauthorized, ()))
+		};
+		let mut db = match req.guard::<Connection<db::Db>>().await.succeeded() {
+			Some(db) => db,
+			None => return Outcome::Failure((Status::ServiceUnavailable, ()))
+		};
+		let staff = match db::get_staff_by_token(&mut db, token).await {
+			Ok(Some(staff)) => staff,
+			Ok(None) => return Outcome::Failure((Status::Unauthorized, ())),
+			Err(e) => {
+				error!("Unable to resolve staff token: {}", e);
+				return Outcome::Failure((Status::InternalServerError, ()));
+			}
+		};
+		let location = rocket::outcome::try_outcome!(req.guard::<models::Location>().await);
+		if staff.location_id.is_none_or(|location_id| location_id == location.id) {
+			Outcome::Success(staff)
+		}
+		else {
+			Outcome::Failure((Status::Forbidden, ()))
+		}
+	}
+}
+
+/// A staff member with at least the manager role.
+pub struct Manager(pub models::Staff);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Manager {
+	type Error = ();
+
+	async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+		let staff = rocket::outcome::try_outcome!(req.guard::<models::Staff>().await);
+		if staff.role >= models::Role::Manager {
+			Outcome::Success(Manager(staff))
+		}
+		else {
+			Outcome::Failure((Status::Forbidden, ()))
+		}
+	}
+}
+
+/// A manager of every location rather than one of them.
+pub struct Owner(pub models::Staff);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Owner {
+	type Error = ();
+
+	async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+		let Manager(staff) = rocket::outcome::try_outcome!(req.guard::<Manager>().await);
+		if staff.location_id.is_none() {
+			Outcome::Success(Owner(staff))
+		}
+		else {
+			Outcome::Failure((Status::Forbidden, ()))
+		}
+	}
+}
@@ -0,0 +1,358 @@
+use std::collections::HashMap;
+use std::fmt;
+use rocket::serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use crate::i18n::{message, Message};
+use crate::models::{self, DiscountKind, DiscountScope, Role};
+use crate::money::{Currency, Money, MoneyError};
+
+/// Billing settings, part of the restaurant settings, e.g.
+/// `tax_rate_percent = 10`. Bills are in `currency`, JPY by default. Parties
+/// of at least `service_charge_min_customers` are charged
+/// `service_charge_percent`; a percentage of 0 disables the service charge.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[serde(crate="rocket::serde")]
+pub struct BillingConfig {
+	/// An ISO 4217 currency code like `JPY`.
+	#[serde(default)]
+	#[schema(value_type = String)]
+	pub currency: Currency,
+	#[serde(default = "default_tax_rate")]
+	pub tax_rate_percent: u32,
+	#[serde(default = "default_service_charge")]
+	pub service_charge_percent: u32,
+	#[serde(default = "default_service_charge_min_customers")]
+	pub service_charge_min_customers: u8,
+	#[serde(default = "default_tip_weight_server")]
+	pub tip_weight_server: u32,
+	#[serde(default = "default_tip_weight_manager")]
+	pub tip_weight_manager: u32
+}
+
+fn default_tax_rate() -> u32 {
+	10
+}
+
+fn default_service_charge() -> u32 {
+	10
+}
+
+fn default_service_charge_min_customers() -> u8 {
+	8
+}
+
+fn default_tip_weight_server() -> u32 {
+	100
+}
+
+fn default_tip_weight_manager() -> u32 {
+	50
+}
+
+impl Default for BillingConfig {
+	fn default() -> Self {
+		BillingConfig {
+			currency: Currency::default(),
+			tax_rate_percent: default_tax_rate(),
+			service_charge_percent: default_service_charge(),
+			service_charge_min_customers: default_service_charge_min_customers(),
+			tip_weight_server: default_tip_weight_server(),
+			tip_weight_manager: default_tip_weight_manager()
+		}
+	}
+}
+
+impl BillingConfig {
+	/// Relative share of the tip pool per hour worked for a role.
+	pub fn tip_weight(&self, role: Role) -> u32 {
+		match role {
+			Role::Server => self.tip_weight_server,
+			Role::Manager => self.tip_weight_manager,
+		}
+	}
+}
+
+fn discount_amount(kind: DiscountKind, value: u32, base: Money) -> Money {
+	let amount = match kind {
+		DiscountKind::Percent => base.percent(value.min(100)),
+		DiscountKind::Fixed => Money::new(value.into(), base.currency),
+	};
+	Money::new(amount.amount.min(base.amount), base.currency)
+}
+
+/// Whether `discount` targets the same line (or the whole bill) as `other`.
+fn same_target(discount: &models::AppliedDiscount, other: &models::AppliedDiscount) -> bool {
+	discount.scope == other.scope
+		&& discount.order_id == other.order_id
+		&& discount.item_id == other.item_id
+}
+
+/// A discount can only be added to a target that already carries discounts
+/// when both the new one and every existing one are stackable.
+pub fn can_stack(existing: &[models::AppliedDiscount], discount: &models::AppliedDiscount) -> bool {
+	existing.iter()
+		.filter(|other| same_target(discount, other))
+		.all(|other| other.stackable && discount.stackable)
+}
+
+/// Why a bill can't be calculated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BillError {
+	/// Items or payments in another currency than the bill's, or amounts
+	/// adding up beyond what an amount can hold.
+	Money(MoneyError),
+	/// An amount too large for the plain numbers of the bill.
+	TooLarge(Money)
+}
+
+impl From<MoneyError> for BillError {
+	fn from(error: MoneyError) -> Self {
+		BillError::Money(error)
+	}
+}
+
+impl BillError {
+	pub fn message(&self) -> Message {
+		match self {
+			BillError::Money(error) => error.message(),
+			BillError::TooLarge(amount) => message!("The amount of {} {} is too large for a bill", amount.formatted(), amount.currency)
+		}
+	}
+}
+
+impl fmt::Display for BillError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		self.message().fmt(f)
+	}
+}
+
+/// The plain number of an amount, for the fields from before there were
+/// currencies.
+fn plain(amount: Money) -> Result<u32, BillError> {
+	amount.to_u32().ok_or(BillError::TooLarge(amount))
+}
+
+/// Builds the bill for a session. Line discounts reduce their line, bill
+/// discounts are applied to what remains, the service charge is added for
+/// large parties and tax is charged on the discounted amount plus service
+/// charge. Percentages are rounded by the currency's rule, and items and
+/// payments in another currency than the bill's are refused.
+pub fn calculate_bill(session: &models::TableSession, mut lines: Vec<models::BillLine>, discounts: Vec<models::AppliedDiscount>, payments: &[models::Payment], config: &BillingConfig) -> Result<models::Bill, BillError> {
+	let zero = Money::new(0, config.currency);
+	for line in &mut lines {
+		let line_total = zero.checked_add(line.price.times(line.amount.into())?)?;
+		let mut discount = zero;
+		for applied in discounts.iter().filter(|d| d.scope == DiscountScope::Line) {
+			if applied.order_id == Some(line.order_id) && applied.item_id == Some(line.item_id) {
+				discount = discount.checked_add(discount_amount(applied.kind, applied.value, line_total.checked_sub(discount)?))?;
+			}
+		}
+		line.amounts = models::LineAmounts { line_total, discount };
+		line.line_total = plain(line_total)?;
+		line.discount = plain(discount)?;
+	}
+	let subtotal = lines.iter().try_fold(zero, |sum, line| sum.checked_add(line.amounts.line_total))?;
+	let line_discounts = lines.iter().try_fold(zero, |sum, line| sum.checked_add(line.amounts.discount))?;
+
+	let discounted = subtotal.checked_sub(line_discounts)?;
+	let mut remaining = discounted;
+	for applied in discounts.iter().filter(|d| d.scope == DiscountScope::Bill) {
+		remaining = remaining.checked_sub(discount_amount(applied.kind, applied.value, remaining))?;
+	}
+	let bill_discounts = discounted.checked_sub(remaining)?;
+
+	let service_charge_percent = if session.customers >= config.service_charge_min_customers {
+		config.service_charge_percent
+	}
+	else {
+		0
+	};
+	let service_charge = remaining.percent(service_charge_percent);
+	let tax = remaining.checked_add(service_charge)?.percent(config.tax_rate_percent);
+	let total = remaining.checked_add(service_charge)?.checked_add(tax)?;
+	let in_currency = |payment: &models::Payment, amount: u32| zero.checked_add(Money::new(amount.into(), payment.currency.unwrap_or(config.currency)));
+	let paid = payments.iter().try_fold(zero, |sum, payment| sum.checked_add(in_currency(payment, payment.amount)?))?;
+	let tips = payments.iter().try_fold(zero, |sum, payment| sum.checked_add(in_currency(payment, payment.tip)?))?;
+	let balance_due = Money::new(total.checked_sub(paid)?.amount.max(0), config.currency);
+
+	let amounts = models::BillAmounts { subtotal, line_discounts, bill_discounts, service_charge, tax, total, paid, tips, balance_due };
+	Ok(models::Bill {
+		table_session_id: session.id,
+		currency: config.currency,
+		lines,
+		discounts,
+		subtotal: plain(subtotal)?,
+		line_discounts: plain(line_discounts)?,
+		bill_discounts: plain(bill_discounts)?,
+		service_charge_percent,
+		service_charge: plain(service_charge)?,
+		tax_rate_percent: config.tax_rate_percent,
+		tax: plain(tax)?,
+		total: plain(total)?,
+		paid: plain(paid)?,
+		tips: plain(tips)?,
+		balance_due: plain(balance_due)?,
+		amounts
+	})
+}
+
+/// Splits each session's tips between the staff who served it, in proportion
+/// to their weight (role weight times hours worked). Sessions whose servers
+/// have no recorded weight are split evenly. Whole yen left over after
+/// rounding go to the server with the largest weight.
+pub fn pool_tips(sessions: &[(u32, Vec<i64>)], weights: &HashMap<i64, f64>) -> HashMap<i64, u32> {
+	let mut shares: HashMap<i64, u32> = HashMap::new();
+	for (tips, servers) in sessions {
+		if servers.is_empty() || *tips == 0 {
+			continue;
+		}
+		let mut session_weights: Vec<(i64, f64)> = servers.iter()
+			.map(|id| (*id, weights.get(id).copied().unwrap_or(0.0)))
+			.collect();
+		if session_weights.iter().all(|(_, weight)| *weight <= 0.0) {
+			session_weights.iter_mut().for_each(|(_, weight)| *weight = 1.0);
+		}
+		let total_weight: f64 = session_weights.iter().map(|(_, weight)| weight).sum();
+
+		let mut distributed = 0;
+		for (id, weight) in &session_weights {
+			let share = (*tips as f64 * weight / total_weight).floor() as u32;
+			*shares.entry(*id).or_default() += share;
+			distributed += share;
+		}
+		if let Some((id, _)) = session_weights.iter().max_by(|a, b| a.1.total_cmp(&b.1).then(b.0.cmp(&a.0))) {
+			*shares.entry(*id).or_default() += tips - distributed;
+		}
+	}
+	shares
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::HashMap;
+	use super::models::{AppliedDiscount, BillLine, DiscountKind, DiscountScope, Payment, TableSession};
+	use super::{BillError, BillingConfig};
+	use crate::money::{Currency, CurrencyMismatch, Money, MoneyError};
+
+	fn line(order_id: i64, item_id: i64, amount: u8, price_yen: i64) -> BillLine {
+		BillLine { order_id, item_id, amount, price: Money::new(price_yen, Currency::JPY), ..Default::default() }
+	}
+
+	fn discount(kind: DiscountKind, value: u32, scope: DiscountScope, target: Option<(i64, i64)>, stackable: bool) -> AppliedDiscount {
+		AppliedDiscount {
+			id: 0,
+			table_session_id: 1,
+			discount_id: None,
+			order_id: target.map(|t| t.0),
+			item_id: target.map(|t| t.1),
+			name: String::from("Test"),
+			kind,
+			value,
+			scope,
+			stackable,
+			reason: String::from("Test"),
+			applied_by: 1,
+			timestamp: String::new()
+		}
+	}
+
+	fn session(customers: u8) -> TableSession {
+		TableSession { id: 1, customers, ..Default::default() }
+	}
+
+	#[test]
+	fn tax_is_charged_after_discounts()
+	{
+		let lines = vec![line(1, 2, 4, 576), line(1, 5, 2, 774)];
+		let discounts = vec![
+			discount(DiscountKind::Percent, 100, DiscountScope::Line, Some((1, 5)), false),
+			discount(DiscountKind::Percent, 10, DiscountScope::Bill, None, false),
+		];
+		let bill = super::calculate_bill(&session(2), lines, discounts, &[], &BillingConfig::default()).unwrap();
+		assert_eq!(bill.subtotal, 3852);
+		assert_eq!(bill.line_discounts, 1548);
+		assert_eq!(bill.bill_discounts, 230);
+		assert_eq!(bill.tax, 207);
+		assert_eq!(bill.total, 2281);
+	}
+
+	#[test]
+	fn fixed_discounts_never_go_below_zero()
+	{
+		let lines = vec![line(1, 3, 1, 300)];
+		let discounts = vec![
+			discount(DiscountKind::Fixed, 500, DiscountScope::Bill, None, true),
+			discount(DiscountKind::Fixed, 500, DiscountScope::Bill, None, true),
+		];
+		let bill = super::calculate_bill(&session(2), lines, discounts, &[], &BillingConfig::default()).unwrap();
+		assert_eq!(bill.bill_discounts, 300);
+		assert_eq!(bill.total, 0);
+	}
+
+	#[test]
+	fn non_stackable_discounts_are_exclusive()
+	{
+		let staff_meal = discount(DiscountKind::Percent, 10, DiscountScope::Bill, None, false);
+		let coupon = discount(DiscountKind::Fixed, 500, DiscountScope::Bill, None, true);
+		let comp = discount(DiscountKind::Percent, 100, DiscountScope::Line, Some((1, 5)), false);
+		assert!(!super::can_stack(std::slice::from_ref(&staff_meal), &coupon));
+		assert!(!super::can_stack(std::slice::from_ref(&coupon), &staff_meal));
+		assert!(super::can_stack(std::slice::from_ref(&coupon), &coupon));
+		assert!(super::can_stack(&[staff_meal], &comp));
+	}
+
+	#[test]
+	fn service_charge_applies_to_large_parties()
+	{
+		let lines = vec![line(1, 2, 10, 1000)];
+		let payments = vec![Payment { amount: 5000, tip: 800, ..Default::default() }];
+		let small = super::calculate_bill(&session(7), lines.clone(), vec![], &payments, &BillingConfig::default()).unwrap();
+		assert_eq!(small.service_charge, 0);
+		assert_eq!(small.total, 11000);
+
+		let large = super::calculate_bill(&session(8), lines, vec![], &payments, &BillingConfig::default()).unwrap();
+		assert_eq!(large.service_charge, 1000);
+		assert_eq!(large.tax, 1100);
+		assert_eq!(large.total, 12100);
+		assert_eq!(large.paid, 5000);
+		assert_eq!(large.tips, 800);
+		assert_eq!(large.balance_due, 7100);
+	}
+
+	#[test]
+	fn bills_are_in_one_currency()
+	{
+		let eur = Currency::parse("EUR").unwrap();
+		let config = BillingConfig { currency: eur, ..Default::default() };
+		let lines = vec![BillLine { price: Money::new(1999, eur), amount: 1, ..Default::default() }];
+		let bill = super::calculate_bill(&session(2), lines.clone(), vec![], &[], &config).unwrap();
+		assert_eq!((bill.currency, bill.tax, bill.total), (eur, 200, 2199));
+		assert_eq!(bill.amounts.total, Money::new(2199, eur));
+
+		let mixed = vec![lines[0].clone(), line(1, 2, 1, 500)];
+		let refused = super::calculate_bill(&session(2), mixed, vec![], &[], &config);
+		assert_eq!(refused.unwrap_err(), BillError::Money(MoneyError::Currency(CurrencyMismatch(eur, Currency::JPY))));
+		let in_yen = Payment { amount: 500, currency: Some(Currency::JPY), ..Default::default() };
+		let refused = super::calculate_bill(&session(2), lines.clone(), vec![], &[in_yen], &config);
+		assert_eq!(refused.unwrap_err(), BillError::Money(MoneyError::Currency(CurrencyMismatch(eur, Currency::JPY))));
+
+		let huge = vec![BillLine { price: Money::new(i64::from(u32::MAX), eur), amount: 2, ..Default::default() }];
+		let refused = super::calculate_bill(&session(2), huge, vec![], &[], &config);
+		assert_eq!(refused.unwrap_err(), BillError::TooLarge(Money::new(2 * i64::from(u32::MAX), eur)));
+		let overflowing = vec![BillLine { price: Money::new(i64::MAX / 2, eur), amount: 3, ..Default::default() }];
+		let refused = super::calculate_bill(&session(2), overflowing, vec![], &[], &config);
+		assert_eq!(refused.unwrap_err(), BillError::Money(MoneyError::Overflow(eur)));
+	}
+
+	#[test]
+	fn tips_are_pooled_by_weight()
+	{
+		let weights = HashMap::from([(1, 100.0 * 6.0), (2, 100.0 * 3.0), (3, 50.0 * 2.0)]);
+		let shares = super::pool_tips(&[(1000, vec![1, 2, 3]), (301, vec![4, 5])], &weights);
+		assert_eq!(shares[&1], 600);
+		assert_eq!(shares[&2], 300);
+		assert_eq!(shares[&3], 100);
+		assert_eq!(shares[&4] + shares[&5], 301);
+		assert_eq!(shares[&4], 151);
+	}
+}
@@ -1,316 +1,2035 @@
+use std::collections::HashMap;
 use rocket::{Rocket, Build};
 use rocket::fairing::AdHoc;
-use rocket_db_pools::{sqlx, Database, Connection};
-use sqlx::prelude::Row;
+use rocket_db_pools::{sqlx, Database};
+use sqlx::prelude::{FromRow, Row};
 use crate::models;
+use crate::money::{Currency, Money};
+use crate::pagination::{PageRequest, INTEGER_COLUMNS};
+
+// The database is chosen when building: SQLite, or Postgres with the
+// `postgres` feature. Queries are written for both, with `$N` parameters,
+// `RETURNING` for the ids of new rows and timestamps passed in (see `now`).
+
+#[cfg(not(feature = "postgres"))]
+pub type Backend = sqlx::Sqlite;
+#[cfg(feature = "postgres")]
+pub type Backend = sqlx::Postgres;
+
+pub type DbPool = sqlx::Pool<Backend>;
+pub type DbConnection = <Backend as sqlx::Database>::Connection;
+pub type DbRow = <Backend as sqlx::Database>::Row;
+type Arguments<'q> = <Backend as sqlx::database::HasArguments<'q>>::Arguments;
 
 #[derive(Database)]
 #[database("order_db")]
-pub struct Db(sqlx::SqlitePool);
+pub struct Db(DbPool);
 
 type Result<T, E = sqlx::Error> = std::result::Result<Option<T>, E>;
 
-pub async fn get_items(db: &mut Connection<Db>) -> Result<Vec<models::Item>> {
-	let items = sqlx::query(r#"
-		SELECT id, preparation_time, price_yen, name 
+/// `sqlx::query_as!`, checked when building against the SQLite schema
+/// described in `sqlx-data.json`. Postgres builds run the same query
+/// unchecked, without the type overrides in its column aliases.
+macro_rules! checked_query_as {
+	($out:ty, $sql:literal $(, $arg:expr)* $(,)?) => {{
+		#[cfg(not(feature = "postgres"))]
+		let query = sqlx::query_as!($out, $sql $(, $arg)*);
+		#[cfg(feature = "postgres")]
+		let query = {
+			static SQL: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+			sqlx::query_as::<_, $out>(SQL.get_or_init(|| without_overrides($sql)))$(.bind($arg))*
+		};
+		query
+	}};
+}
+
+/// Takes the type overrides of `sqlx::query_as!` out of column aliases:
+/// `AS "id!"` and `AS "table_nr: u8"` become `AS id` and `AS table_nr`.
+#[cfg(feature = "postgres")]
+fn without_overrides(sql: &str) -> String {
+	sql.split('"')
+		.enumerate()
+		.map(|(i, part)| if i % 2 == 1 { part.split([':', '!', '?']).next().unwrap_or(part).trim() } else { part })
+		.collect()
+}
+
+/// The current time as the database stores it, `YYYY-MM-DD HH:MM:SS` in UTC.
+fn now() -> String {
+	timestamp(chrono::Utc::now())
+}
+
+pub fn timestamp(time: chrono::DateTime<chrono::Utc>) -> String {
+	time.format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+fn parse_timestamp(timestamp: &str) -> std::result::Result<chrono::NaiveDateTime, sqlx::Error> {
+	chrono::NaiveDateTime::parse_from_str(timestamp, "%Y-%m-%d %H:%M:%S").map_err(|e| sqlx::Error::Decode(Box::new(e)))
+}
+
+/// Reads an integer column into a model's narrower or unsigned field. Both
+/// databases hand out 64 bit integers, and Postgres has no unsigned ones.
+pub fn get_int<T, I>(row: &DbRow, index: I) -> std::result::Result<T, sqlx::Error>
+where T: TryFrom<i64>, T::Error: std::error::Error + Send + Sync + 'static, I: sqlx::ColumnIndex<DbRow> + std::fmt::Debug {
+	let column = format!("{:?}", index);
+	let value: i64 = row.try_get(index)?;
+	T::try_from(value).map_err(|e| sqlx::Error::ColumnDecode { index: column, source: Box::new(e) })
+}
+
+pub fn get_optional_int<T, I>(row: &DbRow, index: I) -> std::result::Result<Option<T>, sqlx::Error>
+where T: TryFrom<i64>, T::Error: std::error::Error + Send + Sync + 'static, I: sqlx::ColumnIndex<DbRow> + std::fmt::Debug {
+	let column = format!("{:?}", index);
+	let value: Option<i64> = row.try_get(index)?;
+	value.map(T::try_from).transpose().map_err(|e| sqlx::Error::ColumnDecode { index: column, source: Box::new(e) })
+}
+
+/// Reads an amount and the currency it is in from two columns.
+fn get_money(row: &DbRow, amount: &str, currency: &str) -> std::result::Result<Money, sqlx::Error> {
+	let code: String = row.try_get(currency)?;
+	let currency = Currency::parse(&code).ok_or_else(|| sqlx::Error::Decode(format!("Invalid currency '{}'", code).into()))?;
+	Ok(Money::new(row.try_get(amount)?, currency))
+}
+
+/// Binds the cursor of a page to the parameters of its `after_clause`. The
+/// sort values of integer columns are bound as numbers, which Postgres does
+/// not compare with text; `PageRequest::new` made sure they are.
+fn bind_after<'q, O>(query: sqlx::query::QueryAs<'q, Backend, O, Arguments<'q>>, page: &'q PageRequest) -> sqlx::query::QueryAs<'q, Backend, O, Arguments<'q>> {
+	match &page.after {
+		Some((value, id)) if INTEGER_COLUMNS.contains(&page.column) => query.bind(value.parse::<i64>().ok()).bind(*id),
+		Some((value, id)) => query.bind(value.as_str()).bind(*id),
+		None => query,
+	}
+}
+
+pub async fn retrieve_location(db: &mut DbConnection, slug: &str) -> Result<models::Location> {
+	let query_result = sqlx::query("SELECT id, slug, name FROM Locations WHERE slug = $1")
+		.bind(slug)
+		.try_map(|record: DbRow| {
+			Ok(models::Location {
+				id: record.try_get("id")?,
+				slug: record.try_get("slug")?,
+				name: record.try_get("name")?
+			})
+		})
+		.fetch_optional(&mut *db)
+		.await?;
+	Ok(query_result)
+}
+
+/// Adds a location with a copy of the menu of `menu_from`, which it can then
+/// change and price on its own.
+pub async fn create_location(db: &mut DbConnection, location: &models::Location, menu_from: i64) -> Result<models::Location> {
+	let mut tx = sqlx::Connection::begin(&mut *db).await?;
+	let location_id: i64 = sqlx::query("INSERT INTO Locations (slug, name) VALUES ($1, $2) RETURNING id")
+		.bind(&location.slug)
+		.bind(&location.name)
+		.fetch_one(&mut tx)
+		.await?
+		.try_get("id")?;
+	sqlx::query(r#"
+		INSERT INTO Items (preparation_time, price, currency, name, station, location_id)
+		SELECT preparation_time, price, currency, name, station, $1
 		FROM Items
+		WHERE location_id = $2
+		ORDER BY id
 		"#)
-		.map(|record: sqlx::sqlite::SqliteRow| {
-			models::Item {
-				id: record.get_unchecked(0),
-				preparation_time: record.get_unchecked(1),
-				price_yen: record.get_unchecked(2),
-				name: record.get_unchecked(3)
-			}
-		})
-		.fetch_all(&mut **db)
+		.bind(location_id)
+		.bind(menu_from)
+		.execute(&mut tx)
 		.await?;
-	Ok(Some(items))
+	// The copies were added in the order of the originals, so the nth item of
+	// each menu is the same dish.
+	sqlx::query(r#"
+		INSERT INTO ItemTranslations (item_id, locale, name)
+		SELECT copy.id, ItemTranslations.locale, ItemTranslations.name
+		FROM ItemTranslations
+		JOIN (SELECT id, ROW_NUMBER() OVER (ORDER BY id) AS nr FROM Items WHERE location_id = $2) AS original ON original.id = ItemTranslations.item_id
+		JOIN (SELECT id, ROW_NUMBER() OVER (ORDER BY id) AS nr FROM Items WHERE location_id = $1) AS copy ON copy.nr = original.nr
+		"#)
+		.bind(location_id)
+		.bind(menu_from)
+		.execute(&mut tx)
+		.await?;
+	sqlx::query(r#"
+		INSERT INTO Discounts (name, kind, value, scope, stackable, requires_manager, location_id)
+		SELECT name, kind, value, scope, stackable, requires_manager, $1
+		FROM Discounts
+		WHERE location_id = $2
+		ORDER BY id
+		"#)
+		.bind(location_id)
+		.bind(menu_from)
+		.execute(&mut tx)
+		.await?;
+	tx.commit().await?;
+	retrieve_location(db, &location.slug).await
 }
 
-pub async fn get_item(db: &mut Connection<Db>, item_id: i64) -> Result<models::Item> {
-	let query_result = sqlx::query(r#"
-		SELECT id, preparation_time, price_yen, name 
-		FROM Items 
-		WHERE id = ?
+/// Sessions started, payments and tips taken at every location in
+/// `[start, end)`.
+pub async fn retrieve_location_reports(db: &mut DbConnection, start: &str, end: &str) -> Result<Vec<models::LocationReport>> {
+	let reports = sqlx::query(r#"
+		SELECT Locations.id AS location_id, Locations.slug, Locations.name,
+		(SELECT COUNT(*) FROM TableSessions WHERE location_id = Locations.id AND session_start >= $1 AND session_start < $2) AS sessions,
+		(SELECT CAST(COALESCE(SUM(amount), 0) AS BIGINT) FROM Payments, TableSessions
+			WHERE TableSessions.id = Payments.table_session_id AND location_id = Locations.id AND timestamp >= $1 AND timestamp < $2) AS revenue,
+		(SELECT CAST(COALESCE(SUM(tip), 0) AS BIGINT) FROM Payments, TableSessions
+			WHERE TableSessions.id = Payments.table_session_id AND location_id = Locations.id AND timestamp >= $1 AND timestamp < $2) AS tips
+		FROM Locations
+		ORDER BY Locations.id
 		"#)
-		.bind(item_id)
-		.map(|record: sqlx::sqlite::SqliteRow| {
-			models::Item {
-				id: record.get_unchecked(0),
-				preparation_time: record.get_unchecked(1),
-				price_yen: record.get_unchecked(2),
-				name: record.get_unchecked(3)
-			}
+		.bind(start)
+		.bind(end)
+		.try_map(|record: DbRow| {
+			Ok(models::LocationReport {
+				location_id: record.try_get("location_id")?,
+				slug: record.try_get("slug")?,
+				name: record.try_get("name")?,
+				sessions: get_int(&record, "sessions")?,
+				revenue: get_int(&record, "revenue")?,
+				tips: get_int(&record, "tips")?
+			})
 		})
-		.fetch_optional(&mut **db)
+		.fetch_all(&mut *db)
+		.await?;
+	Ok(Some(reports))
+}
+
+/// A page of a location's menu, optionally within a price range.
+pub async fn get_items(db: &mut DbConnection, location_id: i64, page: &PageRequest, min_price: Option<u32>, max_price: Option<u32>) -> Result<Vec<models::Item>> {
+	let sql = format!(r#"
+		SELECT id, preparation_time, price, currency, name, station, location_id
+		FROM Items
+		WHERE location_id = $1
+		AND ($2 IS NULL OR price >= $2)
+		AND ($3 IS NULL OR price <= $3)
+		{}
+		ORDER BY {}
+		LIMIT $4
+		"#, page.after_clause("id", 5), page.order_by("id"));
+	let query = sqlx::query_as::<_, models::Item>(&sql)
+		.bind(location_id)
+		.bind(min_price.map(i64::from))
+		.bind(max_price.map(i64::from))
+		.bind(i64::from(page.fetch_limit()));
+	let items = bind_after(query, page)
+		.fetch_all(&mut *db)
+		.await?;
+	Ok(Some(items))
+}
+
+/// An item on a location's menu.
+pub async fn get_item(db: &mut DbConnection, location_id: i64, item_id: i64) -> Result<models::Item> {
+	let query_result = checked_query_as!(models::ItemRow, r#"
+		SELECT id, preparation_time AS "preparation_time: u32", price, currency, name, station AS "station: models::Station", location_id
+		FROM Items
+		WHERE id = $1
+		AND location_id = $2
+		"#, item_id, location_id)
+		.fetch_optional(&mut *db)
 		.await?;
 	match query_result {
-		Some(item) => Ok(Some(item)),
+		Some(item) => Ok(Some(item.try_into()?)),
 		None => Ok(None),
 	}
 }
 
-pub async fn create_order(db: &mut Connection<Db>, active_session_id: i64, order: &models::Order) -> Result<models::Order> {
-	let result = sqlx::query("INSERT INTO Orders ('table_session_id') VALUES (?)")
+pub async fn create_item(db: &mut DbConnection, location_id: i64, item: &models::Item) -> Result<models::Item> {
+	let item_id: i64 = sqlx::query("INSERT INTO Items (preparation_time, price, currency, name, station, location_id) VALUES ($1, $2, $3, $4, $5, $6) RETURNING id")
+		.bind(i64::from(item.preparation_time))
+		.bind(item.price.amount)
+		.bind(item.price.currency.code())
+		.bind(&item.name)
+		.bind(item.station)
+		.bind(location_id)
+		.fetch_one(&mut *db)
+		.await?
+		.try_get("id")?;
+	get_item(db, location_id, item_id).await
+}
+
+/// Changes an item on a location's menu, leaving the other locations' alone.
+pub async fn update_item(db: &mut DbConnection, location_id: i64, item_id: i64, item: &models::Item) -> Result<models::Item> {
+	sqlx::query(r#"
+		UPDATE Items
+		SET preparation_time = $1, price = $2, currency = $3, name = $4, station = $5
+		WHERE id = $6
+		AND location_id = $7
+		"#)
+		.bind(i64::from(item.preparation_time))
+		.bind(item.price.amount)
+		.bind(item.price.currency.code())
+		.bind(&item.name)
+		.bind(item.station)
+		.bind(item_id)
+		.bind(location_id)
+		.execute(&mut *db)
+		.await?;
+	get_item(db, location_id, item_id).await
+}
+
+pub async fn retrieve_item_translations(db: &mut DbConnection, item_id: i64) -> Result<Vec<models::ItemTranslation>> {
+	let translations = sqlx::query("SELECT locale, name FROM ItemTranslations WHERE item_id = $1 ORDER BY locale")
+		.bind(item_id)
+		.try_map(|record: DbRow| Ok(models::ItemTranslation {
+			locale: record.try_get("locale")?,
+			name: record.try_get("name")?
+		}))
+		.fetch_all(&mut *db)
+		.await?;
+	Ok(Some(translations))
+}
+
+pub async fn save_item_translation(db: &mut DbConnection, item_id: i64, translation: &models::ItemTranslation) -> std::result::Result<(), sqlx::Error> {
+	sqlx::query("INSERT INTO ItemTranslations (item_id, locale, name) VALUES ($1, $2, $3) ON CONFLICT (item_id, locale) DO UPDATE SET name = excluded.name")
+		.bind(item_id)
+		.bind(&translation.locale)
+		.bind(&translation.name)
+		.execute(&mut *db)
+		.await?;
+	Ok(())
+}
+
+/// The names of items in the first of `locales` they are translated into;
+/// items translated into none of them are left out.
+pub async fn retrieve_item_names(db: &mut DbConnection, item_ids: &[i64], locales: &[&str]) -> Result<HashMap<i64, String>> {
+	if item_ids.is_empty() {
+		return Ok(Some(HashMap::new()));
+	}
+	let sql = format!("SELECT item_id, locale, name FROM ItemTranslations WHERE item_id IN ({})", placeholders(item_ids.len()));
+	let mut query = sqlx::query(&sql);
+	for item_id in item_ids {
+		query = query.bind(item_id);
+	}
+	let mut names: HashMap<i64, (usize, String)> = HashMap::new();
+	for record in query.fetch_all(&mut *db).await? {
+		let (item_id, locale, name): (i64, String, String) = (record.try_get("item_id")?, record.try_get("locale")?, record.try_get("name")?);
+		if let Some(rank) = locales.iter().position(|preferred| *preferred == locale) {
+			if names.get(&item_id).is_none_or(|(best, _)| rank < *best) {
+				names.insert(item_id, (rank, name));
+			}
+		}
+	}
+	Ok(Some(names.into_iter().map(|(item_id, (_, name))| (item_id, name)).collect()))
+}
+
+pub async fn create_order(db: &mut DbConnection, active_session_id: i64, order: &models::Order, staff_id: Option<i64>) -> Result<models::Order> {
+	let order_id: i64 = sqlx::query("INSERT INTO Orders (table_session_id, staff_id) VALUES ($1, $2) RETURNING id")
+		.bind(active_session_id)
+		.bind(staff_id)
+		.fetch_one(&mut *db)
+		.await?
+		.try_get("id")?;
+	let fired_courses: Vec<models::Course> = sqlx::query(r#"
+		SELECT course
+		FROM SessionCourses
+		WHERE table_session_id = $1
+		AND fired_at IS NOT NULL
+		"#)
 		.bind(active_session_id)
-		.execute(&mut **db)
+		.try_map(|record: DbRow| record.try_get("course"))
+		.fetch_all(&mut *db)
 		.await?;
-	let order_id = result.last_insert_rowid();
 	for item in &order.order_items {
-		sqlx::query("INSERT INTO OrderItems ('item_id', 'order_id', 'amount') VALUES (?, ?, ?)")
+		let fire = item.course.is_none_or(|course| fired_courses.contains(&course));
+		sqlx::query(r#"
+		INSERT INTO OrderItems (item_id, order_id, amount, course, fired_at)
+		VALUES ($1, $2, $3, $4, $5)
+		"#)
 		.bind(item.item_id)
 		.bind(order_id)
-		.bind(item.amount)
-		.execute(&mut **db)
+		.bind(i64::from(item.amount))
+		.bind(item.course)
+		.bind(fire.then(now))
+		.execute(&mut *db)
 		.await?;
 	}
 	let order = retrieve_order(db, active_session_id, order_id).await?;
 	Ok(order)
 }
 
-pub async fn retrieve_orders(db: &mut Connection<Db>, table_session_id: i64) -> Result<Vec<models::Order>> {
-	let mut orders = sqlx::query(r#"
-		SELECT id, timestamp
-		FROM Orders 
-		WHERE table_session_id = ?
-		"#)
+/// A page of a session's orders, optionally only those with items still
+/// held (`Held`) or with all of them sent to the kitchen (`Fired`). The items
+/// of all the orders are loaded with one more query.
+pub async fn retrieve_orders(db: &mut DbConnection, table_session_id: i64, page: &PageRequest, status: Option<models::ItemStatus>) -> Result<Vec<models::Order>> {
+	let held = status.map(|status| status == models::ItemStatus::Held);
+	let sql = format!(r#"
+		SELECT id, table_session_id, timestamp, staff_id, version
+		FROM Orders
+		WHERE table_session_id = $1
+		AND ($2 IS NULL OR $2 = EXISTS (SELECT 1 FROM OrderItems WHERE order_id = Orders.id AND fired_at IS NULL))
+		{}
+		ORDER BY {}
+		LIMIT $3
+		"#, page.after_clause("id", 4), page.order_by("id"));
+	let query = sqlx::query_as::<_, models::Order>(&sql)
 		.bind(table_session_id)
-		.map(|record: sqlx::sqlite::SqliteRow| {
-			models::Order {
-				id: record.get_unchecked(0),
-				timestamp: record.get_unchecked(1),
-				table_session_id,
-				order_items: Default::default()
-			}
-		})
-		.fetch_all(&mut **db)
+		.bind(held)
+		.bind(i64::from(page.fetch_limit()));
+	let mut orders = bind_after(query, page)
+		.fetch_all(&mut *db)
 		.await?;
+	if orders.is_empty() {
+		return Ok(Some(orders));
+	}
 
-	for mut order in &mut orders {
-		let items = sqlx::query(r#"
-			SELECT Items.id, amount 
-			FROM Items, OrderItems
-			WHERE Items.id = OrderItems.item_id 
-			AND OrderItems.order_id = ?
-			"#)
-			.bind(order.id)
-			.map(|record: sqlx::sqlite::SqliteRow| {
-				models::OrderItem {
-					item_id: record.get_unchecked(0),
-					amount: record.get_unchecked(1)
-				}
-			})
-			.fetch_all(&mut **db)
-			.await?;
-		order.order_items = items;
+	let order_ids: Vec<i64> = orders.iter().filter_map(|order| order.id).collect();
+	let mut items: HashMap<i64, Vec<models::OrderItem>> = HashMap::new();
+	for (order_id, item) in retrieve_order_items_by_order(&mut *db, &order_ids).await?.unwrap_or_default() {
+		items.entry(order_id).or_default().push(item);
+	}
+	for order in &mut orders {
+		order.order_items = order.id.and_then(|order_id| items.remove(&order_id)).unwrap_or_default();
 	}
 	Ok(Some(orders))
 }
 
-pub async fn retrieve_order(db: &mut Connection<Db>, table_session_id: i64, order_id: i64) -> Result<models::Order> {
-	let items = sqlx::query(r#"
-		SELECT Items.id, amount 
+pub async fn retrieve_order(db: &mut DbConnection, table_session_id: i64, order_id: i64) -> Result<models::Order> {
+	let items = checked_query_as!(models::OrderItem, r#"
+		SELECT Items.id AS item_id, amount AS "amount: u8", course AS "course: models::Course", fired_at IS NULL AS "held!: bool"
 		FROM Items, OrderItems
-		WHERE Items.id = OrderItems.item_id 
-		AND OrderItems.order_id = ?
-		"#)
-		.bind(order_id)
-		.map(|record: sqlx::sqlite::SqliteRow| {
-			models::OrderItem {
-				item_id: record.get_unchecked(0),
-				amount: record.get_unchecked(1)
-			}
-		})
-		.fetch_all(&mut **db)
+		WHERE Items.id = OrderItems.item_id
+		AND OrderItems.order_id = $1
+		ORDER BY OrderItems.id
+		"#, order_id)
+		.fetch_all(&mut *db)
 		.await?;
 
-	let query_result = sqlx::query(r#"
-		SELECT id, timestamp
-		FROM Orders 
-		WHERE table_session_id = ?
-		AND id = ?
+	let query_result = sqlx::query_as::<_, models::Order>(r#"
+		SELECT id, table_session_id, timestamp, staff_id, version
+		FROM Orders
+		WHERE table_session_id = $1
+		AND id = $2
 		"#)
 		.bind(table_session_id)
 		.bind(order_id)
-		.map(|record: sqlx::sqlite::SqliteRow| {
-			models::Order {
-				id: record.get_unchecked(0),
-				timestamp: record.get_unchecked(1),
-				table_session_id,
-				order_items: Default::default()
-			}
-		})
-		.fetch_optional(&mut **db)
+		.fetch_optional(&mut *db)
 		.await?;
-	
-	match query_result {
-		Some(mut order) => {
-			order.order_items = items;
-			Ok(Some(order))
-		}
-		None => Ok(None),
-	}
+	Ok(query_result.map(|order| models::Order { order_items: items, ..order }))
 }
 
-pub async fn delete_order(db: &mut Connection<Db>, table_session_id: i64, order_id: i64) -> std::result::Result<bool, sqlx::Error> {
-	sqlx::query("DELETE FROM OrderItems WHERE order_id = ?")
+/// Deletes an order if it is still at `version`.
+pub async fn delete_order(db: &mut DbConnection, table_session_id: i64, order_id: i64, version: i64) -> std::result::Result<bool, sqlx::Error> {
+	sqlx::query(r#"
+		DELETE FROM OrderItems
+		WHERE order_id = (SELECT id FROM Orders WHERE id = $1 AND table_session_id = $2 AND version = $3)
+		"#)
 		.bind(order_id)
-		.execute(&mut **db)
+		.bind(table_session_id)
+		.bind(version)
+		.execute(&mut *db)
 		.await?;
-	let result = sqlx::query("DELETE FROM Orders WHERE id = ? and table_session_id = ?")
+	let result = sqlx::query("DELETE FROM Orders WHERE id = $1 and table_session_id = $2 AND version = $3")
 		.bind(order_id)
 		.bind(table_session_id)
-		.execute(&mut **db)
+		.bind(version)
+		.execute(&mut *db)
 		.await?;
 	Ok(result.rows_affected() == 1)
 }
 
-pub async fn create_session(db: &mut Connection<Db>, table_nr: u8, session: &models::TableSession) -> Result<models::TableSession> {
-	let result = sqlx::query("INSERT INTO TableSessions ('table_nr', 'customers') VALUES (?, ?)")
-		.bind(table_nr)
-		.bind(session.customers)
-		.execute(&mut **db)
+/// Bumps the version of an order if it is still at `version`. Returns false
+/// when someone else changed it first.
+async fn bump_order_version(db: &mut DbConnection, order_id: i64, version: i64) -> std::result::Result<bool, sqlx::Error> {
+	let result = sqlx::query("UPDATE Orders SET version = version + 1 WHERE id = $1 AND version = $2")
+		.bind(order_id)
+		.bind(version)
+		.execute(&mut *db)
 		.await?;
-	let table_session_id = result.last_insert_rowid();
-	let query_result = sqlx::query(r#"
-		SELECT id, table_nr, customers, session_start, session_end, active 
+	Ok(result.rows_affected() == 1)
+}
+
+pub async fn create_session(db: &mut DbConnection, location_id: i64, table_nr: u8, session: &models::TableSession) -> Result<models::TableSession> {
+	let table_session_id: i64 = sqlx::query("INSERT INTO TableSessions (location_id, table_nr, customers) VALUES ($1, $2, $3) RETURNING id")
+		.bind(location_id)
+		.bind(i64::from(table_nr))
+		.bind(i64::from(session.customers))
+		.fetch_one(&mut *db)
+		.await?
+		.try_get("id")?;
+	let query_result = checked_query_as!(models::TableSession, r#"
+		SELECT id AS "id!", table_nr AS "table_nr: u8", customers AS "customers: u8", session_start, COALESCE(session_end, '') AS "session_end!: String", active AS "active: bool", version AS "version!", location_id
 		FROM TableSessions
-		WHERE id = ?
-		"#)
-		.bind(table_session_id)
-		.map(|record: sqlx::sqlite::SqliteRow| {
-			models::TableSession {
-				id: record.get_unchecked(0),
-				table_nr: record.get_unchecked(1),
-				customers: record.get_unchecked(2),
-				session_start: record.get_unchecked(3),
-				session_end: record.get_unchecked(4),
-				active: record.get_unchecked(5)
-			}
-		})
-		.fetch_optional(&mut **db)
+		WHERE id = $1
+		"#, table_session_id)
+		.fetch_optional(&mut *db)
 		.await?;
-	
+
 	match query_result {
 		Some(session) => Ok(Some(session)),
 		None => Ok(None),
 	}
 }
 
-pub async fn retrieve_active_table_sessions(db: &mut Connection<Db>) -> Result<Vec<models::TableSession>> {
-	let sessions = sqlx::query(r#"
-		SELECT id, table_nr, customers, session_start, session_end, active 
+pub async fn retrieve_active_table_sessions(db: &mut DbConnection, location_id: i64) -> Result<Vec<models::TableSession>> {
+	let sessions = checked_query_as!(models::TableSession, r#"
+		SELECT id AS "id!", table_nr AS "table_nr: u8", customers AS "customers: u8", session_start, COALESCE(session_end, '') AS "session_end!: String", active AS "active: bool", version AS "version!", location_id
 		FROM TableSessions
 		WHERE active = TRUE
-		"#)
-		.map(|record: sqlx::sqlite::SqliteRow| {
-			models::TableSession {
-				id: record.get_unchecked(0),
-				table_nr: record.get_unchecked(1),
-				customers: record.get_unchecked(2),
-				session_start: record.get_unchecked(3),
-				session_end: record.get_unchecked(4),
-				active: record.get_unchecked(5)
-			}
-		})
-		.fetch_all(&mut **db)
+		AND location_id = $1
+		"#, location_id)
+		.fetch_all(&mut *db)
 		.await?;
 	Ok(Some(sessions))
 }
 
-pub async fn retrieve_table_sessions(db: &mut Connection<Db>, table_nr: u8) -> Result<Vec<models::TableSession>> {
-	let sessions = sqlx::query(r#"
-		SELECT id, table_nr, customers, session_start, session_end, active 
+/// A page of a table's sessions, optionally only those started from `start`
+/// and before `end`.
+pub async fn retrieve_table_sessions(db: &mut DbConnection, location_id: i64, table_nr: u8, page: &PageRequest, start: Option<&str>, end: Option<&str>) -> Result<Vec<models::TableSession>> {
+	let sql = format!(r#"
+		SELECT id, table_nr, customers, session_start, COALESCE(session_end, '') AS session_end, active, version, location_id
 		FROM TableSessions
-		WHERE table_nr = ?
-		"#)
-		.bind(table_nr)
-		.map(|record: sqlx::sqlite::SqliteRow| {
-			models::TableSession {
-				id: record.get_unchecked(0),
-				table_nr: record.get_unchecked(1),
-				customers: record.get_unchecked(2),
-				session_start: record.get_unchecked(3),
-				session_end: record.get_unchecked(4),
-				active: record.get_unchecked(5)
-			}
-		})
-		.fetch_all(&mut **db)
+		WHERE location_id = $1
+		AND table_nr = $2
+		AND ($3 IS NULL OR session_start >= $3)
+		AND ($4 IS NULL OR session_start < $4)
+		{}
+		ORDER BY {}
+		LIMIT $5
+		"#, page.after_clause("id", 6), page.order_by("id"));
+	let query = sqlx::query_as::<_, models::TableSession>(&sql)
+		.bind(location_id)
+		.bind(i64::from(table_nr))
+		.bind(start)
+		.bind(end)
+		.bind(i64::from(page.fetch_limit()));
+	let sessions = bind_after(query, page)
+		.fetch_all(&mut *db)
 		.await?;
 	Ok(Some(sessions))
 }
 
-pub async fn retrieve_active_table_session(db: &mut Connection<Db>, table_nr: u8) -> Result<models::TableSession> {
-	let query_result = sqlx::query(r#"
-		SELECT id, table_nr, customers, session_start, session_end, active 
+pub async fn retrieve_table_session(db: &mut DbConnection, table_session_id: i64) -> Result<models::TableSession> {
+	let query_result = checked_query_as!(models::TableSession, r#"
+		SELECT id AS "id!", table_nr AS "table_nr: u8", customers AS "customers: u8", session_start, COALESCE(session_end, '') AS "session_end!: String", active AS "active: bool", version AS "version!", location_id
 		FROM TableSessions
-		WHERE table_nr = ?
+		WHERE id = $1
+		"#, table_session_id)
+		.fetch_optional(&mut *db)
+		.await?;
+	Ok(query_result)
+}
+
+pub async fn retrieve_active_table_session(db: &mut DbConnection, location_id: i64, table_nr: u8) -> Result<models::TableSession> {
+	let table_nr = i64::from(table_nr);
+	let query_result = checked_query_as!(models::TableSession, r#"
+		SELECT id AS "id!", table_nr AS "table_nr: u8", customers AS "customers: u8", session_start, COALESCE(session_end, '') AS "session_end!: String", active AS "active: bool", version AS "version!", location_id
+		FROM TableSessions
+		WHERE table_nr = $1
 		and active = TRUE
-		"#)
-		.bind(table_nr)
-		.map(|record: sqlx::sqlite::SqliteRow| {
-			models::TableSession {
-				id: record.get_unchecked(0),
-				table_nr: record.get_unchecked(1),
-				customers: record.get_unchecked(2),
-				session_start: record.get_unchecked(3),
-				session_end: record.get_unchecked(4),
-				active: record.get_unchecked(5)
-			}
-		})
-		.fetch_optional(&mut **db)
+		AND location_id = $2
+		"#, table_nr, location_id)
+		.fetch_optional(&mut *db)
 		.await?;
-	
+
 	match query_result {
 		Some(session) => Ok(Some(session)),
 		None => Ok(None),
 	}
 }
 
-pub async fn deactivate_table_session(db: &mut Connection<Db>, table_session_id: i64) -> std::result::Result<bool, sqlx::Error> {
+/// Ends a session if it is still at `version`.
+pub async fn deactivate_table_session(db: &mut DbConnection, table_session_id: i64, version: i64) -> std::result::Result<bool, sqlx::Error> {
 	let result = sqlx::query(r#"
-		UPDATE TableSessions 
-		SET active = FALSE, session_end = Datetime('now') 
-		WHERE id = ?
+		UPDATE TableSessions
+		SET active = FALSE, session_end = $1, version = version + 1
+		WHERE id = $2
+		AND version = $3
 		"#)
+		.bind(now())
 		.bind(table_session_id)
-		.execute(&mut **db)
+		.bind(version)
+		.execute(&mut *db)
 		.await?;
 	Ok(result.rows_affected() == 1)
 }
 
-pub async fn delete_item_from_order(db: &mut Connection<Db>, order_id: i64, item_id: i64) -> std::result::Result<bool, sqlx::Error> {
+/// Ends the active sessions that neither started nor had an order, payment
+/// or signal since `cutoff`, returning the id, table and location of each.
+pub async fn close_idle_sessions(pool: &DbPool, cutoff: &str) -> std::result::Result<Vec<(i64, u8, i64)>, sqlx::Error> {
+	sqlx::query(r#"
+		UPDATE TableSessions
+		SET active = FALSE, session_end = $1, version = version + 1
+		WHERE active = TRUE
+		AND session_start < $2
+		AND NOT EXISTS (SELECT 1 FROM Orders WHERE Orders.table_session_id = TableSessions.id AND Orders.timestamp >= $2)
+		AND NOT EXISTS (SELECT 1 FROM Payments WHERE Payments.table_session_id = TableSessions.id AND Payments.timestamp >= $2)
+		AND NOT EXISTS (SELECT 1 FROM Signals WHERE Signals.table_session_id = TableSessions.id AND Signals.raised_at >= $2)
+		RETURNING id, table_nr, location_id
+		"#)
+		.bind(now())
+		.bind(cutoff)
+		.try_map(|record: DbRow| Ok((record.try_get("id")?, get_int(&record, "table_nr")?, record.try_get("location_id")?)))
+		.fetch_all(pool)
+		.await
+}
+
+/// Removes an item from an order at `version`, bumping the version when the
+/// item was in the order. `None` when the order changed since, which leaves
+/// it as it was.
+pub async fn delete_item_from_order(db: &mut DbConnection, order_id: i64, item_id: i64, version: i64) -> Result<bool> {
+	let mut tx = sqlx::Connection::begin(&mut *db).await?;
 	let result = sqlx::query(r#"
-		DELETE FROM OrderItems 
-		WHERE order_id = ?
-		AND item_id = ?
+		DELETE FROM OrderItems
+		WHERE order_id = $1
+		AND item_id = $2
 		"#)
 		.bind(order_id)
 		.bind(item_id)
-		.execute(&mut **db)
+		.execute(&mut tx)
+		.await?;
+	if result.rows_affected() == 0 {
+		return Ok(Some(false));
+	}
+	if !bump_order_version(&mut tx, order_id, version).await? {
+		return Ok(None);
+	}
+	tx.commit().await?;
+	Ok(Some(true))
+}
+
+pub async fn get_staff_by_token(db: &mut DbConnection, token: &str) -> Result<models::Staff> {
+	let query_result = sqlx::query(r#"
+		SELECT id, name, role, location_id
+		FROM Staff
+		WHERE token = $1
+		"#)
+		.bind(token)
+		.try_map(|record: DbRow| {
+			Ok(models::Staff {
+				id: record.try_get("id")?,
+				name: record.try_get("name")?,
+				role: record.try_get("role")?,
+				location_id: record.try_get("location_id")?
+			})
+		})
+		.fetch_optional(&mut *db)
+		.await?;
+	Ok(query_result)
+}
+
+fn map_discount(record: DbRow) -> std::result::Result<models::Discount, sqlx::Error> {
+	Ok(models::Discount {
+		id: record.try_get("id")?,
+		name: record.try_get("name")?,
+		kind: record.try_get("kind")?,
+		value: get_int(&record, "value")?,
+		scope: record.try_get("scope")?,
+		stackable: record.try_get("stackable")?,
+		requires_manager: record.try_get("requires_manager")?,
+		location_id: record.try_get("location_id")?
+	})
+}
+
+pub async fn get_discounts(db: &mut DbConnection, location_id: i64) -> Result<Vec<models::Discount>> {
+	let discounts = sqlx::query(r#"
+		SELECT id, name, kind, value, scope, stackable, requires_manager, location_id
+		FROM Discounts
+		WHERE location_id = $1
+		ORDER BY id
+		"#)
+		.bind(location_id)
+		.try_map(map_discount)
+		.fetch_all(&mut *db)
+		.await?;
+	Ok(Some(discounts))
+}
+
+pub async fn get_discount(db: &mut DbConnection, location_id: i64, discount_id: i64) -> Result<models::Discount> {
+	let query_result = sqlx::query(r#"
+		SELECT id, name, kind, value, scope, stackable, requires_manager, location_id
+		FROM Discounts
+		WHERE id = $1
+		AND location_id = $2
+		"#)
+		.bind(discount_id)
+		.bind(location_id)
+		.try_map(map_discount)
+		.fetch_optional(&mut *db)
+		.await?;
+	Ok(query_result)
+}
+
+pub async fn create_discount(db: &mut DbConnection, location_id: i64, discount: &models::Discount) -> Result<models::Discount> {
+	let discount_id: i64 = sqlx::query(r#"
+		INSERT INTO Discounts (name, kind, value, scope, stackable, requires_manager, location_id)
+		VALUES ($1, $2, $3, $4, $5, $6, $7)
+		RETURNING id
+		"#)
+		.bind(&discount.name)
+		.bind(discount.kind)
+		.bind(i64::from(discount.value))
+		.bind(discount.scope)
+		.bind(discount.stackable)
+		.bind(discount.requires_manager)
+		.bind(location_id)
+		.fetch_one(&mut *db)
+		.await?
+		.try_get("id")?;
+	get_discount(db, location_id, discount_id).await
+}
+
+fn map_applied_discount(record: DbRow) -> std::result::Result<models::AppliedDiscount, sqlx::Error> {
+	Ok(models::AppliedDiscount {
+		id: record.try_get("id")?,
+		table_session_id: record.try_get("table_session_id")?,
+		discount_id: record.try_get("discount_id")?,
+		order_id: record.try_get("order_id")?,
+		item_id: record.try_get("item_id")?,
+		name: record.try_get("name")?,
+		kind: record.try_get("kind")?,
+		value: get_int(&record, "value")?,
+		scope: record.try_get("scope")?,
+		stackable: record.try_get("stackable")?,
+		reason: record.try_get("reason")?,
+		applied_by: record.try_get("applied_by")?,
+		timestamp: record.try_get("timestamp")?
+	})
+}
+
+pub async fn retrieve_applied_discounts(db: &mut DbConnection, table_session_id: i64) -> Result<Vec<models::AppliedDiscount>> {
+	let discounts = sqlx::query(r#"
+		SELECT id, table_session_id, discount_id, order_id, item_id, name, kind, value, scope, stackable, reason, applied_by, timestamp
+		FROM AppliedDiscounts
+		WHERE table_session_id = $1
+		ORDER BY id
+		"#)
+		.bind(table_session_id)
+		.try_map(map_applied_discount)
+		.fetch_all(&mut *db)
+		.await?;
+	Ok(Some(discounts))
+}
+
+/// A change to what a session owes, like a discount or payment, makes a new
+/// version of it.
+async fn bump_session_version(db: &mut DbConnection, table_session_id: i64) -> std::result::Result<(), sqlx::Error> {
+	sqlx::query("UPDATE TableSessions SET version = version + 1 WHERE id = $1")
+		.bind(table_session_id)
+		.execute(&mut *db)
+		.await?;
+	Ok(())
+}
+
+pub async fn create_applied_discount(db: &mut DbConnection, discount: &models::AppliedDiscount) -> Result<models::AppliedDiscount> {
+	let mut tx = sqlx::Connection::begin(&mut *db).await?;
+	let query_result = sqlx::query(r#"
+		INSERT INTO AppliedDiscounts (table_session_id, discount_id, order_id, item_id, name, kind, value, scope, stackable, reason, applied_by)
+		VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+		RETURNING id, table_session_id, discount_id, order_id, item_id, name, kind, value, scope, stackable, reason, applied_by, timestamp
+		"#)
+		.bind(discount.table_session_id)
+		.bind(discount.discount_id)
+		.bind(discount.order_id)
+		.bind(discount.item_id)
+		.bind(&discount.name)
+		.bind(discount.kind)
+		.bind(i64::from(discount.value))
+		.bind(discount.scope)
+		.bind(discount.stackable)
+		.bind(&discount.reason)
+		.bind(discount.applied_by)
+		.try_map(map_applied_discount)
+		.fetch_optional(&mut tx)
+		.await?;
+	if query_result.is_some() {
+		bump_session_version(&mut tx, discount.table_session_id).await?;
+	}
+	tx.commit().await?;
+	Ok(query_result)
+}
+
+pub async fn delete_applied_discount(db: &mut DbConnection, table_session_id: i64, applied_discount_id: i64) -> std::result::Result<bool, sqlx::Error> {
+	let mut tx = sqlx::Connection::begin(&mut *db).await?;
+	let result = sqlx::query("DELETE FROM AppliedDiscounts WHERE id = $1 AND table_session_id = $2")
+		.bind(applied_discount_id)
+		.bind(table_session_id)
+		.execute(&mut tx)
+		.await?;
+	if result.rows_affected() == 0 {
+		return Ok(false);
+	}
+	bump_session_version(&mut tx, table_session_id).await?;
+	tx.commit().await?;
+	Ok(true)
+}
+
+pub async fn retrieve_bill_lines(db: &mut DbConnection, table_session_id: i64) -> Result<Vec<models::BillLine>> {
+	let lines = sqlx::query(r#"
+		SELECT Orders.id AS order_id, Items.id AS item_id, Items.name, OrderItems.amount, Items.price, Items.currency
+		FROM Orders, OrderItems, Items
+		WHERE Orders.id = OrderItems.order_id
+		AND Items.id = OrderItems.item_id
+		AND Orders.table_session_id = $1
+		ORDER BY Orders.id, OrderItems.id
+		"#)
+		.bind(table_session_id)
+		.try_map(|record: DbRow| {
+			let price = get_money(&record, "price", "currency")?;
+			Ok(models::BillLine {
+				order_id: record.try_get("order_id")?,
+				item_id: record.try_get("item_id")?,
+				name: record.try_get("name")?,
+				amount: get_int(&record, "amount")?,
+				price,
+				price_yen: price.yen(),
+				..Default::default()
+			})
+		})
+		.fetch_all(&mut *db)
+		.await?;
+	Ok(Some(lines))
+}
+
+fn map_payment(record: DbRow) -> std::result::Result<models::Payment, sqlx::Error> {
+	let currency: Option<String> = record.try_get("currency")?;
+	Ok(models::Payment {
+		id: record.try_get("id")?,
+		table_session_id: record.try_get("table_session_id")?,
+		staff_id: record.try_get("staff_id")?,
+		amount: get_int(&record, "amount")?,
+		tip: get_int(&record, "tip")?,
+		currency: match currency {
+			Some(code) => Some(Currency::parse(&code).ok_or_else(|| sqlx::Error::Decode(format!("Invalid currency '{}'", code).into()))?),
+			None => None
+		},
+		method: record.try_get("method")?,
+		timestamp: record.try_get("timestamp")?
+	})
+}
+
+pub async fn retrieve_payments(db: &mut DbConnection, table_session_id: i64) -> Result<Vec<models::Payment>> {
+	let payments = sqlx::query(r#"
+		SELECT id, table_session_id, staff_id, amount, tip, method, timestamp, currency
+		FROM Payments
+		WHERE table_session_id = $1
+		ORDER BY id
+		"#)
+		.bind(table_session_id)
+		.try_map(map_payment)
+		.fetch_all(&mut *db)
+		.await?;
+	Ok(Some(payments))
+}
+
+pub async fn create_payment(db: &mut DbConnection, table_session_id: i64, staff_id: i64, payment: &models::Payment) -> Result<models::Payment> {
+	let mut tx = sqlx::Connection::begin(&mut *db).await?;
+	let query_result = sqlx::query(r#"
+		INSERT INTO Payments (table_session_id, staff_id, amount, tip, method, currency)
+		VALUES ($1, $2, $3, $4, $5, $6)
+		RETURNING id, table_session_id, staff_id, amount, tip, method, timestamp, currency
+		"#)
+		.bind(table_session_id)
+		.bind(staff_id)
+		.bind(i64::from(payment.amount))
+		.bind(i64::from(payment.tip))
+		.bind(&payment.method)
+		.bind(payment.currency.map(|currency| currency.to_string()))
+		.try_map(map_payment)
+		.fetch_optional(&mut tx)
+		.await?;
+	if query_result.is_some() {
+		bump_session_version(&mut tx, table_session_id).await?;
+	}
+	tx.commit().await?;
+	Ok(query_result)
+}
+
+fn map_shift(record: DbRow) -> std::result::Result<models::Shift, sqlx::Error> {
+	Ok(models::Shift {
+		id: record.try_get("id")?,
+		staff_id: record.try_get("staff_id")?,
+		clock_in: record.try_get("clock_in")?,
+		clock_out: record.try_get("clock_out")?
+	})
+}
+
+pub async fn retrieve_open_shift(db: &mut DbConnection, staff_id: i64) -> Result<models::Shift> {
+	let query_result = sqlx::query(r#"
+		SELECT id, staff_id, clock_in, clock_out
+		FROM Shifts
+		WHERE staff_id = $1
+		AND clock_out IS NULL
+		"#)
+		.bind(staff_id)
+		.try_map(map_shift)
+		.fetch_optional(&mut *db)
+		.await?;
+	Ok(query_result)
+}
+
+pub async fn create_shift(db: &mut DbConnection, staff_id: i64) -> Result<models::Shift> {
+	sqlx::query("INSERT INTO Shifts (staff_id, clock_in) VALUES ($1, $2)")
+		.bind(staff_id)
+		.bind(now())
+		.execute(&mut *db)
+		.await?;
+	retrieve_open_shift(db, staff_id).await
+}
+
+pub async fn close_shift(db: &mut DbConnection, shift_id: i64) -> std::result::Result<bool, sqlx::Error> {
+	let result = sqlx::query(r#"
+		UPDATE Shifts
+		SET clock_out = $1
+		WHERE id = $2
+		AND clock_out IS NULL
+		"#)
+		.bind(now())
+		.bind(shift_id)
+		.execute(&mut *db)
 		.await?;
 	Ok(result.rows_affected() == 1)
 }
 
-async fn run_migrations(rocket: Rocket<Build>) -> rocket::fairing::Result {
-    match Db::fetch(&rocket) {
-        Some(db) => match sqlx::migrate!().run(&**db).await {
-            Ok(_) => {
-				Ok(rocket)
+/// Tips per session of a location for payments taken in `[start, end)`,
+/// together with the staff who served each session (took one of its orders
+/// or payments).
+pub async fn retrieve_session_tips(db: &mut DbConnection, location_id: i64, start: &str, end: &str) -> Result<Vec<(u32, Vec<i64>)>> {
+	let tips: Vec<(i64, u32)> = sqlx::query(r#"
+		SELECT table_session_id, CAST(SUM(tip) AS BIGINT) AS tip
+		FROM Payments
+		WHERE timestamp >= $1
+		AND timestamp < $2
+		AND table_session_id IN (SELECT id FROM TableSessions WHERE location_id = $3)
+		GROUP BY table_session_id
+		"#)
+		.bind(start)
+		.bind(end)
+		.bind(location_id)
+		.try_map(|record: DbRow| Ok((record.try_get("table_session_id")?, get_int(&record, "tip")?)))
+		.fetch_all(&mut *db)
+		.await?;
+
+	let servers: Vec<(i64, i64)> = sqlx::query(r#"
+		SELECT table_session_id, staff_id FROM Orders WHERE staff_id IS NOT NULL
+		UNION
+		SELECT table_session_id, staff_id FROM Payments
+		"#)
+		.try_map(|record: DbRow| Ok((record.try_get("table_session_id")?, record.try_get("staff_id")?)))
+		.fetch_all(&mut *db)
+		.await?;
+
+	let sessions = tips.into_iter()
+		.map(|(table_session_id, tip)| {
+			let staff = servers.iter()
+				.filter(|(session_id, _)| *session_id == table_session_id)
+				.map(|(_, staff_id)| *staff_id)
+				.collect();
+			(tip, staff)
+		})
+		.collect();
+	Ok(Some(sessions))
+}
+
+/// Every staff member of a location with the hours of their shifts that fall
+/// in `[start, end)`. Shifts still open count up to the current time.
+pub async fn retrieve_staff_hours(db: &mut DbConnection, location_id: i64, start: &str, end: &str) -> Result<Vec<(models::Staff, f64)>> {
+	let shifts: Vec<(models::Staff, Option<(String, String)>)> = sqlx::query(r#"
+		SELECT Staff.id, Staff.name, Staff.role, Staff.location_id, Shifts.clock_in, COALESCE(Shifts.clock_out, $3) AS clock_out
+		FROM Staff
+		LEFT JOIN Shifts ON Shifts.staff_id = Staff.id AND Shifts.clock_in < $2 AND COALESCE(Shifts.clock_out, $3) > $1
+		WHERE Staff.location_id = $4
+		ORDER BY Staff.id, Shifts.id
+		"#)
+		.bind(start)
+		.bind(end)
+		.bind(now())
+		.bind(location_id)
+		.try_map(|record: DbRow| {
+			let staff = models::Staff {
+				id: record.try_get("id")?,
+				name: record.try_get("name")?,
+				role: record.try_get("role")?,
+				location_id: record.try_get("location_id")?
+			};
+			let clock_in: Option<String> = record.try_get("clock_in")?;
+			let clock_out: Option<String> = record.try_get("clock_out")?;
+			Ok((staff, clock_in.zip(clock_out)))
+		})
+		.fetch_all(&mut *db)
+		.await?;
+
+	let (start, end) = (parse_timestamp(start)?, parse_timestamp(end)?);
+	let mut hours: Vec<(models::Staff, f64)> = Vec::new();
+	for (staff, shift) in shifts {
+		let worked = match shift {
+			Some((clock_in, clock_out)) => {
+				let from = parse_timestamp(&clock_in)?.max(start);
+				let to = parse_timestamp(&clock_out)?.min(end);
+				(to - from).num_seconds().max(0) as f64 / 3600.0
 			},
-            Err(e) => {
-                rocket::error!("Failed to initialize SQLx database: {}", e);
-                Err(rocket)
-            }
+			None => 0.0,
+		};
+		match hours.last_mut() {
+			Some((last, total)) if last.id == staff.id => *total += worked,
+			_ => hours.push((staff, worked)),
+		}
+	}
+	Ok(Some(hours))
+}
+
+const SIGNAL_COLUMNS: &str = "Signals.id, Signals.table_session_id, TableSessions.table_nr, Signals.kind, Signals.raised_at, Signals.raised_by, Signals.acknowledged_at, Signals.acknowledged_by";
+
+fn map_signal(record: DbRow) -> std::result::Result<models::Signal, sqlx::Error> {
+	Ok(models::Signal {
+		id: record.try_get("id")?,
+		table_session_id: record.try_get("table_session_id")?,
+		table_nr: get_int(&record, "table_nr")?,
+		kind: record.try_get("kind")?,
+		raised_at: record.try_get("raised_at")?,
+		raised_by: record.try_get("raised_by")?,
+		acknowledged_at: record.try_get("acknowledged_at")?,
+		acknowledged_by: record.try_get("acknowledged_by")?
+	})
+}
+
+/// Raises a signal, unless one of the same kind is still open for the
+/// session, which is given back instead. The flag tells whether the signal
+/// is new.
+pub async fn create_signal(db: &mut DbConnection, table_session_id: i64, kind: models::SignalKind, raised_by: Option<i64>) -> Result<(models::Signal, bool)> {
+	let signal_id: Option<i64> = sqlx::query(r#"
+		INSERT INTO Signals (table_session_id, kind, raised_at, raised_by)
+		VALUES ($1, $2, $3, $4)
+		ON CONFLICT (table_session_id, kind) WHERE acknowledged_at IS NULL DO NOTHING
+		RETURNING id
+		"#)
+		.bind(table_session_id)
+		.bind(kind)
+		.bind(now())
+		.bind(raised_by)
+		.try_map(|record: DbRow| record.try_get("id"))
+		.fetch_optional(&mut *db)
+		.await?;
+	if let Some(signal_id) = signal_id {
+		return Ok(retrieve_signal(db, table_session_id, signal_id).await?.map(|signal| (signal, true)));
+	}
+	let sql = format!("SELECT {} FROM Signals JOIN TableSessions ON TableSessions.id = Signals.table_session_id WHERE Signals.table_session_id = $1 AND Signals.kind = $2 AND Signals.acknowledged_at IS NULL", SIGNAL_COLUMNS);
+	let open = sqlx::query(&sql)
+		.bind(table_session_id)
+		.bind(kind)
+		.try_map(map_signal)
+		.fetch_optional(&mut *db)
+		.await?;
+	Ok(open.map(|signal| (signal, false)))
+}
+
+pub async fn retrieve_signal(db: &mut DbConnection, table_session_id: i64, signal_id: i64) -> Result<models::Signal> {
+	let sql = format!("SELECT {} FROM Signals JOIN TableSessions ON TableSessions.id = Signals.table_session_id WHERE Signals.id = $1 AND Signals.table_session_id = $2", SIGNAL_COLUMNS);
+	let signal = sqlx::query(&sql)
+		.bind(signal_id)
+		.bind(table_session_id)
+		.try_map(map_signal)
+		.fetch_optional(&mut *db)
+		.await?;
+	Ok(signal)
+}
+
+pub async fn retrieve_session_signals(db: &mut DbConnection, table_session_id: i64) -> Result<Vec<models::Signal>> {
+	let sql = format!("SELECT {} FROM Signals JOIN TableSessions ON TableSessions.id = Signals.table_session_id WHERE Signals.table_session_id = $1 ORDER BY Signals.id", SIGNAL_COLUMNS);
+	let signals = sqlx::query(&sql)
+		.bind(table_session_id)
+		.try_map(map_signal)
+		.fetch_all(&mut *db)
+		.await?;
+	Ok(Some(signals))
+}
+
+/// The signals of the location's active sessions no one acknowledged yet,
+/// oldest first.
+pub async fn retrieve_open_signals(db: &mut DbConnection, location_id: i64) -> Result<Vec<models::Signal>> {
+	let sql = format!(r#"
+		SELECT {}
+		FROM Signals
+		JOIN TableSessions ON TableSessions.id = Signals.table_session_id
+		WHERE TableSessions.location_id = $1
+		AND TableSessions.active = TRUE
+		AND Signals.acknowledged_at IS NULL
+		ORDER BY Signals.id
+		"#, SIGNAL_COLUMNS);
+	let signals = sqlx::query(&sql)
+		.bind(location_id)
+		.try_map(map_signal)
+		.fetch_all(&mut *db)
+		.await?;
+	Ok(Some(signals))
+}
+
+pub async fn acknowledge_signal(db: &mut DbConnection, signal_id: i64, staff_id: i64) -> std::result::Result<bool, sqlx::Error> {
+	let result = sqlx::query("UPDATE Signals SET acknowledged_at = $1, acknowledged_by = $2 WHERE id = $3 AND acknowledged_at IS NULL")
+		.bind(now())
+		.bind(staff_id)
+		.bind(signal_id)
+		.execute(&mut *db)
+		.await?;
+	Ok(result.rows_affected() == 1)
+}
+
+/// The shifts of the location's staff overlapping `[start, end)`, each with
+/// how many seconds the signals its staff member acknowledged during it
+/// waited.
+pub async fn retrieve_signal_response_times(db: &mut DbConnection, location_id: i64, start: &str, end: &str) -> Result<Vec<(models::Shift, String, Vec<i64>)>> {
+	let rows = sqlx::query(r#"
+		SELECT Shifts.id, Shifts.staff_id, Shifts.clock_in, Shifts.clock_out, Staff.name, Signals.raised_at, Signals.acknowledged_at
+		FROM Shifts
+		JOIN Staff ON Staff.id = Shifts.staff_id
+		LEFT JOIN Signals ON Signals.acknowledged_by = Shifts.staff_id
+			AND Signals.acknowledged_at >= Shifts.clock_in
+			AND Signals.acknowledged_at <= COALESCE(Shifts.clock_out, $3)
+		WHERE Staff.location_id = $4
+		AND Shifts.clock_in < $2
+		AND COALESCE(Shifts.clock_out, $3) > $1
+		ORDER BY Shifts.id, Signals.id
+		"#)
+		.bind(start)
+		.bind(end)
+		.bind(now())
+		.bind(location_id)
+		.try_map(|record: DbRow| {
+			let shift = models::Shift {
+				id: record.try_get("id")?,
+				staff_id: record.try_get("staff_id")?,
+				clock_in: record.try_get("clock_in")?,
+				clock_out: record.try_get("clock_out")?
+			};
+			let raised_at: Option<String> = record.try_get("raised_at")?;
+			let name: String = record.try_get("name")?;
+			let acknowledged_at: Option<String> = record.try_get("acknowledged_at")?;
+			Ok((shift, name, raised_at.zip(acknowledged_at)))
+		})
+		.fetch_all(&mut *db)
+		.await?;
+
+	let mut shifts: Vec<(models::Shift, String, Vec<i64>)> = Vec::new();
+	for (shift, name, signal) in rows {
+		let waited = match signal {
+			Some((raised_at, acknowledged_at)) => Some((parse_timestamp(&acknowledged_at)? - parse_timestamp(&raised_at)?).num_seconds().max(0)),
+			None => None,
+		};
+		match shifts.last_mut() {
+			Some((last, _, times)) if last.id == shift.id => times.extend(waited),
+			_ => shifts.push((shift, name, waited.into_iter().collect())),
+		}
+	}
+	Ok(Some(shifts))
+}
+
+/// Signals raised at the location in `[start, end)` that were never
+/// acknowledged.
+pub async fn count_unacknowledged_signals(db: &mut DbConnection, location_id: i64, start: &str, end: &str) -> std::result::Result<i64, sqlx::Error> {
+	sqlx::query(r#"
+		SELECT COUNT(*) AS unacknowledged
+		FROM Signals
+		JOIN TableSessions ON TableSessions.id = Signals.table_session_id
+		WHERE TableSessions.location_id = $1
+		AND Signals.raised_at >= $2
+		AND Signals.raised_at < $3
+		AND Signals.acknowledged_at IS NULL
+		"#)
+		.bind(location_id)
+		.bind(start)
+		.bind(end)
+		.fetch_one(&mut *db)
+		.await?
+		.try_get("unacknowledged")
+}
+
+/// Items of the orders of active sessions (of a location or of one session)
+/// as the kitchen sees them: station, course, preparation time and when they
+/// were fired.
+pub async fn retrieve_queue_items(db: &mut DbConnection, location_id: Option<i64>, table_session_id: Option<i64>) -> Result<Vec<models::QueueItem>> {
+	let items = sqlx::query(r#"
+		SELECT TableSessions.id AS table_session_id, TableSessions.table_nr, Orders.id AS order_id, Items.id AS item_id, Items.name, Items.station,
+		OrderItems.course, OrderItems.amount, Items.preparation_time, OrderItems.fired_at
+		FROM TableSessions, Orders, OrderItems, Items
+		WHERE TableSessions.id = Orders.table_session_id
+		AND Orders.id = OrderItems.order_id
+		AND Items.id = OrderItems.item_id
+		AND TableSessions.active = TRUE
+		AND ($1 IS NULL OR TableSessions.location_id = $1)
+		AND ($2 IS NULL OR TableSessions.id = $2)
+		ORDER BY Orders.id, OrderItems.id
+		"#)
+		.bind(location_id)
+		.bind(table_session_id)
+		.try_map(|record: DbRow| {
+			Ok(models::QueueItem {
+				table_session_id: record.try_get("table_session_id")?,
+				table_nr: get_int(&record, "table_nr")?,
+				order_id: record.try_get("order_id")?,
+				item_id: record.try_get("item_id")?,
+				name: record.try_get("name")?,
+				station: record.try_get("station")?,
+				course: record.try_get("course")?,
+				amount: get_int(&record, "amount")?,
+				preparation_time: get_int(&record, "preparation_time")?,
+				fired_at: record.try_get("fired_at")?,
+				..Default::default()
+			})
+		})
+		.fetch_all(&mut *db)
+		.await?;
+	Ok(Some(items))
+}
+
+/// Marks a course as fired for a session, firing its held items. Items of the
+/// course ordered later go to the kitchen straight away.
+pub async fn fire_course(db: &mut DbConnection, table_session_id: i64, course: models::Course) -> std::result::Result<u64, sqlx::Error> {
+	let fired_at = now();
+	sqlx::query(r#"
+		INSERT INTO SessionCourses (table_session_id, course, fired_at)
+		VALUES ($1, $2, $3)
+		ON CONFLICT(table_session_id, course) DO UPDATE SET fired_at = excluded.fired_at
+		"#)
+		.bind(table_session_id)
+		.bind(course)
+		.bind(&fired_at)
+		.execute(&mut *db)
+		.await?;
+	sqlx::query(r#"
+		UPDATE Orders
+		SET version = version + 1
+		WHERE table_session_id = $1
+		AND id IN (SELECT order_id FROM OrderItems WHERE fired_at IS NULL AND course = $2)
+		"#)
+		.bind(table_session_id)
+		.bind(course)
+		.execute(&mut *db)
+		.await?;
+	let result = sqlx::query(r#"
+		UPDATE OrderItems
+		SET fired_at = $1
+		WHERE fired_at IS NULL
+		AND course = $2
+		AND order_id IN (SELECT id FROM Orders WHERE table_session_id = $3)
+		"#)
+		.bind(&fired_at)
+		.bind(course)
+		.bind(table_session_id)
+		.execute(&mut *db)
+		.await?;
+	Ok(result.rows_affected())
+}
+
+/// Holds a course for a session again, so items of it ordered from now on wait
+/// to be fired. Items already sent to the kitchen are not affected.
+pub async fn hold_course(db: &mut DbConnection, table_session_id: i64, course: models::Course) -> std::result::Result<bool, sqlx::Error> {
+	let result = sqlx::query(r#"
+		INSERT INTO SessionCourses (table_session_id, course, fired_at)
+		VALUES ($1, $2, NULL)
+		ON CONFLICT(table_session_id, course) DO UPDATE SET fired_at = NULL
+		"#)
+		.bind(table_session_id)
+		.bind(course)
+		.execute(&mut *db)
+		.await?;
+	Ok(result.rows_affected() == 1)
+}
+
+pub async fn fire_order_items(db: &mut DbConnection, order_id: i64) -> std::result::Result<u64, sqlx::Error> {
+	sqlx::query(r#"
+		UPDATE Orders
+		SET version = version + 1
+		WHERE id = $1
+		AND id IN (SELECT order_id FROM OrderItems WHERE fired_at IS NULL)
+		"#)
+		.bind(order_id)
+		.execute(&mut *db)
+		.await?;
+	let result = sqlx::query(r#"
+		UPDATE OrderItems
+		SET fired_at = $1
+		WHERE fired_at IS NULL
+		AND order_id = $2
+		"#)
+		.bind(now())
+		.bind(order_id)
+		.execute(&mut *db)
+		.await?;
+	Ok(result.rows_affected())
+}
+
+/// Moves a held item of an order to another course.
+/// Moves a held item of an order at `version` to another course, bumping the
+/// version when there was such an item. `None` when the order changed since,
+/// which leaves it as it was.
+pub async fn update_item_course(db: &mut DbConnection, order_id: i64, item_id: i64, course: models::Course, version: i64) -> Result<bool> {
+	let mut tx = sqlx::Connection::begin(&mut *db).await?;
+	let result = sqlx::query(r#"
+		UPDATE OrderItems
+		SET course = $1
+		WHERE order_id = $2
+		AND item_id = $3
+		AND fired_at IS NULL
+		"#)
+		.bind(course)
+		.bind(order_id)
+		.bind(item_id)
+		.execute(&mut tx)
+		.await?;
+	if result.rows_affected() == 0 {
+		return Ok(Some(false));
+	}
+	if !bump_order_version(&mut tx, order_id, version).await? {
+		return Ok(None);
+	}
+	tx.commit().await?;
+	Ok(Some(true))
+}
+
+/// Claims an idempotency key for a request, returning false when the key was
+/// already used. A claim without a response that is older than `stale` was
+/// left by a request that never finished and is taken over. Keys are
+/// forgotten after a day.
+pub async fn claim_idempotency_key(db: &mut DbConnection, key: &str, scope: &str, request_hash: &str, stale: chrono::Duration) -> std::result::Result<bool, sqlx::Error> {
+	let now = chrono::Utc::now();
+	sqlx::query("DELETE FROM IdempotencyKeys WHERE created < $1")
+		.bind(timestamp(now - chrono::Duration::days(1)))
+		.execute(&mut *db)
+		.await?;
+	let result = sqlx::query(r#"
+		INSERT INTO IdempotencyKeys (key, scope, request_hash, created, claimed)
+		VALUES ($1, $2, $3, $4, $4)
+		ON CONFLICT(key, scope) DO UPDATE
+		SET request_hash = excluded.request_hash, claimed = excluded.claimed
+		WHERE IdempotencyKeys.response IS NULL
+		AND (IdempotencyKeys.claimed IS NULL OR IdempotencyKeys.claimed < $5)
+		"#)
+		.bind(key)
+		.bind(scope)
+		.bind(request_hash)
+		.bind(timestamp(now))
+		.bind(timestamp(now - stale))
+		.execute(&mut *db)
+		.await?;
+	Ok(result.rows_affected() == 1)
+}
+
+/// The request hash and, once the first request finished, the response stored
+/// for an idempotency key.
+pub async fn retrieve_idempotency_key(db: &mut DbConnection, key: &str, scope: &str) -> Result<(String, Option<String>)> {
+	let stored = sqlx::query("SELECT request_hash, response FROM IdempotencyKeys WHERE key = $1 AND scope = $2")
+		.bind(key)
+		.bind(scope)
+		.try_map(|record: DbRow| Ok((record.try_get("request_hash")?, record.try_get("response")?)))
+		.fetch_optional(&mut *db)
+		.await?;
+	Ok(stored)
+}
+
+pub async fn store_idempotent_response(db: &mut DbConnection, key: &str, scope: &str, response: &str) -> std::result::Result<(), sqlx::Error> {
+	sqlx::query("UPDATE IdempotencyKeys SET response = $1 WHERE key = $2 AND scope = $3")
+		.bind(response)
+		.bind(key)
+		.bind(scope)
+		.execute(&mut *db)
+		.await?;
+	Ok(())
+}
+
+/// Frees a key whose request failed, so a retry can try again.
+pub async fn release_idempotency_key(db: &mut DbConnection, key: &str, scope: &str) -> std::result::Result<(), sqlx::Error> {
+	sqlx::query("DELETE FROM IdempotencyKeys WHERE key = $1 AND scope = $2 AND response IS NULL")
+		.bind(key)
+		.bind(scope)
+		.execute(&mut *db)
+		.await?;
+	Ok(())
+}
+
+/// The settings last saved through the API, as JSON.
+pub async fn retrieve_settings(pool: &DbPool) -> Result<String> {
+	let settings = sqlx::query("SELECT settings FROM Settings WHERE id = 1")
+		.try_map(|record: DbRow| record.try_get("settings"))
+		.fetch_optional(pool)
+		.await?;
+	Ok(settings)
+}
+
+pub async fn save_settings(db: &mut DbConnection, settings: &str) -> std::result::Result<(), sqlx::Error> {
+	sqlx::query("INSERT INTO Settings (id, settings) VALUES (1, $1) ON CONFLICT (id) DO UPDATE SET settings = excluded.settings")
+		.bind(settings)
+		.execute(&mut *db)
+		.await?;
+	Ok(())
+}
+
+fn map_guest_order(record: DbRow) -> std::result::Result<models::GuestOrder, sqlx::Error> {
+	let items: String = record.try_get("items")?;
+	Ok(models::GuestOrder {
+		id: record.try_get("id")?,
+		table_session_id: record.try_get("table_session_id")?,
+		order_items: rocket::serde::json::from_str(&items).map_err(|e| sqlx::Error::Decode(Box::new(e)))?,
+		status: record.try_get("status")?,
+		order_id: record.try_get("order_id")?,
+		timestamp: record.try_get("timestamp")?
+	})
+}
+
+pub async fn create_guest_order(db: &mut DbConnection, table_session_id: i64, order_items: &[models::OrderItem], status: models::GuestOrderStatus, order_id: Option<i64>) -> Result<models::GuestOrder> {
+	let items = rocket::serde::json::to_string(&order_items).map_err(|e| sqlx::Error::Protocol(e.to_string()))?;
+	let query_result = sqlx::query(r#"
+		INSERT INTO GuestOrders (table_session_id, items, status, order_id, timestamp)
+		VALUES ($1, $2, $3, $4, $5)
+		RETURNING id, table_session_id, items, status, order_id, timestamp
+		"#)
+		.bind(table_session_id)
+		.bind(items)
+		.bind(status)
+		.bind(order_id)
+		.bind(now())
+		.try_map(map_guest_order)
+		.fetch_optional(&mut *db)
+		.await?;
+	Ok(query_result)
+}
+
+/// Places a guest order as an order of the session and records it as placed,
+/// both or neither.
+pub async fn place_guest_order(db: &mut DbConnection, table_session_id: i64, order: &models::Order) -> Result<(models::Order, models::GuestOrder)> {
+	let mut tx = sqlx::Connection::begin(&mut *db).await?;
+	let placed = match create_order(&mut tx, table_session_id, order, None).await? {
+		Some(placed) => placed,
+		None => return Ok(None),
+	};
+	let guest_order = match create_guest_order(&mut tx, table_session_id, &placed.order_items, models::GuestOrderStatus::Placed, placed.id).await? {
+		Some(guest_order) => guest_order,
+		None => return Ok(None),
+	};
+	tx.commit().await?;
+	Ok(Some((placed, guest_order)))
+}
+
+pub async fn retrieve_guest_orders(db: &mut DbConnection, table_session_id: i64) -> Result<Vec<models::GuestOrder>> {
+	let guest_orders = sqlx::query("SELECT id, table_session_id, items, status, order_id, timestamp FROM GuestOrders WHERE table_session_id = $1 ORDER BY id")
+		.bind(table_session_id)
+		.try_map(map_guest_order)
+		.fetch_all(&mut *db)
+		.await?;
+	Ok(Some(guest_orders))
+}
+
+pub async fn retrieve_guest_order(db: &mut DbConnection, table_session_id: i64, guest_order_id: i64) -> Result<models::GuestOrder> {
+	let guest_order = sqlx::query("SELECT id, table_session_id, items, status, order_id, timestamp FROM GuestOrders WHERE id = $1 AND table_session_id = $2")
+		.bind(guest_order_id)
+		.bind(table_session_id)
+		.try_map(map_guest_order)
+		.fetch_optional(&mut *db)
+		.await?;
+	Ok(guest_order)
+}
+
+/// Moves a guest order from status `from` to `to`, unless someone else
+/// already moved it.
+pub async fn update_guest_order_status(db: &mut DbConnection, guest_order_id: i64, from: models::GuestOrderStatus, to: models::GuestOrderStatus) -> std::result::Result<bool, sqlx::Error> {
+	let result = sqlx::query("UPDATE GuestOrders SET status = $1 WHERE id = $2 AND status = $3")
+		.bind(to)
+		.bind(guest_order_id)
+		.bind(from)
+		.execute(&mut *db)
+		.await?;
+	Ok(result.rows_affected() == 1)
+}
+
+pub async fn set_guest_order_placed(db: &mut DbConnection, guest_order_id: i64, order_id: i64) -> std::result::Result<(), sqlx::Error> {
+	sqlx::query("UPDATE GuestOrders SET status = 'placed', order_id = $1 WHERE id = $2")
+		.bind(order_id)
+		.bind(guest_order_id)
+		.execute(&mut *db)
+		.await?;
+	Ok(())
+}
+
+fn map_webhook(record: DbRow) -> std::result::Result<models::Webhook, sqlx::Error> {
+	let events: String = record.try_get("events")?;
+	Ok(models::Webhook {
+		id: record.try_get("id")?,
+		url: record.try_get("url")?,
+		secret: record.try_get("secret")?,
+		events: events.split(',').map(String::from).collect(),
+		location_id: record.try_get("location_id")?
+	})
+}
+
+pub async fn create_webhook(db: &mut DbConnection, location_id: i64, webhook: &models::Webhook, secret: &str) -> Result<models::Webhook> {
+	let webhook = sqlx::query("INSERT INTO Webhooks (url, secret, events, location_id) VALUES ($1, $2, $3, $4) RETURNING id, url, secret, events, location_id")
+		.bind(&webhook.url)
+		.bind(secret)
+		.bind(webhook.events.join(","))
+		.bind(location_id)
+		.try_map(map_webhook)
+		.fetch_optional(&mut *db)
+		.await?;
+	Ok(webhook)
+}
+
+pub async fn get_webhooks(db: &mut DbConnection, location_id: i64) -> Result<Vec<models::Webhook>> {
+	let webhooks = sqlx::query("SELECT id, url, NULL AS secret, events, location_id FROM Webhooks WHERE active = TRUE AND location_id = $1")
+		.bind(location_id)
+		.try_map(map_webhook)
+		.fetch_all(&mut *db)
+		.await?;
+	Ok(Some(webhooks))
+}
+
+pub async fn deactivate_webhook(db: &mut DbConnection, location_id: i64, webhook_id: i64) -> std::result::Result<bool, sqlx::Error> {
+	let result = sqlx::query("UPDATE Webhooks SET active = FALSE WHERE id = $1 AND location_id = $2 AND active = TRUE")
+		.bind(webhook_id)
+		.bind(location_id)
+		.execute(&mut *db)
+		.await?;
+	Ok(result.rows_affected() == 1)
+}
+
+pub async fn retrieve_webhook_deliveries(db: &mut DbConnection, location_id: i64, webhook_id: i64) -> Result<Vec<models::WebhookDelivery>> {
+	let deliveries = sqlx::query(r#"
+		SELECT id, webhook_id, event_id, event_type, attempt, status_code, error, succeeded, timestamp, delivery_id
+		FROM WebhookDeliveries
+		WHERE webhook_id = $1
+		AND webhook_id IN (SELECT id FROM Webhooks WHERE location_id = $2)
+		ORDER BY id
+		"#)
+		.bind(webhook_id)
+		.bind(location_id)
+		.try_map(|record: DbRow| {
+			Ok(models::WebhookDelivery {
+				id: record.try_get("id")?,
+				webhook_id: record.try_get("webhook_id")?,
+				event_id: record.try_get("event_id")?,
+				event_type: record.try_get("event_type")?,
+				attempt: get_int(&record, "attempt")?,
+				status_code: get_optional_int(&record, "status_code")?,
+				error: record.try_get("error")?,
+				succeeded: record.try_get("succeeded")?,
+				timestamp: record.try_get("timestamp")?,
+				delivery_id: record.try_get("delivery_id")?
+			})
+		})
+		.fetch_all(&mut *db)
+		.await?;
+	Ok(Some(deliveries))
+}
+
+fn map_dead_letter(record: DbRow) -> std::result::Result<models::DeadLetter, sqlx::Error> {
+	Ok(models::DeadLetter {
+		id: record.try_get("id")?,
+		webhook_id: record.try_get("webhook_id")?,
+		event_id: record.try_get("event_id")?,
+		event_type: record.try_get("event_type")?,
+		payload: record.try_get("payload")?,
+		attempts: get_int(&record, "attempts")?,
+		last_error: record.try_get("last_error")?,
+		timestamp: record.try_get("timestamp")?,
+		delivery_id: record.try_get("delivery_id")?
+	})
+}
+
+pub async fn retrieve_dead_letters(db: &mut DbConnection, location_id: i64) -> Result<Vec<models::DeadLetter>> {
+	let dead_letters = sqlx::query(r#"
+		SELECT id, webhook_id, event_id, event_type, payload, attempts, last_error, timestamp, delivery_id
+		FROM WebhookDeadLetters
+		WHERE webhook_id IN (SELECT id FROM Webhooks WHERE location_id = $1)
+		ORDER BY id
+		"#)
+		.bind(location_id)
+		.try_map(map_dead_letter)
+		.fetch_all(&mut *db)
+		.await?;
+	Ok(Some(dead_letters))
+}
+
+// The webhook functions below take the pool rather than a request's
+// connection, as deliveries happen in the background.
+
+pub async fn retrieve_subscribed_webhooks(pool: &DbPool, location_id: i64, event_type: &str) -> Result<Vec<models::Webhook>> {
+	let webhooks = sqlx::query("SELECT id, url, secret, events, location_id FROM Webhooks WHERE active = TRUE AND location_id = $1")
+		.bind(location_id)
+		.try_map(map_webhook)
+		.fetch_all(pool)
+		.await?
+		.into_iter()
+		.filter(|webhook| webhook.events.iter().any(|event| event == "*" || event == event_type))
+		.collect();
+	Ok(Some(webhooks))
+}
+
+pub async fn retrieve_webhook(pool: &DbPool, webhook_id: i64) -> Result<models::Webhook> {
+	let webhook = sqlx::query("SELECT id, url, secret, events, location_id FROM Webhooks WHERE id = $1 AND active = TRUE")
+		.bind(webhook_id)
+		.try_map(map_webhook)
+		.fetch_optional(pool)
+		.await?;
+	Ok(webhook)
+}
+
+pub async fn create_webhook_delivery(pool: &DbPool, delivery: &models::WebhookDelivery) -> std::result::Result<(), sqlx::Error> {
+	sqlx::query(r#"
+		INSERT INTO WebhookDeliveries (webhook_id, event_id, event_type, attempt, status_code, error, succeeded, delivery_id)
+		VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+		"#)
+		.bind(delivery.webhook_id)
+		.bind(delivery.event_id)
+		.bind(&delivery.event_type)
+		.bind(i64::from(delivery.attempt))
+		.bind(delivery.status_code.map(i64::from))
+		.bind(&delivery.error)
+		.bind(delivery.succeeded)
+		.bind(&delivery.delivery_id)
+		.execute(pool)
+		.await?;
+	Ok(())
+}
+
+pub async fn create_dead_letter(pool: &DbPool, dead_letter: &models::DeadLetter) -> std::result::Result<(), sqlx::Error> {
+	sqlx::query(r#"
+		INSERT INTO WebhookDeadLetters (webhook_id, event_id, event_type, payload, attempts, last_error, delivery_id)
+		VALUES ($1, $2, $3, $4, $5, $6, $7)
+		"#)
+		.bind(dead_letter.webhook_id)
+		.bind(dead_letter.event_id)
+		.bind(&dead_letter.event_type)
+		.bind(&dead_letter.payload)
+		.bind(i64::from(dead_letter.attempts))
+		.bind(&dead_letter.last_error)
+		.bind(&dead_letter.delivery_id)
+		.execute(pool)
+		.await?;
+	Ok(())
+}
+
+/// Removes a dead letter so it can be delivered again. Those of removed
+/// webhooks, or of webhooks at other locations, are kept.
+pub async fn take_dead_letter(pool: &DbPool, location_id: i64, dead_letter_id: i64) -> Result<models::DeadLetter> {
+	let dead_letter = sqlx::query(r#"
+		DELETE FROM WebhookDeadLetters
+		WHERE id = $1
+		AND webhook_id IN (SELECT id FROM Webhooks WHERE active = TRUE AND location_id = $2)
+		RETURNING id, webhook_id, event_id, event_type, payload, attempts, last_error, timestamp, delivery_id
+		"#)
+		.bind(dead_letter_id)
+		.bind(location_id)
+		.try_map(map_dead_letter)
+		.fetch_optional(pool)
+		.await?;
+	Ok(dead_letter)
+}
+
+// The GraphQL loaders below also take the pool, and load the rows for many
+// keys with one query each.
+
+fn placeholders(count: usize) -> String {
+	(1..=count).map(|n| format!("${}", n)).collect::<Vec<String>>().join(", ")
+}
+
+pub async fn retrieve_menu(pool: &DbPool, location_id: i64, min_price: Option<u32>, max_price: Option<u32>) -> Result<Vec<models::Item>> {
+	let (min_price, max_price) = (min_price.map(i64::from), max_price.map(i64::from));
+	let items = checked_query_as!(models::ItemRow, r#"
+		SELECT id, preparation_time AS "preparation_time: u32", price, currency, name, station AS "station: models::Station", location_id
+		FROM Items
+		WHERE ($1 IS NULL OR price >= $1)
+		AND ($2 IS NULL OR price <= $2)
+		AND location_id = $3
+		ORDER BY id
+		"#, min_price, max_price, location_id)
+		.fetch_all(pool)
+		.await?
+		.into_iter()
+		.map(models::Item::try_from)
+		.collect::<std::result::Result<_, _>>()?;
+	Ok(Some(items))
+}
+
+pub async fn retrieve_items_by_id(pool: &DbPool, item_ids: &[i64]) -> Result<Vec<models::Item>> {
+	let sql = format!(r#"
+		SELECT id, preparation_time, price, currency, name, station, location_id
+		FROM Items
+		WHERE id IN ({})
+		"#, placeholders(item_ids.len()));
+	let mut query = sqlx::query_as::<_, models::Item>(&sql);
+	for item_id in item_ids {
+		query = query.bind(item_id);
+	}
+	let items = query
+		.fetch_all(pool)
+		.await?;
+	Ok(Some(items))
+}
+
+/// The orders of several sessions, without their items.
+pub async fn retrieve_orders_by_session(pool: &DbPool, table_session_ids: &[i64]) -> Result<Vec<models::Order>> {
+	let sql = format!(r#"
+		SELECT id, table_session_id, timestamp, staff_id, version
+		FROM Orders
+		WHERE table_session_id IN ({})
+		ORDER BY id
+		"#, placeholders(table_session_ids.len()));
+	let mut query = sqlx::query_as::<_, models::Order>(&sql);
+	for table_session_id in table_session_ids {
+		query = query.bind(table_session_id);
+	}
+	let orders = query
+		.fetch_all(pool)
+		.await?;
+	Ok(Some(orders))
+}
+
+/// The items of several orders, each with the id of its order, on a
+/// connection or the pool.
+pub async fn retrieve_order_items_by_order<'c, E>(executor: E, order_ids: &[i64]) -> Result<Vec<(i64, models::OrderItem)>>
+where E: sqlx::Executor<'c, Database = Backend> {
+	let sql = format!(r#"
+		SELECT order_id, item_id, amount, course, fired_at IS NULL AS held
+		FROM OrderItems, Items
+		WHERE Items.id = OrderItems.item_id
+		AND order_id IN ({})
+		ORDER BY OrderItems.id
+		"#, placeholders(order_ids.len()));
+	let mut query = sqlx::query(&sql);
+	for order_id in order_ids {
+		query = query.bind(order_id);
+	}
+	let items = query
+		.try_map(|record: DbRow| Ok((record.try_get("order_id")?, models::OrderItem::from_row(&record)?)))
+		.fetch_all(executor)
+		.await?;
+	Ok(Some(items))
+}
+
+/// The error code of an insert breaking a unique index.
+#[cfg(not(feature = "postgres"))]
+const UNIQUE_VIOLATION: &str = "2067"; // SQLITE_CONSTRAINT_UNIQUE
+#[cfg(feature = "postgres")]
+const UNIQUE_VIOLATION: &str = "23505"; // unique_violation
+
+/// Whether an insert broke a unique index, like the one allowing a single
+/// active session per table.
+pub fn is_unique_violation(e: &sqlx::Error) -> bool {
+	match e {
+		sqlx::Error::Database(e) => e.code().as_deref() == Some(UNIQUE_VIOLATION),
+		_ => false,
+	}
+}
+
+/// Looks a table up by name.
+#[cfg(not(feature = "postgres"))]
+const FIND_TABLE: &str = "SELECT name FROM sqlite_master WHERE type = 'table' AND name = $1";
+#[cfg(feature = "postgres")]
+const FIND_TABLE: &str = "SELECT table_name FROM information_schema.tables WHERE table_schema = current_schema() AND table_name = lower($1)";
+
+/// Tables that have more than one active session, with the session ids from
+/// oldest to newest.
+pub async fn retrieve_duplicate_sessions(pool: &DbPool) -> std::result::Result<Vec<(u8, Vec<i64>)>, sqlx::Error> {
+	let tables = sqlx::query(FIND_TABLE)
+		.bind("TableSessions")
+		.fetch_optional(pool)
+		.await?;
+	if tables.is_none() {
+		return Ok(Vec::new());
+	}
+	let sessions: Vec<(u8, i64)> = sqlx::query(r#"
+		SELECT table_nr, id
+		FROM TableSessions
+		WHERE active = TRUE
+		AND table_nr IN (SELECT table_nr FROM TableSessions WHERE active = TRUE GROUP BY table_nr HAVING COUNT(*) > 1)
+		ORDER BY table_nr, id
+		"#)
+		.try_map(|record: DbRow| Ok((get_int(&record, "table_nr")?, record.try_get("id")?)))
+		.fetch_all(pool)
+		.await?;
+	let mut duplicates: Vec<(u8, Vec<i64>)> = Vec::new();
+	for (table_nr, id) in sessions {
+		match duplicates.last_mut() {
+			Some((last_table_nr, ids)) if *last_table_nr == table_nr => ids.push(id),
+			_ => duplicates.push((table_nr, vec![id])),
+		}
+	}
+	Ok(duplicates)
+}
+
+/// Merges `duplicates` into the session `keep`: their orders, discounts,
+/// payments and course states move over and they are closed.
+pub async fn merge_sessions(pool: &DbPool, keep: i64, duplicates: &[i64]) -> std::result::Result<(), sqlx::Error> {
+	let mut tx = pool.begin().await?;
+	for duplicate in duplicates {
+		for statement in [
+			"UPDATE Orders SET table_session_id = $1 WHERE table_session_id = $2",
+			"UPDATE AppliedDiscounts SET table_session_id = $1 WHERE table_session_id = $2",
+			"UPDATE Payments SET table_session_id = $1 WHERE table_session_id = $2",
+			"INSERT INTO SessionCourses (table_session_id, course, fired_at) SELECT $1, course, fired_at FROM SessionCourses WHERE table_session_id = $2 ON CONFLICT DO NOTHING",
+			"UPDATE TableSessions SET customers = (SELECT MAX(customers) FROM TableSessions WHERE id IN ($1, $2)) WHERE id = $1",
+		] {
+			sqlx::query(statement)
+				.bind(keep)
+				.bind(duplicate)
+				.execute(&mut tx)
+				.await?;
+		}
+		sqlx::query("DELETE FROM SessionCourses WHERE table_session_id = $1")
+			.bind(duplicate)
+			.execute(&mut tx)
+			.await?;
+		sqlx::query(r#"
+			UPDATE TableSessions
+			SET active = FALSE, session_end = $1, version = version + 1
+			WHERE id = $2
+			"#)
+			.bind(now())
+			.bind(duplicate)
+			.execute(&mut tx)
+			.await?;
+	}
+	tx.commit().await
+}
+
+/// Reconciles tables with several active sessions, which could be opened
+/// before the database allowed only one, by merging each table's sessions
+/// into its oldest. Runs with the migrations until the index exists and
+/// returns how many sessions were merged away.
+pub async fn repair_duplicate_sessions(pool: &DbPool) -> std::result::Result<usize, sqlx::Error> {
+	let mut merged = 0;
+	for (table_nr, ids) in retrieve_duplicate_sessions(pool).await? {
+		let (keep, duplicates) = ids.split_first().expect("duplicates have at least two sessions");
+		rocket::warn!("Table #{} has {} active sessions, merging {:?} into {}", table_nr, ids.len(), duplicates, keep);
+		merge_sessions(pool, *keep, duplicates).await?;
+		merged += duplicates.len();
+	}
+	Ok(merged)
+}
+
+/// The migration adding the index that allows one active session per table.
+const ONE_ACTIVE_SESSION_MIGRATION: i64 = 20261019097000;
+
+/// The migrations of the database in use. Postgres has its own copy of each
+/// one, with the same version.
+fn migrator() -> sqlx::migrate::Migrator {
+	#[cfg(not(feature = "postgres"))]
+	let migrator = sqlx::migrate!();
+	#[cfg(feature = "postgres")]
+	let migrator = sqlx::migrate!("migrations/postgres");
+	migrator
+}
+
+async fn migrate_before_index(pool: &DbPool) -> std::result::Result<(), sqlx::migrate::MigrateError> {
+	let mut before_index = migrator();
+	before_index.migrations = before_index.migrations.iter()
+		.filter(|migration| migration.version < ONE_ACTIVE_SESSION_MIGRATION)
+		.cloned()
+		.collect();
+	before_index.set_ignore_missing(true);
+	before_index.run(pool).await
+}
+
+async fn migration_applied(pool: &DbPool, version: i64) -> std::result::Result<bool, sqlx::Error> {
+	let applied = sqlx::query("SELECT version FROM _sqlx_migrations WHERE version = $1 AND success = TRUE")
+		.bind(version)
+		.fetch_optional(pool)
+		.await?;
+	Ok(applied.is_some())
+}
+
+async fn migrate(pool: &DbPool) -> std::result::Result<(), String> {
+	// Sessions duplicated before the index existed are merged once the rest of
+	// the schema is in place, or the index could not be created.
+	migrate_before_index(pool).await.map_err(|e| e.to_string())?;
+	if !migration_applied(pool, ONE_ACTIVE_SESSION_MIGRATION).await.map_err(|e| e.to_string())? {
+		repair_duplicate_sessions(pool).await.map_err(|e| format!("Unable to repair duplicate sessions: {}", e))?;
+	}
+	migrator().run(pool).await.map_err(|e| e.to_string())
+}
+
+async fn run_migrations(rocket: Rocket<Build>) -> rocket::fairing::Result {
+    let result = match Db::fetch(&rocket) {
+        Some(db) => migrate(db).await,
+        None => return Err(rocket),
+    };
+    match result {
+        Ok(_) => {
+			Ok(rocket)
+		},
+        Err(e) => {
+            rocket::error!("Failed to initialize SQLx database: {}", e);
+            Err(rocket)
         }
-        None => Err(rocket),
     }
 }
 
+/// URL schemes of the database this build talks to.
+#[cfg(not(feature = "postgres"))]
+const URL_SCHEMES: [&str; 1] = ["sqlite:"];
+#[cfg(feature = "postgres")]
+const URL_SCHEMES: [&str; 2] = ["postgres:", "postgresql:"];
+
+/// Stops the launch when `databases.order_db.url` is for another database
+/// than the one built in.
+async fn check_backend(rocket: Rocket<Build>) -> rocket::fairing::Result {
+	let url: String = rocket.figment().extract_inner("databases.order_db.url").unwrap_or_default();
+	if URL_SCHEMES.iter().any(|scheme| url.starts_with(scheme)) {
+		Ok(rocket)
+	}
+	else {
+		rocket::error!("databases.order_db.url has to start with {} in this build, Postgres needs the `postgres` feature", URL_SCHEMES.join(" or "));
+		Err(rocket)
+	}
+}
+
 pub fn stage() -> AdHoc {
 	AdHoc::on_ignite("Stage database", |rocket| async {
-		rocket.attach(Db::init())
+		rocket.attach(AdHoc::try_on_ignite("Database backend", check_backend))
+		.attach(Db::init())
 		.attach(AdHoc::try_on_ignite("Database migrations", run_migrations))
+		.attach(crate::repository::stage())
 	})
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+	use rocket_db_pools::sqlx;
+	use sqlx::prelude::Row;
+	use super::{DbPool, DbRow};
+	use crate::testing::TestDatabase;
+
+	async fn pool(database: &TestDatabase) -> DbPool {
+		DbPool::connect(database.url()).await.unwrap()
+	}
+
+	#[rocket::async_test]
+	async fn duplicate_sessions_are_merged()
+	{
+		let database = TestDatabase::new();
+		let pool = pool(&database).await;
+		super::migrate_before_index(&pool).await.unwrap();
+
+		// What the race left behind before the unique index existed.
+		let mut ids = Vec::new();
+		for customers in [2_i64, 3] {
+			let id: i64 = sqlx::query("INSERT INTO TableSessions (table_nr, customers) VALUES (38, $1) RETURNING id")
+				.bind(customers)
+				.fetch_one(&pool).await.unwrap()
+				.get(0);
+			ids.push(id);
+		}
+		sqlx::query("INSERT INTO Orders (table_session_id) VALUES ($1)")
+			.bind(ids[1])
+			.execute(&pool).await.unwrap();
+
+		assert_eq!(super::retrieve_duplicate_sessions(&pool).await.unwrap(), vec![(38, ids.clone())]);
+		super::migrate(&pool).await.unwrap();
+		assert!(super::retrieve_duplicate_sessions(&pool).await.unwrap().is_empty());
+
+		let sessions: Vec<(i64, i64)> = sqlx::query("SELECT id, customers FROM TableSessions WHERE active = TRUE")
+			.map(|record: DbRow| (record.get(0), record.get(1)))
+			.fetch_all(&pool).await.unwrap();
+		assert_eq!(sessions, vec![(ids[0], 3)]);
+		let order_session: i64 = sqlx::query("SELECT table_session_id FROM Orders")
+			.map(|record: DbRow| record.get(0))
+			.fetch_one(&pool).await.unwrap();
+		assert_eq!(order_session, ids[0]);
+
+		let result = sqlx::query("INSERT INTO TableSessions (table_nr, customers) VALUES (38, 1)")
+			.execute(&pool).await;
+		assert!(super::is_unique_violation(&result.unwrap_err()));
+	}
+
+	#[rocket::async_test]
+	async fn open_signals_are_raised_once()
+	{
+		let database = TestDatabase::new();
+		let pool = pool(&database).await;
+		super::migrate(&pool).await.unwrap();
+		let session_id: i64 = sqlx::query("INSERT INTO TableSessions (table_nr, customers) VALUES (40, 2) RETURNING id")
+			.fetch_one(&pool).await.unwrap()
+			.get(0);
+
+		let (mut first, mut second) = (pool.acquire().await.unwrap(), pool.acquire().await.unwrap());
+		let kind = super::models::SignalKind::CallServer;
+		let (a, b) = rocket::tokio::join!(
+			super::create_signal(&mut first, session_id, kind, None),
+			super::create_signal(&mut second, session_id, kind, None)
+		);
+		let ((a, a_raised), (b, b_raised)) = (a.unwrap().unwrap(), b.unwrap().unwrap());
+		assert_eq!(a.id, b.id);
+		assert!(a_raised != b_raised);
+	}
+
+	#[rocket::async_test]
+	async fn rows_are_mapped_by_column_name()
+	{
+		let database = TestDatabase::new();
+		let pool = pool(&database).await;
+		super::migrate(&pool).await.unwrap();
+
+		let menu = super::retrieve_menu(&pool, 1, None, None).await.unwrap().unwrap();
+		let reordered = sqlx::query_as::<_, super::models::Item>("SELECT station, currency, name, price, preparation_time, location_id, id FROM Items ORDER BY id")
+			.fetch_all(&pool).await.unwrap();
+		assert_eq!(menu.len(), reordered.len());
+		for (item, other) in menu.iter().zip(&reordered) {
+			assert_eq!((item.id, item.preparation_time, item.price, &item.name, item.station), (other.id, other.preparation_time, other.price, &other.name, other.station));
+		}
+		assert!(menu.iter().any(|item| item.price.amount != i64::from(item.preparation_time)));
+		assert!(menu.iter().all(|item| item.price_yen == Some(item.price.amount as u32)));
+	}
+
+	/// Compares `retrieve_orders` with loading each order's items separately,
+	/// as it used to, on a busy session. Run with
+	/// `cargo test --release retrieve_orders_benchmark -- --ignored --nocapture`.
+	#[rocket::async_test]
+	#[ignore]
+	async fn retrieve_orders_benchmark()
+	{
+		const ORDERS: i64 = 200;
+		const ITEMS_PER_ORDER: i64 = 6;
+		const RUNS: u32 = 50;
+
+		let database = TestDatabase::new();
+		let pool = pool(&database).await;
+		super::migrate(&pool).await.unwrap();
+		let mut tx = pool.begin().await.unwrap();
+		let session_id: i64 = sqlx::query("INSERT INTO TableSessions (table_nr, customers) VALUES (1, 4) RETURNING id")
+			.fetch_one(&mut tx).await.unwrap()
+			.get(0);
+		for _ in 0..ORDERS {
+			let order_id: i64 = sqlx::query("INSERT INTO Orders (table_session_id) VALUES ($1) RETURNING id")
+				.bind(session_id)
+				.fetch_one(&mut tx).await.unwrap()
+				.get(0);
+			for item_id in 1..=ITEMS_PER_ORDER {
+				sqlx::query("INSERT INTO OrderItems (order_id, item_id, amount) VALUES ($1, $2, 1)")
+					.bind(order_id)
+					.bind(item_id)
+					.execute(&mut tx).await.unwrap();
+			}
+		}
+		tx.commit().await.unwrap();
+
+		let page = super::PageRequest::new(Some(ORDERS as u32), None, None, &[("id", "id")]).unwrap();
+		let mut db = pool.acquire().await.unwrap();
+
+		let start = std::time::Instant::now();
+		let mut orders = Vec::new();
+		for _ in 0..RUNS {
+			orders = super::retrieve_orders(&mut db, session_id, &page, None).await.unwrap().unwrap();
+		}
+		let batched = start.elapsed() / RUNS;
+
+		let start = std::time::Instant::now();
+		let mut per_order = Vec::new();
+		for _ in 0..RUNS {
+			per_order = Vec::new();
+			let order_ids: Vec<i64> = sqlx::query("SELECT id FROM Orders WHERE table_session_id = $1 ORDER BY id")
+				.bind(session_id)
+				.map(|record: DbRow| record.get(0))
+				.fetch_all(&mut db).await.unwrap();
+			for order_id in order_ids {
+				let items: Vec<i64> = sqlx::query("SELECT Items.id FROM Items, OrderItems WHERE Items.id = OrderItems.item_id AND OrderItems.order_id = $1 ORDER BY OrderItems.id")
+					.bind(order_id)
+					.map(|record: DbRow| record.get(0))
+					.fetch_all(&mut db).await.unwrap();
+				per_order.push(items);
+			}
+		}
+		let unbatched = start.elapsed() / RUNS;
+
+		println!("{} orders of {} items: {:?} batched, {:?} with a query per order", ORDERS, ITEMS_PER_ORDER, batched, unbatched);
+		assert_eq!(orders.len(), ORDERS as usize);
+		assert_eq!(orders.iter().map(|order| order.order_items.iter().map(|item| item.item_id).collect::<Vec<i64>>()).collect::<Vec<_>>(), per_order);
+		assert!(batched < unbatched);
+	}
+}
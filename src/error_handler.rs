@@ -5,9 +5,14 @@ use rocket::response::Response;
 use rocket::http::{Status, ContentType};
 use simplelog::{ WriteLogger, LevelFilter, Config};
 use log::error;
+use crate::i18n;
+use crate::models;
 
+/// Logs to `log_file` from `Rocket.toml` or `ROCKET_LOG_FILE`, `server.log`
+/// by default.
 pub fn initialize_logger() {
-    let _ = WriteLogger::init(LevelFilter::Info, Config::default(), File::create("server.log").unwrap());
+    let log_file = rocket::Config::figment().extract_inner::<String>("log_file").unwrap_or_else(|_| String::from("server.log"));
+    let _ = WriteLogger::init(LevelFilter::Info, Config::default(), File::create(log_file).unwrap());
 }
 
 #[derive(Error, Debug)]
@@ -20,19 +25,69 @@ pub enum Error {
     Json {
         #[from] source: rocket::error::Error
     },
-    #[error("API Error: {msg:?}")]
+    #[error("API Error: {msg}")]
     Api {
-        msg: String
+        msg: i18n::Message
+    },
+    #[error("Forbidden: {msg}")]
+    Forbidden {
+        msg: i18n::Message
+    },
+    #[error("Conflict: {msg}")]
+    Conflict {
+        msg: i18n::Message
+    },
+    #[error("Unprocessable: {msg}")]
+    Unprocessable {
+        msg: i18n::Message
+    },
+    #[error("Precondition failed: {msg}")]
+    PreconditionFailed {
+        msg: i18n::Message
+    },
+    #[error("Precondition required: {msg}")]
+    PreconditionRequired {
+        msg: i18n::Message
+    },
+    #[error("Too many requests: {msg}")]
+    TooManyRequests {
+        msg: i18n::Message
+    }
+}
+
+impl Error {
+    /// What went wrong, for the client. Database and JSON errors are only
+    /// logged.
+    pub fn message(&self) -> Option<&i18n::Message> {
+        match self {
+            Error::Sql { .. } | Error::Json { .. } => None,
+            Error::Api { msg } | Error::Forbidden { msg } | Error::Conflict { msg } | Error::Unprocessable { msg }
+                | Error::PreconditionFailed { msg } | Error::PreconditionRequired { msg } | Error::TooManyRequests { msg } => Some(msg)
+        }
     }
 }
 
 impl<'r, 'o: 'r> rocket::response::Responder<'r, 'o> for Error {
-    fn respond_to(self, _: &'r rocket::Request<'_>) -> rocket::response::Result<'o> {
+    fn respond_to(self, req: &'r rocket::Request<'_>) -> rocket::response::Result<'o> {
         {
             error!("{}", self);
-            let body = "{\"status\": \"failed\"}";
+            let status = match self {
+                Error::Forbidden { .. } => Status::Forbidden,
+                Error::Conflict { .. } => Status::Conflict,
+                Error::Unprocessable { .. } => Status::UnprocessableEntity,
+                Error::PreconditionFailed { .. } => Status::PreconditionFailed,
+                Error::PreconditionRequired { .. } => Status::PreconditionRequired,
+                Error::TooManyRequests { .. } => Status::TooManyRequests,
+                _ => Status::InternalServerError
+            };
+            let message = self.message().map(|msg| match req.rocket().state::<i18n::Catalogs>() {
+                Some(catalogs) => catalogs.translate(i18n::Locale::of(req), msg),
+                None => msg.to_string()
+            });
+            let failure = models::Failure { status: String::from("failed"), message };
+            let body = rocket::serde::json::to_string(&failure).map_err(|_| Status::InternalServerError)?;
             Response::build()
-                .status(Status::InternalServerError)
+                .status(status)
                 .header(ContentType::JSON)
                 .sized_body(body.len(), Cursor::new(body))
                 .ok()
@@ -0,0 +1,83 @@
+use rocket::http::Header;
+use rocket::request::{self, FromRequest, Request};
+use rocket::response::{self, Responder};
+use rocket::serde::json::Json;
+use rocket::serde::Serialize;
+use crate::error_handler::Error;
+use crate::i18n::{message, Message};
+
+/// The `ETag` of a versioned row. It includes the id because URLs like
+/// `/tables/<table_nr>/active` point at another session once one ends.
+pub fn etag(id: i64, version: i64) -> String {
+	format!("\"{}-{}\"", id, version)
+}
+
+fn parse(tag: &str) -> Option<(i64, i64)> {
+	let tag = tag.trim().trim_start_matches("W/").strip_prefix('"')?.strip_suffix('"')?;
+	let (id, version) = tag.split_once('-')?;
+	Some((id.parse().ok()?, version.parse().ok()?))
+}
+
+/// A JSON response with an `ETag` header.
+pub struct Tagged<T>(pub Json<T>, pub String);
+
+impl<'r, T: Serialize> Responder<'r, 'static> for Tagged<T> {
+	fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+		rocket::Response::build_from(self.0.respond_to(request)?)
+			.header(Header::new("ETag", self.1))
+			.ok()
+	}
+}
+
+/// The `If-Match` header mutations must send with the `ETag` they last saw,
+/// or `*` to overwrite whatever is there.
+pub struct IfMatch(Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IfMatch {
+	type Error = ();
+
+	async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, ()> {
+		request::Outcome::Success(IfMatch(request.headers().get_one("If-Match").map(String::from)))
+	}
+}
+
+/// Why a conditional change was refused.
+pub enum Precondition {
+	Required,
+	Failed(Message)
+}
+
+impl From<Precondition> for Error {
+	fn from(precondition: Precondition) -> Self {
+		match precondition {
+			Precondition::Required => Error::PreconditionRequired{
+				msg: message!("Changes need an If-Match header")
+			},
+			Precondition::Failed(msg) => Error::PreconditionFailed{ msg },
+		}
+	}
+}
+
+impl IfMatch {
+	/// The version the client expects the row `id` to be at, to make the
+	/// change conditional on. A missing header is a 428 and a tag for another
+	/// row or an older version a 412.
+	pub fn expected_version(&self, id: i64, current_version: i64) -> Result<i64, Precondition> {
+		let header = match &self.0 {
+			Some(header) => header,
+			None => return Err(Precondition::Required),
+		};
+		for tag in header.split(',') {
+			if tag.trim() == "*" {
+				return Ok(current_version);
+			}
+			if let Some((tag_id, version)) = parse(tag) {
+				if tag_id == id && version == current_version {
+					return Ok(version);
+				}
+			}
+		}
+		Err(Precondition::Failed(message!("{} does not match the current version {}", header, etag(id, current_version))))
+	}
+}
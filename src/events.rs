@@ -0,0 +1,190 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use rocket::fairing::AdHoc;
+use rocket::request::{self, FromRequest, Request};
+use rocket::tokio::sync::broadcast;
+use crate::models::{Event, EventData, Station};
+
+/// How many past events are kept for subscribers resuming after a reconnect.
+const HISTORY: usize = 1000;
+
+/// How many events a subscriber can fall behind by, a power of two like the
+/// channel rounds it to. Fewer than the history, so one that falls behind
+/// can still catch up from it.
+const BUFFER: usize = 256;
+
+/// Managed state publishing events to every `GET /events` subscriber. Clones
+/// publish to the same subscribers.
+#[derive(Clone)]
+pub struct Events {
+	sender: broadcast::Sender<Event>,
+	history: Arc<Mutex<(u64, VecDeque<Event>)>>
+}
+
+/// The events kept for a resuming subscriber. `reset` is set when the id it
+/// resumes after is not one of this process's, because it is from before a
+/// restart or older than the kept events, so it may have missed more than
+/// `events` and should reload what it shows. `latest` is the id of the newest
+/// event when subscribing; the receiver gets the events after it.
+pub struct Missed {
+	pub events: Vec<Event>,
+	pub reset: bool,
+	pub latest: u64
+}
+
+impl Events {
+	/// Event ids continue from the time the process started, in microseconds,
+	/// so they keep increasing across restarts and an id from before one is
+	/// never mistaken for a new event. They stay below 2^53 for JavaScript.
+	pub fn new() -> Self {
+		let (sender, _) = broadcast::channel(BUFFER);
+		let boot = u64::try_from(chrono::Utc::now().timestamp_micros()).unwrap_or(0);
+		Events { sender, history: Arc::new(Mutex::new((boot, VecDeque::with_capacity(HISTORY)))) }
+	}
+
+	/// Numbers the event and sends it to the current subscribers.
+	pub fn publish(&self, location_id: i64, table_nr: u8, stations: Vec<Station>, data: EventData) -> Event {
+		let mut history = self.history.lock().expect("event history lock");
+		history.0 += 1;
+		let event = Event { id: history.0, location_id, table_nr, stations, data };
+		if history.1.len() == HISTORY {
+			history.1.pop_front();
+		}
+		history.1.push_back(event.clone());
+		// Sending fails only when nobody is listening.
+		let _ = self.sender.send(event.clone());
+		event
+	}
+
+	/// Subscribes to new events, returning the kept events after `last_event_id`
+	/// along with the receiver. Both are taken under the history lock, so the
+	/// receiver picks up exactly where the replayed events end.
+	pub fn subscribe(&self, last_event_id: Option<u64>) -> (Missed, broadcast::Receiver<Event>) {
+		let history = self.history.lock().expect("event history lock");
+		let receiver = self.sender.subscribe();
+		let missed = match last_event_id {
+			Some(last_event_id) => {
+				let first = history.1.front().map_or(history.0, |event| event.id - 1);
+				Missed {
+					events: history.1.iter().filter(|event| event.id > last_event_id).cloned().collect(),
+					reset: last_event_id < first || last_event_id > history.0,
+					latest: history.0
+				}
+			},
+			None => Missed { events: Vec::new(), reset: false, latest: history.0 },
+		};
+		(missed, receiver)
+	}
+}
+
+impl Default for Events {
+	fn default() -> Self {
+		Events::new()
+	}
+}
+
+/// Which events a subscriber wants: those of its location, narrowed down by
+/// the `table` and `station` query parameters of `GET /events`.
+#[derive(Debug, Clone, Copy)]
+pub struct Filter {
+	pub location_id: i64,
+	pub table_nr: Option<u8>,
+	pub station: Option<Station>
+}
+
+impl Filter {
+	pub fn matches(&self, event: &Event) -> bool {
+		self.location_id == event.location_id
+			&& self.table_nr.is_none_or(|table_nr| table_nr == event.table_nr)
+			&& self.station.is_none_or(|station| event.stations.contains(&station))
+	}
+}
+
+/// The `Last-Event-ID` header browsers send when an `EventSource` reconnects.
+pub struct LastEventId(pub Option<u64>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for LastEventId {
+	type Error = ();
+
+	async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, ()> {
+		request::Outcome::Success(LastEventId(
+			request.headers().get_one("Last-Event-ID").and_then(|id| id.trim().parse().ok())
+		))
+	}
+}
+
+pub fn stage() -> AdHoc {
+	AdHoc::on_ignite("Events", |rocket| async {
+		rocket.manage(Events::new())
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use rocket::tokio::sync::broadcast::error::TryRecvError;
+	use super::{Events, Filter};
+	use crate::models::{EventData, Station};
+
+	#[test]
+	fn subscribers_resume_after_last_event_id()
+	{
+		let events = Events::new();
+		let opened = events.publish(1, 3, Vec::new(), EventData::SessionOpened { table_session_id: 1, customers: 2 });
+		let voided = events.publish(1, 4, vec![Station::Bar], EventData::OrderVoided { order_id: 5 });
+		let (missed, mut receiver) = events.subscribe(Some(opened.id));
+		assert_eq!(missed.events.len(), 1);
+		assert_eq!((missed.events[0].id, missed.reset), (voided.id, false));
+		assert!(!events.subscribe(Some(opened.id - 1)).0.reset);
+
+		events.publish(1, 3, Vec::new(), EventData::SessionClosed { table_session_id: 1 });
+		assert_eq!(receiver.try_recv().expect("live event").id, voided.id + 1);
+		assert!(events.subscribe(None).0.events.is_empty());
+	}
+
+	#[test]
+	fn lagging_subscribers_catch_up_from_the_history()
+	{
+		let events = Events::new();
+		let (subscribed, mut receiver) = events.subscribe(None);
+		for table_session_id in 0..=super::BUFFER as i64 {
+			events.publish(1, 3, Vec::new(), EventData::SessionOpened { table_session_id, customers: 2 });
+		}
+		assert!(matches!(receiver.try_recv(), Err(TryRecvError::Lagged(1))));
+		let (missed, _) = events.subscribe(Some(subscribed.latest));
+		assert!(!missed.reset);
+		assert_eq!(missed.events.len(), super::BUFFER + 1);
+		assert_eq!(missed.events[0].id, subscribed.latest + 1);
+	}
+
+	#[test]
+	fn ids_from_before_a_restart_reset_subscribers()
+	{
+		let before = Events::new();
+		let seen = before.publish(1, 3, Vec::new(), EventData::SessionOpened { table_session_id: 1, customers: 2 });
+		std::thread::sleep(std::time::Duration::from_millis(1));
+
+		let after = Events::new();
+		let opened = after.publish(1, 3, Vec::new(), EventData::SessionOpened { table_session_id: 2, customers: 4 });
+		assert!(opened.id > seen.id);
+		let (missed, _) = after.subscribe(Some(seen.id));
+		assert!(missed.reset);
+		assert_eq!(missed.events.len(), 1);
+		assert!(after.subscribe(Some(opened.id + 1)).0.reset);
+		assert!(!after.subscribe(Some(opened.id)).0.reset);
+	}
+
+	#[test]
+	fn filters_by_location_table_and_station()
+	{
+		let events = Events::new();
+		let opened = events.publish(1, 3, Vec::new(), EventData::SessionOpened { table_session_id: 1, customers: 2 });
+		let voided = events.publish(1, 4, vec![Station::Bar], EventData::OrderVoided { order_id: 5 });
+		let by_table = Filter { location_id: 1, table_nr: Some(3), station: None };
+		assert!(by_table.matches(&opened) && !by_table.matches(&voided));
+		let by_station = Filter { location_id: 1, table_nr: None, station: Some(Station::Bar) };
+		assert!(!by_station.matches(&opened) && by_station.matches(&voided));
+		let elsewhere = Filter { location_id: 2, table_nr: Some(3), station: None };
+		assert!(!elsewhere.matches(&opened));
+	}
+}
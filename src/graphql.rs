@@ -0,0 +1,495 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use async_graphql::dataloader::{DataLoader, Loader};
+use async_graphql::{Context, Enum, InputObject, Object, Schema, SimpleObject, Subscription};
+use rocket::fairing::AdHoc;
+use rocket::futures::stream::{self, Stream, StreamExt};
+use rocket::response::stream::{Event, EventStream};
+use rocket::serde::json::Json;
+use rocket::tokio::select;
+use rocket::tokio::sync::broadcast::error::RecvError;
+use rocket::{Shutdown, State};
+use rocket_db_pools::sqlx;
+use crate::error_handler::Error;
+use crate::i18n::message;
+use crate::{db, events, handlers, kitchen, models, repository, service, settings};
+
+pub type ApiSchema = Schema<Query, Mutation, Subscription>;
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+#[graphql(remote = "models::Station")]
+pub enum Station {
+	Grill,
+	Fryer,
+	Bar,
+	Dessert
+}
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+#[graphql(remote = "models::Course")]
+pub enum Course {
+	Starter,
+	Main,
+	Dessert
+}
+
+// Loaders collect the keys requested while resolving one level of a query
+// and load them with a single query, so a list of sessions with their
+// orders and items takes one query per level rather than one per row.
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct SessionId(i64);
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct OrderId(i64);
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct ItemId(i64);
+
+pub struct DbLoader(db::DbPool);
+
+impl Loader<SessionId> for DbLoader {
+	type Value = Vec<models::Order>;
+	type Error = Arc<sqlx::Error>;
+
+	async fn load(&self, keys: &[SessionId]) -> Result<HashMap<SessionId, Self::Value>, Self::Error> {
+		let ids: Vec<i64> = keys.iter().map(|key| key.0).collect();
+		let mut orders: HashMap<SessionId, Self::Value> = keys.iter().map(|key| (*key, Vec::new())).collect();
+		for order in db::retrieve_orders_by_session(&self.0, &ids).await?.unwrap_or_default() {
+			orders.entry(SessionId(order.table_session_id)).or_default().push(order);
+		}
+		Ok(orders)
+	}
+}
+
+impl Loader<OrderId> for DbLoader {
+	type Value = Vec<models::OrderItem>;
+	type Error = Arc<sqlx::Error>;
+
+	async fn load(&self, keys: &[OrderId]) -> Result<HashMap<OrderId, Self::Value>, Self::Error> {
+		let ids: Vec<i64> = keys.iter().map(|key| key.0).collect();
+		let mut items: HashMap<OrderId, Self::Value> = keys.iter().map(|key| (*key, Vec::new())).collect();
+		for (order_id, item) in db::retrieve_order_items_by_order(&self.0, &ids).await?.unwrap_or_default() {
+			items.entry(OrderId(order_id)).or_default().push(item);
+		}
+		Ok(items)
+	}
+}
+
+impl Loader<ItemId> for DbLoader {
+	type Value = models::Item;
+	type Error = Arc<sqlx::Error>;
+
+	async fn load(&self, keys: &[ItemId]) -> Result<HashMap<ItemId, Self::Value>, Self::Error> {
+		let ids: Vec<i64> = keys.iter().map(|key| key.0).collect();
+		Ok(db::retrieve_items_by_id(&self.0, &ids).await?.unwrap_or_default()
+			.into_iter()
+			.map(|item| (ItemId(item.id), item))
+			.collect())
+	}
+}
+
+/// An amount in the minor units of its currency, e.g. yen or cents.
+#[derive(SimpleObject)]
+pub struct Money {
+	amount: i64,
+	currency: String
+}
+
+pub struct Item(models::Item);
+
+#[Object]
+impl Item {
+	async fn id(&self) -> i64 {
+		self.0.id
+	}
+
+	async fn name(&self) -> &str {
+		&self.0.name
+	}
+
+	async fn price(&self) -> Money {
+		Money { amount: self.0.price.amount, currency: self.0.price.currency.to_string() }
+	}
+
+	/// The price for prices in yen, from before there were currencies.
+	async fn price_yen(&self) -> Option<u32> {
+		self.0.price_yen
+	}
+
+	/// In seconds.
+	async fn preparation_time(&self) -> u32 {
+		self.0.preparation_time
+	}
+
+	async fn station(&self) -> Station {
+		self.0.station.into()
+	}
+}
+
+pub struct OrderItem(models::OrderItem);
+
+#[Object]
+impl OrderItem {
+	async fn item_id(&self) -> i64 {
+		self.0.item_id
+	}
+
+	async fn amount(&self) -> u8 {
+		self.0.amount
+	}
+
+	async fn course(&self) -> Option<Course> {
+		self.0.course.map(Course::from)
+	}
+
+	/// Whether the item waits for its course to be fired.
+	async fn held(&self) -> bool {
+		self.0.held
+	}
+
+	async fn item(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<Item>> {
+		let loader = ctx.data::<DataLoader<DbLoader>>()?;
+		Ok(loader.load_one(ItemId(self.0.item_id)).await?.map(Item))
+	}
+}
+
+pub struct Order(models::Order);
+
+#[Object]
+impl Order {
+	async fn id(&self) -> Option<i64> {
+		self.0.id
+	}
+
+	async fn table_session_id(&self) -> i64 {
+		self.0.table_session_id
+	}
+
+	async fn timestamp(&self) -> &str {
+		&self.0.timestamp
+	}
+
+	async fn staff_id(&self) -> Option<i64> {
+		self.0.staff_id
+	}
+
+	async fn version(&self) -> i64 {
+		self.0.version
+	}
+
+	async fn items(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<OrderItem>> {
+		let loader = ctx.data::<DataLoader<DbLoader>>()?;
+		let items = match self.0.id {
+			Some(order_id) => loader.load_one(OrderId(order_id)).await?.unwrap_or_default(),
+			None => Vec::new(),
+		};
+		Ok(items.into_iter().map(OrderItem).collect())
+	}
+}
+
+/// The totals of a session's bill, in the minor units of its currency.
+#[derive(SimpleObject)]
+pub struct Bill {
+	currency: String,
+	subtotal: u32,
+	line_discounts: u32,
+	bill_discounts: u32,
+	service_charge: u32,
+	tax: u32,
+	total: u32,
+	paid: u32,
+	tips: u32,
+	balance_due: u32
+}
+
+impl From<models::Bill> for Bill {
+	fn from(bill: models::Bill) -> Self {
+		Bill {
+			currency: bill.currency.to_string(),
+			subtotal: bill.subtotal,
+			line_discounts: bill.line_discounts,
+			bill_discounts: bill.bill_discounts,
+			service_charge: bill.service_charge,
+			tax: bill.tax,
+			total: bill.total,
+			paid: bill.paid,
+			tips: bill.tips,
+			balance_due: bill.balance_due
+		}
+	}
+}
+
+pub struct TableSession(models::TableSession);
+
+#[Object]
+impl TableSession {
+	async fn id(&self) -> i64 {
+		self.0.id
+	}
+
+	async fn table_nr(&self) -> u8 {
+		self.0.table_nr
+	}
+
+	async fn customers(&self) -> u8 {
+		self.0.customers
+	}
+
+	async fn session_start(&self) -> &str {
+		&self.0.session_start
+	}
+
+	async fn session_end(&self) -> Option<&str> {
+		Some(self.0.session_end.as_str()).filter(|session_end| !session_end.is_empty())
+	}
+
+	async fn active(&self) -> bool {
+		self.0.active
+	}
+
+	async fn version(&self) -> i64 {
+		self.0.version
+	}
+
+	async fn orders(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<Order>> {
+		let loader = ctx.data::<DataLoader<DbLoader>>()?;
+		Ok(loader.load_one(SessionId(self.0.id)).await?.unwrap_or_default().into_iter().map(Order).collect())
+	}
+
+	async fn bill(&self, ctx: &Context<'_>) -> async_graphql::Result<Bill> {
+		let settings = ctx.data::<settings::Settings>()?;
+		let repository = ctx.data::<repository::Repository>()?;
+		Ok(service::bill(repository.as_ref(), &settings.billing(), &self.0).await?.into())
+	}
+}
+
+pub struct Query;
+
+#[Object]
+impl Query {
+	/// The location's menu.
+	async fn items(&self, ctx: &Context<'_>, min_price: Option<u32>, max_price: Option<u32>) -> async_graphql::Result<Vec<Item>> {
+		let pool = ctx.data::<db::DbPool>()?;
+		let location = ctx.data::<models::Location>()?;
+		Ok(db::retrieve_menu(pool, location.id, min_price, max_price).await?.unwrap_or_default().into_iter().map(Item).collect())
+	}
+
+	async fn item(&self, ctx: &Context<'_>, id: i64) -> async_graphql::Result<Option<Item>> {
+		let loader = ctx.data::<DataLoader<DbLoader>>()?;
+		let location = ctx.data::<models::Location>()?;
+		Ok(loader.load_one(ItemId(id)).await?.filter(|item| item.location_id == location.id).map(Item))
+	}
+
+	async fn active_sessions(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<TableSession>> {
+		let repository = ctx.data::<repository::Repository>()?;
+		let location = ctx.data::<models::Location>()?;
+		Ok(service::active_sessions(repository.as_ref(), location.id).await?.into_iter().map(TableSession).collect())
+	}
+
+	/// A session of the location by id; closed sessions are for managers.
+	async fn session(&self, ctx: &Context<'_>, id: i64) -> async_graphql::Result<TableSession> {
+		let staff = ctx.data::<models::Staff>()?;
+		let repository = ctx.data::<repository::Repository>()?;
+		let location = ctx.data::<models::Location>()?;
+		Ok(TableSession(service::readable_session(repository.as_ref(), location.id, staff, id).await?))
+	}
+}
+
+#[derive(InputObject)]
+pub struct OrderItemInput {
+	item_id: i64,
+	amount: u8,
+	course: Option<Course>
+}
+
+pub struct Mutation;
+
+#[Object]
+impl Mutation {
+	/// Orders for the table's active session, sent to the kitchen like
+	/// `POST /tables/<table_nr>/orders`.
+	async fn create_order(&self, ctx: &Context<'_>, table_nr: u8, items: Vec<OrderItemInput>) -> async_graphql::Result<Order> {
+		let staff = ctx.data::<models::Staff>()?;
+		let kitchen = ctx.data::<kitchen::Kitchen>()?;
+		let events = ctx.data::<events::Events>()?;
+		let order = models::Order {
+			order_items: items.into_iter()
+				.map(|item| models::OrderItem {
+					item_id: item.item_id,
+					amount: item.amount,
+					course: item.course.map(models::Course::from),
+					held: false
+				})
+				.collect(),
+			..Default::default()
+		};
+		let repository = ctx.data::<repository::Repository>()?;
+		let location = ctx.data::<models::Location>()?;
+		let settings = ctx.data::<settings::Settings>()?;
+		let order = handlers::create_table_order(repository.as_ref(), kitchen, events, settings, location.id, Some(staff.clone()), table_nr, &order).await?;
+		Ok(Order(order.into_inner()))
+	}
+}
+
+/// An order being created, changed or voided.
+pub struct OrderEvent(models::Event);
+
+#[Object]
+impl OrderEvent {
+	async fn id(&self) -> u64 {
+		self.0.id
+	}
+
+	async fn table_nr(&self) -> u8 {
+		self.0.table_nr
+	}
+
+	/// `order_created`, `order_updated` or `order_voided`.
+	#[graphql(name = "type")]
+	async fn kind(&self) -> &str {
+		match self.0.data {
+			models::EventData::OrderCreated { .. } => "order_created",
+			models::EventData::OrderUpdated { .. } => "order_updated",
+			_ => "order_voided",
+		}
+	}
+
+	async fn order_id(&self) -> Option<i64> {
+		match &self.0.data {
+			models::EventData::OrderCreated { order } => order.id,
+			models::EventData::OrderUpdated { order_id } | models::EventData::OrderVoided { order_id } => Some(*order_id),
+			_ => None,
+		}
+	}
+
+	/// The order as created; only set for `order_created`.
+	async fn order(&self) -> Option<Order> {
+		match &self.0.data {
+			models::EventData::OrderCreated { order } => Some(Order(order.clone())),
+			_ => None,
+		}
+	}
+}
+
+pub struct Subscription;
+
+#[Subscription]
+impl Subscription {
+	/// The location's order events, optionally for one table. A subscriber
+	/// that falls behind gets an error and the subscription ends, like
+	/// `GET /events` disconnects it, so it subscribes again and reloads what
+	/// it missed.
+	async fn order_events(&self, ctx: &Context<'_>, table_nr: Option<u8>) -> async_graphql::Result<impl Stream<Item = async_graphql::Result<OrderEvent>>> {
+		let location_id = ctx.data::<models::Location>()?.id;
+		let (_, receiver) = ctx.data::<events::Events>()?.subscribe(None);
+		Ok(stream::unfold(Some(receiver), move |receiver| async move {
+			let mut receiver = receiver?;
+			loop {
+				match receiver.recv().await {
+					Ok(event) if event.location_id == location_id && table_nr.is_none_or(|table_nr| table_nr == event.table_nr) && matches!(event.data,
+						models::EventData::OrderCreated { .. } | models::EventData::OrderUpdated { .. } | models::EventData::OrderVoided { .. }) => {
+						return Some((Ok(OrderEvent(event)), Some(receiver)));
+					},
+					Ok(_) => continue,
+					Err(RecvError::Lagged(missed)) => {
+						return Some((Err(format!("Missed {} events, subscribe again", missed).into()), None));
+					},
+					Err(RecvError::Closed) => return None,
+				}
+			}
+		}))
+	}
+}
+
+/// Queries and mutations. Fields resolve with the staff member's rights, at
+/// the location of the request.
+#[rocket::post("/graphql", data = "<request>")]
+#[utoipa::path(
+	post,
+	path = "/graphql",
+	tag = "graphql",
+	request_body(content = Object, description = "A GraphQL request: `query`, and optionally `variables` and `operationName`"),
+	responses(
+		(status = 200, description = "The GraphQL response, with `data` and any `errors`", body = Object)
+	),
+	security(("staff_token" = []))
+)]
+pub async fn graphql(schema: &State<ApiSchema>, pool: &State<db::Db>, repository: &State<repository::Repository>, location: models::Location, staff: models::Staff, request: Json<async_graphql::Request>) -> Json<async_graphql::Response> {
+	let pool: db::DbPool = (***pool).clone();
+	let request = request.into_inner()
+		.data(DataLoader::new(DbLoader(pool.clone()), rocket::tokio::spawn))
+		.data(pool)
+		.data(repository.inner().clone())
+		.data(location)
+		.data(staff);
+	Json(schema.execute(request).await)
+}
+
+/// Subscriptions, as server-sent events carrying a GraphQL response each.
+#[rocket::get("/graphql/stream?<query>&<variables>")]
+#[utoipa::path(
+	get,
+	path = "/graphql/stream",
+	tag = "graphql",
+	params(
+		("query" = String, Query, description = "A GraphQL subscription"),
+		("variables" = Option<String>, Query, description = "Its variables, as a JSON object")
+	),
+	responses(
+		(status = 200, description = "Server-sent events, a GraphQL response each", body = Object, content_type = "text/event-stream"),
+		(status = 500, description = "Failed", body = models::Failure)
+	),
+	security(("staff_token" = []))
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn graphql_stream(schema: &State<ApiSchema>, pool: &State<db::Db>, repository: &State<repository::Repository>, location: models::Location, staff: models::Staff, mut shutdown: Shutdown, query: String, variables: Option<&str>) -> std::result::Result<EventStream![], Error> {
+	let variables = match variables {
+		Some(variables) => rocket::serde::json::from_str(variables).map_err(|e| Error::Api{
+			msg: message!("Invalid variables: {}", e)
+		})?,
+		None => async_graphql::Variables::default(),
+	};
+	let pool: db::DbPool = (***pool).clone();
+	let request = async_graphql::Request::new(query)
+		.variables(variables)
+		.data(DataLoader::new(DbLoader(pool.clone()), rocket::tokio::spawn))
+		.data(pool)
+		.data(repository.inner().clone())
+		.data(location)
+		.data(staff);
+	let mut responses = schema.inner().clone().execute_stream(request);
+	Ok(EventStream! {
+		loop {
+			let response = select! {
+				response = responses.next() => match response {
+					Some(response) => response,
+					None => break,
+				},
+				_ = &mut shutdown => break,
+			};
+			yield Event::json(&response);
+		}
+	})
+}
+
+/// The schema holds the settings, kitchen and event bus, so it is built after
+/// their stages.
+pub fn stage() -> AdHoc {
+	AdHoc::try_on_ignite("GraphQL", |rocket| async {
+		let (settings, kitchen, events) = match (rocket.state::<settings::Settings>(), rocket.state::<kitchen::Kitchen>(), rocket.state::<events::Events>()) {
+			(Some(settings), Some(kitchen), Some(events)) => (settings.clone(), kitchen.clone(), events.clone()),
+			_ => {
+				rocket::error!("GraphQL needs the settings, kitchen and events stages");
+				return Err(rocket);
+			}
+		};
+		let schema: ApiSchema = Schema::build(Query, Mutation, Subscription)
+			.data(settings)
+			.data(kitchen)
+			.data(events)
+			.finish();
+		Ok(rocket.manage(schema)
+		.mount(handlers::API_BASE, rocket::routes![graphql, graphql_stream]))
+	})
+}
@@ -0,0 +1,207 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use hmac::{Hmac, Mac};
+use rocket::fairing::AdHoc;
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::serde::Deserialize;
+use sha2::Sha256;
+use crate::models;
+use crate::repository;
+use crate::webhooks;
+
+/// The `[default.guests]` table of `Rocket.toml`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(crate="rocket::serde", default)]
+pub struct GuestConfig {
+	/// Signs table tokens. When it's empty one is generated at launch, and
+	/// tokens stop working when the server restarts.
+	pub secret: String,
+	/// How long a table token works, unless its session ends before.
+	pub token_minutes: u32,
+	/// Requests of a table token a minute, of which `orders_per_minute` may
+	/// place orders.
+	pub requests_per_minute: u32,
+	pub orders_per_minute: u32,
+	/// Holds the first order guests place at a table until staff approve it.
+	pub approve_first_order: bool
+}
+
+impl Default for GuestConfig {
+	fn default() -> Self {
+		GuestConfig {
+			secret: String::new(),
+			token_minutes: 240,
+			requests_per_minute: 60,
+			orders_per_minute: 3,
+			approve_first_order: false
+		}
+	}
+}
+
+/// What a table token is limited in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Limit {
+	Requests,
+	Orders
+}
+
+/// Managed state signing table tokens and keeping their rate limits.
+pub struct Guests {
+	config: GuestConfig,
+	recent: Mutex<HashMap<(i64, Limit), VecDeque<Instant>>>
+}
+
+fn sign(secret: &str, payload: &str) -> Hmac<Sha256> {
+	let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+	mac.update(payload.as_bytes());
+	mac
+}
+
+impl Guests {
+	pub fn new(mut config: GuestConfig) -> Self {
+		if config.secret.is_empty() {
+			config.secret = webhooks::generate_secret();
+		}
+		Guests { config, recent: Mutex::new(HashMap::new()) }
+	}
+
+	pub fn approves_first_order(&self) -> bool {
+		self.config.approve_first_order
+	}
+
+	/// A token for the guests of a session, `<session id>.<expiry>.<signature>`
+	/// with the expiry in seconds since the epoch, and when it expires.
+	pub fn token(&self, table_session_id: i64, now: i64) -> models::GuestToken {
+		let expires = now + i64::from(self.config.token_minutes) * 60;
+		let payload = format!("{}.{}", table_session_id, expires);
+		let signature = hex::encode(sign(&self.config.secret, &payload).finalize().into_bytes());
+		models::GuestToken {
+			token: format!("{}.{}", payload, signature),
+			expires_at: chrono::DateTime::from_timestamp(expires, 0).unwrap_or_default().format("%Y-%m-%d %H:%M:%S").to_string()
+		}
+	}
+
+	/// The session of a token that is signed by us and not expired yet.
+	pub fn verify(&self, token: &str, now: i64) -> Option<i64> {
+		let (payload, signature) = token.rsplit_once('.')?;
+		let (table_session_id, expires) = payload.split_once('.')?;
+		sign(&self.config.secret, payload).verify_slice(&hex::decode(signature).ok()?).ok()?;
+		let expires: i64 = expires.parse().ok()?;
+		(now < expires).then_some(table_session_id.parse().ok()?)
+	}
+
+	/// Counts a request of the session's guests, unless they already made as
+	/// many as they may in the last minute.
+	pub fn allow(&self, table_session_id: i64, limit: Limit) -> bool {
+		let allowed = match limit {
+			Limit::Requests => self.config.requests_per_minute,
+			Limit::Orders => self.config.orders_per_minute
+		};
+		let now = Instant::now();
+		let mut recent = self.recent.lock().expect("rate limits lock");
+		recent.retain(|_, times| times.back().is_some_and(|last| now.duration_since(*last) < Duration::from_secs(60)));
+		let times = recent.entry((table_session_id, limit)).or_default();
+		while times.front().is_some_and(|first| now.duration_since(*first) >= Duration::from_secs(60)) {
+			times.pop_front();
+		}
+		if times.len() >= allowed as usize {
+			return false;
+		}
+		times.push_back(now);
+		true
+	}
+
+	/// Gives back the last counted request of the session's guests, when it
+	/// turned out not to count, like an order that was not accepted.
+	pub fn release(&self, table_session_id: i64, limit: Limit) {
+		let mut recent = self.recent.lock().expect("rate limits lock");
+		if let Some(times) = recent.get_mut(&(table_session_id, limit)) {
+			times.pop_back();
+		}
+	}
+}
+
+/// The guests of a table, holding a token of its session in an
+/// `Authorization: Bearer <token>` header. Tokens of ended sessions no
+/// longer work.
+pub struct Guest(pub models::TableSession);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Guest {
+	type Error = ();
+
+	async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+		let (guests, repository) = match (req.rocket().state::<Guests>(), req.rocket().state::<repository::Repository>()) {
+			(Some(guests), Some(repository)) => (guests, repository),
+			_ => return Outcome::Failure((Status::ServiceUnavailable, ()))
+		};
+		let table_session_id = match req.headers().get_one("Authorization")
+			.and_then(|value| value.strip_prefix("Bearer "))
+			.and_then(|token| guests.verify(token, chrono::Utc::now().timestamp())) {
+			Some(table_session_id) => table_session_id,
+			None => return Outcome::Failure((Status::Unauthorized, ()))
+		};
+		let session = match repository.session(table_session_id).await {
+			Ok(Some(session)) if session.active => session,
+			Ok(_) => return Outcome::Failure((Status::Unauthorized, ())),
+			Err(e) => {
+				log::error!("Unable to resolve table token: {}", e);
+				return Outcome::Failure((Status::InternalServerError, ()));
+			}
+		};
+		if !guests.allow(session.id, Limit::Requests) {
+			return Outcome::Failure((Status::TooManyRequests, ()));
+		}
+		Outcome::Success(Guest(session))
+	}
+}
+
+pub fn stage() -> AdHoc {
+	AdHoc::try_on_ignite("Guests", |rocket| async {
+		if rocket.state::<Guests>().is_some() {
+			return Ok(rocket);
+		}
+		let config = match rocket.figment().find_value("guests") {
+			Ok(_) => match rocket.figment().extract_inner::<GuestConfig>("guests") {
+				Ok(config) => config,
+				Err(e) => {
+					rocket::error!("Invalid guests configuration: {}", e);
+					return Err(rocket);
+				}
+			},
+			Err(_) => GuestConfig::default()
+		};
+		if config.secret.is_empty() {
+			rocket::warn!("No guests.secret configured, table tokens stop working on restart");
+		}
+		Ok(rocket.manage(Guests::new(config)))
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{GuestConfig, Guests, Limit};
+
+	#[test]
+	fn table_tokens_are_signed_and_expire()
+	{
+		let guests = Guests::new(GuestConfig { secret: String::from("secret"), orders_per_minute: 2, ..Default::default() });
+		let token = guests.token(7, 1_000).token;
+		assert_eq!(guests.verify(&token, 1_000), Some(7));
+		assert_eq!(guests.verify(&token, 1_000 + 240 * 60), None);
+		assert_eq!(guests.verify(&token.replacen("7.", "8.", 1), 1_000), None);
+		assert_eq!(guests.verify("7.99999999999.00", 1_000), None);
+		let other = Guests::new(GuestConfig { secret: String::from("other"), ..Default::default() });
+		assert_eq!(other.verify(&token, 1_000), None);
+
+		assert!(guests.allow(7, Limit::Orders) && guests.allow(7, Limit::Orders));
+		assert!(!guests.allow(7, Limit::Orders));
+		guests.release(7, Limit::Orders);
+		assert!(guests.allow(7, Limit::Orders));
+		assert!(!guests.allow(7, Limit::Orders));
+		assert!(guests.allow(8, Limit::Orders));
+		assert!(guests.allow(7, Limit::Requests));
+	}
+}
@@ -1,169 +1,1231 @@
 use rocket::fairing::AdHoc;
+use rocket::http::ContentType;
+use rocket::response::stream::{Event, EventStream};
+use rocket::tokio::select;
+use rocket::tokio::sync::broadcast::error::RecvError;
+use rocket::{Shutdown, State};
 use rocket_db_pools::Connection;
 use rocket::serde::json::Json;
 use crate::db;
 use crate::models;
+use crate::auth;
+use crate::billing;
+use crate::money;
+use crate::receipt;
+use crate::kitchen;
+use crate::events;
+use crate::webhooks;
+use crate::timeouts;
+use crate::idempotency;
+use crate::etag;
+use crate::pagination;
+use crate::openapi;
+use crate::graphql;
+use crate::repository;
+use crate::service;
+use crate::locations;
+use crate::settings;
+use crate::i18n::{self, message};
+use crate::guests;
 use crate::error_handler::Error;
+use log::{error, info};
 
 type Result<T, E = Error> = std::result::Result<Json<T>, E>;
 
+/// Where the routes are mounted; a breaking change gets a new version.
+pub const API_BASE: &str = "/api/v1";
+
+/// An item, named in the language of `Accept-Language` where it has a
+/// translation.
 #[rocket::get("/items/<item_id>")]
-pub async fn get_item(mut db: Connection<db::Db>, item_id: i64) -> Result<models::Item> {
-	let db_result = db::get_item(&mut db, item_id).await?;
-	match db_result {
+#[utoipa::path(
+	get,
+	path = "/items/{item_id}",
+	tag = "items",
+	params(
+		("item_id" = i64, Path, description = "Item id"),
+		("Accept-Language" = Option<String>, Header, description = "Languages to name items in, e.g. `ja-JP,ja;q=0.9`")
+	),
+	responses(
+		(status = 200, description = "Success", body = models::Item),
+		(status = 500, description = "Failed", body = models::Failure)
+	)
+)]
+pub async fn get_item(repository: &State<repository::Repository>, location: models::Location, locale: i18n::Locale, item_id: i64) -> Result<models::Item> {
+	Ok(Json(service::item(repository.as_ref(), location.id, item_id, locale).await?))
+}
+
+const ITEM_SORT: [pagination::SortField; 4] = [("id", "id"), ("name", "name"), ("price", "price"), ("preparation_time", "preparation_time")];
+const SESSION_SORT: [pagination::SortField; 3] = [("id", "id"), ("session_start", "session_start"), ("customers", "customers")];
+const ORDER_SORT: [pagination::SortField; 2] = [("id", "id"), ("timestamp", "timestamp")];
+
+/// The menu, a page at a time, optionally within a price range in the minor
+/// units of the currency (yen, or cents of euros). Items are named in the
+/// language of `Accept-Language`, or the default locale, where they are
+/// translated.
+#[rocket::get("/items?<limit>&<cursor>&<sort>&<min_price>&<max_price>")]
+#[utoipa::path(
+	get,
+	path = "/items",
+	tag = "items",
+	params(
+		("limit" = Option<u32>, Query, description = "Page size, 50 by default and at most 200"),
+		("cursor" = Option<String>, Query, description = "`next_cursor` of the previous page"),
+		("sort" = Option<String>, Query, description = "`id`, `name`, `price` or `preparation_time`, `-` in front to sort descending"),
+		("min_price" = Option<u32>, Query, description = "Lowest price in minor units"),
+		("max_price" = Option<u32>, Query, description = "Highest price in minor units"),
+		("Accept-Language" = Option<String>, Header, description = "Languages to name items in, e.g. `ja-JP,ja;q=0.9`")
+	),
+	responses(
+		(status = 200, description = "Success", body = models::ItemPage),
+		(status = 422, description = "An invalid limit, cursor or sort", body = models::Failure),
+		(status = 500, description = "Failed", body = models::Failure)
+	)
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn get_items(repository: &State<repository::Repository>, location: models::Location, locale: i18n::Locale, limit: Option<u32>, cursor: Option<&str>, sort: Option<&str>, min_price: Option<u32>, max_price: Option<u32>) -> std::result::Result<pagination::Paginated<models::Item>, Error> {
+	let page = pagination::PageRequest::new(limit, cursor, sort, &ITEM_SORT).map_err(|msg| Error::Unprocessable{ msg })?;
+	Ok(pagination::Paginated(service::items(repository.as_ref(), location.id, &page, min_price, max_price, locale).await?))
+}
+
+/// An item of a request, priced with `price` or the older `price_yen`, in the
+/// currency bills are in.
+fn priced_item(settings: &settings::Settings, item: Json<models::Item>) -> std::result::Result<models::Item, i18n::Message> {
+	let item = item.into_inner().with_legacy_price();
+	let currency = settings.billing().currency;
+	if item.price.currency != currency {
+		return Err(message!("Item '{}' is priced in {}, but bills are in {}", item.name, item.price.currency, currency));
+	}
+	Ok(item)
+}
+
+/// Adds an item to the location's menu.
+#[rocket::post("/items", data = "<item>")]
+#[utoipa::path(
+	post,
+	path = "/items",
+	tag = "items",
+	request_body = models::Item,
+	responses(
+		(status = 200, description = "Success", body = models::Item),
+		(status = 403, description = "Not a manager of the location"),
+		(status = 422, description = "Priced in another currency than bills", body = models::Failure),
+		(status = 500, description = "Failed", body = models::Failure)
+	),
+	security(("staff_token" = []))
+)]
+pub async fn new_item(repository: &State<repository::Repository>, settings: &State<settings::Settings>, location: models::Location, _manager: auth::Manager, item: Json<models::Item>) -> Result<models::Item> {
+	let item = priced_item(settings, item).map_err(|msg| Error::Unprocessable{ msg })?;
+	Ok(Json(repository.create_item(location.id, &item).await?))
+}
+
+/// Changes an item, like its price, on the location's menu only.
+#[rocket::put("/items/<item_id>", data = "<item>")]
+#[utoipa::path(
+	put,
+	path = "/items/{item_id}",
+	tag = "items",
+	params(
+		("item_id" = i64, Path, description = "Item id")
+	),
+	request_body = models::Item,
+	responses(
+		(status = 200, description = "Success", body = models::Item),
+		(status = 403, description = "Not a manager of the location"),
+		(status = 422, description = "Priced in another currency than bills", body = models::Failure),
+		(status = 500, description = "Failed", body = models::Failure)
+	),
+	security(("staff_token" = []))
+)]
+pub async fn update_item(repository: &State<repository::Repository>, settings: &State<settings::Settings>, location: models::Location, _manager: auth::Manager, item_id: i64, item: Json<models::Item>) -> Result<models::Item> {
+	let item = priced_item(settings, item).map_err(|msg| Error::Unprocessable{ msg })?;
+	match repository.update_item(location.id, item_id, &item).await? {
 		Some(item) => Ok(Json(item)),
 		None => Err(Error::Api{
-			msg: format!("Unable to get item with ID {}", item_id)
+			msg: message!("Unable to get item with ID {}", item_id)
 		})
 	}
 }
 
-#[rocket::get("/items")]
-pub async fn get_items(mut db: Connection<db::Db>) -> Result<Vec<models::Item>> {
-	let db_result = db::get_items(&mut db).await?;
-	match db_result {
-		Some(items) => Ok(Json(items)),
-		None => Err(Error::Api{
-			msg: String::from("Unable to get items")
-		})
+/// The names an item has in other languages than its own.
+#[rocket::get("/items/<item_id>/translations")]
+#[utoipa::path(
+	get,
+	path = "/items/{item_id}/translations",
+	tag = "items",
+	params(
+		("item_id" = i64, Path, description = "Item id")
+	),
+	responses(
+		(status = 200, description = "Success", body = [models::ItemTranslation]),
+		(status = 500, description = "Failed", body = models::Failure)
+	)
+)]
+pub async fn get_item_translations(repository: &State<repository::Repository>, location: models::Location, item_id: i64) -> Result<Vec<models::ItemTranslation>> {
+	if repository.item(location.id, item_id).await?.is_none() {
+		return Err(Error::Api{
+			msg: message!("Unable to get item with ID {}", item_id)
+		});
+	}
+	Ok(Json(repository.item_translations(item_id).await?))
+}
+
+/// Names an item in a locale, `ja`, `zh`, `ko` or `en`, replacing the name
+/// it had in it.
+#[rocket::put("/items/<item_id>/translations/<locale>", data = "<translation>")]
+#[utoipa::path(
+	put,
+	path = "/items/{item_id}/translations/{locale}",
+	tag = "items",
+	params(
+		("item_id" = i64, Path, description = "Item id"),
+		("locale" = String, Path, description = "`en`, `ja`, `zh` or `ko`")
+	),
+	request_body = models::ItemTranslation,
+	responses(
+		(status = 200, description = "Success", body = models::ItemTranslation),
+		(status = 403, description = "Not a manager of the location"),
+		(status = 422, description = "Unsupported locale or empty name", body = models::Failure),
+		(status = 500, description = "Failed", body = models::Failure)
+	),
+	security(("staff_token" = []))
+)]
+pub async fn update_item_translation(repository: &State<repository::Repository>, location: models::Location, _manager: auth::Manager, item_id: i64, locale: &str, translation: Json<models::ItemTranslation>) -> Result<models::ItemTranslation> {
+	if !i18n::LOCALES.contains(&locale) {
+		return Err(Error::Unprocessable{
+			msg: message!("Unsupported locale '{}', expected one of: {}", locale, i18n::LOCALES.join(", "))
+		});
+	}
+	let translation = models::ItemTranslation { locale: locale.to_string(), ..translation.into_inner() };
+	if translation.name.trim().is_empty() {
+		return Err(Error::Unprocessable{
+			msg: message!("A translated name can't be empty")
+		});
+	}
+	if repository.item(location.id, item_id).await?.is_none() {
+		return Err(Error::Api{
+			msg: message!("Unable to get item with ID {}", item_id)
+		});
 	}
+	repository.save_item_translation(item_id, &translation).await?;
+	Ok(Json(translation))
 }
 
+/// Retries with the same `Idempotency-Key` header get the first order back
+/// instead of creating another one.
 #[rocket::post("/tables/<table_nr>/orders", data = "<order>")]
-pub async fn new_order(mut db: Connection<db::Db>, table_nr: u8, order: Json<models::Order>) -> Result<models::Order> {
-	let db_result = db::retrieve_active_table_session(&mut db, table_nr).await?;
-	match db_result {
-		Some(active_session) => {
-			let db_result = db::create_order(&mut db, active_session.id, &order).await?;
-			match db_result {
-				Some(new_order) => Ok(Json(new_order)),
-				None => Err(Error::Api{
-					msg: String::from("Unable to create order")
-				})
-			}
+#[utoipa::path(
+	post,
+	path = "/tables/{table_nr}/orders",
+	tag = "orders",
+	params(
+		("table_nr" = u8, Path, description = "Table number"),
+		("Idempotency-Key" = Option<String>, Header, description = "Makes retries of the request safe")
+	),
+	request_body = models::Order,
+	responses(
+		(status = 200, description = "Success", body = models::Order),
+		(status = 409, description = "A request with the same key is in progress, or the restaurant is closed", body = models::Failure),
+		(status = 422, description = "The key was used for another body", body = models::Failure),
+		(status = 500, description = "Failed", body = models::Failure)
+	)
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn new_order(pool: &State<db::Db>, repository: &State<repository::Repository>, kitchen: &State<kitchen::Kitchen>, events: &State<events::Events>, settings: &State<settings::Settings>, location: models::Location, staff: Option<models::Staff>, key: idempotency::IdempotencyKey, table_nr: u8, order: idempotency::HashedJson<models::Order>) -> std::result::Result<idempotency::Idempotent<models::Order>, Error> {
+	let claim = match key.begin(&mut *pool.acquire().await?, &format!("POST /locations/{}/tables/{}/orders", location.slug, table_nr), &order).await? {
+		idempotency::Begin::Replay(response) => return Ok(idempotency::Idempotent::Replayed(response)),
+		idempotency::Begin::Proceed(claim) => claim
+	};
+	let result = create_table_order(repository.as_ref(), kitchen, events, settings, location.id, staff, table_nr, &order).await;
+	idempotency::finish(&mut *pool.acquire().await?, claim, result).await
+}
+
+/// Orders are only taken within the opening hours, when there are any.
+fn closed() -> Error {
+	Error::Conflict{
+		msg: message!("The restaurant is closed, orders are taken during opening hours")
+	}
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn create_table_order(repository: &dyn repository::RestaurantRepository, kitchen: &kitchen::Kitchen, events: &events::Events, settings: &settings::Settings, location_id: i64, staff: Option<models::Staff>, table_nr: u8, order: &models::Order) -> Result<models::Order> {
+	if !settings.current().is_open(chrono::Utc::now()) {
+		return Err(closed());
+	}
+	let (active_session, new_order) = service::place_order(repository, location_id, table_nr, &order.order_items, staff.map(|staff| staff.id)).await?;
+	announce_order(repository, kitchen, events, location_id, table_nr, active_session.id, &new_order).await;
+	Ok(Json(new_order))
+}
+
+/// Tells the subscribers about a new order and sends the kitchen tickets of
+/// its items that are not held back. The order is placed by then, so this
+/// doesn't fail the request; tickets that could not be sent are logged, to be
+/// reprinted.
+#[allow(clippy::too_many_arguments)]
+async fn announce_order(repository: &dyn repository::RestaurantRepository, kitchen: &kitchen::Kitchen, events: &events::Events, location_id: i64, table_nr: u8, table_session_id: i64, order: &models::Order) {
+	let order_id = order.id.unwrap_or_default();
+	let items: Vec<models::QueueItem> = match repository.queue_items(table_session_id).await {
+		Ok(items) => items.into_iter().filter(|item| item.order_id == order_id).collect(),
+		Err(e) => {
+			error!("Unable to send the kitchen tickets of order {} at table #{}: {}", order_id, table_nr, e);
+			Vec::new()
+		}
+	};
+	events.publish(location_id, table_nr, stations(&items), models::EventData::OrderCreated { order: order.clone() });
+	let fired: Vec<models::QueueItem> = items.into_iter().filter(|item| item.fired_at.is_some()).collect();
+	kitchen.dispatch(kitchen::tickets_for(models::TicketKind::New, &fired)).await;
+}
+
+/// The active session's orders, a page at a time. `status=held` keeps those
+/// with items still held back, `status=fired` those sent to the kitchen.
+#[rocket::get("/tables/<table_nr>/orders?<limit>&<cursor>&<sort>&<status>")]
+#[utoipa::path(
+	get,
+	path = "/tables/{table_nr}/orders",
+	tag = "orders",
+	params(
+		("table_nr" = u8, Path, description = "Table number"),
+		("limit" = Option<u32>, Query, description = "Page size, 50 by default and at most 200"),
+		("cursor" = Option<String>, Query, description = "`next_cursor` of the previous page"),
+		("sort" = Option<String>, Query, description = "`id` or `timestamp`, `-` in front to sort descending"),
+		("status" = Option<models::ItemStatus>, Query, description = "Only orders with items held back, or all of them fired")
+	),
+	responses(
+		(status = 200, description = "Success", body = models::OrderPage),
+		(status = 422, description = "An invalid limit, cursor, sort or status", body = models::Failure),
+		(status = 500, description = "Failed", body = models::Failure)
+	)
+)]
+pub async fn get_orders(repository: &State<repository::Repository>, location: models::Location, table_nr: u8, limit: Option<u32>, cursor: Option<&str>, sort: Option<&str>, status: Option<&str>) -> std::result::Result<pagination::Paginated<models::Order>, Error> {
+	let page = pagination::PageRequest::new(limit, cursor, sort, &ORDER_SORT).map_err(|msg| Error::Unprocessable{ msg })?;
+	let status = parse_status(status).map_err(|msg| Error::Unprocessable{ msg })?;
+	let active_session = service::active_session(repository.as_ref(), location.id, table_nr).await?;
+	Ok(pagination::Paginated(service::orders(repository.as_ref(), active_session.id, &page, status).await?))
+}
+
+#[rocket::get("/tables/<table_nr>/orders/<order_id>")]
+#[utoipa::path(
+	get,
+	path = "/tables/{table_nr}/orders/{order_id}",
+	tag = "orders",
+	params(
+		("table_nr" = u8, Path, description = "Table number"),
+		("order_id" = i64, Path, description = "Order id")
+	),
+	responses(
+		(status = 200, description = "The order, with its ETag", body = models::Order),
+		(status = 500, description = "Failed", body = models::Failure)
+	)
+)]
+pub async fn get_order(repository: &State<repository::Repository>, location: models::Location, table_nr: u8, order_id: i64) -> std::result::Result<etag::Tagged<models::Order>, Error> {
+	let active_session = service::active_session(repository.as_ref(), location.id, table_nr).await?;
+	let order = service::order(repository.as_ref(), active_session.id, order_id).await?;
+	let tag = etag::etag(order_id, order.version);
+	Ok(etag::Tagged(Json(order), tag))
+}
+
+#[rocket::delete("/tables/<table_nr>/orders/<order_id>")]
+#[utoipa::path(
+	delete,
+	path = "/tables/{table_nr}/orders/{order_id}",
+	tag = "orders",
+	params(
+		("table_nr" = u8, Path, description = "Table number"),
+		("order_id" = i64, Path, description = "Order id"),
+		("If-Match" = String, Header, description = "ETag of the current version, or `*`")
+	),
+	responses(
+		(status = 200, description = "`success`, or `failed` when the session has no such order", body = String),
+		(status = 412, description = "The order was changed since", body = models::Failure),
+		(status = 428, description = "If-Match is missing", body = models::Failure),
+		(status = 500, description = "Failed", body = models::Failure)
+	)
+)]
+pub async fn remove_order(repository: &State<repository::Repository>, events: &State<events::Events>, location: models::Location, if_match: etag::IfMatch, table_nr: u8, order_id: i64) -> Result<String> {
+	let active_session = service::active_session(repository.as_ref(), location.id, table_nr).await?;
+	let order = match repository.order(active_session.id, order_id).await? {
+		Some(order) => order,
+		None => return Ok(Json(String::from("failed")))
+	};
+	let version = if_match.expected_version(order_id, order.version)?;
+	let stations = order_stations(repository.as_ref(), active_session.id, order_id, None).await?;
+	if repository.delete_order(active_session.id, order_id, version).await? {
+		events.publish(location.id, table_nr, stations, models::EventData::OrderVoided { order_id });
+		Ok(Json(String::from("success")))
+	}
+	else {
+		Err(stale_order(order_id))
+	}
+}
+
+/// Retries with the same `Idempotency-Key` header get the opened session back.
+/// Only staff are given the session's guest token; retries by anyone else
+/// don't replay theirs.
+#[rocket::post("/tables/<table_nr>", data = "<session>")]
+#[utoipa::path(
+	post,
+	path = "/tables/{table_nr}",
+	tag = "sessions",
+	params(
+		("table_nr" = u8, Path, description = "Table number"),
+		("Idempotency-Key" = Option<String>, Header, description = "Makes retries of the request safe")
+	),
+	request_body = models::TableSession,
+	responses(
+		(status = 200, description = "Success, with the `guest_token` of the session when opened by staff", body = models::OpenedSession),
+		(status = 409, description = "The table already has an active session, or a request with the same key is in progress", body = models::Failure),
+		(status = 422, description = "The key was used for another body", body = models::Failure),
+		(status = 500, description = "Failed", body = models::Failure)
+	)
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn new_session(pool: &State<db::Db>, repository: &State<repository::Repository>, events: &State<events::Events>, guests: &State<guests::Guests>, location: models::Location, staff: Option<models::Staff>, key: idempotency::IdempotencyKey, table_nr: u8, session: idempotency::HashedJson<models::TableSession>) -> std::result::Result<idempotency::Idempotent<models::OpenedSession>, Error> {
+	let scope = match staff {
+		Some(_) => format!("POST /locations/{}/tables/{} by staff", location.slug, table_nr),
+		None => format!("POST /locations/{}/tables/{}", location.slug, table_nr)
+	};
+	let claim = match key.begin(&mut *pool.acquire().await?, &scope, &session).await? {
+		idempotency::Begin::Replay(response) => return Ok(idempotency::Idempotent::Replayed(response)),
+		idempotency::Begin::Proceed(claim) => claim
+	};
+	let guests = staff.map(|_| guests.inner());
+	let result = open_session(repository.as_ref(), events, guests, location.id, table_nr, &session).await;
+	idempotency::finish(&mut *pool.acquire().await?, claim, result).await
+}
+
+/// Opens the session, with a token for its guests to order with if `guests`
+/// is given.
+async fn open_session(repository: &dyn repository::RestaurantRepository, events: &events::Events, guests: Option<&guests::Guests>, location_id: i64, table_nr: u8, session: &models::TableSession) -> Result<models::OpenedSession> {
+	let session = service::open_session(repository, location_id, table_nr, session.customers).await?;
+	events.publish(location_id, table_nr, Vec::new(), models::EventData::SessionOpened {
+		table_session_id: session.id,
+		customers: session.customers
+	});
+	let guest_token = guests.map(|guests| guests.token(session.id, chrono::Utc::now().timestamp()));
+	Ok(Json(models::OpenedSession { session, guest_token }))
+}
+
+#[rocket::get("/tables")]
+#[utoipa::path(
+	get,
+	path = "/tables",
+	tag = "sessions",
+	responses(
+		(status = 200, description = "Success", body = [models::TableSession]),
+		(status = 500, description = "Failed", body = models::Failure)
+	)
+)]
+pub async fn get_active_sessions(repository: &State<repository::Repository>, location: models::Location) -> Result<Vec<models::TableSession>> {
+	Ok(Json(service::active_sessions(repository.as_ref(), location.id).await?))
+}
+
+/// All of a table's sessions, a page at a time, optionally only those started
+/// `from` and `to` the given days (YYYY-MM-DD, both included).
+#[rocket::get("/tables/<table_nr>?<limit>&<cursor>&<sort>&<from>&<to>")]
+#[utoipa::path(
+	get,
+	path = "/tables/{table_nr}",
+	tag = "sessions",
+	params(
+		("table_nr" = u8, Path, description = "Table number"),
+		("limit" = Option<u32>, Query, description = "Page size, 50 by default and at most 200"),
+		("cursor" = Option<String>, Query, description = "`next_cursor` of the previous page"),
+		("sort" = Option<String>, Query, description = "`id`, `session_start` or `customers`, `-` in front to sort descending"),
+		("from" = Option<String>, Query, description = "First day, YYYY-MM-DD"),
+		("to" = Option<String>, Query, description = "Last day, YYYY-MM-DD")
+	),
+	responses(
+		(status = 200, description = "Success", body = models::SessionPage),
+		(status = 422, description = "An invalid limit, cursor, sort or date", body = models::Failure),
+		(status = 500, description = "Failed", body = models::Failure)
+	)
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn get_sessions(repository: &State<repository::Repository>, settings: &State<settings::Settings>, location: models::Location, table_nr: u8, limit: Option<u32>, cursor: Option<&str>, sort: Option<&str>, from: Option<&str>, to: Option<&str>) -> std::result::Result<pagination::Paginated<models::TableSession>, Error> {
+	let page = pagination::PageRequest::new(limit, cursor, sort, &SESSION_SORT).map_err(|msg| Error::Unprocessable{ msg })?;
+	let parse = |date: Option<&str>| match date {
+		Some(date) => chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d")
+			.map(Some)
+			.map_err(|_| message!("Invalid date '{}', expected YYYY-MM-DD", date)),
+		None => Ok(None)
+	};
+	let (from, to) = match (parse(from), parse(to)) {
+		(Ok(from), Ok(to)) => (from, to),
+		(Err(msg), _) | (_, Err(msg)) => return Err(Error::Unprocessable{ msg })
+	};
+	Ok(pagination::Paginated(service::sessions(repository.as_ref(), &settings.current(), location.id, table_nr, &page, from, to).await?))
+}
+
+#[rocket::get("/tables/<table_nr>/active")]
+#[utoipa::path(
+	get,
+	path = "/tables/{table_nr}/active",
+	tag = "sessions",
+	params(
+		("table_nr" = u8, Path, description = "Table number")
+	),
+	responses(
+		(status = 200, description = "The session, with its ETag", body = models::TableSession),
+		(status = 500, description = "Failed", body = models::Failure)
+	)
+)]
+pub async fn get_active_session(repository: &State<repository::Repository>, location: models::Location, table_nr: u8) -> std::result::Result<etag::Tagged<models::TableSession>, Error> {
+	let active_session = service::active_session(repository.as_ref(), location.id, table_nr).await?;
+	let tag = etag::etag(active_session.id, active_session.version);
+	Ok(etag::Tagged(Json(active_session), tag))
+}
+
+#[rocket::delete("/tables/<table_nr>")]
+#[utoipa::path(
+	delete,
+	path = "/tables/{table_nr}",
+	tag = "sessions",
+	params(
+		("table_nr" = u8, Path, description = "Table number"),
+		("If-Match" = String, Header, description = "ETag of the current version, or `*`")
+	),
+	responses(
+		(status = 200, description = "`success` once the session is closed", body = String),
+		(status = 412, description = "The session was changed since", body = models::Failure),
+		(status = 428, description = "If-Match is missing", body = models::Failure),
+		(status = 500, description = "Failed", body = models::Failure)
+	)
+)]
+pub async fn end_session(repository: &State<repository::Repository>, events: &State<events::Events>, location: models::Location, if_match: etag::IfMatch, table_nr: u8) -> Result<String> {
+	let active_session = service::active_session(repository.as_ref(), location.id, table_nr).await?;
+	let version = if_match.expected_version(active_session.id, active_session.version)?;
+	service::close_session(repository.as_ref(), &active_session, version).await?;
+	events.publish(location.id, table_nr, Vec::new(), models::EventData::SessionClosed { table_session_id: active_session.id });
+	Ok(Json(String::from("success")))
+}
+
+#[rocket::delete("/tables/<table_nr>/orders/<order_id>/<item_id>")]
+#[utoipa::path(
+	delete,
+	path = "/tables/{table_nr}/orders/{order_id}/{item_id}",
+	tag = "orders",
+	params(
+		("table_nr" = u8, Path, description = "Table number"),
+		("order_id" = i64, Path, description = "Order id"),
+		("item_id" = i64, Path, description = "Item id"),
+		("If-Match" = String, Header, description = "ETag of the current version, or `*`")
+	),
+	responses(
+		(status = 200, description = "`success`, or `failed` when the order or item is not found", body = String),
+		(status = 412, description = "The order was changed since", body = models::Failure),
+		(status = 428, description = "If-Match is missing", body = models::Failure),
+		(status = 500, description = "Failed", body = models::Failure)
+	)
+)]
+pub async fn remove_item(repository: &State<repository::Repository>, events: &State<events::Events>, location: models::Location, if_match: etag::IfMatch, table_nr: u8, order_id: i64, item_id: i64) -> Result<String> {
+	let active_session = service::active_session(repository.as_ref(), location.id, table_nr).await?;
+	let order = match repository.order(active_session.id, order_id).await? {
+		Some(order) => order,
+		None => return Ok(Json(String::from("failed")))
+	};
+	let version = if_match.expected_version(order_id, order.version)?;
+	let stations = order_stations(repository.as_ref(), active_session.id, order_id, Some(item_id)).await?;
+	match repository.delete_order_item(order_id, item_id, version).await? {
+		Some(true) => {
+			events.publish(location.id, table_nr, stations, models::EventData::OrderUpdated { order_id });
+			Ok(Json(String::from("success")))
 		},
+		Some(false) => Ok(Json(String::from("failed"))),
+		None => Err(stale_order(order_id))
+	}
+}
+
+/// The location's predefined discounts.
+#[rocket::get("/discounts")]
+#[utoipa::path(
+	get,
+	path = "/discounts",
+	tag = "billing",
+	responses(
+		(status = 200, description = "Success", body = [models::Discount]),
+		(status = 500, description = "Failed", body = models::Failure)
+	)
+)]
+pub async fn get_discounts(repository: &State<repository::Repository>, location: models::Location) -> Result<Vec<models::Discount>> {
+	Ok(Json(repository.discounts(location.id).await?))
+}
+
+/// Adds a predefined discount to the location. Managers of one location
+/// only add them there; owners at any location.
+#[rocket::post("/discounts", data = "<discount>")]
+#[utoipa::path(
+	post,
+	path = "/discounts",
+	tag = "billing",
+	request_body = models::Discount,
+	responses(
+		(status = 200, description = "Success", body = models::Discount),
+		(status = 403, description = "Not a manager"),
+		(status = 500, description = "Failed", body = models::Failure)
+	),
+	security(("staff_token" = []))
+)]
+pub async fn new_discount(repository: &State<repository::Repository>, location: models::Location, _manager: auth::Manager, discount: Json<models::Discount>) -> Result<models::Discount> {
+	Ok(Json(repository.create_discount(location.id, &discount).await?))
+}
+
+#[rocket::get("/tables/<table_nr>/bill")]
+#[utoipa::path(
+	get,
+	path = "/tables/{table_nr}/bill",
+	tag = "billing",
+	params(
+		("table_nr" = u8, Path, description = "Table number")
+	),
+	responses(
+		(status = 200, description = "Success", body = models::Bill),
+		(status = 500, description = "Failed", body = models::Failure)
+	)
+)]
+pub async fn get_bill(repository: &State<repository::Repository>, settings: &State<settings::Settings>, location: models::Location, table_nr: u8) -> Result<models::Bill> {
+	let active_session = service::active_session(repository.as_ref(), location.id, table_nr).await?;
+	Ok(Json(service::bill(repository.as_ref(), &settings.billing(), &active_session).await?))
+}
+
+/// A new token for the guests of a table, e.g. to print its QR code again.
+/// Tokens given before keep working until they expire.
+#[rocket::get("/tables/<table_nr>/guest-token")]
+#[utoipa::path(
+	get,
+	path = "/tables/{table_nr}/guest-token",
+	tag = "guests",
+	params(
+		("table_nr" = u8, Path, description = "Table number")
+	),
+	responses(
+		(status = 200, description = "Success", body = models::GuestToken),
+		(status = 500, description = "Failed", body = models::Failure)
+	),
+	security(("staff_token" = []))
+)]
+pub async fn get_guest_token(repository: &State<repository::Repository>, guests: &State<guests::Guests>, location: models::Location, _staff: models::Staff, table_nr: u8) -> Result<models::GuestToken> {
+	let active_session = service::active_session(repository.as_ref(), location.id, table_nr).await?;
+	Ok(Json(guests.token(active_session.id, chrono::Utc::now().timestamp())))
+}
+
+/// The orders guests of the table's active session placed with their token,
+/// including those waiting for approval.
+#[rocket::get("/tables/<table_nr>/guest-orders")]
+#[utoipa::path(
+	get,
+	path = "/tables/{table_nr}/guest-orders",
+	tag = "guests",
+	params(
+		("table_nr" = u8, Path, description = "Table number")
+	),
+	responses(
+		(status = 200, description = "Success", body = [models::GuestOrder]),
+		(status = 500, description = "Failed", body = models::Failure)
+	),
+	security(("staff_token" = []))
+)]
+pub async fn get_table_guest_orders(mut db: Connection<db::Db>, repository: &State<repository::Repository>, location: models::Location, _staff: models::Staff, table_nr: u8) -> Result<Vec<models::GuestOrder>> {
+	let active_session = service::active_session(repository.as_ref(), location.id, table_nr).await?;
+	Ok(Json(db::retrieve_guest_orders(&mut db, active_session.id).await?.unwrap_or_default()))
+}
+
+async fn pending_guest_order(db: &mut db::DbConnection, table_session_id: i64, guest_order_id: i64) -> std::result::Result<models::GuestOrder, Error> {
+	match db::retrieve_guest_order(db, table_session_id, guest_order_id).await? {
+		Some(guest_order) if guest_order.status == models::GuestOrderStatus::Pending => Ok(guest_order),
+		Some(_) => Err(Error::Conflict{
+			msg: message!("Guest order {} is no longer pending", guest_order_id)
+		}),
 		None => Err(Error::Api{
-			msg: format!("No active session for table #{}", table_nr)
+			msg: message!("Unable to get guest order with ID {}", guest_order_id)
 		})
 	}
 }
 
-#[rocket::get("/tables/<table_nr>/orders")]
-pub async fn get_orders(mut db: Connection<db::Db>, table_nr: u8) -> Result<Vec<models::Order>> {
-	let db_result = db::retrieve_active_table_session(&mut db, table_nr).await?;
+/// Places a pending guest order as an order taken by the approving staff
+/// member. Later orders of the session's guests need no approval.
+#[rocket::post("/tables/<table_nr>/guest-orders/<guest_order_id>/approve")]
+#[utoipa::path(
+	post,
+	path = "/tables/{table_nr}/guest-orders/{guest_order_id}/approve",
+	tag = "guests",
+	params(
+		("table_nr" = u8, Path, description = "Table number"),
+		("guest_order_id" = i64, Path, description = "Guest order id")
+	),
+	responses(
+		(status = 200, description = "The guest order, placed", body = models::GuestOrder),
+		(status = 409, description = "The guest order was approved or rejected already, or the restaurant is closed", body = models::Failure),
+		(status = 500, description = "Failed", body = models::Failure)
+	),
+	security(("staff_token" = []))
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn approve_guest_order(mut db: Connection<db::Db>, repository: &State<repository::Repository>, kitchen: &State<kitchen::Kitchen>, events: &State<events::Events>, settings: &State<settings::Settings>, location: models::Location, staff: models::Staff, table_nr: u8, guest_order_id: i64) -> Result<models::GuestOrder> {
+	let active_session = service::active_session(repository.as_ref(), location.id, table_nr).await?;
+	let mut guest_order = pending_guest_order(&mut db, active_session.id, guest_order_id).await?;
+	if !db::update_guest_order_status(&mut db, guest_order.id, models::GuestOrderStatus::Pending, models::GuestOrderStatus::Placed).await? {
+		return Err(Error::Conflict{
+			msg: message!("Guest order {} is no longer pending", guest_order_id)
+		});
+	}
+	let order = models::Order { order_items: guest_order.order_items.clone(), ..Default::default() };
+	let Json(order) = match create_table_order(repository.as_ref(), kitchen, events, settings, location.id, Some(staff), table_nr, &order).await {
+		Ok(order) => order,
+		Err(e) => {
+			db::update_guest_order_status(&mut db, guest_order.id, models::GuestOrderStatus::Placed, models::GuestOrderStatus::Pending).await?;
+			return Err(e);
+		}
+	};
+	let order_id = order.id.unwrap_or_default();
+	db::set_guest_order_placed(&mut db, guest_order.id, order_id).await?;
+	guest_order.status = models::GuestOrderStatus::Placed;
+	guest_order.order_id = Some(order_id);
+	Ok(Json(guest_order))
+}
+
+#[rocket::post("/tables/<table_nr>/guest-orders/<guest_order_id>/reject")]
+#[utoipa::path(
+	post,
+	path = "/tables/{table_nr}/guest-orders/{guest_order_id}/reject",
+	tag = "guests",
+	params(
+		("table_nr" = u8, Path, description = "Table number"),
+		("guest_order_id" = i64, Path, description = "Guest order id")
+	),
+	responses(
+		(status = 200, description = "The guest order, rejected", body = models::GuestOrder),
+		(status = 409, description = "The guest order was approved or rejected already", body = models::Failure),
+		(status = 500, description = "Failed", body = models::Failure)
+	),
+	security(("staff_token" = []))
+)]
+pub async fn reject_guest_order(mut db: Connection<db::Db>, repository: &State<repository::Repository>, location: models::Location, _staff: models::Staff, table_nr: u8, guest_order_id: i64) -> Result<models::GuestOrder> {
+	let active_session = service::active_session(repository.as_ref(), location.id, table_nr).await?;
+	let mut guest_order = pending_guest_order(&mut db, active_session.id, guest_order_id).await?;
+	if !db::update_guest_order_status(&mut db, guest_order.id, models::GuestOrderStatus::Pending, models::GuestOrderStatus::Rejected).await? {
+		return Err(Error::Conflict{
+			msg: message!("Guest order {} is no longer pending", guest_order_id)
+		});
+	}
+	guest_order.status = models::GuestOrderStatus::Rejected;
+	Ok(Json(guest_order))
+}
+
+// The guest API: what guests holding the table token of a session can do,
+// at the location of that session.
+
+/// The session of the table token.
+#[rocket::get("/guest/session")]
+#[utoipa::path(
+	get,
+	path = "/guest/session",
+	tag = "guests",
+	responses(
+		(status = 200, description = "Success", body = models::TableSession),
+		(status = 401, description = "Invalid or expired table token"),
+		(status = 429, description = "Too many requests with the token")
+	),
+	security(("table_token" = []))
+)]
+pub async fn get_guest_session(guest: guests::Guest) -> Result<models::TableSession> {
+	Ok(Json(guest.0))
+}
+
+/// The menu of the session's location, named in the guests' language.
+#[rocket::get("/guest/menu?<limit>&<cursor>&<sort>")]
+#[utoipa::path(
+	get,
+	path = "/guest/menu",
+	tag = "guests",
+	params(
+		("limit" = Option<u32>, Query, description = "Page size, 50 by default and at most 200"),
+		("cursor" = Option<String>, Query, description = "`next_cursor` of the previous page"),
+		("sort" = Option<String>, Query, description = "`id`, `name`, `price` or `preparation_time`, `-` in front to sort descending"),
+		("Accept-Language" = Option<String>, Header, description = "Languages to name items in, e.g. `ja-JP,ja;q=0.9`")
+	),
+	responses(
+		(status = 200, description = "Success", body = models::ItemPage),
+		(status = 401, description = "Invalid or expired table token"),
+		(status = 422, description = "An invalid limit, cursor or sort", body = models::Failure),
+		(status = 429, description = "Too many requests with the token")
+	),
+	security(("table_token" = []))
+)]
+pub async fn get_guest_menu(repository: &State<repository::Repository>, guest: guests::Guest, locale: i18n::Locale, limit: Option<u32>, cursor: Option<&str>, sort: Option<&str>) -> std::result::Result<pagination::Paginated<models::Item>, Error> {
+	let page = pagination::PageRequest::new(limit, cursor, sort, &ITEM_SORT).map_err(|msg| Error::Unprocessable{ msg })?;
+	Ok(pagination::Paginated(service::items(repository.as_ref(), guest.0.location_id, &page, None, None, locale).await?))
+}
+
+/// Orders to the session of the table token. With `approve_first_order`
+/// the guests' first order waits as `pending` until staff approve it.
+#[rocket::post("/guest/orders", data = "<order>")]
+#[utoipa::path(
+	post,
+	path = "/guest/orders",
+	tag = "guests",
+	request_body = models::Order,
+	responses(
+		(status = 200, description = "The order, placed or pending", body = models::GuestOrder),
+		(status = 401, description = "Invalid or expired table token"),
+		(status = 409, description = "The restaurant is closed", body = models::Failure),
+		(status = 429, description = "Too many orders or requests with the token", body = models::Failure),
+		(status = 500, description = "Failed", body = models::Failure)
+	),
+	security(("table_token" = []))
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn new_guest_order(mut db: Connection<db::Db>, repository: &State<repository::Repository>, kitchen: &State<kitchen::Kitchen>, events: &State<events::Events>, settings: &State<settings::Settings>, guests: &State<guests::Guests>, guest: guests::Guest, order: Json<models::Order>) -> Result<models::GuestOrder> {
+	let session = guest.0;
+	if !settings.current().is_open(chrono::Utc::now()) {
+		return Err(closed());
+	}
+	if !guests.allow(session.id, guests::Limit::Orders) {
+		return Err(Error::TooManyRequests{
+			msg: message!("Too many orders from table #{}, try again in a minute", session.table_nr)
+		});
+	}
+	let result = place_guest_order(&mut db, repository.as_ref(), kitchen, events, guests, &session, &order).await;
+	if result.is_err() {
+		// Only accepted orders count against the limit.
+		guests.release(session.id, guests::Limit::Orders);
+	}
+	result
+}
+
+/// Places the order, or keeps it pending when the guests' first order waits
+/// for approval.
+async fn place_guest_order(db: &mut db::DbConnection, repository: &dyn repository::RestaurantRepository, kitchen: &kitchen::Kitchen, events: &events::Events, guests: &guests::Guests, session: &models::TableSession, order: &models::Order) -> Result<models::GuestOrder> {
+	for order_item in &order.order_items {
+		if repository.item(session.location_id, order_item.item_id).await?.is_none() {
+			return Err(Error::Api{
+				msg: message!("Item {} is not on the menu here", order_item.item_id)
+			});
+		}
+	}
+	let guest_orders = db::retrieve_guest_orders(db, session.id).await?.unwrap_or_default();
+	let approved = guest_orders.iter().any(|guest_order| guest_order.status == models::GuestOrderStatus::Placed);
+	let db_result = if guests.approves_first_order() && !approved {
+		let pending = db::create_guest_order(db, session.id, &order.order_items, models::GuestOrderStatus::Pending, None).await?;
+		if let Some(guest_order) = &pending {
+			events.publish(session.location_id, session.table_nr, Vec::new(), models::EventData::GuestOrderPending { guest_order: guest_order.clone() });
+		}
+		pending
+	}
+	else {
+		match db::place_guest_order(db, session.id, order).await? {
+			Some((placed, guest_order)) => {
+				announce_order(repository, kitchen, events, session.location_id, session.table_nr, session.id, &placed).await;
+				Some(guest_order)
+			},
+			None => None
+		}
+	};
 	match db_result {
-		Some(active_session) => {
-			let db_result = db::retrieve_orders(&mut db, active_session.id).await?;
-			match db_result {
-				Some(orders) => Ok(Json(orders)),
-				None => Err(Error::Api{
-					msg: String::from("Unable to get orders")
-				})
-			}
-		},
+		Some(guest_order) => Ok(Json(guest_order)),
 		None => Err(Error::Api{
-			msg: format!("No active session for table #{}", table_nr)
+			msg: message!("Unable to create order")
 		})
 	}
 }
 
-#[rocket::get("/tables/<table_nr>/orders/<order_id>")]
-pub async fn get_order(mut db: Connection<db::Db>, table_nr: u8, order_id: i64) -> Result<models::Order> {
-	let db_result = db::retrieve_active_table_session(&mut db, table_nr).await?;
-	match db_result {
-		Some(active_session) => {
-			let db_result = db::retrieve_order(&mut db, active_session.id, order_id).await?;
-			match db_result {
-				Some(order) => Ok(Json(order)),
-				None => Err(Error::Api{
-					msg: format!("Unable to get order with ID {}", order_id)
-				})
+/// What the guests ordered with the table token, and whether it was placed.
+#[rocket::get("/guest/orders")]
+#[utoipa::path(
+	get,
+	path = "/guest/orders",
+	tag = "guests",
+	responses(
+		(status = 200, description = "Success", body = [models::GuestOrder]),
+		(status = 401, description = "Invalid or expired table token"),
+		(status = 429, description = "Too many requests with the token")
+	),
+	security(("table_token" = []))
+)]
+pub async fn get_guest_orders(mut db: Connection<db::Db>, guest: guests::Guest) -> Result<Vec<models::GuestOrder>> {
+	Ok(Json(db::retrieve_guest_orders(&mut db, guest.0.id).await?.unwrap_or_default()))
+}
+
+/// The running bill of the session of the table token.
+#[rocket::get("/guest/bill")]
+#[utoipa::path(
+	get,
+	path = "/guest/bill",
+	tag = "guests",
+	responses(
+		(status = 200, description = "Success", body = models::Bill),
+		(status = 401, description = "Invalid or expired table token"),
+		(status = 429, description = "Too many requests with the token")
+	),
+	security(("table_token" = []))
+)]
+pub async fn get_guest_bill(repository: &State<repository::Repository>, settings: &State<settings::Settings>, guest: guests::Guest) -> Result<models::Bill> {
+	Ok(Json(service::bill(repository.as_ref(), &settings.billing(), &guest.0).await?))
+}
+
+/// Raises a signal for the session's table, or gives back the one of the
+/// same kind that is still open.
+async fn raise_signal(db: &mut db::DbConnection, events: &events::Events, session: &models::TableSession, kind: models::SignalKind, raised_by: Option<i64>) -> Result<models::Signal> {
+	match db::create_signal(db, session.id, kind, raised_by).await? {
+		Some((signal, raised)) => {
+			if raised {
+				events.publish(session.location_id, session.table_nr, Vec::new(), models::EventData::SignalRaised { signal: signal.clone() });
 			}
+			Ok(Json(signal))
 		},
 		None => Err(Error::Api{
-			msg: format!("No active session for table #{}", table_nr)
+			msg: message!("Unable to raise signal")
 		})
 	}
 }
 
-#[rocket::delete("/tables/<table_nr>/orders/<order_id>")]
-pub async fn remove_order(mut db: Connection<db::Db>, table_nr: u8, order_id: i64) -> Result<String> {
-	let db_result = db::retrieve_active_table_session(&mut db, table_nr).await?;
-	match db_result {
-		Some(active_session) => {
-			let db_result = db::delete_order(&mut db, active_session.id, order_id).await?;
-			if db_result {
-				Ok(Json(String::from("success")))
-			}
-			else {
-				Ok(Json(String::from("failed")))
-			}
+/// Raises `call_server`, `request_bill` or `need_water` for a table on its
+/// guests' behalf.
+#[rocket::post("/tables/<table_nr>/signals", data = "<signal>")]
+#[utoipa::path(
+	post,
+	path = "/tables/{table_nr}/signals",
+	tag = "signals",
+	params(
+		("table_nr" = u8, Path, description = "Table number")
+	),
+	request_body = models::Signal,
+	responses(
+		(status = 200, description = "The signal, or the open one of its kind", body = models::Signal),
+		(status = 500, description = "Failed", body = models::Failure)
+	),
+	security(("staff_token" = []))
+)]
+pub async fn new_signal(mut db: Connection<db::Db>, repository: &State<repository::Repository>, events: &State<events::Events>, location: models::Location, staff: models::Staff, table_nr: u8, signal: Json<models::Signal>) -> Result<models::Signal> {
+	let active_session = service::active_session(repository.as_ref(), location.id, table_nr).await?;
+	raise_signal(&mut db, events, &active_session, signal.kind, Some(staff.id)).await
+}
+
+/// The signals of the table's active session, acknowledged or not.
+#[rocket::get("/tables/<table_nr>/signals")]
+#[utoipa::path(
+	get,
+	path = "/tables/{table_nr}/signals",
+	tag = "signals",
+	params(
+		("table_nr" = u8, Path, description = "Table number")
+	),
+	responses(
+		(status = 200, description = "Success", body = [models::Signal]),
+		(status = 500, description = "Failed", body = models::Failure)
+	),
+	security(("staff_token" = []))
+)]
+pub async fn get_signals(mut db: Connection<db::Db>, repository: &State<repository::Repository>, location: models::Location, _staff: models::Staff, table_nr: u8) -> Result<Vec<models::Signal>> {
+	let active_session = service::active_session(repository.as_ref(), location.id, table_nr).await?;
+	Ok(Json(db::retrieve_session_signals(&mut db, active_session.id).await?.unwrap_or_default()))
+}
+
+/// Marks a signal as taken care of by the staff member.
+#[rocket::post("/tables/<table_nr>/signals/<signal_id>/acknowledge")]
+#[utoipa::path(
+	post,
+	path = "/tables/{table_nr}/signals/{signal_id}/acknowledge",
+	tag = "signals",
+	params(
+		("table_nr" = u8, Path, description = "Table number"),
+		("signal_id" = i64, Path, description = "Signal id")
+	),
+	responses(
+		(status = 200, description = "The signal, acknowledged", body = models::Signal),
+		(status = 409, description = "The signal was acknowledged already", body = models::Failure),
+		(status = 500, description = "Failed", body = models::Failure)
+	),
+	security(("staff_token" = []))
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn acknowledge_signal(mut db: Connection<db::Db>, repository: &State<repository::Repository>, events: &State<events::Events>, location: models::Location, staff: models::Staff, table_nr: u8, signal_id: i64) -> Result<models::Signal> {
+	let active_session = service::active_session(repository.as_ref(), location.id, table_nr).await?;
+	if db::retrieve_signal(&mut db, active_session.id, signal_id).await?.is_none() {
+		return Err(Error::Api{
+			msg: message!("Unable to get signal with ID {}", signal_id)
+		});
+	}
+	if !db::acknowledge_signal(&mut db, signal_id, staff.id).await? {
+		return Err(Error::Conflict{
+			msg: message!("Signal {} was acknowledged already", signal_id)
+		});
+	}
+	match db::retrieve_signal(&mut db, active_session.id, signal_id).await? {
+		Some(signal) => {
+			events.publish(location.id, table_nr, Vec::new(), models::EventData::SignalAcknowledged { signal: signal.clone() });
+			Ok(Json(signal))
 		},
 		None => Err(Error::Api{
-			msg: format!("No active session for table #{}", table_nr)
+			msg: message!("Unable to get signal with ID {}", signal_id)
 		})
 	}
-	
 }
 
-#[rocket::post("/tables/<table_nr>", data = "<session>")]
-pub async fn new_session(mut db: Connection<db::Db>, table_nr: u8, session: Json<models::TableSession>) -> Result<models::TableSession> {
-	let db_result = db::retrieve_active_table_session(&mut db, table_nr).await?;
-	match db_result {
-		Some(_) => Err(Error::Api{
-			msg: format!("Active session for table #{} already exists", table_nr)
+/// The location's active sessions with the signals waiting at each table.
+#[rocket::get("/floor")]
+#[utoipa::path(
+	get,
+	path = "/floor",
+	tag = "signals",
+	responses(
+		(status = 200, description = "Success", body = [models::FloorTable]),
+		(status = 500, description = "Failed", body = models::Failure)
+	),
+	security(("staff_token" = []))
+)]
+pub async fn get_floor(mut db: Connection<db::Db>, repository: &State<repository::Repository>, location: models::Location, _staff: models::Staff) -> Result<Vec<models::FloorTable>> {
+	let sessions = service::active_sessions(repository.as_ref(), location.id).await?;
+	let mut signals = db::retrieve_open_signals(&mut db, location.id).await?.unwrap_or_default();
+	Ok(Json(sessions.into_iter()
+		.map(|session| {
+			let (waiting, others) = signals.drain(..).partition(|signal| signal.table_session_id == session.id);
+			signals = others;
+			models::FloorTable { session, signals: waiting }
+		})
+		.collect()))
+}
+
+/// Calls a server, asks for the bill or for water, from the guests' phones.
+#[rocket::post("/guest/signals", data = "<signal>")]
+#[utoipa::path(
+	post,
+	path = "/guest/signals",
+	tag = "guests",
+	request_body = models::Signal,
+	responses(
+		(status = 200, description = "The signal, or the open one of its kind", body = models::Signal),
+		(status = 401, description = "Invalid or expired table token"),
+		(status = 429, description = "Too many requests with the token")
+	),
+	security(("table_token" = []))
+)]
+pub async fn new_guest_signal(mut db: Connection<db::Db>, events: &State<events::Events>, guest: guests::Guest, signal: Json<models::Signal>) -> Result<models::Signal> {
+	raise_signal(&mut db, events, &guest.0, signal.kind, None).await
+}
+
+/// The signals of the guests' table, so they see when staff are coming.
+#[rocket::get("/guest/signals")]
+#[utoipa::path(
+	get,
+	path = "/guest/signals",
+	tag = "guests",
+	responses(
+		(status = 200, description = "Success", body = [models::Signal]),
+		(status = 401, description = "Invalid or expired table token"),
+		(status = 429, description = "Too many requests with the token")
+	),
+	security(("table_token" = []))
+)]
+pub async fn get_guest_signals(mut db: Connection<db::Db>, guest: guests::Guest) -> Result<Vec<models::Signal>> {
+	Ok(Json(db::retrieve_session_signals(&mut db, guest.0.id).await?.unwrap_or_default()))
+}
+
+/// Applies a predefined discount or, for managers only, a manual comp to the
+/// active session of a table. Every discount records who applied it and why.
+#[rocket::post("/tables/<table_nr>/discounts", data = "<request>")]
+#[utoipa::path(
+	post,
+	path = "/tables/{table_nr}/discounts",
+	tag = "billing",
+	params(
+		("table_nr" = u8, Path, description = "Table number")
+	),
+	request_body = models::DiscountRequest,
+	responses(
+		(status = 200, description = "Success", body = models::AppliedDiscount),
+		(status = 403, description = "The discount requires a manager", body = models::Failure),
+		(status = 409, description = "The discount cannot be combined with those already applied", body = models::Failure),
+		(status = 422, description = "No reason given, a manual comp without a kind, value and scope, or a line discount for an item not ordered in the session", body = models::Failure),
+		(status = 500, description = "Failed", body = models::Failure)
+	),
+	security(("staff_token" = []))
+)]
+pub async fn apply_discount(repository: &State<repository::Repository>, location: models::Location, staff: models::Staff, table_nr: u8, request: Json<models::DiscountRequest>) -> Result<models::AppliedDiscount> {
+	if request.reason.trim().is_empty() {
+		return Err(Error::Unprocessable{
+			msg: message!("A reason is required to apply a discount")
+		});
+	}
+	let active_session = service::active_session(repository.as_ref(), location.id, table_nr).await?;
+
+	let (name, kind, value, scope, stackable) = match request.discount_id {
+		Some(discount_id) => match repository.discount(location.id, discount_id).await? {
+			Some(discount) if discount.requires_manager && staff.role < models::Role::Manager => return Err(Error::Forbidden{
+				msg: message!("Discount '{}' requires a manager", discount.name)
+			}),
+			Some(discount) => (discount.name, discount.kind, discount.value, discount.scope, discount.stackable),
+			None => return Err(Error::Api{
+				msg: message!("Unable to get discount with ID {}", discount_id)
+			})
+		},
+		None if staff.role < models::Role::Manager => return Err(Error::Forbidden{
+			msg: message!("Manual comps require a manager")
 		}),
-		None => {
-			let db_result = db::create_session(&mut db, table_nr, &session).await?;
-			match db_result {
-				Some(session) => Ok(Json(session)),
-				None => Err(Error::Api{
-					msg: format!("Unable to create session for table #{}", table_nr)
+		None => match (request.kind, request.value, request.scope) {
+			(Some(kind), Some(value), Some(scope)) => (String::from("Manual comp"), kind, value, scope, request.stackable),
+			_ => return Err(Error::Unprocessable{
+				msg: message!("A manual comp needs a kind, value and scope")
+			})
+		}
+	};
+
+	let (order_id, item_id) = match scope {
+		models::DiscountScope::Bill => (None, None),
+		models::DiscountScope::Line => {
+			let lines = repository.bill_lines(active_session.id).await?;
+			match (request.order_id, request.item_id) {
+				(Some(order_id), Some(item_id)) if lines.iter().any(|line| line.order_id == order_id && line.item_id == item_id) => (Some(order_id), Some(item_id)),
+				_ => return Err(Error::Unprocessable{
+					msg: message!("Line discounts must target an item ordered in this session")
 				})
 			}
 		}
+	};
+
+	let discount = models::AppliedDiscount {
+		id: 0,
+		table_session_id: active_session.id,
+		discount_id: request.discount_id,
+		order_id,
+		item_id,
+		name,
+		kind,
+		value,
+		scope,
+		stackable,
+		reason: request.reason.trim().to_string(),
+		applied_by: staff.id,
+		timestamp: String::new()
+	};
+	let existing = repository.applied_discounts(active_session.id).await?;
+	if !billing::can_stack(&existing, &discount) {
+		return Err(Error::Conflict{
+			msg: message!("'{}' cannot be combined with the discounts already applied", discount.name)
+		});
 	}
+	Ok(Json(repository.apply_discount(&discount).await?))
 }
 
-#[rocket::get("/tables")]
-pub async fn get_active_sessions(mut db: Connection<db::Db>) -> Result<Vec<models::TableSession>> {
-	let db_result = db::retrieve_active_table_sessions(&mut db).await?;
-	match db_result {
-		Some(sessions) => Ok(Json(sessions)),
-		None => Err(Error::Api{
-			msg: String::from("Unable to get table sessions")
-		})
+#[rocket::delete("/tables/<table_nr>/discounts/<applied_discount_id>")]
+#[utoipa::path(
+	delete,
+	path = "/tables/{table_nr}/discounts/{applied_discount_id}",
+	tag = "billing",
+	params(
+		("table_nr" = u8, Path, description = "Table number"),
+		("applied_discount_id" = i64, Path, description = "Applied discount id")
+	),
+	responses(
+		(status = 200, description = "`success`, or `failed` when no such discount is applied", body = String),
+		(status = 403, description = "Not a manager"),
+		(status = 500, description = "Failed", body = models::Failure)
+	),
+	security(("staff_token" = []))
+)]
+pub async fn remove_discount(repository: &State<repository::Repository>, location: models::Location, manager: auth::Manager, table_nr: u8, applied_discount_id: i64) -> Result<String> {
+	let active_session = service::active_session(repository.as_ref(), location.id, table_nr).await?;
+	if repository.remove_discount(active_session.id, applied_discount_id).await? {
+		info!("Discount {} on table #{} removed by {}", applied_discount_id, table_nr, manager.0.name);
+		Ok(Json(String::from("success")))
+	}
+	else {
+		Ok(Json(String::from("failed")))
 	}
 }
 
-#[rocket::get("/tables/<table_nr>")]
-pub async fn get_sessions(mut db: Connection<db::Db>, table_nr: u8) -> Result<Vec<models::TableSession>> {
-	let db_result = db::retrieve_table_sessions(&mut db, table_nr).await?;
-	match db_result {
-		Some(sessions) => Ok(Json(sessions)),
-		None => Err(Error::Api{
-			msg: format!("Unable to get session for table #{}", table_nr)
-		})
+/// Takes a payment towards the bill of a table's active session. The tip is
+/// optional and is paid on top of `amount`. Retries with the same
+/// `Idempotency-Key` header get the recorded payment back instead of charging
+/// again.
+#[rocket::post("/tables/<table_nr>/payments", data = "<payment>")]
+#[utoipa::path(
+	post,
+	path = "/tables/{table_nr}/payments",
+	tag = "billing",
+	params(
+		("table_nr" = u8, Path, description = "Table number"),
+		("Idempotency-Key" = Option<String>, Header, description = "Makes retries of the request safe")
+	),
+	request_body = models::Payment,
+	responses(
+		(status = 200, description = "Success", body = models::Payment),
+		(status = 409, description = "The payment exceeds the balance due, or a request with the same key is in progress", body = models::Failure),
+		(status = 422, description = "The payment is in another currency than the bill, or the key was used for another body", body = models::Failure),
+		(status = 500, description = "Failed", body = models::Failure)
+	),
+	security(("staff_token" = []))
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn new_payment(pool: &State<db::Db>, repository: &State<repository::Repository>, settings: &State<settings::Settings>, location: models::Location, staff: models::Staff, key: idempotency::IdempotencyKey, table_nr: u8, payment: idempotency::HashedJson<models::Payment>) -> std::result::Result<idempotency::Idempotent<models::Payment>, Error> {
+	let claim = match key.begin(&mut *pool.acquire().await?, &format!("POST /locations/{}/tables/{}/payments", location.slug, table_nr), &payment).await? {
+		idempotency::Begin::Replay(response) => return Ok(idempotency::Idempotent::Replayed(response)),
+		idempotency::Begin::Proceed(claim) => claim
+	};
+	let result = record_payment(repository.as_ref(), &settings.billing(), location.id, staff, table_nr, &payment).await;
+	idempotency::finish(&mut *pool.acquire().await?, claim, result).await
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn record_payment(repository: &dyn repository::RestaurantRepository, config: &billing::BillingConfig, location_id: i64, staff: models::Staff, table_nr: u8, payment: &models::Payment) -> Result<models::Payment> {
+	let active_session = service::active_session(repository, location_id, table_nr).await?;
+	let bill = service::bill(repository, config, &active_session).await?;
+	let currency = payment.currency.unwrap_or(bill.currency);
+	if currency != bill.currency {
+		return Err(Error::Unprocessable{
+			msg: money::CurrencyMismatch(bill.currency, currency).message()
+		});
+	}
+	if i64::from(payment.amount) > bill.amounts.balance_due.amount {
+		return Err(Error::Conflict{
+			msg: message!("Payment of {} exceeds the balance of {} for table #{}", payment.amount, bill.amounts.balance_due.amount, table_nr)
+		});
 	}
+	let payment = models::Payment { currency: Some(currency), ..payment.clone() };
+	Ok(Json(repository.create_payment(active_session.id, staff.id, &payment).await?))
 }
 
-#[rocket::get("/tables/<table_nr>/active")]
-pub async fn get_active_session(mut db: Connection<db::Db>, table_nr: u8) -> Result<models::TableSession> {
-	let db_result = db::retrieve_active_table_session(&mut db, table_nr).await?;
+#[rocket::post("/shifts/clock-in")]
+#[utoipa::path(
+	post,
+	path = "/shifts/clock-in",
+	tag = "staff",
+	responses(
+		(status = 200, description = "Success", body = models::Shift),
+		(status = 500, description = "Failed", body = models::Failure)
+	),
+	security(("staff_token" = []))
+)]
+pub async fn clock_in(mut db: Connection<db::Db>, staff: models::Staff) -> Result<models::Shift> {
+	if db::retrieve_open_shift(&mut db, staff.id).await?.is_some() {
+		return Err(Error::Api{
+			msg: message!("{} is already clocked in", staff.name)
+		});
+	}
+	let db_result = db::create_shift(&mut db, staff.id).await?;
 	match db_result {
-		Some(active_session) => Ok(Json(active_session)),
+		Some(shift) => Ok(Json(shift)),
 		None => Err(Error::Api{
-			msg: format!("No active session for table #{}", table_nr)
+			msg: message!("Unable to start shift")
 		})
 	}
 }
 
-#[rocket::delete("/tables/<table_nr>")]
-pub async fn end_session(mut db: Connection<db::Db>, table_nr: u8) -> Result<String> {
-	let db_result = db::retrieve_active_table_session(&mut db, table_nr).await?;
+#[rocket::post("/shifts/clock-out")]
+#[utoipa::path(
+	post,
+	path = "/shifts/clock-out",
+	tag = "staff",
+	responses(
+		(status = 200, description = "`success`, or `failed` when the shift was closed already", body = String),
+		(status = 500, description = "Failed", body = models::Failure)
+	),
+	security(("staff_token" = []))
+)]
+pub async fn clock_out(mut db: Connection<db::Db>, staff: models::Staff) -> Result<String> {
+	let db_result = db::retrieve_open_shift(&mut db, staff.id).await?;
 	match db_result {
-		Some(active_session) => {
-			let db_result = db::deactivate_table_session(&mut db, active_session.id).await?;
+		Some(shift) => {
+			let db_result = db::close_shift(&mut db, shift.id).await?;
 			if db_result {
 				Ok(Json(String::from("success")))
 			}
@@ -172,135 +1234,973 @@ pub async fn end_session(mut db: Connection<db::Db>, table_nr: u8) -> Result<Str
 			}
 		},
 		None => Err(Error::Api{
-			msg: format!("No active session for table #{}", table_nr)
+			msg: message!("{} is not clocked in", staff.name)
 		})
 	}
 }
 
-#[rocket::delete("/tables/<table_nr>/orders/<order_id>/<item_id>")]
-pub async fn remove_item(mut db: Connection<db::Db>, table_nr: u8, order_id: i64, item_id: i64) -> Result<String> {
-	let db_result = db::retrieve_active_table_session(&mut db, table_nr).await?;
+/// Distributes the tips taken at the location between `from` and `to`
+/// (inclusive dates, defaulting to today) across its staff who served each
+/// session, weighted by role and by hours worked in that period.
+#[rocket::get("/reports/tips?<from>&<to>")]
+#[utoipa::path(
+	get,
+	path = "/reports/tips",
+	tag = "staff",
+	params(
+		("from" = Option<String>, Query, description = "First day, YYYY-MM-DD, today by default"),
+		("to" = Option<String>, Query, description = "Last day, YYYY-MM-DD, today by default")
+	),
+	responses(
+		(status = 200, description = "Success", body = models::TipReport),
+		(status = 403, description = "Not a manager"),
+		(status = 500, description = "Failed", body = models::Failure)
+	),
+	security(("staff_token" = []))
+)]
+pub async fn tip_report(mut db: Connection<db::Db>, settings: &State<settings::Settings>, location: models::Location, _manager: auth::Manager, from: Option<String>, to: Option<String>) -> Result<models::TipReport> {
+	let settings = settings.current();
+	let (from, to) = report_period(settings.today(), from, to).map_err(|msg| Error::Api{ msg })?;
+	let (start, end) = (settings.day_start(from), settings.day_start(to + chrono::Duration::days(1)));
+
+	let sessions = db::retrieve_session_tips(&mut db, location.id, &start, &end).await?.unwrap_or_default();
+	let staff_hours = db::retrieve_staff_hours(&mut db, location.id, &start, &end).await?.unwrap_or_default();
+	let config = settings.billing;
+	let weights = staff_hours.iter()
+		.map(|(staff, hours)| (staff.id, config.tip_weight(staff.role) as f64 * hours))
+		.collect();
+	let shares = billing::pool_tips(&sessions, &weights);
+
+	Ok(Json(models::TipReport {
+		from: from.to_string(),
+		to: to.to_string(),
+		total_tips: sessions.iter().map(|(tips, _)| tips).sum(),
+		shares: staff_hours.into_iter()
+			.filter_map(|(staff, hours)| shares.get(&staff.id).map(|share| models::TipShare {
+				staff_id: staff.id,
+				name: staff.name,
+				role: staff.role,
+				hours,
+				share: *share
+			}))
+			.collect()
+	}))
+}
+
+/// How fast staff answered the location's signals in each shift between
+/// `from` and `to` (inclusive dates, defaulting to today).
+#[rocket::get("/reports/signals?<from>&<to>")]
+#[utoipa::path(
+	get,
+	path = "/reports/signals",
+	tag = "signals",
+	params(
+		("from" = Option<String>, Query, description = "First day, YYYY-MM-DD, today by default"),
+		("to" = Option<String>, Query, description = "Last day, YYYY-MM-DD, today by default")
+	),
+	responses(
+		(status = 200, description = "Success", body = models::SignalReport),
+		(status = 403, description = "Not a manager"),
+		(status = 500, description = "Failed", body = models::Failure)
+	),
+	security(("staff_token" = []))
+)]
+pub async fn signal_report(mut db: Connection<db::Db>, settings: &State<settings::Settings>, location: models::Location, _manager: auth::Manager, from: Option<String>, to: Option<String>) -> Result<models::SignalReport> {
+	let settings = settings.current();
+	let (from, to) = report_period(settings.today(), from, to).map_err(|msg| Error::Api{ msg })?;
+	let (start, end) = (settings.day_start(from), settings.day_start(to + chrono::Duration::days(1)));
+
+	let shifts = db::retrieve_signal_response_times(&mut db, location.id, &start, &end).await?.unwrap_or_default();
+	let unacknowledged = db::count_unacknowledged_signals(&mut db, location.id, &start, &end).await?;
+	Ok(Json(models::SignalReport {
+		from: from.to_string(),
+		to: to.to_string(),
+		unacknowledged: unacknowledged as u32,
+		shifts: shifts.into_iter()
+			.map(|(shift, name, times)| models::SignalShift {
+				shift_id: shift.id,
+				staff_id: shift.staff_id,
+				name,
+				clock_in: shift.clock_in,
+				clock_out: shift.clock_out,
+				acknowledged: times.len() as u32,
+				average_response_seconds: if times.is_empty() { 0 } else { (times.iter().sum::<i64>() / times.len() as i64) as u32 },
+				longest_response_seconds: times.iter().max().copied().unwrap_or_default() as u32
+			})
+			.collect()
+	}))
+}
+
+/// Opens another location with a copy of the current location's menu, for
+/// owners.
+#[rocket::post("/locations", data = "<new_location>")]
+#[utoipa::path(
+	post,
+	path = "/locations",
+	tag = "locations",
+	request_body = models::Location,
+	responses(
+		(status = 200, description = "Success", body = models::Location),
+		(status = 403, description = "Not an owner"),
+		(status = 409, description = "The slug is taken", body = models::Failure),
+		(status = 500, description = "Failed", body = models::Failure)
+	),
+	security(("staff_token" = []))
+)]
+pub async fn new_location(mut db: Connection<db::Db>, location: models::Location, owner: auth::Owner, new_location: Json<models::Location>) -> Result<models::Location> {
+	let slug = new_location.slug.trim().to_string();
+	if slug.is_empty() || !slug.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-') {
+		return Err(Error::Api{
+			msg: message!("Invalid location slug '{}', expected lowercase letters, digits and dashes", slug)
+		});
+	}
+	let new_location = models::Location { slug: slug.clone(), ..new_location.into_inner() };
+	let db_result = match db::create_location(&mut db, &new_location, location.id).await {
+		Err(e) if db::is_unique_violation(&e) => return Err(Error::Conflict{
+			msg: message!("Location '{}' already exists", slug)
+		}),
+		db_result => db_result?
+	};
 	match db_result {
-		Some(_active_session) => {
-			let db_result = db::delete_item_from_order(&mut db, order_id, item_id).await?;
-			if db_result {
-				Ok(Json(String::from("success")))
-			}
-			else {
-				Ok(Json(String::from("failed")))
-			}
+		Some(created) => {
+			info!("{} opened location {} with the menu of {}", owner.0.name, created.slug, location.slug);
+			Ok(Json(created))
 		},
 		None => Err(Error::Api{
-			msg: format!("No active session for table #{}", table_nr)
+			msg: message!("Unable to create location")
 		})
 	}
 }
-pub fn stage() -> AdHoc {
-	AdHoc::on_ignite("Register handlers", |rocket| async {
-		rocket.mount("/", rocket::routes![
-			get_items,
-			get_item,
-			new_order,
-			get_orders,
-			get_order,
-			remove_order,
-			new_session,
-			get_active_sessions,
-			get_sessions,
-			get_active_session,
-			end_session,
-			remove_item
-		])
-	})
+
+/// Sessions, revenue and tips of every location between `from` and `to`
+/// (inclusive dates, defaulting to today), for owners.
+#[rocket::get("/reports/locations?<from>&<to>")]
+#[utoipa::path(
+	get,
+	path = "/reports/locations",
+	tag = "locations",
+	params(
+		("from" = Option<String>, Query, description = "First day, YYYY-MM-DD, today by default"),
+		("to" = Option<String>, Query, description = "Last day, YYYY-MM-DD, today by default")
+	),
+	responses(
+		(status = 200, description = "Success", body = [models::LocationReport]),
+		(status = 403, description = "Not an owner"),
+		(status = 500, description = "Failed", body = models::Failure)
+	),
+	security(("staff_token" = []))
+)]
+pub async fn location_report(mut db: Connection<db::Db>, settings: &State<settings::Settings>, _owner: auth::Owner, from: Option<String>, to: Option<String>) -> Result<Vec<models::LocationReport>> {
+	let settings = settings.current();
+	let (from, to) = report_period(settings.today(), from, to).map_err(|msg| Error::Api{ msg })?;
+	let (start, end) = (settings.day_start(from), settings.day_start(to + chrono::Duration::days(1)));
+	let db_result = db::retrieve_location_reports(&mut db, &start, &end).await?;
+	match db_result {
+		Some(reports) => Ok(Json(reports)),
+		None => Err(Error::Api{
+			msg: message!("Unable to get location reports")
+		})
+	}
 }
 
-#[cfg(test)]
-mod tests {
-    use rocket::local::blocking::Client;
-    use rocket::http::{ContentType, Status};
-	
-	#[test]
-    fn nothing_on_root_test() 
-	{
-        let client = Client::tracked(rocket::build()
-			.attach(super::stage())
-			.attach(super::db::stage()))
-			.expect("valid rocket instance");
-        let response = client.get("/").dispatch();
-        assert_eq!(response.status(), Status::NotFound);
-    }
+/// The restaurant settings, for clients showing the name, currency or
+/// opening hours.
+#[rocket::get("/settings")]
+#[utoipa::path(
+	get,
+	path = "/settings",
+	tag = "settings",
+	responses(
+		(status = 200, description = "Success", body = settings::RestaurantSettings)
+	)
+)]
+pub async fn get_settings(settings: &State<settings::Settings>) -> Json<settings::RestaurantSettings> {
+	Json(settings.current())
+}
 
-	#[test]
-	fn items_list_test() 
-	{
-        let client = Client::tracked(rocket::build()
-			.attach(super::stage())
-			.attach(super::db::stage()))
-			.expect("valid rocket instance");
-        let response = client.get("/items").dispatch();    
-		assert_eq!(response.status(), Status::Ok);
-		assert_eq!(response.content_type(), Some(ContentType::JSON));
-		let items = response.into_json::<Vec<super::models::Item>>().expect("Item list");
-		assert_ne!(items.len(), 0);
+/// Replaces the restaurant settings, for owners. The new settings apply right
+/// away and are kept over those of the configuration from then on.
+#[rocket::put("/settings", data = "<new_settings>")]
+#[utoipa::path(
+	put,
+	path = "/settings",
+	tag = "settings",
+	request_body = settings::RestaurantSettings,
+	responses(
+		(status = 200, description = "Success", body = settings::RestaurantSettings),
+		(status = 403, description = "Not an owner"),
+		(status = 422, description = "Invalid settings", body = models::Failure),
+		(status = 500, description = "Failed", body = models::Failure)
+	),
+	security(("staff_token" = []))
+)]
+pub async fn update_settings(mut db: Connection<db::Db>, settings: &State<settings::Settings>, owner: auth::Owner, new_settings: Json<settings::RestaurantSettings>) -> Result<settings::RestaurantSettings> {
+	let new_settings = new_settings.into_inner();
+	new_settings.validate().map_err(|msg| Error::Unprocessable{ msg })?;
+	let saved = rocket::serde::json::to_string(&new_settings).map_err(|e| Error::Api{
+		msg: message!("Unable to save settings: {}", e)
+	})?;
+	db::save_settings(&mut db, &saved).await?;
+	settings.replace(new_settings.clone());
+	info!("{} changed the settings", owner.0.name);
+	Ok(Json(new_settings))
+}
+
+/// Inclusive dates of a report, `from` and `to` defaulting to today.
+fn report_period(today: chrono::NaiveDate, from: Option<String>, to: Option<String>) -> std::result::Result<(chrono::NaiveDate, chrono::NaiveDate), i18n::Message> {
+	let parse = |date: Option<String>| match date {
+		Some(date) => chrono::NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+			.map_err(|_| message!("Invalid date '{}', expected YYYY-MM-DD", date)),
+		None => Ok(today)
+	};
+	match (parse(from), parse(to)) {
+		(Ok(from), Ok(to)) => Ok((from, to)),
+		(Err(msg), _) | (_, Err(msg)) => Err(msg)
 	}
+}
 
-	#[test]
-	fn single_item_test() 
-	{
-        let client = Client::tracked(rocket::build()
-			.attach(super::stage())
-			.attach(super::db::stage()))
-			.expect("valid rocket instance");
-        let response = client.get("/items/1").dispatch();    
-		assert_eq!(response.status(), Status::Ok);
-		assert_eq!(response.content_type(), Some(ContentType::JSON));
-		let _item = response.into_json::<super::models::Item>().expect("Item");
+/// A session of the location by id, also once it has ended. Any staff member
+/// can look at active sessions, closed ones are for managers.
+#[rocket::get("/sessions/<table_session_id>")]
+#[utoipa::path(
+	get,
+	path = "/sessions/{table_session_id}",
+	tag = "sessions",
+	params(
+		("table_session_id" = i64, Path, description = "Session id")
+	),
+	responses(
+		(status = 200, description = "Success", body = models::TableSession),
+		(status = 403, description = "The session is closed and the caller no manager", body = models::Failure),
+		(status = 500, description = "Failed", body = models::Failure)
+	),
+	security(("staff_token" = []))
+)]
+pub async fn get_session(repository: &State<repository::Repository>, location: models::Location, staff: models::Staff, table_session_id: i64) -> Result<models::TableSession> {
+	let session = service::readable_session(repository.as_ref(), location.id, &staff, table_session_id).await?;
+	Ok(Json(session))
+}
+
+#[rocket::get("/sessions/<table_session_id>/orders?<limit>&<cursor>&<sort>&<status>")]
+#[utoipa::path(
+	get,
+	path = "/sessions/{table_session_id}/orders",
+	tag = "sessions",
+	params(
+		("table_session_id" = i64, Path, description = "Session id"),
+		("limit" = Option<u32>, Query, description = "Page size, 50 by default and at most 200"),
+		("cursor" = Option<String>, Query, description = "`next_cursor` of the previous page"),
+		("sort" = Option<String>, Query, description = "`id` or `timestamp`, `-` in front to sort descending"),
+		("status" = Option<models::ItemStatus>, Query, description = "Only orders with items held back, or all of them fired")
+	),
+	responses(
+		(status = 200, description = "Success", body = models::OrderPage),
+		(status = 403, description = "The session is closed and the caller no manager", body = models::Failure),
+		(status = 422, description = "An invalid limit, cursor, sort or status", body = models::Failure),
+		(status = 500, description = "Failed", body = models::Failure)
+	),
+	security(("staff_token" = []))
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn get_session_orders(repository: &State<repository::Repository>, location: models::Location, staff: models::Staff, table_session_id: i64, limit: Option<u32>, cursor: Option<&str>, sort: Option<&str>, status: Option<&str>) -> std::result::Result<pagination::Paginated<models::Order>, Error> {
+	let page = pagination::PageRequest::new(limit, cursor, sort, &ORDER_SORT).map_err(|msg| Error::Unprocessable{ msg })?;
+	let status = parse_status(status).map_err(|msg| Error::Unprocessable{ msg })?;
+	let session = service::readable_session(repository.as_ref(), location.id, &staff, table_session_id).await?;
+	Ok(pagination::Paginated(service::orders(repository.as_ref(), session.id, &page, status).await?))
+}
+
+#[rocket::get("/sessions/<table_session_id>/orders/<order_id>")]
+#[utoipa::path(
+	get,
+	path = "/sessions/{table_session_id}/orders/{order_id}",
+	tag = "sessions",
+	params(
+		("table_session_id" = i64, Path, description = "Session id"),
+		("order_id" = i64, Path, description = "Order id")
+	),
+	responses(
+		(status = 200, description = "Success", body = models::Order),
+		(status = 403, description = "The session is closed and the caller no manager", body = models::Failure),
+		(status = 500, description = "Failed", body = models::Failure)
+	),
+	security(("staff_token" = []))
+)]
+pub async fn get_session_order(repository: &State<repository::Repository>, location: models::Location, staff: models::Staff, table_session_id: i64, order_id: i64) -> Result<models::Order> {
+	let session = service::readable_session(repository.as_ref(), location.id, &staff, table_session_id).await?;
+	let db_result = repository.order(session.id, order_id).await?;
+	match db_result {
+		Some(order) => Ok(Json(order)),
+		None => Err(Error::Api{
+			msg: message!("Unable to get order with ID {} of session {}", order_id, table_session_id)
+		})
 	}
+}
 
+/// Receipt for a closed session as plain text (`width` 42 or 48 columns),
+/// an ESC/POS byte stream for thermal printers, or a printable HTML page.
+/// Like other closed sessions, it is for managers.
+#[rocket::get("/sessions/<table_session_id>/receipt?<format>&<width>")]
+#[utoipa::path(
+	get,
+	path = "/sessions/{table_session_id}/receipt",
+	tag = "billing",
+	params(
+		("table_session_id" = i64, Path, description = "Session id"),
+		("format" = Option<String>, Query, description = "`text` (default), `escpos` or `html`"),
+		("width" = Option<usize>, Query, description = "Columns of a text or ESC/POS receipt, 42 (default) or 48")
+	),
+	responses(
+		(status = 200, description = "The receipt", body = String, content_type = ["text/plain", "application/octet-stream", "text/html"]),
+		(status = 403, description = "The caller is no manager", body = models::Failure),
+		(status = 500, description = "Failed", body = models::Failure)
+	),
+	security(("staff_token" = []))
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn get_receipt(repository: &State<repository::Repository>, settings: &State<settings::Settings>, location: models::Location, staff: models::Staff, table_session_id: i64, format: Option<&str>, width: Option<usize>) -> std::result::Result<(ContentType, Vec<u8>), Error> {
+	let format = match receipt::Format::parse(format.unwrap_or("text")) {
+		Some(format) => format,
+		None => return Err(Error::Api{
+			msg: message!("Receipt format must be one of text, escpos or html")
+		})
+	};
+	let width = match width.unwrap_or(42) {
+		width @ (42 | 48) => width,
+		width => return Err(Error::Api{
+			msg: message!("Unsupported receipt width {}, expected 42 or 48", width)
+		})
+	};
+	let session = match service::readable_session(repository.as_ref(), location.id, &staff, table_session_id).await? {
+		session if !session.active => session,
+		_ => return Err(Error::Api{
+			msg: message!("Session {} is still active", table_session_id)
+		})
+	};
+	let bill = service::bill(repository.as_ref(), &settings.billing(), &session).await?;
+	Ok(match format {
+		receipt::Format::Text => (ContentType::Plain, receipt::render_text(&session, &bill, width).into_bytes()),
+		receipt::Format::EscPos => (ContentType::Binary, receipt::render_escpos(&session, &bill, width)),
+		receipt::Format::Html => (ContentType::HTML, receipt::render_html(&session, &bill).into_bytes()),
+	})
+}
 
-	#[test]
-	fn add_remove_session_test()
-	{
-		let client = Client::tracked(rocket::build()
-		.attach(super::stage())
-		.attach(super::db::stage()))
-		.expect("valid rocket instance");
+fn parse_course(course: &str) -> Option<models::Course> {
+	rocket::serde::json::from_value(rocket::serde::json::Value::from(course)).ok()
+}
 
-		let response = client.post("/tables/16")
-        .header(ContentType::JSON)
-		.body(r##"{
-			"table_nr":16,
-			"customers":6,
-			"session_start": "UnitTest",
-			"active":true
-		}"##).dispatch();
-		assert_eq!(response.status(), Status::Ok);
-		assert_eq!(response.content_type(), Some(ContentType::JSON));
-		let _session = response.into_json::<super::models::TableSession>().expect("TableSession");
+/// The `status` filter of order lists.
+fn parse_status(status: Option<&str>) -> std::result::Result<Option<models::ItemStatus>, i18n::Message> {
+	match status {
+		Some(status) => match rocket::serde::json::from_value(rocket::serde::json::Value::from(status)) {
+			Ok(status) => Ok(Some(status)),
+			Err(_) => Err(message!("Unknown order status '{}', expected held or fired", status))
+		},
+		None => Ok(None)
+	}
+}
 
-		let response = client.delete("/tables/16").dispatch();
-		assert_eq!(response.status(), Status::Ok);
-		assert_eq!(response.into_string().unwrap().as_str(), "\"success\"");
+/// The `station` filter of kitchen tickets, the queue and events.
+fn parse_station(station: Option<&str>) -> std::result::Result<Option<models::Station>, i18n::Message> {
+	match station {
+		Some(station) => match rocket::serde::json::from_value(rocket::serde::json::Value::from(station)) {
+			Ok(station) => Ok(Some(station)),
+			Err(_) => Err(message!("Unknown station '{}'", station))
+		},
+		None => Ok(None)
 	}
+}
 
-	#[test]
-	fn add_get_remove_order()
-	{
-		let client = Client::tracked(rocket::build()
-		.attach(super::stage())
-		.attach(super::db::stage()))
-		.expect("valid rocket instance");
+fn stale_order(order_id: i64) -> Error {
+	Error::PreconditionFailed{
+		msg: message!("Order {} was changed by someone else", order_id)
+	}
+}
 
-		let _response = client.post("/tables/18")
-        .header(ContentType::JSON)
-		.body(r##"{
-			"table_nr":18,
-			"customers":6,
-			"session_start": "UnitTest",
+/// The kitchen stations preparing an order, or one item of it.
+async fn order_stations(repository: &dyn repository::RestaurantRepository, table_session_id: i64, order_id: i64, item_id: Option<i64>) -> std::result::Result<Vec<models::Station>, Error> {
+	let items: Vec<models::QueueItem> = repository.queue_items(table_session_id).await?
+		.into_iter()
+		.filter(|item| item.order_id == order_id && item_id.is_none_or(|item_id| item_id == item.item_id))
+		.collect();
+	Ok(stations(&items))
+}
+
+fn stations(items: &[models::QueueItem]) -> Vec<models::Station> {
+	let mut stations: Vec<models::Station> = Vec::new();
+	for item in items {
+		if !stations.contains(&item.station) {
+			stations.push(item.station);
+		}
+	}
+	stations
+}
+
+fn publish_fired(events: &events::Events, location_id: i64, items: &[models::QueueItem]) {
+	for item in items {
+		events.publish(location_id, item.table_nr, vec![item.station], models::EventData::KitchenItemStatusChanged {
+			order_id: item.order_id,
+			item_id: item.item_id,
+			station: item.station,
+			status: models::ItemStatus::Fired
+		});
+	}
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn send_tickets(repository: &dyn repository::RestaurantRepository, kitchen: &kitchen::Kitchen, events: &events::Events, location_id: i64, kind: models::TicketKind, table_nr: u8, order_id: i64, station: Option<models::Station>) -> Result<Vec<models::KitchenTicket>> {
+	let active_session = service::active_session(repository, location_id, table_nr).await?;
+	if repository.order(active_session.id, order_id).await?.is_none() {
+		return Err(Error::Api{
+			msg: message!("Unable to get order with ID {}", order_id)
+		});
+	}
+	if kind == models::TicketKind::Fire {
+		let held: Vec<models::QueueItem> = repository.queue_items(active_session.id).await?
+			.into_iter()
+			.filter(|item| item.order_id == order_id && item.fired_at.is_none())
+			.collect();
+		repository.fire_order(order_id).await?;
+		publish_fired(events, location_id, &held);
+	}
+	let items: Vec<models::QueueItem> = repository.queue_items(active_session.id).await?
+		.into_iter()
+		.filter(|item| item.order_id == order_id && item.fired_at.is_some())
+		.filter(|item| station.is_none_or(|station| station == item.station))
+		.collect();
+	Ok(Json(kitchen.dispatch(kitchen::tickets_for(kind, &items)).await))
+}
+
+/// Prints the kitchen tickets of an order again, optionally for one station.
+/// Items still held for a later course are left out.
+#[rocket::post("/tables/<table_nr>/orders/<order_id>/tickets?<station>")]
+#[utoipa::path(
+	post,
+	path = "/tables/{table_nr}/orders/{order_id}/tickets",
+	tag = "kitchen",
+	params(
+		("table_nr" = u8, Path, description = "Table number"),
+		("order_id" = i64, Path, description = "Order id"),
+		("station" = Option<models::Station>, Query, description = "Only reprint this station's ticket")
+	),
+	responses(
+		(status = 200, description = "Success", body = [models::KitchenTicket]),
+		(status = 500, description = "Failed", body = models::Failure)
+	),
+	security(("staff_token" = []))
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn reprint_tickets(repository: &State<repository::Repository>, kitchen: &State<kitchen::Kitchen>, events: &State<events::Events>, location: models::Location, _staff: models::Staff, table_nr: u8, order_id: i64, station: Option<&str>) -> Result<Vec<models::KitchenTicket>> {
+	let station = parse_station(station).map_err(|msg| Error::Api{ msg })?;
+	send_tickets(repository.as_ref(), kitchen, events, location.id, models::TicketKind::Reprint, table_nr, order_id, station).await
+}
+
+/// Tells every station preparing part of an order to start cooking it now,
+/// including items held for a later course.
+#[rocket::post("/tables/<table_nr>/orders/<order_id>/fire")]
+#[utoipa::path(
+	post,
+	path = "/tables/{table_nr}/orders/{order_id}/fire",
+	tag = "kitchen",
+	params(
+		("table_nr" = u8, Path, description = "Table number"),
+		("order_id" = i64, Path, description = "Order id")
+	),
+	responses(
+		(status = 200, description = "Success", body = [models::KitchenTicket]),
+		(status = 500, description = "Failed", body = models::Failure)
+	),
+	security(("staff_token" = []))
+)]
+pub async fn fire_order(repository: &State<repository::Repository>, kitchen: &State<kitchen::Kitchen>, events: &State<events::Events>, location: models::Location, _staff: models::Staff, table_nr: u8, order_id: i64) -> Result<Vec<models::KitchenTicket>> {
+	send_tickets(repository.as_ref(), kitchen, events, location.id, models::TicketKind::Fire, table_nr, order_id, None).await
+}
+
+/// Moves an item that is still held to another course.
+#[rocket::put("/tables/<table_nr>/orders/<order_id>/<item_id>/course/<course>")]
+#[utoipa::path(
+	put,
+	path = "/tables/{table_nr}/orders/{order_id}/{item_id}/course/{course}",
+	tag = "kitchen",
+	params(
+		("table_nr" = u8, Path, description = "Table number"),
+		("order_id" = i64, Path, description = "Order id"),
+		("item_id" = i64, Path, description = "Item id"),
+		("course" = models::Course, Path, description = "Course to move the item to"),
+		("If-Match" = String, Header, description = "ETag of the current version, or `*`")
+	),
+	responses(
+		(status = 200, description = "`success`, or `failed` when the order has no such item", body = String),
+		(status = 412, description = "The order was changed since", body = models::Failure),
+		(status = 428, description = "If-Match is missing", body = models::Failure),
+		(status = 500, description = "Failed", body = models::Failure)
+	),
+	security(("staff_token" = []))
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn set_item_course(repository: &State<repository::Repository>, events: &State<events::Events>, location: models::Location, _staff: models::Staff, if_match: etag::IfMatch, table_nr: u8, order_id: i64, item_id: i64, course: &str) -> Result<String> {
+	let course = match parse_course(course) {
+		Some(course) => course,
+		None => return Err(Error::Api{
+			msg: message!("Unknown course '{}'", course)
+		})
+	};
+	let active_session = service::active_session(repository.as_ref(), location.id, table_nr).await?;
+	let order = match repository.order(active_session.id, order_id).await? {
+		Some(order) => order,
+		None => return Err(Error::Api{
+			msg: message!("Unable to get order with ID {}", order_id)
+		})
+	};
+	let version = if_match.expected_version(order_id, order.version)?;
+	match repository.update_item_course(order_id, item_id, course, version).await? {
+		Some(true) => {
+			let stations = order_stations(repository.as_ref(), active_session.id, order_id, Some(item_id)).await?;
+			events.publish(location.id, table_nr, stations, models::EventData::OrderUpdated { order_id });
+			Ok(Json(String::from("success")))
+		},
+		Some(false) => Ok(Json(String::from("failed"))),
+		None => Err(stale_order(order_id))
+	}
+}
+
+/// Fires a course: its held items go to the kitchen, and items of the course
+/// ordered afterwards are sent straight away.
+#[rocket::post("/tables/<table_nr>/courses/<course>/fire")]
+#[utoipa::path(
+	post,
+	path = "/tables/{table_nr}/courses/{course}/fire",
+	tag = "kitchen",
+	params(
+		("table_nr" = u8, Path, description = "Table number"),
+		("course" = models::Course, Path, description = "Course to fire")
+	),
+	responses(
+		(status = 200, description = "Success", body = [models::KitchenTicket]),
+		(status = 500, description = "Failed", body = models::Failure)
+	),
+	security(("staff_token" = []))
+)]
+pub async fn fire_course(repository: &State<repository::Repository>, kitchen: &State<kitchen::Kitchen>, events: &State<events::Events>, location: models::Location, _staff: models::Staff, table_nr: u8, course: &str) -> Result<Vec<models::KitchenTicket>> {
+	let course = match parse_course(course) {
+		Some(course) => course,
+		None => return Err(Error::Api{
+			msg: message!("Unknown course '{}'", course)
+		})
+	};
+	let active_session = service::active_session(repository.as_ref(), location.id, table_nr).await?;
+	let held: Vec<models::QueueItem> = repository.queue_items(active_session.id).await?
+		.into_iter()
+		.filter(|item| item.course == Some(course) && item.fired_at.is_none())
+		.collect();
+	repository.fire_course(active_session.id, course).await?;
+	publish_fired(events, location.id, &held);
+	Ok(Json(kitchen.dispatch(kitchen::tickets_for(models::TicketKind::Fire, &held)).await))
+}
+
+/// Holds a course again, so items of it ordered from now on wait to be fired.
+/// Returns the table's queue with the suggested fire times.
+#[rocket::post("/tables/<table_nr>/courses/<course>/hold")]
+#[utoipa::path(
+	post,
+	path = "/tables/{table_nr}/courses/{course}/hold",
+	tag = "kitchen",
+	params(
+		("table_nr" = u8, Path, description = "Table number"),
+		("course" = models::Course, Path, description = "Course to hold")
+	),
+	responses(
+		(status = 200, description = "Success", body = [models::QueueItem]),
+		(status = 500, description = "Failed", body = models::Failure)
+	),
+	security(("staff_token" = []))
+)]
+pub async fn hold_course(repository: &State<repository::Repository>, kitchen: &State<kitchen::Kitchen>, location: models::Location, _staff: models::Staff, table_nr: u8, course: &str) -> Result<Vec<models::QueueItem>> {
+	let course = match parse_course(course) {
+		Some(course) => course,
+		None => return Err(Error::Api{
+			msg: message!("Unknown course '{}'", course)
+		})
+	};
+	let active_session = service::active_session(repository.as_ref(), location.id, table_nr).await?;
+	repository.hold_course(active_session.id, course).await?;
+	let items = repository.queue_items(active_session.id).await?;
+	Ok(Json(kitchen::plan(items, chrono::Utc::now().naive_utc(), kitchen.course_interval)))
+}
+
+/// Everything ordered at the location's open tables, with suggested start, ready and fire
+/// times so the items of a course land together.
+#[rocket::get("/kitchen/queue?<station>")]
+#[utoipa::path(
+	get,
+	path = "/kitchen/queue",
+	tag = "kitchen",
+	params(
+		("station" = Option<models::Station>, Query, description = "Only this station's items")
+	),
+	responses(
+		(status = 200, description = "Success", body = [models::QueueItem]),
+		(status = 500, description = "Failed", body = models::Failure)
+	),
+	security(("staff_token" = []))
+)]
+pub async fn get_kitchen_queue(repository: &State<repository::Repository>, kitchen: &State<kitchen::Kitchen>, location: models::Location, _staff: models::Staff, station: Option<&str>) -> Result<Vec<models::QueueItem>> {
+	let station = parse_station(station).map_err(|msg| Error::Api{ msg })?;
+	let items = repository.kitchen_queue(location.id).await?;
+	Ok(Json(kitchen::plan(items, chrono::Utc::now().naive_utc(), kitchen.course_interval)
+		.into_iter()
+		.filter(|item| station.is_none_or(|station| station == item.station))
+		.collect()))
+}
+
+/// Server-sent events about the location's sessions, orders and kitchen,
+/// optionally for one table or station. Clients resume after the last event they saw by
+/// sending its id as the `Last-Event-ID` header, which `EventSource` does on
+/// reconnect, or as `last_event_id`. An id from before a restart, or older
+/// than the kept events, gets a `reset` event first.
+#[rocket::get("/events?<table>&<station>&<last_event_id>")]
+#[utoipa::path(
+	get,
+	path = "/events",
+	tag = "events",
+	params(
+		("table" = Option<u8>, Query, description = "Only this table's events"),
+		("station" = Option<models::Station>, Query, description = "Only events concerning this station"),
+		("last_event_id" = Option<u64>, Query, description = "Resume after this event, like the Last-Event-ID header")
+	),
+	responses(
+		(status = 200, description = "Server-sent events", body = models::Event, content_type = "text/event-stream"),
+		(status = 401, description = "No valid staff token"),
+		(status = 500, description = "Failed", body = models::Failure)
+	),
+	security(("staff_token" = []))
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn get_events(events: &State<events::Events>, location: models::Location, _staff: models::Staff, resume: events::LastEventId, mut shutdown: Shutdown, table: Option<u8>, station: Option<&str>, last_event_id: Option<u64>) -> std::result::Result<EventStream![], Error> {
+	let station = parse_station(station).map_err(|msg| Error::Api{ msg })?;
+	let filter = events::Filter { location_id: location.id, table_nr: table, station };
+	let (missed, mut receiver) = events.subscribe(resume.0.or(last_event_id));
+	Ok(EventStream! {
+		if missed.reset {
+			yield Event::json(&rocket::serde::json::json!({"type": "reset"})).event("reset");
+		}
+		for event in missed.events.into_iter().filter(|event| filter.matches(event)) {
+			yield Event::json(&event).id(event.id.to_string());
+		}
+		loop {
+			let event = select! {
+				event = receiver.recv() => match event {
+					Ok(event) => event,
+					// A subscriber that fell behind is disconnected, so it
+					// reconnects and catches up from the history.
+					Err(RecvError::Closed) | Err(RecvError::Lagged(_)) => break,
+				},
+				_ = &mut shutdown => break,
+			};
+			if filter.matches(&event) {
+				yield Event::json(&event).id(event.id.to_string());
+			}
+		}
+	})
+}
+
+/// Registers an endpoint to be posted the events it subscribes to at the
+/// location. The response is the only time the signing secret is shown.
+#[rocket::post("/webhooks", data = "<webhook>")]
+#[utoipa::path(
+	post,
+	path = "/webhooks",
+	tag = "webhooks",
+	request_body = models::Webhook,
+	responses(
+		(status = 200, description = "The webhook, with its secret", body = models::Webhook),
+		(status = 403, description = "Not a manager"),
+		(status = 500, description = "Failed", body = models::Failure)
+	),
+	security(("staff_token" = []))
+)]
+pub async fn new_webhook(repository: &State<repository::Repository>, location: models::Location, manager: auth::Manager, webhook: Json<models::Webhook>) -> Result<models::Webhook> {
+	if let Err(msg) = webhooks::validate(&webhook) {
+		return Err(Error::Api{ msg });
+	}
+	let secret = webhook.secret.clone().unwrap_or_else(webhooks::generate_secret);
+	let webhook = repository.create_webhook(location.id, &webhook, &secret).await?;
+	info!("{} registered webhook {} for {}", manager.0.name, webhook.id, webhook.url);
+	Ok(Json(webhook))
+}
+
+#[rocket::get("/webhooks")]
+#[utoipa::path(
+	get,
+	path = "/webhooks",
+	tag = "webhooks",
+	responses(
+		(status = 200, description = "Success", body = [models::Webhook]),
+		(status = 403, description = "Not a manager"),
+		(status = 500, description = "Failed", body = models::Failure)
+	),
+	security(("staff_token" = []))
+)]
+pub async fn get_webhooks(repository: &State<repository::Repository>, location: models::Location, _manager: auth::Manager) -> Result<Vec<models::Webhook>> {
+	Ok(Json(repository.webhooks(location.id).await?))
+}
+
+/// Stops posting events to a webhook. Its delivery log is kept.
+#[rocket::delete("/webhooks/<webhook_id>")]
+#[utoipa::path(
+	delete,
+	path = "/webhooks/{webhook_id}",
+	tag = "webhooks",
+	params(
+		("webhook_id" = i64, Path, description = "Webhook id")
+	),
+	responses(
+		(status = 200, description = "`success`, or `failed` when there is no such active webhook", body = String),
+		(status = 403, description = "Not a manager"),
+		(status = 500, description = "Failed", body = models::Failure)
+	),
+	security(("staff_token" = []))
+)]
+pub async fn remove_webhook(repository: &State<repository::Repository>, location: models::Location, _manager: auth::Manager, webhook_id: i64) -> Result<String> {
+	if repository.remove_webhook(location.id, webhook_id).await? {
+		Ok(Json(String::from("success")))
+	}
+	else {
+		Ok(Json(String::from("failed")))
+	}
+}
+
+/// Every attempt to deliver events to a webhook, oldest first.
+#[rocket::get("/webhooks/<webhook_id>/deliveries")]
+#[utoipa::path(
+	get,
+	path = "/webhooks/{webhook_id}/deliveries",
+	tag = "webhooks",
+	params(
+		("webhook_id" = i64, Path, description = "Webhook id")
+	),
+	responses(
+		(status = 200, description = "Success", body = [models::WebhookDelivery]),
+		(status = 403, description = "Not a manager"),
+		(status = 500, description = "Failed", body = models::Failure)
+	),
+	security(("staff_token" = []))
+)]
+pub async fn get_webhook_deliveries(repository: &State<repository::Repository>, location: models::Location, _manager: auth::Manager, webhook_id: i64) -> Result<Vec<models::WebhookDelivery>> {
+	Ok(Json(repository.webhook_deliveries(location.id, webhook_id).await?))
+}
+
+/// Payloads that could not be delivered after every retry.
+#[rocket::get("/webhooks/dead-letters")]
+#[utoipa::path(
+	get,
+	path = "/webhooks/dead-letters",
+	tag = "webhooks",
+	responses(
+		(status = 200, description = "Success", body = [models::DeadLetter]),
+		(status = 403, description = "Not a manager"),
+		(status = 500, description = "Failed", body = models::Failure)
+	),
+	security(("staff_token" = []))
+)]
+pub async fn get_dead_letters(repository: &State<repository::Repository>, location: models::Location, _manager: auth::Manager) -> Result<Vec<models::DeadLetter>> {
+	Ok(Json(repository.dead_letters(location.id).await?))
+}
+
+/// Delivers a dead letter again in the background, with the usual retries.
+/// If those fail too it becomes a new dead letter.
+#[rocket::post("/webhooks/dead-letters/<dead_letter_id>/retry")]
+#[utoipa::path(
+	post,
+	path = "/webhooks/dead-letters/{dead_letter_id}/retry",
+	tag = "webhooks",
+	params(
+		("dead_letter_id" = i64, Path, description = "Dead letter id")
+	),
+	responses(
+		(status = 200, description = "Success", body = models::DeadLetter),
+		(status = 403, description = "Not a manager"),
+		(status = 500, description = "Failed", body = models::Failure)
+	),
+	security(("staff_token" = []))
+)]
+pub async fn retry_dead_letter(pool: &State<db::Db>, repository: &State<repository::Repository>, webhooks: &State<webhooks::Webhooks>, location: models::Location, _manager: auth::Manager, dead_letter_id: i64) -> Result<models::DeadLetter> {
+	let dead_letter = match repository.take_dead_letter(location.id, dead_letter_id).await? {
+		Some(dead_letter) => dead_letter,
+		None => return Err(Error::Api{
+			msg: message!("No dead letter with ID {} for an active webhook", dead_letter_id)
+		})
+	};
+	let webhook = match repository.webhook(dead_letter.webhook_id).await? {
+		Some(webhook) => webhook,
+		None => return Err(Error::Api{
+			msg: message!("Webhook {} has been removed", dead_letter.webhook_id)
+		})
+	};
+	let (webhooks, pool, retried) = (webhooks.inner().clone(), (***pool).clone(), dead_letter.clone());
+	rocket::tokio::spawn(async move {
+		// Dead letters from before delivery ids get one now.
+		let delivery_id = match retried.delivery_id.as_str() {
+			"" => webhooks::generate_delivery_id(),
+			delivery_id => String::from(delivery_id)
+		};
+		webhooks.deliver(&pool, &webhook, &delivery_id, retried.event_id, &retried.event_type, &retried.payload).await;
+	});
+	Ok(Json(dead_letter))
+}
+
+pub fn stage() -> AdHoc {
+	AdHoc::on_ignite("Register handlers", |rocket| async {
+		rocket.attach(settings::stage())
+		.attach(i18n::stage())
+		.attach(guests::stage())
+		.attach(kitchen::stage())
+		.attach(events::stage())
+		.attach(webhooks::stage())
+		.attach(timeouts::stage())
+		.attach(openapi::stage())
+		.attach(graphql::stage())
+		.attach(locations::stage())
+		.mount(API_BASE, rocket::routes![
+			get_items,
+			get_item,
+			new_item,
+			update_item,
+			get_item_translations,
+			update_item_translation,
+			new_order,
+			get_orders,
+			get_order,
+			remove_order,
+			new_session,
+			get_active_sessions,
+			get_sessions,
+			get_active_session,
+			end_session,
+			remove_item,
+			get_discounts,
+			new_discount,
+			get_bill,
+			get_guest_token,
+			get_table_guest_orders,
+			approve_guest_order,
+			reject_guest_order,
+			get_guest_session,
+			get_guest_menu,
+			new_guest_order,
+			get_guest_orders,
+			get_guest_bill,
+			new_signal,
+			get_signals,
+			acknowledge_signal,
+			get_floor,
+			new_guest_signal,
+			get_guest_signals,
+			apply_discount,
+			remove_discount,
+			new_payment,
+			clock_in,
+			clock_out,
+			tip_report,
+			signal_report,
+			new_location,
+			location_report,
+			get_settings,
+			update_settings,
+			get_session,
+			get_session_orders,
+			get_session_order,
+			get_receipt,
+			reprint_tickets,
+			fire_order,
+			set_item_course,
+			fire_course,
+			hold_course,
+			get_kitchen_queue,
+			get_events,
+			new_webhook,
+			get_webhooks,
+			remove_webhook,
+			get_webhook_deliveries,
+			get_dead_letters,
+			retry_dead_letter
+		])
+	})
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::http::{ContentType, Header, Status};
+    use rocket::serde::json::Value;
+    use std::sync::Arc;
+    use crate::testing;
+	
+	#[test]
+    fn nothing_on_root_test() 
+	{
+        let client = testing::client();
+        let response = client.get("/").dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+    }
+
+	#[test]
+	fn items_list_test() 
+	{
+        let client = testing::client();
+        let response = client.get("/api/v1/items").dispatch();    
+		assert_eq!(response.status(), Status::Ok);
+		assert_eq!(response.content_type(), Some(ContentType::JSON));
+		let items = response.into_json::<super::models::Page<super::models::Item>>().expect("Item list").data;
+		assert_ne!(items.len(), 0);
+	}
+
+	#[test]
+	fn single_item_test() 
+	{
+        let client = testing::client();
+        let response = client.get("/api/v1/items/1").dispatch();    
+		assert_eq!(response.status(), Status::Ok);
+		assert_eq!(response.content_type(), Some(ContentType::JSON));
+		let _item = response.into_json::<super::models::Item>().expect("Item");
+	}
+
+
+	#[test]
+	fn add_remove_session_test()
+	{
+		let client = testing::client();
+
+		let response = client.post("/api/v1/tables/16")
+        .header(ContentType::JSON)
+		.body(r##"{
+			"table_nr":16,
+			"customers":6,
+			"session_start": "UnitTest",
+			"active":true
+		}"##).dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		assert_eq!(response.content_type(), Some(ContentType::JSON));
+		let _session = response.into_json::<super::models::TableSession>().expect("TableSession");
+
+		let response = client.delete("/api/v1/tables/16").header(Header::new("If-Match", "*")).dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		assert_eq!(response.into_string().unwrap().as_str(), "\"success\"");
+	}
+
+	#[test]
+	fn add_get_remove_order()
+	{
+		let client = testing::client();
+
+		let _response = client.post("/api/v1/tables/18")
+        .header(ContentType::JSON)
+		.body(r##"{
+			"table_nr":18,
+			"customers":6,
+			"session_start": "UnitTest",
 			"active":true
 		}"##).dispatch();	
 
-		let response = client.post("/tables/18/orders")
+		let response = client.post("/api/v1/tables/18/orders")
         .header(ContentType::JSON)
 		.body(r##"{
 			"order_items": 
@@ -320,24 +2220,25 @@ mod tests {
 		assert_eq!(response.content_type(), Some(ContentType::JSON));
 		let _order = response.into_json::<super::models::Order>().expect("Order");
 
-		let response = client.get("/tables/18/orders").dispatch();
+		let response = client.get("/api/v1/tables/18/orders").dispatch();
 		assert_eq!(response.status(), Status::Ok);
 		assert_eq!(response.content_type(), Some(ContentType::JSON));
 
-		let order_id = response.into_json::<Vec<super::models::Order>>().expect("Order")[0].id.unwrap();
+		let order_id = response.into_json::<super::models::Page<super::models::Order>>().expect("Order").data[0].id.unwrap();
 		println!("Order_id is {:?}", order_id);
 
-		let request_uri = format!("/tables/18/orders/{:?}", order_id);
+		let request_uri = format!("/api/v1/tables/18/orders/{:?}", order_id);
 		let response = client.get(request_uri).dispatch();
 		assert_eq!(response.status(), Status::Ok);
 		assert_eq!(response.content_type(), Some(ContentType::JSON));
+		let etag = response.headers().get_one("ETag").expect("ETag").to_string();
 
-		let request_uri = format!("/tables/18/orders/{:?}", order_id);
-		let response = client.delete(request_uri).dispatch();
+		let request_uri = format!("/api/v1/tables/18/orders/{:?}", order_id);
+		let response = client.delete(request_uri).header(Header::new("If-Match", etag)).dispatch();
 		assert_eq!(response.status(), Status::Ok);
 		assert_eq!(response.into_string().unwrap().as_str(), "\"success\"");
 
-		let response = client.delete("/tables/18").dispatch();
+		let response = client.delete("/api/v1/tables/18").header(Header::new("If-Match", "*")).dispatch();
 		assert_eq!(response.status(), Status::Ok);
 		assert_eq!(response.into_string().unwrap().as_str(), "\"success\"");
 	}
@@ -345,12 +2246,9 @@ mod tests {
 	#[test]
 		fn get_sessions()
 		{
-			let client = Client::tracked(rocket::build()
-			.attach(super::stage())
-			.attach(super::db::stage()))
-			.expect("valid rocket instance");
+			let client = testing::client();
 
-			let response = client.post("/tables/14")
+			let response = client.post("/api/v1/tables/14")
 			.header(ContentType::JSON)
 			.body(r##"{
 				"table_nr":14,
@@ -358,24 +2256,1307 @@ mod tests {
 				"session_start": "UnitTest",
 				"active":true
 			}"##).dispatch();
+			assert_eq!(response.status(), Status::Ok);
 
-			let response = client.get("/tables").dispatch();    
+			let response = client.get("/api/v1/tables").dispatch();    
 			assert_eq!(response.status(), Status::Ok);
 			assert_eq!(response.content_type(), Some(ContentType::JSON));
 			let _session = response.into_json::<Vec<super::models::TableSession>>().expect("Session");
 			
-			let response = client.get("/tables/14").dispatch();    
+			let response = client.get("/api/v1/tables/14").dispatch();    
 			assert_eq!(response.status(), Status::Ok);
 			assert_eq!(response.content_type(), Some(ContentType::JSON));
-			let _session = response.into_json::<Vec<super::models::TableSession>>().expect("Session");
+			let _session = response.into_json::<super::models::Page<super::models::TableSession>>().expect("Session");
 
-			let response = client.get("/tables/14/active").dispatch();    
+			let response = client.get("/api/v1/tables/14/active").dispatch();    
 			assert_eq!(response.status(), Status::Ok);
 			assert_eq!(response.content_type(), Some(ContentType::JSON));
 			let _session = response.into_json::<super::models::TableSession>().expect("Session");
 
-			let response = client.delete("/tables/14").dispatch();
+			let response = client.delete("/api/v1/tables/14").header(Header::new("If-Match", "*")).dispatch();
 			assert_eq!(response.status(), Status::Ok);
 			assert_eq!(response.into_string().unwrap().as_str(), "\"success\"");
 	}
-}
\ No newline at end of file
+
+	#[test]
+	fn discounts_and_bill_test()
+	{
+		let client = testing::client();
+
+		testing::open_session(&client, 20, 2);
+
+		let response = client.post("/api/v1/tables/20/orders")
+		.header(ContentType::JSON)
+		.body(r##"{
+			"order_items":
+			[
+				{
+					"item_id": 2,
+					"amount": 4
+				},
+				{
+					"item_id": 5,
+					"amount": 2
+				}
+			]
+		}"##).dispatch();
+		let order_id = response.into_json::<super::models::Order>().expect("Order").id.unwrap();
+
+		let response = client.post("/api/v1/tables/20/discounts")
+		.header(ContentType::JSON)
+		.body(r##"{
+			"discount_id": 1,
+			"reason": "Staff meal"
+		}"##).dispatch();
+		assert_eq!(response.status(), Status::Unauthorized);
+
+		let response = client.post("/api/v1/tables/20/discounts")
+		.header(ContentType::JSON)
+		.header(testing::server())
+		.body(r##"{
+			"discount_id": 1,
+			"reason": "Staff meal"
+		}"##).dispatch();
+		assert_eq!(response.status(), Status::Ok);
+
+		let comp = format!(r##"{{
+			"kind": "percent",
+			"value": 100,
+			"scope": "line",
+			"order_id": {},
+			"item_id": 5,
+			"reason": "Dish arrived cold"
+		}}"##, order_id);
+		let response = client.post("/api/v1/tables/20/discounts")
+		.header(ContentType::JSON)
+		.header(testing::server())
+		.body(comp.clone()).dispatch();
+		assert_eq!(response.status(), Status::Forbidden);
+
+		let response = client.post("/api/v1/tables/20/discounts")
+		.header(ContentType::JSON)
+		.header(testing::manager())
+		.body(comp).dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		let applied = response.into_json::<super::models::AppliedDiscount>().expect("AppliedDiscount");
+		assert_eq!(applied.reason, "Dish arrived cold");
+
+		let response = client.post("/api/v1/tables/20/discounts")
+		.header(ContentType::JSON)
+		.header(testing::server())
+		.body(r##"{
+			"discount_id": 3,
+			"reason": "Coupon"
+		}"##).dispatch();
+		assert_eq!(response.status(), Status::Conflict);
+
+		let response = client.post("/api/v1/tables/20/discounts")
+		.header(ContentType::JSON)
+		.header(testing::server())
+		.body(r##"{
+			"discount_id": 1,
+			"reason": " "
+		}"##).dispatch();
+		assert_eq!(response.status(), Status::UnprocessableEntity);
+
+		let response = client.get("/api/v1/tables/20/bill").dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		let bill = response.into_json::<super::models::Bill>().expect("Bill");
+		assert_eq!(bill.subtotal, 3852);
+		assert_eq!(bill.line_discounts, 1548);
+		assert_eq!(bill.bill_discounts, 230);
+		assert_eq!(bill.tax, 207);
+		assert_eq!(bill.total, 2281);
+
+		let response = client.delete("/api/v1/tables/20").header(Header::new("If-Match", "*")).dispatch();
+		assert_eq!(response.status(), Status::Ok);
+	}
+
+	#[test]
+	fn payments_and_tips_test()
+	{
+		let client = testing::client();
+
+		testing::open_session(&client, 22, 8);
+
+		let response = client.post("/api/v1/tables/22/orders")
+		.header(ContentType::JSON)
+		.header(testing::server())
+		.body(r##"{
+			"order_items":
+			[
+				{
+					"item_id": 3,
+					"amount": 1
+				}
+			]
+		}"##).dispatch();
+		assert_eq!(response.status(), Status::Ok);
+
+		let response = client.get("/api/v1/tables/22/bill").dispatch();
+		let bill = response.into_json::<super::models::Bill>().expect("Bill");
+		assert_eq!(bill.service_charge, 111);
+		assert_eq!(bill.total, 1353);
+
+		let response = client.post("/api/v1/tables/22/payments")
+		.header(ContentType::JSON)
+		.header(testing::server())
+		.body(r##"{
+			"amount": 2000,
+			"method": "cash"
+		}"##).dispatch();
+		assert_eq!(response.status(), Status::Conflict);
+		assert_eq!(response.into_json::<Value>().expect("Failure")["message"], "Payment of 2000 exceeds the balance of 1353 for table #22");
+
+		let response = client.post("/api/v1/tables/22/payments")
+		.header(ContentType::JSON)
+		.header(testing::server())
+		.body(r##"{
+			"amount": 1353,
+			"tip": 200,
+			"method": "card"
+		}"##).dispatch();
+		assert_eq!(response.status(), Status::Ok);
+
+		let response = client.get("/api/v1/tables/22/bill").dispatch();
+		let bill = response.into_json::<super::models::Bill>().expect("Bill");
+		assert_eq!(bill.tips, 200);
+		assert_eq!(bill.balance_due, 0);
+
+		let response = client.get("/api/v1/reports/tips")
+		.header(testing::server())
+		.dispatch();
+		assert_eq!(response.status(), Status::Forbidden);
+
+		let response = client.get("/api/v1/reports/tips")
+		.header(testing::manager())
+		.dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		let report = response.into_json::<super::models::TipReport>().expect("TipReport");
+		assert_eq!(report.total_tips, 200);
+		assert!(report.shares.iter().any(|share| share.staff_id == 2 && share.share > 0));
+
+		let response = client.delete("/api/v1/tables/22").header(Header::new("If-Match", "*")).dispatch();
+		assert_eq!(response.status(), Status::Ok);
+	}
+
+	#[test]
+	fn receipt_test()
+	{
+		let client = testing::client();
+
+		let session_id = testing::open_session(&client, 24, 2);
+
+		let _response = client.post("/api/v1/tables/24/orders")
+		.header(ContentType::JSON)
+		.body(r##"{
+			"order_items":
+			[
+				{
+					"item_id": 1,
+					"amount": 2
+				}
+			]
+		}"##).dispatch();
+
+		let request_uri = format!("/api/v1/sessions/{}/receipt", session_id);
+		let response = client.get(request_uri.clone()).header(testing::manager()).dispatch();
+		assert_eq!(response.status(), Status::InternalServerError);
+
+		let response = client.delete("/api/v1/tables/24").header(Header::new("If-Match", "*")).dispatch();
+		assert_eq!(response.status(), Status::Ok);
+
+		assert_eq!(client.get(request_uri.clone()).dispatch().status(), Status::Unauthorized);
+		assert_eq!(client.get(request_uri.clone()).header(testing::server()).dispatch().status(), Status::Forbidden);
+		let response = client.get(request_uri.clone()).header(testing::manager()).dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		assert_eq!(response.content_type(), Some(ContentType::Plain));
+		let text = response.into_string().unwrap();
+		assert!(text.lines().all(|line| line.chars().count() <= 42));
+		assert!(text.contains("2 x Bresaola and sausage spaghetti"));
+
+		let response = client.get(format!("{}?format=text&width=48", request_uri)).header(testing::manager()).dispatch();
+		assert!(response.into_string().unwrap().lines().any(|line| line.chars().count() == 48));
+
+		let response = client.get(format!("{}?format=escpos", request_uri)).header(testing::manager()).dispatch();
+		assert_eq!(response.content_type(), Some(ContentType::Binary));
+		assert!(response.into_bytes().unwrap().starts_with(&[0x1b, b'@']));
+
+		let response = client.get(format!("{}?format=html", request_uri)).header(testing::manager()).dispatch();
+		assert_eq!(response.content_type(), Some(ContentType::HTML));
+		assert!(response.into_string().unwrap().contains("Table #24"));
+	}
+
+	#[test]
+	fn kitchen_tickets_test()
+	{
+		let sink = Arc::new(super::kitchen::MemorySink::default());
+		let client = testing::client_with(|rocket| rocket
+		.manage(super::kitchen::Kitchen::new(sink.clone())));
+
+		testing::open_session(&client, 26, 3);
+
+		let response = client.post("/api/v1/tables/26/orders")
+		.header(ContentType::JSON)
+		.body(r##"{
+			"order_items":
+			[
+				{
+					"item_id": 1,
+					"amount": 2
+				},
+				{
+					"item_id": 7,
+					"amount": 1
+				},
+				{
+					"item_id": 8,
+					"amount": 3
+				}
+			]
+		}"##).dispatch();
+		let order_id = response.into_json::<super::models::Order>().expect("Order").id.unwrap();
+
+		let tickets = sink.tickets();
+		assert_eq!(tickets.len(), 3);
+		assert_eq!(tickets[0].station, super::models::Station::Grill);
+		assert_eq!(tickets[1].station, super::models::Station::Fryer);
+		assert_eq!(tickets[2].station, super::models::Station::Dessert);
+		assert!(tickets.iter().all(|ticket| ticket.order_id == order_id && ticket.table_nr == 26));
+
+		let response = client.post(format!("/api/v1/tables/26/orders/{}/tickets?station=dessert", order_id)).dispatch();
+		assert_eq!(response.status(), Status::Unauthorized);
+		let response = client.post(format!("/api/v1/tables/26/orders/{}/tickets?station=dessert", order_id)).header(testing::server()).dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		let reprinted = response.into_json::<Vec<super::models::KitchenTicket>>().expect("Tickets");
+		assert_eq!(reprinted.len(), 1);
+		assert_eq!(reprinted[0].kind, super::models::TicketKind::Reprint);
+
+		let response = client.post(format!("/api/v1/tables/26/orders/{}/fire", order_id)).header(testing::server()).dispatch();
+		assert_eq!(response.into_json::<Vec<super::models::KitchenTicket>>().expect("Tickets").len(), 3);
+		assert_eq!(sink.tickets().len(), 7);
+
+		let response = client.delete("/api/v1/tables/26").header(Header::new("If-Match", "*")).dispatch();
+		assert_eq!(response.status(), Status::Ok);
+	}
+
+	#[test]
+	fn course_hold_and_fire_test()
+	{
+		let sink = Arc::new(super::kitchen::MemorySink::default());
+		let client = testing::client_with(|rocket| rocket
+		.manage(super::kitchen::Kitchen::new(sink.clone())));
+
+		testing::open_session(&client, 28, 2);
+
+		let response = client.post("/api/v1/tables/28/orders")
+		.header(ContentType::JSON)
+		.body(r##"{
+			"order_items":
+			[
+				{
+					"item_id": 7,
+					"amount": 2,
+					"course": "starter"
+				},
+				{
+					"item_id": 1,
+					"amount": 2,
+					"course": "main"
+				},
+				{
+					"item_id": 8,
+					"amount": 2,
+					"course": "main"
+				}
+			]
+		}"##).dispatch();
+		let order_id = response.into_json::<super::models::Order>().expect("Order").id.unwrap();
+		assert!(sink.tickets().is_empty());
+
+		let response = client.get(format!("/api/v1/tables/28/orders/{}", order_id)).dispatch();
+		let order = response.into_json::<Value>().expect("Order");
+		assert!(order["order_items"].as_array().unwrap().iter().all(|item| item["held"] == true));
+
+		let response = client.put(format!("/api/v1/tables/28/orders/{}/8/course/dessert", order_id)).header(Header::new("If-Match", "*")).header(testing::server()).dispatch();
+		assert_eq!(response.into_json::<String>().unwrap(), "success");
+
+		let response = client.post("/api/v1/tables/28/courses/starter/fire").header(testing::server()).dispatch();
+		let tickets = response.into_json::<Vec<super::models::KitchenTicket>>().expect("Tickets");
+		assert_eq!(tickets.len(), 1);
+		assert_eq!(tickets[0].station, super::models::Station::Fryer);
+		assert_eq!(tickets[0].course, Some(super::models::Course::Starter));
+
+		let response = client.get("/api/v1/kitchen/queue?station=grill").header(testing::server()).dispatch();
+		let queue = response.into_json::<Vec<super::models::QueueItem>>().expect("Queue");
+		let main = queue.iter().find(|item| item.order_id == order_id).expect("Held main");
+		assert!(main.fired_at.is_none());
+		assert!(main.fire_at.is_some());
+
+		let response = client.put(format!("/api/v1/tables/28/orders/{}/7/course/main", order_id)).header(Header::new("If-Match", "*")).header(testing::server()).dispatch();
+		assert_eq!(response.into_json::<String>().unwrap(), "failed");
+
+		let response = client.post("/api/v1/tables/28/courses/main/fire").header(testing::server()).dispatch();
+		assert_eq!(response.into_json::<Vec<super::models::KitchenTicket>>().expect("Tickets").len(), 1);
+
+		let _response = client.post("/api/v1/tables/28/orders")
+		.header(ContentType::JSON)
+		.body(r##"{
+			"order_items":
+			[
+				{
+					"item_id": 1,
+					"amount": 1,
+					"course": "main"
+				}
+			]
+		}"##).dispatch();
+		assert_eq!(sink.tickets().len(), 3);
+		assert_eq!(sink.tickets()[2].kind, super::models::TicketKind::New);
+
+		let response = client.post("/api/v1/tables/28/courses/fish/fire").header(testing::server()).dispatch();
+		assert_eq!(response.status(), Status::InternalServerError);
+
+		let response = client.delete("/api/v1/tables/28").header(Header::new("If-Match", "*")).dispatch();
+		assert_eq!(response.status(), Status::Ok);
+	}
+
+	#[test]
+	fn events_test()
+	{
+		let client = testing::client_with(|rocket| rocket
+		.manage(super::kitchen::Kitchen::new(Arc::new(super::kitchen::MemorySink::default()))));
+
+		testing::open_session(&client, 30, 2);
+
+		testing::open_session(&client, 31, 2);
+
+		let response = client.post("/api/v1/tables/30/orders")
+		.header(ContentType::JSON)
+		.body(r##"{
+			"order_items":
+			[
+				{
+					"item_id": 7,
+					"amount": 1,
+					"course": "main"
+				}
+			]
+		}"##).dispatch();
+		let order_id = response.into_json::<super::models::Order>().expect("Order").id.unwrap();
+
+		let response = client.post("/api/v1/tables/30/courses/main/fire").header(testing::server()).dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		let _response = client.delete("/api/v1/tables/30").header(Header::new("If-Match", "*")).dispatch();
+		let _response = client.delete("/api/v1/tables/31").header(Header::new("If-Match", "*")).dispatch();
+
+		// Ends the otherwise endless streams once the history is replayed.
+		client.rocket().shutdown().notify();
+
+		let events = |uri: &str| -> Vec<Value> {
+			client.get(uri).header(testing::server()).dispatch().into_string().expect("Events")
+				.lines()
+				.filter_map(|line| line.strip_prefix("data:"))
+				.map(|data| rocket::serde::json::from_str(data).expect("Event"))
+				.collect()
+		};
+		// Id 0 is from before the process started, so whoever sends it may
+		// have missed more than the kept events.
+		let mut table = events("/api/v1/events?table=30&last_event_id=0");
+		assert_eq!(table.remove(0)["type"], "reset");
+		let types: Vec<&str> = table.iter().map(|event| event["type"].as_str().unwrap()).collect();
+		assert_eq!(types, ["session_opened", "order_created", "kitchen_item_status_changed", "session_closed"]);
+		assert_eq!(table[1]["order"]["id"], order_id);
+
+		let mut fryer = events("/api/v1/events?station=fryer&last_event_id=0");
+		assert_eq!(fryer.remove(0)["type"], "reset");
+		assert_eq!(fryer.len(), 2);
+		assert_eq!(fryer[1]["status"], "fired");
+
+		let resumed = client.get("/api/v1/events")
+		.header(testing::server())
+		.header(Header::new("Last-Event-ID", table[2]["id"].to_string()))
+		.dispatch().into_string().expect("Events");
+		assert_eq!(resumed.matches("data:").count(), 2);
+		assert!(events("/api/v1/events").is_empty());
+		let response = client.get("/api/v1/events").dispatch();
+		assert_eq!(response.status(), Status::Unauthorized);
+		let response = client.get("/api/v1/events").header(Header::new("Authorization", "Bearer not-a-token")).dispatch();
+		assert_eq!(response.status(), Status::Unauthorized);
+	}
+
+	/// Accepts webhook posts, failing the first one for table #32 so it is
+	/// retried, and passes on the bodies and signatures of those for table #32.
+	fn webhook_receiver() -> (u16, std::sync::mpsc::Receiver<(String, String)>) {
+		use std::io::{BufRead, BufReader, Read, Write};
+		let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("webhook receiver");
+		let port = listener.local_addr().unwrap().port();
+		let (sender, receiver) = std::sync::mpsc::channel();
+		std::thread::spawn(move || {
+			let mut failed = false;
+			for stream in listener.incoming() {
+				let mut stream = BufReader::new(stream.expect("webhook connection"));
+				let (mut length, mut signature) = (0, String::new());
+				loop {
+					let mut line = String::new();
+					stream.read_line(&mut line).unwrap();
+					let line = line.trim_end();
+					if line.is_empty() {
+						break;
+					}
+					if let Some((name, value)) = line.split_once(": ") {
+						match name.to_lowercase().as_str() {
+							"content-length" => length = value.parse().unwrap(),
+							"x-webhook-signature" => signature = String::from(value),
+							_ => (),
+						}
+					}
+				}
+				let mut body = vec![0; length];
+				stream.read_exact(&mut body).unwrap();
+				let body = String::from_utf8(body).unwrap();
+				let ours = body.contains(r#""table_nr":32"#);
+				let status = if ours && !failed { "500 Internal Server Error" } else { "200 OK" };
+				failed |= ours;
+				write!(stream.get_mut(), "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status).unwrap();
+				if ours && status == "200 OK" {
+					let _ = sender.send((body, signature));
+				}
+			}
+		});
+		(port, receiver)
+	}
+
+	#[test]
+	fn webhooks_test()
+	{
+		let client = testing::client_with(|rocket| {
+			let figment = rocket.figment().clone()
+			.merge(("webhooks.retry_base_ms", 10))
+			.merge(("webhooks.max_attempts", 3));
+			rocket.configure(figment)
+			.manage(super::kitchen::Kitchen::new(Arc::new(super::kitchen::MemorySink::default())))
+		});
+		let manager = testing::manager;
+
+		let (port, received) = webhook_receiver();
+		let response = client.post("/api/v1/webhooks")
+		.header(ContentType::JSON)
+		.header(manager())
+		.body(format!(r##"{{
+			"url": "http://127.0.0.1:{}/hook",
+			"secret": "test-secret",
+			"events": ["session_opened"]
+		}}"##, port)).dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		let webhook = response.into_json::<Value>().expect("Webhook");
+		assert_eq!(webhook["secret"], "test-secret");
+
+		let closed_port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+		let response = client.post("/api/v1/webhooks")
+		.header(ContentType::JSON)
+		.header(manager())
+		.body(format!(r##"{{
+			"url": "http://127.0.0.1:{}/hook",
+			"events": ["*"]
+		}}"##, closed_port)).dispatch();
+		let unreachable = response.into_json::<Value>().expect("Webhook");
+		assert_eq!(unreachable["secret"].as_str().map(str::len), Some(32));
+
+		let response = client.post("/api/v1/webhooks")
+		.header(ContentType::JSON)
+		.body(r##"{
+			"url": "http://127.0.0.1/hook",
+			"events": ["*"]
+		}"##).dispatch();
+		assert_eq!(response.status(), Status::Unauthorized);
+
+		testing::open_session(&client, 32, 2);
+
+		let (body, signature) = received.recv_timeout(std::time::Duration::from_secs(10)).expect("Webhook post");
+		assert_eq!(signature, super::webhooks::signature("test-secret", &body));
+		let event = rocket::serde::json::from_str::<Value>(&body).expect("Event");
+		assert_eq!(event["type"], "session_opened");
+
+		// Deliveries are logged and dead letters kept in the background.
+		let eventually = |done: &dyn Fn() -> bool| {
+			for _ in 0..100 {
+				if done() {
+					return true;
+				}
+				std::thread::sleep(std::time::Duration::from_millis(50));
+			}
+			false
+		};
+		assert!(eventually(&|| {
+			let response = client.get(format!("/api/v1/webhooks/{}/deliveries", webhook["id"])).header(manager()).dispatch();
+			let deliveries = response.into_json::<Vec<super::models::WebhookDelivery>>().expect("Deliveries");
+			deliveries.iter().any(|delivery| delivery.status_code == Some(500) && !delivery.succeeded)
+				&& deliveries.iter().any(|delivery| delivery.succeeded)
+				// A retry carries the X-Webhook-Id of the attempt before it.
+				&& deliveries.iter().all(|delivery| delivery.delivery_id.len() == 32 && delivery.delivery_id == deliveries[0].delivery_id)
+		}));
+		assert!(eventually(&|| {
+			let response = client.get("/api/v1/webhooks/dead-letters").header(manager()).dispatch();
+			response.into_json::<Vec<super::models::DeadLetter>>().expect("Dead letters")
+				.iter()
+				.any(|dead_letter| unreachable["id"] == dead_letter.webhook_id && dead_letter.attempts == 3 && dead_letter.payload.contains(r#""table_nr":32"#))
+		}));
+
+		for id in [&webhook["id"], &unreachable["id"]] {
+			let response = client.delete(format!("/api/v1/webhooks/{}", id)).header(manager()).dispatch();
+			assert_eq!(response.into_json::<String>().unwrap(), "success");
+		}
+		let response = client.delete("/api/v1/tables/32").header(Header::new("If-Match", "*")).dispatch();
+		assert_eq!(response.status(), Status::Ok);
+	}
+
+	#[test]
+	fn idempotency_test()
+	{
+		let client = testing::client_with(|rocket| rocket
+		.manage(super::kitchen::Kitchen::new(Arc::new(super::kitchen::MemorySink::default()))));
+		let key = |name: &str| Header::new("Idempotency-Key", format!("{}-key", name));
+
+		let session_key = key("session");
+		let open = || client.post("/api/v1/tables/34")
+		.header(ContentType::JSON)
+		.header(session_key.clone())
+		.body(r##"{
+			"customers":2
+		}"##).dispatch();
+		let first = open().into_json::<Value>().expect("Session");
+		let response = open();
+		assert_eq!(response.status(), Status::Ok);
+		assert_eq!(response.headers().get_one("Idempotent-Replayed"), Some("true"));
+		assert_eq!(response.into_json::<Value>().expect("Session")["id"], first["id"]);
+
+		let order_key = key("order");
+		let order = |amount: u8| client.post("/api/v1/tables/34/orders")
+		.header(ContentType::JSON)
+		.header(order_key.clone())
+		.body(format!(r##"{{
+			"order_items":
+			[
+				{{
+					"item_id": 3,
+					"amount": {}
+				}}
+			]
+		}}"##, amount)).dispatch();
+		assert_eq!(order(1).status(), Status::Ok);
+		assert_eq!(order(1).status(), Status::Ok);
+		assert_eq!(order(2).status(), Status::UnprocessableEntity);
+
+		let response = client.get("/api/v1/tables/34/orders").dispatch();
+		assert_eq!(response.into_json::<super::models::Page<super::models::Order>>().expect("Orders").data.len(), 1);
+
+		let payment_key = key("payment");
+		let pay = || client.post("/api/v1/tables/34/payments")
+		.header(ContentType::JSON)
+		.header(testing::server())
+		.header(payment_key.clone())
+		.body(r##"{
+			"amount": 100,
+			"method": "card"
+		}"##).dispatch();
+		let response = pay();
+		assert_eq!(response.status(), Status::Ok);
+		assert_eq!(response.into_json::<super::models::Payment>().expect("Payment").currency.map(|currency| currency.to_string()), Some(String::from("JPY")));
+		assert_eq!(pay().status(), Status::Ok);
+		let response = client.get("/api/v1/tables/34/bill").dispatch();
+		let bill = response.into_json::<super::models::Bill>().expect("Bill");
+		assert_eq!((bill.paid, bill.amounts.paid.amount, bill.amounts.paid.currency.code()), (100, 100, "JPY"));
+		let response = client.post("/api/v1/tables/34/payments")
+		.header(ContentType::JSON)
+		.header(testing::server())
+		.body(r##"{"amount": 100, "currency": "EUR", "method": "card"}"##).dispatch();
+		assert_eq!(response.status(), Status::UnprocessableEntity);
+
+		let response = client.delete("/api/v1/tables/34").header(Header::new("If-Match", "*")).dispatch();
+		assert_eq!(response.status(), Status::Ok);
+	}
+
+	#[test]
+	fn optimistic_concurrency_test()
+	{
+		let client = testing::client_with(|rocket| rocket
+		.manage(super::kitchen::Kitchen::new(Arc::new(super::kitchen::MemorySink::default()))));
+
+		testing::open_session(&client, 36, 2);
+
+		let response = client.post("/api/v1/tables/36/orders")
+		.header(ContentType::JSON)
+		.body(r##"{
+			"order_items":
+			[
+				{
+					"item_id": 2,
+					"amount": 1
+				},
+				{
+					"item_id": 4,
+					"amount": 1
+				}
+			]
+		}"##).dispatch();
+		let order_id = response.into_json::<super::models::Order>().expect("Order").id.unwrap();
+
+		let response = client.get(format!("/api/v1/tables/36/orders/{}", order_id)).dispatch();
+		let etag = response.headers().get_one("ETag").expect("ETag").to_string();
+
+		let response = client.delete(format!("/api/v1/tables/36/orders/{}/2", order_id)).dispatch();
+		assert_eq!(response.status(), Status::PreconditionRequired);
+
+		let response = client.delete(format!("/api/v1/tables/36/orders/{}/2", order_id))
+		.header(Header::new("If-Match", etag.clone())).dispatch();
+		assert_eq!(response.into_json::<String>().unwrap(), "success");
+
+		// A second server still holding the old ETag has to refresh first.
+		let response = client.delete(format!("/api/v1/tables/36/orders/{}/4", order_id))
+		.header(Header::new("If-Match", etag.clone())).dispatch();
+		assert_eq!(response.status(), Status::PreconditionFailed);
+
+		let response = client.get(format!("/api/v1/tables/36/orders/{}", order_id)).dispatch();
+		assert_ne!(response.headers().get_one("ETag"), Some(etag.as_str()));
+		let etag = response.headers().get_one("ETag").expect("ETag").to_string();
+
+		// Removing an item that isn't in the order changes nothing, so the
+		// ETag stays valid.
+		let response = client.delete(format!("/api/v1/tables/36/orders/{}/9", order_id))
+		.header(Header::new("If-Match", etag.clone())).dispatch();
+		assert_eq!(response.into_json::<String>().unwrap(), "failed");
+		let response = client.get(format!("/api/v1/tables/36/orders/{}", order_id)).dispatch();
+		assert_eq!(response.headers().get_one("ETag"), Some(etag.as_str()));
+
+		// Payments change what the session owes, and with it its version.
+		let response = client.get("/api/v1/tables/36/active").dispatch();
+		let session_etag = response.headers().get_one("ETag").expect("ETag").to_string();
+		let response = client.post("/api/v1/tables/36/payments")
+		.header(ContentType::JSON)
+		.header(testing::server())
+		.body(r##"{"amount": 100, "method": "cash"}"##).dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		let response = client.delete("/api/v1/tables/36").header(Header::new("If-Match", session_etag)).dispatch();
+		assert_eq!(response.status(), Status::PreconditionFailed);
+
+		let response = client.get("/api/v1/tables/36/active").dispatch();
+		let session_etag = response.headers().get_one("ETag").expect("ETag").to_string();
+		let response = client.delete("/api/v1/tables/36").header(Header::new("If-Match", "\"0-1\"")).dispatch();
+		assert_eq!(response.status(), Status::PreconditionFailed);
+		let response = client.delete("/api/v1/tables/36").header(Header::new("If-Match", session_etag)).dispatch();
+		assert_eq!(response.status(), Status::Ok);
+	}
+
+	#[rocket::async_test]
+	async fn concurrent_sessions_test()
+	{
+		let client = testing::async_client().await;
+
+		let open = || client.post("/api/v1/tables/37")
+		.header(ContentType::JSON)
+		.body(r##"{
+			"customers":2
+		}"##).dispatch();
+		let responses = rocket::futures::future::join_all((0..8).map(|_| open())).await;
+		let statuses: Vec<Status> = responses.iter().map(|response| response.status()).collect();
+		assert_eq!(statuses.iter().filter(|status| **status == Status::Ok).count(), 1);
+		assert!(statuses.iter().all(|status| *status == Status::Ok || *status == Status::Conflict));
+
+		let response = client.get("/api/v1/tables").dispatch().await;
+		let sessions = response.into_json::<Vec<rocket::serde::json::Value>>().await.expect("Sessions");
+		assert_eq!(sessions.iter().filter(|session| session["table_nr"] == 37).count(), 1);
+
+		let response = client.delete("/api/v1/tables/37").header(Header::new("If-Match", "*")).dispatch().await;
+		assert_eq!(response.status(), Status::Ok);
+	}
+
+	#[test]
+	fn pagination_test()
+	{
+		let client = testing::client_with(|rocket| rocket
+		.manage(super::kitchen::Kitchen::new(Arc::new(super::kitchen::MemorySink::default()))));
+
+		let response = client.get("/api/v1/items?limit=2&sort=-price").dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		let link = response.headers().get_one("Link").expect("Link").to_string();
+		let first = response.into_json::<super::models::Page<super::models::Item>>().expect("Items");
+		assert_eq!(first.data.len(), 2);
+		assert!(first.data[0].price.amount >= first.data[1].price.amount);
+		let cursor = first.next_cursor.expect("next cursor");
+		assert_eq!(link, format!("</api/v1/items?limit=2&sort=-price&cursor={}>; rel=\"next\"", cursor));
+
+		let response = client.get(format!("/api/v1/items?limit=2&sort=-price&cursor={}", cursor)).dispatch();
+		let second = response.into_json::<super::models::Page<super::models::Item>>().expect("Items");
+		assert!(second.data.iter().all(|item| item.price.amount <= first.data[1].price.amount && !first.data.iter().any(|seen| seen.id == item.id)));
+
+		let response = client.get(format!("/api/v1/items?limit=2&sort=name&cursor={}", cursor)).dispatch();
+		assert_eq!(response.status(), Status::UnprocessableEntity);
+		let response = client.get("/api/v1/tables/39/orders?status=eaten").dispatch();
+		assert_eq!(response.status(), Status::UnprocessableEntity);
+
+		let response = client.get("/api/v1/items?min_price=500&max_price=1500").dispatch();
+		assert!(response.headers().get_one("Link").is_none());
+		let items = response.into_json::<super::models::Page<super::models::Item>>().expect("Items");
+		assert!(items.next_cursor.is_none());
+		assert!(items.data.iter().all(|item| (500..=1500).contains(&item.price.amount)));
+
+		testing::open_session(&client, 39, 2);
+		let today = chrono::Utc::now().naive_utc().date();
+		let response = client.get(format!("/api/v1/tables/39?from={}&to={}", today, today)).dispatch();
+		let sessions = response.into_json::<rocket::serde::json::Value>().expect("Sessions");
+		assert!(sessions["data"].as_array().unwrap().iter().any(|session| session["active"] == true));
+		let response = client.get(format!("/api/v1/tables/39?to={}", today - chrono::Duration::days(1))).dispatch();
+		assert!(response.into_json::<super::models::Page<super::models::TableSession>>().expect("Sessions").data.is_empty());
+
+		let _response = client.post("/api/v1/tables/39/orders")
+		.header(ContentType::JSON)
+		.body(r##"{
+			"order_items":
+			[
+				{
+					"item_id": 2,
+					"amount": 1,
+					"course": "dessert"
+				}
+			]
+		}"##).dispatch();
+		let response = client.get("/api/v1/tables/39/orders?status=held").dispatch();
+		assert_eq!(response.into_json::<super::models::Page<super::models::Order>>().expect("Orders").data.len(), 1);
+		let response = client.get("/api/v1/tables/39/orders?status=fired").dispatch();
+		assert!(response.into_json::<super::models::Page<super::models::Order>>().expect("Orders").data.is_empty());
+
+		let response = client.delete("/api/v1/tables/39").header(Header::new("If-Match", "*")).dispatch();
+		assert_eq!(response.status(), Status::Ok);
+	}
+
+	#[test]
+	fn session_history_test()
+	{
+		let client = testing::client_with(|rocket| rocket
+		.manage(super::kitchen::Kitchen::new(Arc::new(super::kitchen::MemorySink::default()))));
+		let server = testing::server();
+		let manager = testing::manager();
+
+		let session_id = testing::open_session(&client, 40, 3);
+		let response = client.post("/api/v1/tables/40/orders")
+		.header(ContentType::JSON)
+		.body(r##"{
+			"order_items":
+			[
+				{
+					"item_id": 3,
+					"amount": 1
+				}
+			]
+		}"##).dispatch();
+		let order_id = response.into_json::<super::models::Order>().expect("Order").id.unwrap();
+
+		let response = client.get(format!("/api/v1/sessions/{}", session_id)).dispatch();
+		assert_eq!(response.status(), Status::Unauthorized);
+		let response = client.get(format!("/api/v1/sessions/{}/orders", session_id)).header(server.clone()).dispatch();
+		assert_eq!(response.status(), Status::Ok);
+
+		let response = client.delete("/api/v1/tables/40").header(Header::new("If-Match", "*")).dispatch();
+		assert_eq!(response.status(), Status::Ok);
+
+		// After checkout the orders are only reachable through the session.
+		let response = client.get("/api/v1/tables/40/orders").dispatch();
+		assert_eq!(response.status(), Status::InternalServerError);
+		let response = client.get(format!("/api/v1/sessions/{}/orders", session_id)).header(server.clone()).dispatch();
+		assert_eq!(response.status(), Status::Forbidden);
+
+		let response = client.get(format!("/api/v1/sessions/{}", session_id)).header(manager.clone()).dispatch();
+		let session = response.into_json::<Value>().expect("TableSession");
+		assert_eq!(session["active"], false);
+		assert_eq!(session["customers"], 3);
+		let response = client.get(format!("/api/v1/sessions/{}/orders", session_id)).header(manager.clone()).dispatch();
+		let orders = response.into_json::<super::models::Page<super::models::Order>>().expect("Orders");
+		assert_eq!(orders.data.len(), 1);
+		assert_eq!(orders.data[0].id, Some(order_id));
+		let response = client.get(format!("/api/v1/sessions/{}/orders/{}", session_id, order_id)).header(manager.clone()).dispatch();
+		let order = response.into_json::<super::models::Order>().expect("Order");
+		assert_eq!(order.order_items[0].item_id, 3);
+		let response = client.get(format!("/api/v1/sessions/{}/orders/{}", session_id + 1, order_id)).header(manager).dispatch();
+		assert_ne!(response.status(), Status::Ok);
+	}
+
+	#[test]
+	fn graphql_test()
+	{
+		let client = testing::client_with(|rocket| rocket
+		.manage(super::kitchen::Kitchen::new(Arc::new(super::kitchen::MemorySink::default()))));
+		let server = testing::server();
+
+		testing::open_session(&client, 41, 2);
+
+		let response = client.post("/api/v1/graphql")
+		.header(ContentType::JSON)
+		.body(r##"{"query":"{ items { id } }"}"##).dispatch();
+		assert_eq!(response.status(), Status::Unauthorized);
+		let response = client.get("/api/v1/graphql/stream?query=subscription%20%7B%20orderEvents%20%7B%20orderId%20%7D%20%7D").dispatch();
+		assert_eq!(response.status(), Status::Unauthorized);
+
+		let response = client.post("/api/v1/graphql")
+		.header(ContentType::JSON)
+		.header(server.clone())
+		.body(r##"{
+			"query":"mutation { createOrder(tableNr: 41, items: [{itemId: 3, amount: 2}, {itemId: 1, amount: 1, course: MAIN}]) { id items { itemId amount course } } }"
+		}"##).dispatch();
+		let body = response.into_json::<Value>().expect("GraphQL response");
+		assert_eq!(body["errors"], Value::Null, "{}", body);
+		assert_eq!(body["data"]["createOrder"]["items"].as_array().unwrap().len(), 2);
+		assert_eq!(body["data"]["createOrder"]["items"][1]["course"], "MAIN");
+
+		let response = client.post("/api/v1/graphql")
+		.header(ContentType::JSON)
+		.header(server.clone())
+		.body(r##"{
+			"query":"{ activeSessions { tableNr orders { id items { amount item { name priceYen } } } bill { subtotal } } }"
+		}"##).dispatch();
+		let body = response.into_json::<Value>().expect("GraphQL response");
+		assert_eq!(body["errors"], Value::Null, "{}", body);
+		let session = body["data"]["activeSessions"].as_array().unwrap().iter()
+			.find(|session| session["tableNr"] == 41)
+			.expect("session of table 41");
+		let items = session["orders"][0]["items"].as_array().unwrap();
+		assert!(items.iter().all(|item| item["item"]["name"].is_string()));
+		let subtotal: u64 = items.iter()
+			.map(|item| item["amount"].as_u64().unwrap() * item["item"]["priceYen"].as_u64().unwrap())
+			.sum();
+		assert_eq!(session["bill"]["subtotal"].as_u64(), Some(subtotal));
+
+		let response = client.post("/api/v1/graphql")
+		.header(ContentType::JSON)
+		.header(server)
+		.body(r##"{"query":"{ session(id: -1) { id } }"}"##).dispatch();
+		let body = response.into_json::<Value>().expect("GraphQL response");
+		assert!(body["errors"].is_array());
+
+		client.delete("/api/v1/tables/41").header(Header::new("If-Match", "*")).dispatch();
+	}
+
+	#[test]
+	fn locations_test()
+	{
+		let client = testing::client_with(|rocket| rocket
+		.manage(super::kitchen::Kitchen::new(Arc::new(super::kitchen::MemorySink::default()))));
+		let harbor = testing::at("harbor");
+
+		let response = client.post("/api/v1/locations")
+		.header(ContentType::JSON)
+		.header(testing::manager())
+		.body(r##"{"slug": "harbor", "name": "Harbor"}"##).dispatch();
+		assert_eq!(response.status(), Status::Forbidden);
+		let response = client.post("/api/v1/locations")
+		.header(ContentType::JSON)
+		.header(testing::owner())
+		.body(r##"{"slug": "harbor", "name": "Harbor"}"##).dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		let location = response.into_json::<Value>().expect("Location");
+		assert_eq!(location["slug"], "harbor");
+		let response = client.post("/api/v1/locations")
+		.header(ContentType::JSON)
+		.header(testing::owner())
+		.body(r##"{"slug": "harbor", "name": "Harbor again"}"##).dispatch();
+		assert_eq!(response.status(), Status::Conflict);
+
+		// The new location starts with its own copy of the menu.
+		let main_menu = client.get("/api/v1/items").dispatch().into_json::<super::models::Page<super::models::Item>>().expect("Items").data;
+		let harbor_menu = client.get("/api/v1/items").header(harbor.clone()).dispatch().into_json::<super::models::Page<super::models::Item>>().expect("Items").data;
+		assert_eq!(main_menu.len(), harbor_menu.len());
+		assert!(harbor_menu.iter().all(|item| main_menu.iter().all(|other| other.id != item.id)));
+		let (main_item, harbor_item) = (&main_menu[0], &harbor_menu[0]);
+		assert_eq!(main_item.name, harbor_item.name);
+
+		let response = client.put(format!("/api/v1/items/{}", harbor_item.id))
+		.header(ContentType::JSON)
+		.header(harbor.clone())
+		.header(testing::manager())
+		.body(format!(r##"{{"id": 0, "name": "{}", "price_yen": 999, "preparation_time": 60}}"##, harbor_item.name)).dispatch();
+		assert_eq!(response.status(), Status::Forbidden);
+		let response = client.put(format!("/api/v1/items/{}", harbor_item.id))
+		.header(ContentType::JSON)
+		.header(harbor.clone())
+		.header(testing::owner())
+		.body(format!(r##"{{"id": 0, "name": "{}", "price_yen": 999, "preparation_time": 60}}"##, harbor_item.name)).dispatch();
+		assert_eq!(response.into_json::<super::models::Item>().expect("Item").price_yen, Some(999));
+		let response = client.get(format!("/api/v1/items/{}", main_item.id)).dispatch();
+		assert_eq!(response.into_json::<super::models::Item>().expect("Item").price, main_item.price);
+		let response = client.get(format!("/api/v1/items/{}", main_item.id)).header(harbor.clone()).dispatch();
+		assert_eq!(response.status(), Status::InternalServerError);
+
+		// Table 5 is a different table at each location.
+		let main_session = testing::open_session(&client, 5, 2);
+		let response = client.post("/api/v1/tables/5")
+		.header(ContentType::JSON)
+		.header(harbor.clone())
+		.body(r##"{"customers": 4}"##).dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		let harbor_session = response.into_json::<Value>().expect("TableSession")["id"].as_i64().unwrap();
+		assert_ne!(main_session, harbor_session);
+
+		let order = |item_id: i64| format!(r##"{{"order_items": [{{"item_id": {}, "amount": 1}}]}}"##, item_id);
+		let response = client.post("/api/v1/tables/5/orders").header(ContentType::JSON).header(harbor.clone()).body(order(main_item.id)).dispatch();
+		assert_eq!(response.status(), Status::InternalServerError);
+		let response = client.post("/api/v1/tables/5/orders").header(ContentType::JSON).header(harbor.clone()).body(order(harbor_item.id)).dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		testing::place_order(&client, 5, &[(main_item.id, 2)]);
+
+		let response = client.get("/api/v1/tables/5/bill").header(harbor.clone()).dispatch();
+		assert_eq!(response.into_json::<super::models::Bill>().expect("Bill").subtotal, 999);
+		let response = client.get("/api/v1/tables/5/bill").dispatch();
+		assert_eq!(response.into_json::<super::models::Bill>().expect("Bill").subtotal as i64, 2 * main_item.price.amount);
+		let response = client.get("/api/v1/tables").header(harbor.clone()).dispatch();
+		let sessions = response.into_json::<Vec<Value>>().expect("TableSessions");
+		assert_eq!(sessions.iter().map(|session| session["id"].as_i64().unwrap()).collect::<Vec<i64>>(), vec![harbor_session]);
+		let response = client.get("/api/v1/kitchen/queue").header(harbor.clone()).header(testing::owner()).dispatch();
+		let queue = response.into_json::<Vec<super::models::QueueItem>>().expect("Queue");
+		assert!(!queue.is_empty() && queue.iter().all(|item| item.table_session_id == harbor_session));
+
+		// Sessions of one location are not found from another, and staff of
+		// one location are turned away at the others.
+		let response = client.get(format!("/api/v1/sessions/{}", main_session)).header(harbor.clone()).header(testing::owner()).dispatch();
+		assert_eq!(response.status(), Status::InternalServerError);
+		let response = client.get(format!("/api/v1/sessions/{}", harbor_session)).header(harbor.clone()).header(testing::server()).dispatch();
+		assert_eq!(response.status(), Status::Forbidden);
+		let response = client.get(format!("/api/v1/sessions/{}", harbor_session)).header(harbor.clone()).header(testing::owner()).dispatch();
+		assert_eq!(response.status(), Status::Ok);
+
+		// Discounts are copied to a new location, and each location manages
+		// and applies its own.
+		let discounts = |location: Header<'static>| client.get("/api/v1/discounts").header(location).dispatch().into_json::<Vec<Value>>().expect("Discounts");
+		let (main_discounts, harbor_discounts) = (discounts(testing::at("main")), discounts(harbor.clone()));
+		assert_eq!(main_discounts.len(), harbor_discounts.len());
+		assert!(harbor_discounts.iter().all(|discount| discount["location_id"] == location["id"] && main_discounts.iter().all(|other| other["id"] != discount["id"])));
+		let discount = r##"{"name": "Harbor happy hour", "kind": "percent", "value": 15, "scope": "bill"}"##;
+		let response = client.post("/api/v1/discounts").header(ContentType::JSON).header(harbor.clone()).header(testing::manager()).body(discount).dispatch();
+		assert_eq!(response.status(), Status::Forbidden);
+		let response = client.post("/api/v1/discounts").header(ContentType::JSON).header(harbor.clone()).header(testing::owner()).body(discount).dispatch();
+		assert_eq!(response.into_json::<Value>().expect("Discount")["location_id"], location["id"]);
+		assert_eq!(discounts(testing::at("main")).len(), main_discounts.len());
+		let applied = format!(r##"{{"discount_id": {}, "reason": "Regular"}}"##, main_discounts[0]["id"]);
+		let response = client.post("/api/v1/tables/5/discounts").header(ContentType::JSON).header(harbor.clone()).header(testing::owner()).body(applied).dispatch();
+		assert_eq!(response.status(), Status::InternalServerError);
+
+		// Webhooks are only listed and removed at their own location.
+		let response = client.post("/api/v1/webhooks")
+		.header(ContentType::JSON)
+		.header(harbor.clone())
+		.header(testing::owner())
+		.body(r##"{"url": "http://127.0.0.1:9/hook", "events": ["order_created"]}"##).dispatch();
+		let webhook = response.into_json::<Value>().expect("Webhook");
+		assert_eq!(webhook["location_id"], location["id"]);
+		let response = client.get("/api/v1/webhooks").header(testing::manager()).dispatch();
+		assert!(response.into_json::<Vec<super::models::Webhook>>().expect("Webhooks").is_empty());
+		let response = client.delete(format!("/api/v1/webhooks/{}", webhook["id"])).header(testing::manager()).dispatch();
+		assert_eq!(response.into_json::<String>().unwrap(), "failed");
+		let response = client.get("/api/v1/webhooks").header(harbor.clone()).header(testing::owner()).dispatch();
+		assert_eq!(response.into_json::<Vec<super::models::Webhook>>().expect("Webhooks").len(), 1);
+		let response = client.delete(format!("/api/v1/webhooks/{}", webhook["id"])).header(harbor.clone()).header(testing::owner()).dispatch();
+		assert_eq!(response.into_json::<String>().unwrap(), "success");
+
+		// The location can also come from a path prefix or subdomain.
+		let response = client.get("/locations/harbor/api/v1/tables/5/active").dispatch();
+		assert_eq!(response.into_json::<Value>().expect("TableSession")["id"], harbor_session);
+		let response = client.get("/api/v1/tables/5/active").header(Header::new("Host", "harbor.example.com")).dispatch();
+		assert_eq!(response.into_json::<Value>().expect("TableSession")["id"], harbor_session);
+		let response = client.get("/api/v1/tables/5/active").header(Header::new("Host", "www.example.com")).dispatch();
+		assert_eq!(response.into_json::<Value>().expect("TableSession")["id"], main_session);
+		let response = client.get("/api/v1/tables").header(testing::at("nowhere")).dispatch();
+		assert_eq!(response.status(), Status::NotFound);
+		let response = client.get("/locations/nowhere/api/v1/tables").dispatch();
+		assert_eq!(response.status(), Status::NotFound);
+
+		let payment = r##"{"amount": 999, "tip": 100, "method": "card"}"##;
+		let response = client.post("/api/v1/tables/5/payments").header(ContentType::JSON).header(harbor.clone()).header(testing::owner()).body(payment).dispatch();
+		assert_eq!(response.status(), Status::Ok);
+
+		let response = client.get("/api/v1/reports/locations").header(testing::manager()).dispatch();
+		assert_eq!(response.status(), Status::Forbidden);
+		let response = client.get("/api/v1/reports/locations").header(testing::owner()).dispatch();
+		let reports = response.into_json::<Vec<super::models::LocationReport>>().expect("LocationReports");
+		assert_eq!(reports.iter().map(|report| report.slug.as_str()).collect::<Vec<&str>>(), vec!["main", "harbor"]);
+		assert_eq!(location["id"], reports[1].location_id);
+		assert_eq!((reports[0].sessions, reports[0].revenue, reports[0].tips), (1, 0, 0));
+		assert_eq!((reports[1].sessions, reports[1].revenue, reports[1].tips), (1, 999, 100));
+		let response = client.get("/api/v1/reports/tips").header(testing::manager()).dispatch();
+		assert_eq!(response.into_json::<super::models::TipReport>().expect("TipReport").total_tips, 0);
+	}
+
+	#[test]
+	fn settings_test()
+	{
+		let client = testing::client();
+		let response = client.get("/api/v1/settings").dispatch();
+		let mut settings = response.into_json::<Value>().expect("Settings");
+		assert_eq!(settings["currency"], "JPY");
+		assert_eq!(settings["tax_rate_percent"], 10);
+
+		testing::open_session(&client, 23, 2);
+		testing::place_order(&client, 23, &[(3, 1)]);
+		let response = client.get("/api/v1/tables/23/bill").dispatch();
+		let bill = response.into_json::<super::models::Bill>().expect("Bill");
+		assert_eq!(bill.tax, bill.subtotal / 10);
+
+		settings["tax_rate_percent"] = Value::from(8);
+		settings["opening_hours"] = rocket::serde::json::json!([{"day": "friday", "open": "17:00", "close": "01:00"}]);
+		let response = client.put("/api/v1/settings")
+		.header(ContentType::JSON)
+		.header(testing::manager())
+		.body(settings.to_string()).dispatch();
+		assert_eq!(response.status(), Status::Forbidden);
+		let response = client.put("/api/v1/settings")
+		.header(ContentType::JSON)
+		.header(testing::owner())
+		.body(settings.to_string().replace("Asia/Tokyo", "Asia/Atlantis")).dispatch();
+		assert_eq!(response.status(), Status::UnprocessableEntity);
+		let response = client.put("/api/v1/settings")
+		.header(ContentType::JSON)
+		.header(testing::owner())
+		.body(settings.to_string()).dispatch();
+		assert_eq!(response.status(), Status::Ok);
+
+		let response = client.get("/api/v1/tables/23/bill").dispatch();
+		let bill = response.into_json::<super::models::Bill>().expect("Bill");
+		assert_eq!((bill.tax_rate_percent, bill.tax), (8, bill.subtotal * 8 / 100));
+		let response = client.get("/api/v1/settings").dispatch();
+		assert_eq!(response.into_json::<Value>().expect("Settings"), settings);
+
+		// Only open the day after tomorrow, in Tokyo.
+		use chrono::Datelike;
+		let days = ["monday", "tuesday", "wednesday", "thursday", "friday", "saturday", "sunday"];
+		let today = chrono::Utc::now().with_timezone(&chrono_tz::Asia::Tokyo).weekday().num_days_from_monday() as usize;
+		settings["opening_hours"] = rocket::serde::json::json!([{"day": days[(today + 2) % 7], "open": "10:00", "close": "11:00"}]);
+		let response = client.put("/api/v1/settings")
+		.header(ContentType::JSON)
+		.header(testing::owner())
+		.body(settings.to_string()).dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		let response = client.post("/api/v1/tables/23/orders")
+		.header(ContentType::JSON)
+		.body(r#"{"order_items":[{"item_id":3,"amount":1}]}"#).dispatch();
+		assert_eq!(response.status(), Status::Conflict);
+	}
+
+	#[test]
+	fn money_test()
+	{
+		let client = testing::client();
+		let item = client.get("/api/v1/items/1").dispatch().into_json::<Value>().expect("Item");
+		assert_eq!(item["price"]["currency"], "JPY");
+		assert_eq!(item["price"]["amount"], item["price_yen"]);
+
+		let response = client.post("/api/v1/items")
+		.header(ContentType::JSON)
+		.header(testing::manager())
+		.body(r##"{"id": 0, "name": "Croissant", "price": {"amount": 450, "currency": "EUR"}, "preparation_time": 60}"##).dispatch();
+		assert_eq!(response.status(), Status::UnprocessableEntity);
+		let response = client.post("/api/v1/items")
+		.header(ContentType::JSON)
+		.header(testing::manager())
+		.body(r##"{"id": 0, "name": "Onigiri", "price_yen": 250, "preparation_time": 60}"##).dispatch();
+		let onigiri = response.into_json::<super::models::Item>().expect("Item");
+		assert_eq!((onigiri.price.amount, onigiri.price.currency.code()), (250, "JPY"));
+
+		// Once bills are in euros, yen prices can't be added to them.
+		let mut settings = client.get("/api/v1/settings").dispatch().into_json::<Value>().expect("Settings");
+		settings["currency"] = Value::from("EUR");
+		let response = client.put("/api/v1/settings").header(ContentType::JSON).header(testing::owner()).body(settings.to_string()).dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		testing::open_session(&client, 24, 2);
+		testing::place_order(&client, 24, &[(onigiri.id, 1)]);
+		let response = client.get("/api/v1/tables/24/bill").dispatch();
+		assert_eq!(response.status(), Status::UnprocessableEntity);
+
+		let response = client.post("/api/v1/items")
+		.header(ContentType::JSON)
+		.header(testing::manager())
+		.body(r##"{"id": 0, "name": "Croissant", "price": {"amount": 450, "currency": "EUR"}, "preparation_time": 60}"##).dispatch();
+		let croissant = response.into_json::<Value>().expect("Item");
+		assert_eq!(croissant["price"]["amount"], 450);
+		assert!(croissant.get("price_yen").is_none());
+	}
+
+	#[test]
+	fn i18n_test()
+	{
+		let client = testing::client();
+		let japanese = || Header::new("Accept-Language", "ja-JP,ja;q=0.9,en;q=0.8");
+		let item = client.get("/api/v1/items/2").header(japanese()).dispatch().into_json::<Value>().expect("Item");
+		assert_eq!(item["name"], "ほうれん草とかぼちゃのリゾット");
+		let items = client.get("/api/v1/items?limit=3").header(Header::new("Accept-Language", "fr, ko;q=0.5")).dispatch().into_json::<Value>().expect("Items");
+		assert_eq!(items["data"][0]["name"], "브레사올라 소시지 스파게티");
+		let item = client.get("/api/v1/items/2").dispatch().into_json::<Value>().expect("Item");
+		assert_eq!(item["name"], "Spinach and squash risotto");
+
+		let response = client.put("/api/v1/items/2/translations/zh")
+		.header(ContentType::JSON)
+		.header(testing::server())
+		.body(r##"{"name": "菠菜南瓜意式烩饭"}"##).dispatch();
+		assert_eq!(response.status(), Status::Forbidden);
+		let response = client.put("/api/v1/items/2/translations/fr")
+		.header(ContentType::JSON)
+		.header(testing::manager())
+		.body(r##"{"name": "Risotto aux épinards"}"##).dispatch();
+		assert_eq!(response.status(), Status::UnprocessableEntity);
+		let response = client.put("/api/v1/items/2/translations/zh")
+		.header(ContentType::JSON)
+		.header(testing::manager())
+		.body(r##"{"name": "菠菜南瓜意式烩饭"}"##).dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		let item = client.get("/api/v1/items/2").header(Header::new("Accept-Language", "zh-CN")).dispatch().into_json::<Value>().expect("Item");
+		assert_eq!(item["name"], "菠菜南瓜意式烩饭");
+		let translations = client.get("/api/v1/items/2/translations").dispatch().into_json::<Value>().expect("Translations");
+		assert_eq!(translations.as_array().map(Vec::len), Some(3));
+
+		// Errors explain themselves in the guest's language, or the default
+		// locale when there is no catalog for it.
+		let response = client.get("/api/v1/tables/31/bill").header(japanese()).dispatch();
+		let failure = response.into_json::<Value>().expect("Failure");
+		assert_eq!(failure["status"], "failed");
+		assert_eq!(failure["message"], "テーブル31番に利用中のセッションがありません");
+		let response = client.get("/api/v1/tables/31/bill").header(Header::new("Accept-Language", "de")).dispatch();
+		assert_eq!(response.into_json::<Value>().expect("Failure")["message"], "No active session for table #31");
+
+		let mut settings = client.get("/api/v1/settings").dispatch().into_json::<Value>().expect("Settings");
+		settings["default_locale"] = Value::from("ko");
+		let response = client.put("/api/v1/settings").header(ContentType::JSON).header(testing::owner()).body(settings.to_string()).dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		let response = client.get("/api/v1/tables/31/bill").header(Header::new("Accept-Language", "de")).dispatch();
+		assert_eq!(response.into_json::<Value>().expect("Failure")["message"], "31번 테이블에 이용 중인 세션이 없습니다");
+		let item = client.get("/api/v1/items/2").dispatch().into_json::<Value>().expect("Item");
+		assert_eq!(item["name"], "시금치 단호박 리소토");
+	}
+
+	#[test]
+	fn guests_test()
+	{
+		let client = testing::client();
+		let session = client.post("/api/v1/tables/41").header(ContentType::JSON).header(testing::server()).body(r#"{"customers":2}"#).dispatch().into_json::<Value>().expect("OpenedSession");
+		let token = session["guest_token"]["token"].as_str().expect("guest token").to_string();
+		let guest = || Header::new("Authorization", format!("Bearer {}", token));
+		// Only staff are given the token of a session they open.
+		let response = client.post("/api/v1/tables/44").header(ContentType::JSON).body(r#"{"customers":2}"#).dispatch();
+		assert!(response.into_json::<Value>().expect("OpenedSession").get("guest_token").is_none());
+		assert_eq!(client.delete("/api/v1/tables/44").header(Header::new("If-Match", "*")).dispatch().status(), Status::Ok);
+
+		assert_eq!(client.get("/api/v1/guest/menu").dispatch().status(), Status::Unauthorized);
+		assert_eq!(client.get("/api/v1/guest/menu").header(testing::server()).dispatch().status(), Status::Unauthorized);
+		let menu = client.get("/api/v1/guest/menu").header(guest()).header(Header::new("Accept-Language", "ja")).dispatch().into_json::<Value>().expect("Menu");
+		assert_eq!(menu["data"][0]["name"], "ブレザオラとソーセージのスパゲッティ");
+		let response = client.get("/api/v1/guest/session").header(guest()).dispatch();
+		assert_eq!(response.into_json::<Value>().expect("TableSession")["table_nr"], 41);
+
+		let response = client.post("/api/v1/guest/orders").header(ContentType::JSON).header(guest()).body(r#"{"order_items":[{"item_id":1,"amount":2}]}"#).dispatch();
+		let guest_order = response.into_json::<super::models::GuestOrder>().expect("GuestOrder");
+		assert_eq!(guest_order.status, super::models::GuestOrderStatus::Placed);
+		let orders = client.get("/api/v1/tables/41/orders").dispatch().into_json::<Value>().expect("Orders");
+		assert_eq!(orders["data"][0]["id"].as_i64(), guest_order.order_id);
+		let bill = client.get("/api/v1/guest/bill").header(guest()).dispatch().into_json::<super::models::Bill>().expect("Bill");
+		assert_eq!(bill.subtotal, 2 * 2972);
+
+		// A token only orders to its own session, and only while it lasts.
+		let tampered = Header::new("Authorization", format!("Bearer {}", token.replacen(&session["id"].to_string(), "999", 1)));
+		assert_eq!(client.get("/api/v1/guest/bill").header(tampered).dispatch().status(), Status::Unauthorized);
+		let response = client.get("/api/v1/tables/41/guest-token").header(testing::server()).dispatch();
+		assert!(response.into_json::<super::models::GuestToken>().expect("GuestToken").token.starts_with(&format!("{}.", session["id"])));
+		let response = client.delete("/api/v1/tables/41").header(Header::new("If-Match", "*")).dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		assert_eq!(client.get("/api/v1/guest/bill").header(guest()).dispatch().status(), Status::Unauthorized);
+	}
+
+	#[test]
+	fn guest_approval_test()
+	{
+		let config = crate::guests::GuestConfig { approve_first_order: true, orders_per_minute: 2, ..Default::default() };
+		let client = testing::client_with(|rocket| rocket.manage(crate::guests::Guests::new(config)));
+		let session = client.post("/api/v1/tables/42").header(ContentType::JSON).header(testing::server()).body(r#"{"customers":2}"#).dispatch().into_json::<Value>().expect("OpenedSession");
+		let guest = || Header::new("Authorization", format!("Bearer {}", session["guest_token"]["token"].as_str().expect("guest token")));
+		let order = r#"{"order_items":[{"item_id":3,"amount":1}]}"#;
+
+		let response = client.post("/api/v1/guest/orders").header(ContentType::JSON).header(guest()).body(order).dispatch();
+		let pending = response.into_json::<super::models::GuestOrder>().expect("GuestOrder");
+		assert_eq!((pending.status, pending.order_id), (super::models::GuestOrderStatus::Pending, None));
+		let bill = client.get("/api/v1/guest/bill").header(guest()).dispatch().into_json::<super::models::Bill>().expect("Bill");
+		assert_eq!(bill.subtotal, 0);
+
+		let uri = format!("/api/v1/tables/42/guest-orders/{}/approve", pending.id);
+		assert_eq!(client.post(uri.as_str()).dispatch().status(), Status::Unauthorized);
+		let guest_orders = client.get("/api/v1/tables/42/guest-orders").header(testing::server()).dispatch().into_json::<Vec<super::models::GuestOrder>>().expect("GuestOrders");
+		assert_eq!(guest_orders.len(), 1);
+		let approved = client.post(uri.as_str()).header(testing::server()).dispatch().into_json::<super::models::GuestOrder>().expect("GuestOrder");
+		assert_eq!(approved.status, super::models::GuestOrderStatus::Placed);
+		assert_eq!(client.post(uri.as_str()).header(testing::server()).dispatch().status(), Status::Conflict);
+		let reject = format!("/api/v1/tables/42/guest-orders/{}/reject", pending.id);
+		assert_eq!(client.post(reject).header(testing::server()).dispatch().status(), Status::Conflict);
+		let bill = client.get("/api/v1/guest/bill").header(guest()).dispatch().into_json::<super::models::Bill>().expect("Bill");
+		assert_eq!(bill.subtotal, 1119);
+
+		// Once approved, the guests' orders are placed straight away, as many
+		// as they may place a minute. Orders that are not accepted don't count.
+		let response = client.post("/api/v1/guest/orders").header(ContentType::JSON).header(guest()).body(r#"{"order_items":[{"item_id":999,"amount":1}]}"#).dispatch();
+		assert_eq!(response.status(), Status::InternalServerError);
+		let response = client.post("/api/v1/guest/orders").header(ContentType::JSON).header(guest()).body(order).dispatch();
+		assert_eq!(response.into_json::<super::models::GuestOrder>().expect("GuestOrder").status, super::models::GuestOrderStatus::Placed);
+		let response = client.post("/api/v1/guest/orders").header(ContentType::JSON).header(guest()).body(order).dispatch();
+		assert_eq!(response.status(), Status::TooManyRequests);
+	}
+
+	#[test]
+	fn signals_test()
+	{
+		let client = testing::client();
+		let response = client.post("/api/v1/shifts/clock-in").header(testing::server()).dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		let session = client.post("/api/v1/tables/43").header(ContentType::JSON).header(testing::server()).body(r#"{"customers":2}"#).dispatch().into_json::<Value>().expect("OpenedSession");
+		let guest = || Header::new("Authorization", format!("Bearer {}", session["guest_token"]["token"].as_str().expect("guest token")));
+
+		let response = client.post("/api/v1/guest/signals").header(ContentType::JSON).header(guest()).body(r#"{"kind":"call_server"}"#).dispatch();
+		let call = response.into_json::<Value>().expect("Signal");
+		assert_eq!((&call["table_nr"], &call["kind"], &call["raised_by"]), (&Value::from(43), &Value::from("call_server"), &Value::Null));
+		// Asking again while no one came yet keeps the one signal.
+		let response = client.post("/api/v1/guest/signals").header(ContentType::JSON).header(guest()).body(r#"{"kind":"call_server"}"#).dispatch();
+		assert_eq!(response.into_json::<Value>().expect("Signal")["id"], call["id"]);
+		let response = client.post("/api/v1/tables/43/signals").header(ContentType::JSON).header(testing::server()).body(r#"{"kind":"need_water"}"#).dispatch();
+		let water = response.into_json::<Value>().expect("Signal");
+		assert_eq!((&water["kind"], &water["raised_by"]), (&Value::from("need_water"), &Value::from(2)));
+		let response = client.post("/api/v1/tables/43/signals").header(ContentType::JSON).body(r#"{"kind":"request_bill"}"#).dispatch();
+		assert_eq!(response.status(), Status::Unauthorized);
+
+		let floor = |client: &rocket::local::blocking::Client| -> Value {
+			let floor = client.get("/api/v1/floor").header(testing::server()).dispatch().into_json::<Value>().expect("FloorTables");
+			floor.as_array().expect("FloorTables").iter().find(|table| table["table_nr"] == 43).expect("table #43")["signals"].clone()
+		};
+		let waiting: Vec<Value> = floor(&client).as_array().expect("Signals").iter().map(|signal| signal["id"].clone()).collect();
+		assert_eq!(waiting, [call["id"].clone(), water["id"].clone()]);
+
+		let uri = format!("/api/v1/tables/43/signals/{}/acknowledge", call["id"]);
+		let acknowledged = client.post(uri.as_str()).header(testing::server()).dispatch().into_json::<Value>().expect("Signal");
+		assert_eq!(acknowledged["acknowledged_by"], 2);
+		assert!(acknowledged["acknowledged_at"].is_string());
+		assert_eq!(client.post(uri.as_str()).header(testing::server()).dispatch().status(), Status::Conflict);
+		assert_eq!(client.post("/api/v1/tables/43/signals/999/acknowledge").header(testing::server()).dispatch().status(), Status::InternalServerError);
+		let signals = client.get("/api/v1/guest/signals").header(guest()).dispatch().into_json::<Vec<Value>>().expect("Signals");
+		assert_eq!(signals.len(), 2);
+		assert_eq!(floor(&client).as_array().expect("Signals").len(), 1);
+
+		let (missed, _) = client.rocket().state::<crate::events::Events>().expect("events").subscribe(Some(0));
+		let types: Vec<String> = missed.events.iter()
+			.filter(|event| event.table_nr == 43)
+			.map(|event| rocket::serde::json::to_value(event).expect("Event")["type"].as_str().unwrap().to_string())
+			.collect();
+		assert_eq!(types, ["session_opened", "signal_raised", "signal_raised", "signal_acknowledged"]);
+
+		assert_eq!(client.get("/api/v1/reports/signals").header(testing::server()).dispatch().status(), Status::Forbidden);
+		let report = client.get("/api/v1/reports/signals").header(testing::manager()).dispatch().into_json::<super::models::SignalReport>().expect("SignalReport");
+		assert_eq!(report.unacknowledged, 1);
+		let shift = report.shifts.iter().find(|shift| shift.staff_id == 2).expect("server's shift");
+		assert_eq!(shift.acknowledged, 1);
+		assert!(shift.longest_response_seconds >= shift.average_response_seconds);
+
+		let response = client.delete("/api/v1/tables/43").header(Header::new("If-Match", "*")).dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		assert!(client.get("/api/v1/guest/signals").header(guest()).dispatch().status() == Status::Unauthorized);
+	}
+}
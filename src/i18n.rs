@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::fmt;
+use rocket::fairing::AdHoc;
+use rocket::request::{FromRequest, Outcome, Request};
+use crate::settings;
+
+/// The locales guests can read the API in. Messages are written in English,
+/// the others have catalogs in `locales/`.
+pub const LOCALES: [&str; 4] = ["en", "ja", "zh", "ko"];
+
+const CATALOGS: [(&str, &str); 3] = [
+	("ja", include_str!("../locales/ja.json")),
+	("zh", include_str!("../locales/zh.json")),
+	("ko", include_str!("../locales/ko.json")),
+];
+
+/// The locale a request is answered in, negotiated from its
+/// `Accept-Language`, and the restaurant's `default_locale` for what isn't
+/// translated into it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Locale {
+	pub tag: &'static str,
+	pub fallback: &'static str
+}
+
+/// One of ours for a language tag like `ja-JP` or `zh-Hant`.
+fn supported(tag: &str) -> Option<&'static str> {
+	let language = tag.trim().split(['-', '_']).next()?.to_ascii_lowercase();
+	LOCALES.iter().copied().find(|locale| *locale == language)
+}
+
+impl Locale {
+	/// The locale the `Accept-Language` header ranks highest among ours, by
+	/// its `q` weights, or `default` when it has none of them.
+	pub fn negotiate(accept_language: Option<&str>, default: &str) -> Locale {
+		let fallback = supported(default).unwrap_or("en");
+		let mut ranges: Vec<(&str, f32)> = accept_language.unwrap_or("").split(',')
+			.filter_map(|range| {
+				let mut params = range.split(';');
+				let tag = params.next()?.trim();
+				let weight = params.find_map(|param| param.trim().strip_prefix("q=")).map_or(Some(1.0), |q| q.trim().parse().ok())?;
+				(!tag.is_empty() && weight > 0.0).then_some((tag, weight))
+			})
+			.collect();
+		ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+		let tag = ranges.iter()
+			.find_map(|(tag, _)| if *tag == "*" { Some(fallback) } else { supported(tag) })
+			.unwrap_or(fallback);
+		Locale { tag, fallback }
+	}
+
+	pub fn of(req: &Request<'_>) -> Locale {
+		let default = req.rocket().state::<settings::Settings>().map_or_else(|| String::from("en"), |settings| settings.current().default_locale);
+		Locale::negotiate(req.headers().get_one("Accept-Language"), &default)
+	}
+
+	/// The locales to look translations up in, best first.
+	pub fn preferred(&self) -> Vec<&'static str> {
+		let mut preferred = vec![self.tag];
+		if self.fallback != self.tag {
+			preferred.push(self.fallback);
+		}
+		preferred
+	}
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Locale {
+	type Error = Infallible;
+
+	async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+		Outcome::Success(Locale::of(req))
+	}
+}
+
+/// A message for API clients: its English template, with `{}` where its
+/// values go, and the values. The template is also its key in the catalogs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+	pub template: &'static str,
+	pub arguments: Vec<String>
+}
+
+impl Message {
+	pub fn new(template: &'static str, arguments: Vec<String>) -> Message {
+		Message { template, arguments }
+	}
+}
+
+/// The message in English.
+impl fmt::Display for Message {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(&render(self.template, &self.arguments))
+	}
+}
+
+/// A `Message` like `format!` makes a `String`, from a template with `{}`
+/// for each value.
+macro_rules! message {
+	($template:literal $(, $argument:expr)* $(,)?) => {
+		$crate::i18n::Message::new($template, vec![$($argument.to_string()),*])
+	};
+}
+pub(crate) use message;
+
+/// Translations of API messages, keyed by their English template. A
+/// translation puts the values in order with `{}`, or by position with `{0}`,
+/// `{1}`, ... when its word order differs.
+pub struct Catalogs(HashMap<&'static str, HashMap<String, String>>);
+
+impl Catalogs {
+	pub fn load() -> Result<Catalogs, String> {
+		let mut catalogs = HashMap::new();
+		for (locale, catalog) in CATALOGS {
+			let messages = rocket::serde::json::from_str(catalog).map_err(|e| format!("Invalid catalog '{}': {}", locale, e))?;
+			catalogs.insert(locale, messages);
+		}
+		Ok(Catalogs(catalogs))
+	}
+
+	/// `message` in the locale, else in its fallback, else in English.
+	pub fn translate(&self, locale: Locale, message: &Message) -> String {
+		locale.preferred().into_iter()
+			.find_map(|tag| self.0.get(tag)?.get(message.template))
+			.map_or_else(|| message.to_string(), |translation| render(translation, &message.arguments))
+	}
+}
+
+fn render(translation: &str, arguments: &[String]) -> String {
+	let mut rendered = String::new();
+	let mut next = 0;
+	let mut rest = translation;
+	while let Some(start) = rest.find('{') {
+		let end = match rest[start..].find('}') {
+			Some(end) => start + end,
+			None => break
+		};
+		rendered.push_str(&rest[..start]);
+		let index = match &rest[start + 1..end] {
+			"" => {
+				next += 1;
+				Some(next - 1)
+			},
+			position => position.parse().ok()
+		};
+		match index {
+			Some(index) => rendered.push_str(arguments.get(index).map_or("", String::as_str)),
+			None => rendered.push_str(&rest[start..=end])
+		}
+		rest = &rest[end + 1..];
+	}
+	rendered.push_str(rest);
+	rendered
+}
+
+/// Loads the message catalogs, stopping the launch when one is invalid.
+pub fn stage() -> AdHoc {
+	AdHoc::try_on_ignite("Message catalogs", |rocket| async {
+		match Catalogs::load() {
+			Ok(catalogs) => Ok(rocket.manage(catalogs)),
+			Err(e) => {
+				rocket::error!("{}", e);
+				Err(rocket)
+			}
+		}
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{Catalogs, Locale};
+
+	#[test]
+	fn requests_get_their_language()
+	{
+		let negotiate = |header| Locale::negotiate(header, "en").tag;
+		assert_eq!(negotiate(Some("ja-JP,ja;q=0.9,en;q=0.8")), "ja");
+		assert_eq!(negotiate(Some("fr-FR, en;q=0.5, ko;q=0.7")), "ko");
+		assert_eq!(negotiate(Some("zh-Hant-TW")), "zh");
+		assert_eq!(negotiate(Some("fr, *;q=0.1")), "en");
+		assert_eq!(negotiate(Some("ja;q=0, ko")), "ko");
+		assert_eq!(negotiate(None), "en");
+		assert_eq!(Locale::negotiate(Some("de"), "ja"), Locale { tag: "ja", fallback: "ja" });
+
+		let catalogs = Catalogs::load().expect("catalogs");
+		let ja = Locale::negotiate(Some("ja"), "en");
+		assert_eq!(catalogs.translate(ja, &message!("No active session for table #{}", 3)), "テーブル3番に利用中のセッションがありません");
+		assert_eq!(catalogs.translate(ja, &message!("Payment of {} exceeds the balance of {} for table #{}", 500, 300, 3)), "支払額500がテーブル3番の残高300を超えています");
+		assert_eq!(catalogs.translate(ja, &message!("Not in any catalog, table #{}", 3)), "Not in any catalog, table #3");
+		assert_eq!(catalogs.translate(ja, &message!("Unable to get order with ID {} of session {}", 4, 2)), "セッション2にID 4の注文が見つかりません");
+		let ko_then_ja = Locale::negotiate(Some("ko"), "ja");
+		assert_eq!(catalogs.translate(ko_then_ja, &message!("No active session for table #{}", 3)), "3번 테이블에 이용 중인 세션이 없습니다");
+		let en = Locale::negotiate(Some("en"), "en");
+		assert_eq!(catalogs.translate(en, &message!("Unknown station '{}'", "wok")), "Unknown station 'wok'");
+
+		// Every translation keeps every value of its message.
+		for catalog in catalogs.0.values() {
+			for (template, translation) in catalog {
+				let values: Vec<String> = (0..template.matches("{}").count()).map(|i| format!("<{}>", i)).collect();
+				let translated = super::render(translation, &values);
+				assert!(values.iter().all(|value| translated.contains(value.as_str())), "{} loses values", translation);
+			}
+		}
+	}
+}
@@ -0,0 +1,189 @@
+use std::io;
+use std::ops::Deref;
+use rocket::data::{self, Data, FromData, Limits};
+use rocket::http::{ContentType, Header, Status};
+use rocket::request::{self, local_cache, FromRequest, Request};
+use rocket::response::{self, Responder};
+use rocket::serde::json::{self, Json};
+use rocket::serde::{DeserializeOwned, Serialize};
+use sha2::{Digest, Sha256};
+use crate::db;
+use crate::error_handler::Error;
+use crate::i18n::message;
+
+/// The `Idempotency-Key` header clients send so a retried request is not
+/// carried out twice.
+pub struct IdempotencyKey(pub Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IdempotencyKey {
+	type Error = ();
+
+	async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, ()> {
+		let key = request.headers().get_one("Idempotency-Key")
+			.map(str::trim)
+			.filter(|key| !key.is_empty())
+			.map(String::from);
+		request::Outcome::Success(IdempotencyKey(key))
+	}
+}
+
+/// A claim is taken over when its request has not finished after this long,
+/// because it panicked or its connection was lost.
+const STALE_CLAIM_SECONDS: i64 = 60;
+
+/// A JSON body like `Json`, along with the hash of the bytes the client sent,
+/// so only a byte for byte retry is replayed.
+pub struct HashedJson<T> {
+	value: T,
+	hash: String
+}
+
+impl<T> Deref for HashedJson<T> {
+	type Target = T;
+
+	fn deref(&self) -> &T {
+		&self.value
+	}
+}
+
+#[rocket::async_trait]
+impl<'r, T: DeserializeOwned> FromData<'r> for HashedJson<T> {
+	type Error = json::Error<'r>;
+
+	async fn from_data(request: &'r Request<'_>, data: Data<'r>) -> data::Outcome<'r, Self> {
+		let limit = request.limits().get("json").unwrap_or(Limits::JSON);
+		let body = match data.open(limit).into_string().await {
+			Ok(body) if body.is_complete() => body.into_inner(),
+			Ok(_) => return data::Outcome::Failure((Status::PayloadTooLarge, json::Error::Io(io::Error::new(io::ErrorKind::UnexpectedEof, "data limit exceeded")))),
+			Err(e) => return data::Outcome::Failure((Status::BadRequest, json::Error::Io(e))),
+		};
+		let body: &'r str = local_cache!(request, body);
+		match json::from_str(body) {
+			Ok(value) => data::Outcome::Success(HashedJson { value, hash: hex::encode(Sha256::digest(body.as_bytes())) }),
+			Err(e) if e.classify() == json::serde_json::error::Category::Data => data::Outcome::Failure((Status::UnprocessableEntity, json::Error::Parse(body, e))),
+			Err(e) => data::Outcome::Failure((Status::BadRequest, json::Error::Parse(body, e))),
+		}
+	}
+}
+
+/// A key claimed by the first request using it.
+pub struct Claim {
+	key: String,
+	scope: String
+}
+
+pub enum Begin {
+	/// Carry out the request, then `finish` the claim, if there is one.
+	Proceed(Option<Claim>),
+	/// The request was already carried out; reply with the stored response.
+	Replay(String)
+}
+
+/// Either a handler's response or the one stored for an earlier request with
+/// the same key, which is marked with an `Idempotent-Replayed` header.
+pub enum Idempotent<T> {
+	Fresh(Json<T>),
+	Replayed(String)
+}
+
+impl<'r, T: Serialize> Responder<'r, 'static> for Idempotent<T> {
+	fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+		match self {
+			Idempotent::Fresh(json) => json.respond_to(request),
+			Idempotent::Replayed(body) => rocket::Response::build_from((ContentType::JSON, body).respond_to(request)?)
+				.header(Header::new("Idempotent-Replayed", "true"))
+				.ok(),
+		}
+	}
+}
+
+impl IdempotencyKey {
+	/// Claims the key for this request in `scope` (the method and path), or
+	/// finds the response to replay. Reusing a key with a different body is a
+	/// 422, and retrying while the first request is still running a 409.
+	pub async fn begin<T>(&self, db: &mut db::DbConnection, scope: &str, body: &HashedJson<T>) -> std::result::Result<Begin, Error> {
+		let key = match &self.0 {
+			Some(key) => key,
+			None => return Ok(Begin::Proceed(None)),
+		};
+		let stale = chrono::Duration::seconds(STALE_CLAIM_SECONDS);
+		if db::claim_idempotency_key(db, key, scope, &body.hash, stale).await? {
+			return Ok(Begin::Proceed(Some(Claim { key: key.clone(), scope: String::from(scope) })));
+		}
+		match db::retrieve_idempotency_key(db, key, scope).await? {
+			Some((stored_hash, _)) if stored_hash != body.hash => Err(Error::Unprocessable{
+				msg: message!("Idempotency key '{}' was used with a different request body", key)
+			}),
+			Some((_, Some(response))) => Ok(Begin::Replay(response)),
+			Some((_, None)) => Err(Error::Conflict{
+				msg: message!("A request with idempotency key '{}' is still in progress", key)
+			}),
+			// Released by a failed request in the meantime.
+			None => Err(Error::Conflict{
+				msg: message!("Idempotency key '{}' was released, retry the request", key)
+			}),
+		}
+	}
+}
+
+/// Stores a successful response for replays, or releases the key when the
+/// request failed so it can be retried. Requests only fail before they
+/// change anything, so a retry of a failed one can't do it twice: whatever
+/// follows their write, like events and kitchen tickets, is logged when it
+/// fails rather than failing them.
+pub async fn finish<T: Serialize>(db: &mut db::DbConnection, claim: Option<Claim>, result: std::result::Result<Json<T>, Error>) -> std::result::Result<Idempotent<T>, Error> {
+	let claim = match claim {
+		Some(claim) => claim,
+		None => return result.map(Idempotent::Fresh),
+	};
+	match &result {
+		Ok(response) => match rocket::serde::json::to_string(&response.0) {
+			Ok(response) => db::store_idempotent_response(db, &claim.key, &claim.scope, &response).await?,
+			Err(_) => db::release_idempotency_key(db, &claim.key, &claim.scope).await?,
+		},
+		Err(_) => db::release_idempotency_key(db, &claim.key, &claim.scope).await?,
+	}
+	result.map(Idempotent::Fresh)
+}
+
+#[cfg(test)]
+mod tests {
+	use chrono::Utc;
+	use rocket::http::{ContentType, Header, Status};
+	use rocket_db_pools::{sqlx, Database};
+	use sha2::{Digest, Sha256};
+	use crate::db;
+
+	#[rocket::async_test]
+	async fn stale_claims_are_taken_over()
+	{
+		let client = crate::testing::async_client().await;
+		let pool = db::Db::fetch(client.rocket()).expect("database");
+		let open = |key: &'static str, body: &'static str| client.post("/api/v1/tables/38")
+			.header(ContentType::JSON)
+			.header(Header::new("Idempotency-Key", key))
+			.body(body)
+			.dispatch();
+
+		// Claims left by requests that never finished, one just now.
+		for (key, claimed) in [("running", Utc::now()), ("stuck", Utc::now() - chrono::Duration::minutes(5))] {
+			sqlx::query("INSERT INTO IdempotencyKeys (key, scope, request_hash, created, claimed) VALUES ($1, 'POST /locations/main/tables/38', $2, $3, $3)")
+				.bind(key)
+				.bind(hex::encode(Sha256::digest(br#"{"customers":2}"#)))
+				.bind(db::timestamp(claimed))
+				.execute(&**pool)
+				.await
+				.unwrap();
+		}
+		assert_eq!(open("running", r#"{"customers":2}"#).await.status(), Status::Conflict);
+		assert_eq!(open("stuck", r#"{"customers":2}"#).await.status(), Status::Ok);
+		let response = open("stuck", r#"{"customers":2}"#).await;
+		assert_eq!(response.headers().get_one("Idempotent-Replayed"), Some("true"));
+		// The same session, sent differently, is not the same request.
+		assert_eq!(open("stuck", r#"{"customers": 2}"#).await.status(), Status::UnprocessableEntity);
+
+		let response = client.delete("/api/v1/tables/38").header(Header::new("If-Match", "*")).dispatch().await;
+		assert_eq!(response.status(), Status::Ok);
+	}
+}
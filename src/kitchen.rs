@@ -0,0 +1,426 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use rocket::fairing::AdHoc;
+use rocket::serde::Deserialize;
+use chrono::{Duration as TimeDelta, NaiveDateTime};
+use log::error;
+use crate::models::{Course, KitchenTicket, QueueItem, Station, TicketKind, TicketLine};
+
+const ESC: u8 = 0x1b;
+const GS: u8 = 0x1d;
+const TICKET_WIDTH: usize = 42;
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Somewhere kitchen tickets can be sent: a printer, a file, or memory in tests.
+pub trait TicketSink: Send + Sync {
+	fn send(&self, ticket: &KitchenTicket) -> io::Result<()>;
+}
+
+/// Appends tickets as plain text to `<directory>/<station>.txt`.
+pub struct FileSink {
+	directory: PathBuf
+}
+
+impl FileSink {
+	pub fn new(directory: PathBuf) -> Self {
+		FileSink { directory }
+	}
+}
+
+impl TicketSink for FileSink {
+	fn send(&self, ticket: &KitchenTicket) -> io::Result<()> {
+		fs::create_dir_all(&self.directory)?;
+		let path = self.directory.join(format!("{}.txt", station_name(ticket.station).to_lowercase()));
+		let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+		file.write_all(render_text(ticket).as_bytes())
+	}
+}
+
+/// Sends tickets as ESC/POS to network thermal printers, one per station,
+/// given as `host:port` (usually port 9100).
+pub struct EscPosSink {
+	printers: HashMap<Station, String>
+}
+
+impl EscPosSink {
+	pub fn new(printers: HashMap<Station, String>) -> Self {
+		EscPosSink { printers }
+	}
+}
+
+impl TicketSink for EscPosSink {
+	fn send(&self, ticket: &KitchenTicket) -> io::Result<()> {
+		let address = self.printers.get(&ticket.station).ok_or_else(|| io::Error::new(
+			io::ErrorKind::NotFound,
+			format!("No printer configured for the {} station", station_name(ticket.station))
+		))?;
+		let address = address.to_socket_addrs()?.next().ok_or_else(|| io::Error::new(
+			io::ErrorKind::NotFound,
+			format!("Unable to resolve printer address {}", address)
+		))?;
+		let mut stream = TcpStream::connect_timeout(&address, Duration::from_secs(3))?;
+		stream.write_all(&render_escpos(ticket))?;
+		stream.flush()
+	}
+}
+
+/// Keeps every ticket it is sent, so tests can check what the kitchen saw.
+#[derive(Default)]
+pub struct MemorySink {
+	tickets: Mutex<Vec<KitchenTicket>>
+}
+
+#[cfg(test)]
+impl MemorySink {
+	pub fn tickets(&self) -> Vec<KitchenTicket> {
+		self.tickets.lock().expect("ticket lock").clone()
+	}
+}
+
+impl TicketSink for MemorySink {
+	fn send(&self, ticket: &KitchenTicket) -> io::Result<()> {
+		self.tickets.lock().expect("ticket lock").push(ticket.clone());
+		Ok(())
+	}
+}
+
+fn station_name(station: Station) -> &'static str {
+	match station {
+		Station::Grill => "GRILL",
+		Station::Fryer => "FRYER",
+		Station::Bar => "BAR",
+		Station::Dessert => "DESSERT",
+	}
+}
+
+/// Splits queue items into one ticket per order, station and course, in the
+/// order they first appear.
+pub fn tickets_for(kind: TicketKind, items: &[QueueItem]) -> Vec<KitchenTicket> {
+	let mut tickets: Vec<KitchenTicket> = Vec::new();
+	for item in items {
+		let line = TicketLine { name: item.name.clone(), amount: item.amount };
+		match tickets.iter_mut().find(|ticket| ticket.order_id == item.order_id && ticket.station == item.station && ticket.course == item.course) {
+			Some(ticket) => ticket.lines.push(line),
+			None => tickets.push(KitchenTicket {
+				kind,
+				station: item.station,
+				course: item.course,
+				table_nr: item.table_nr,
+				order_id: item.order_id,
+				lines: vec![line]
+			}),
+		}
+	}
+	tickets
+}
+
+fn parse_time(time: &Option<String>) -> Option<NaiveDateTime> {
+	time.as_deref().and_then(|time| NaiveDateTime::parse_from_str(time, TIME_FORMAT).ok())
+}
+
+fn format_time(time: NaiveDateTime) -> Option<String> {
+	Some(time.format(TIME_FORMAT).to_string())
+}
+
+/// Suggests when to cook each queued item so everything fired together lands
+/// together: an item starts its preparation time before the slowest item
+/// fired with it is done. Held courses of a session are suggested a `fire_at`
+/// in course order, so each lands `course_interval` after the one before it,
+/// and never earlier than `now`.
+pub fn plan(mut items: Vec<QueueItem>, now: NaiveDateTime, course_interval: TimeDelta) -> Vec<QueueItem> {
+	let preparation = |item: &QueueItem| TimeDelta::seconds(item.preparation_time as i64);
+
+	let mut landings: HashMap<(i64, Option<Course>, NaiveDateTime), NaiveDateTime> = HashMap::new();
+	let mut held: BTreeMap<(i64, Course), TimeDelta> = BTreeMap::new();
+	for item in &items {
+		match (parse_time(&item.fired_at), item.course) {
+			(Some(fired_at), course) => {
+				let landing = landings.entry((item.table_session_id, course, fired_at)).or_insert(fired_at);
+				*landing = (*landing).max(fired_at + preparation(item));
+			},
+			(None, Some(course)) => {
+				let longest = held.entry((item.table_session_id, course)).or_insert_with(TimeDelta::zero);
+				*longest = (*longest).max(preparation(item));
+			},
+			(None, None) => (),
+		}
+	}
+
+	let mut last_landing: HashMap<i64, NaiveDateTime> = HashMap::new();
+	for ((session, _, _), landing) in &landings {
+		let last = last_landing.entry(*session).or_insert(*landing);
+		*last = (*last).max(*landing);
+	}
+	let mut fire_at: HashMap<(i64, Course), NaiveDateTime> = HashMap::new();
+	for ((session, course), longest) in &held {
+		let suggested = match last_landing.get(session) {
+			Some(landing) => (*landing + course_interval - *longest).max(now),
+			None => now,
+		};
+		last_landing.insert(*session, suggested + *longest);
+		fire_at.insert((*session, *course), suggested);
+	}
+
+	for item in &mut items {
+		let landing = match (parse_time(&item.fired_at), item.course) {
+			(Some(fired_at), course) => landings.get(&(item.table_session_id, course, fired_at)).copied(),
+			(None, Some(course)) => fire_at.get(&(item.table_session_id, course)).map(|suggested| {
+				item.fire_at = format_time(*suggested);
+				*suggested + held[&(item.table_session_id, course)]
+			}),
+			(None, None) => None,
+		};
+		if let Some(landing) = landing {
+			item.start_at = format_time(landing - preparation(item));
+			item.ready_at = format_time(landing);
+		}
+	}
+	items
+}
+
+fn header_rows(ticket: &KitchenTicket) -> Vec<String> {
+	let mut rows = Vec::new();
+	match ticket.kind {
+		TicketKind::New => (),
+		TicketKind::Reprint => rows.push(String::from("*** REPRINT ***")),
+		TicketKind::Fire => rows.push(String::from("*** FIRE ***")),
+	}
+	let station = station_name(ticket.station);
+	let table = format!("Table #{}", ticket.table_nr);
+	rows.push(format!("{}{}{}", station, " ".repeat(TICKET_WIDTH.saturating_sub(station.len() + table.len()).max(1)), table));
+	rows.push(format!("Order #{}  {}", ticket.order_id, chrono::Local::now().format("%H:%M:%S")));
+	match ticket.course {
+		Some(Course::Starter) => rows.push(String::from("Course: STARTER")),
+		Some(Course::Main) => rows.push(String::from("Course: MAIN")),
+		Some(Course::Dessert) => rows.push(String::from("Course: DESSERT")),
+		None => (),
+	}
+	rows
+}
+
+fn line_rows(ticket: &KitchenTicket) -> Vec<String> {
+	ticket.lines.iter()
+		.map(|line| format!("{} x {}", line.amount, line.name).chars().take(TICKET_WIDTH).collect())
+		.collect()
+}
+
+pub fn render_text(ticket: &KitchenTicket) -> String {
+	let mut text = String::new();
+	for row in header_rows(ticket) {
+		text.push_str(&row);
+		text.push('\n');
+	}
+	text.push_str(&"-".repeat(TICKET_WIDTH));
+	text.push('\n');
+	for row in line_rows(ticket) {
+		text.push_str(&row);
+		text.push('\n');
+	}
+	text.push('\n');
+	text
+}
+
+/// Kitchen printers print the header double size so it can be read from
+/// across the pass, then cut the paper after each ticket.
+pub fn render_escpos(ticket: &KitchenTicket) -> Vec<u8> {
+	let ascii = |text: &str| -> Vec<u8> {
+		text.chars().map(|c| if c.is_ascii() { c as u8 } else { b'?' }).collect()
+	};
+	let mut bytes = vec![ESC, b'@', GS, b'!', 0x11];
+	for row in header_rows(ticket) {
+		bytes.extend(ascii(&row));
+		bytes.push(b'\n');
+	}
+	bytes.extend([GS, b'!', 0]);
+	bytes.extend("-".repeat(TICKET_WIDTH).as_bytes());
+	bytes.push(b'\n');
+	for row in line_rows(ticket) {
+		bytes.extend(ascii(&row));
+		bytes.push(b'\n');
+	}
+	bytes.extend([ESC, b'd', 4, GS, b'V', 66, 0]);
+	bytes
+}
+
+/// Managed state handing tickets to the configured sink.
+#[derive(Clone)]
+pub struct Kitchen {
+	sink: Arc<dyn TicketSink>,
+	pub course_interval: TimeDelta
+}
+
+impl Kitchen {
+	pub fn new(sink: Arc<dyn TicketSink>) -> Self {
+		Kitchen { sink, course_interval: TimeDelta::minutes(default_course_interval_minutes()) }
+	}
+
+	/// Sends every ticket, logging the ones that could not be printed. Sinks do
+	/// blocking IO, so this runs off the async workers. Returns the tickets that
+	/// were sent.
+	pub async fn dispatch(&self, tickets: Vec<KitchenTicket>) -> Vec<KitchenTicket> {
+		let sink = self.sink.clone();
+		let result = rocket::tokio::task::spawn_blocking(move || {
+			tickets.into_iter()
+				.filter(|ticket| match sink.send(ticket) {
+					Ok(()) => true,
+					Err(e) => {
+						error!("Unable to send {} ticket for order {}: {}", station_name(ticket.station), ticket.order_id, e);
+						false
+					}
+				})
+				.collect()
+		}).await;
+		result.unwrap_or_default()
+	}
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Default)]
+#[serde(crate="rocket::serde", rename_all="lowercase")]
+enum SinkKind {
+	#[default]
+	File,
+	Tcp,
+	Memory
+}
+
+fn default_directory() -> PathBuf {
+	PathBuf::from("kitchen_tickets")
+}
+
+fn default_course_interval_minutes() -> i64 {
+	20
+}
+
+/// The `[default.kitchen]` table of `Rocket.toml`.
+#[derive(Debug, Deserialize)]
+#[serde(crate="rocket::serde")]
+struct KitchenConfig {
+	#[serde(default)]
+	sink: SinkKind,
+	#[serde(default = "default_directory")]
+	directory: PathBuf,
+	#[serde(default)]
+	printers: HashMap<Station, String>,
+	/// Minutes between one course landing and the next.
+	#[serde(default = "default_course_interval_minutes")]
+	course_interval_minutes: i64
+}
+
+pub fn stage() -> AdHoc {
+	AdHoc::try_on_ignite("Kitchen tickets", |rocket| async {
+		if rocket.state::<Kitchen>().is_some() {
+			return Ok(rocket);
+		}
+		let config = match rocket.figment().find_value("kitchen") {
+			Ok(_) => match rocket.figment().extract_inner::<KitchenConfig>("kitchen") {
+				Ok(config) => config,
+				Err(e) => {
+					rocket::error!("Invalid kitchen configuration: {}", e);
+					return Err(rocket);
+				}
+			},
+			Err(_) => KitchenConfig {
+				sink: SinkKind::File,
+				directory: default_directory(),
+				printers: HashMap::new(),
+				course_interval_minutes: default_course_interval_minutes()
+			}
+		};
+		let sink: Arc<dyn TicketSink> = match config.sink {
+			SinkKind::File => Arc::new(FileSink::new(config.directory)),
+			SinkKind::Tcp => Arc::new(EscPosSink::new(config.printers)),
+			SinkKind::Memory => Arc::new(MemorySink::default()),
+		};
+		let mut kitchen = Kitchen::new(sink);
+		kitchen.course_interval = TimeDelta::minutes(config.course_interval_minutes);
+		Ok(rocket.manage(kitchen))
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::HashMap;
+	use std::io::Read;
+	use std::net::TcpListener;
+	use chrono::{Duration, NaiveDateTime};
+	use super::{EscPosSink, TicketSink};
+	use crate::models::{Course, QueueItem, Station, TicketKind};
+
+	fn item(order_id: i64, name: &str, station: Station, course: Option<Course>, preparation_time: u32, fired_at: Option<&str>) -> QueueItem {
+		QueueItem {
+			table_session_id: 1,
+			table_nr: 4,
+			order_id,
+			name: String::from(name),
+			station,
+			course,
+			amount: 1,
+			preparation_time,
+			fired_at: fired_at.map(String::from),
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn orders_are_split_by_station()
+	{
+		let tickets = super::tickets_for(TicketKind::New, &[
+			item(10, "Spaghetti", Station::Grill, None, 300, None),
+			item(10, "Cupcakes", Station::Dessert, None, 300, None),
+			item(10, "Risotto", Station::Grill, None, 300, None),
+		]);
+		assert_eq!(tickets.len(), 2);
+		assert_eq!(tickets[0].station, Station::Grill);
+		assert_eq!(tickets[0].lines.len(), 2);
+		assert_eq!(tickets[1].station, Station::Dessert);
+	}
+
+	#[test]
+	fn courses_land_together()
+	{
+		let now = NaiveDateTime::parse_from_str("2022-05-16 18:10:00", super::TIME_FORMAT).unwrap();
+		let fired = Some("2022-05-16 18:00:00");
+		let items = super::plan(vec![
+			item(10, "Soup", Station::Grill, Some(Course::Starter), 300, fired),
+			item(10, "Fries", Station::Fryer, Some(Course::Starter), 600, fired),
+			item(10, "Steak", Station::Grill, Some(Course::Main), 1200, None),
+			item(10, "Risotto", Station::Grill, Some(Course::Main), 900, None),
+			item(11, "Cake", Station::Dessert, Some(Course::Dessert), 60, None),
+		], now, Duration::minutes(20));
+
+		// The starter lands when the fries are done, so the soup starts later.
+		assert_eq!(items[0].start_at.as_deref(), Some("2022-05-16 18:05:00"));
+		assert_eq!(items[0].ready_at.as_deref(), Some("2022-05-16 18:10:00"));
+		assert_eq!(items[1].start_at.as_deref(), Some("2022-05-16 18:00:00"));
+		// The main should land 20 minutes after the starter.
+		assert_eq!(items[2].fire_at.as_deref(), Some("2022-05-16 18:10:00"));
+		assert_eq!(items[3].start_at.as_deref(), Some("2022-05-16 18:15:00"));
+		assert_eq!(items[3].ready_at.as_deref(), Some("2022-05-16 18:30:00"));
+		// And the dessert 20 minutes after the main.
+		assert_eq!(items[4].fire_at.as_deref(), Some("2022-05-16 18:49:00"));
+	}
+
+	#[test]
+	fn escpos_sink_prints_to_station_printer()
+	{
+		let printer = TcpListener::bind("127.0.0.1:0").expect("printer stand-in");
+		let sink = EscPosSink::new(HashMap::from([(Station::Bar, printer.local_addr().unwrap().to_string())]));
+		let mut lemonade = item(10, "Lemonade", Station::Bar, None, 60, None);
+		lemonade.amount = 3;
+		let tickets = super::tickets_for(TicketKind::New, &[lemonade]);
+		sink.send(&tickets[0]).expect("printed ticket");
+
+		let mut received = Vec::new();
+		printer.accept().unwrap().0.read_to_end(&mut received).unwrap();
+		assert!(received.starts_with(&[0x1b, b'@']));
+		assert!(String::from_utf8_lossy(&received).contains("3 x Lemonade"));
+
+		let tickets = super::tickets_for(TicketKind::New, &[item(10, "Steak", Station::Grill, None, 600, None)]);
+		assert!(sink.send(&tickets[0]).is_err());
+	}
+}
@@ -0,0 +1,128 @@
+use rocket::fairing::AdHoc;
+use rocket::http::Status;
+use rocket::http::uri::{Host, Origin};
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket_db_pools::Connection;
+use log::error;
+use crate::db;
+use crate::models;
+
+/// The location requests are for when they name none, unless
+/// `default_location` is configured.
+pub const DEFAULT_LOCATION: &str = "main";
+
+/// The location requests go to when they name none, from the configuration.
+pub struct DefaultLocation(pub String);
+
+/// The slug of a `/locations/<slug>` path prefix, taken off before routing.
+struct PathLocation(Option<String>);
+
+/// The location of the request, resolved once and shared by its guards.
+struct Resolved(Result<models::Location, Status>);
+
+/// Splits `/locations/<slug>/rest` into the slug and `/rest`.
+fn split_prefix(path: &str) -> Option<(&str, &str)> {
+	let rest = path.strip_prefix("/locations/")?;
+	let (slug, rest) = rest.split_once('/')?;
+	(!slug.is_empty()).then_some((slug, rest))
+}
+
+/// The first label of a host name with a subdomain, like `harbor` for
+/// `harbor.example.com` or `harbor.localhost`. IP addresses have none.
+fn subdomain(domain: &str) -> Option<&str> {
+	if domain.parse::<std::net::IpAddr>().is_ok() {
+		return None;
+	}
+	let labels: Vec<&str> = domain.split('.').collect();
+	match labels.as_slice() {
+		[first, "localhost"] => Some(first),
+		[first, _, _, ..] => Some(first),
+		_ => None,
+	}
+}
+
+async fn find(db: &mut Connection<db::Db>, slug: &str) -> Result<Option<models::Location>, Status> {
+	db::retrieve_location(db, slug).await.map_err(|e| {
+		error!("Unable to resolve location '{}': {}", slug, e);
+		Status::InternalServerError
+	})
+}
+
+/// Looks the location up by the path prefix, the `X-Location` header, the
+/// subdomain and finally the default location. Locations the request names
+/// in its path or header must exist; subdomains that are no location, like
+/// `www`, fall through to the default.
+async fn resolve(req: &Request<'_>) -> Result<models::Location, Status> {
+	let mut db = match req.guard::<Connection<db::Db>>().await.succeeded() {
+		Some(db) => db,
+		None => return Err(Status::ServiceUnavailable)
+	};
+	let named = req.local_cache(|| PathLocation(None)).0.as_deref()
+		.or_else(|| req.headers().get_one("X-Location").map(str::trim));
+	if let Some(slug) = named {
+		return find(&mut db, slug).await?.ok_or(Status::NotFound);
+	}
+	let host = req.headers().get_one("Host").and_then(|host| Host::parse(host).ok());
+	if let Some(slug) = host.as_ref().and_then(|host| subdomain(host.domain().as_str())) {
+		if let Some(location) = find(&mut db, slug).await? {
+			return Ok(location);
+		}
+	}
+	let default = req.rocket().state::<DefaultLocation>().map_or(DEFAULT_LOCATION, |default| default.0.as_str());
+	find(&mut db, default).await?.ok_or(Status::NotFound)
+}
+
+/// The location a request is for. Menus, tables and sessions are those of
+/// this location; an unknown location is a 404.
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for models::Location {
+	type Error = ();
+
+	async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+		match &req.local_cache_async(async { Resolved(resolve(req).await) }).await.0 {
+			Ok(location) => Outcome::Success(location.clone()),
+			Err(status) => Outcome::Failure((*status, ()))
+		}
+	}
+}
+
+/// Takes a `/locations/<slug>` prefix off the path, so
+/// `/locations/harbor/api/v1/tables` is routed like `/api/v1/tables` at the
+/// `harbor` location.
+pub fn stage() -> AdHoc {
+	AdHoc::on_ignite("Locations", |rocket| async {
+		let default = rocket.figment().extract_inner::<String>("default_location").unwrap_or_else(|_| String::from(DEFAULT_LOCATION));
+		rocket.manage(DefaultLocation(default))
+		.attach(AdHoc::on_request("Location prefix", |req, _| Box::pin(async move {
+			let (slug, rest) = match split_prefix(req.uri().path().as_str()) {
+				Some((slug, rest)) => (slug.to_string(), rest.to_string()),
+				None => return
+			};
+			let uri = match req.uri().query() {
+				Some(query) => format!("/{}?{}", rest, query),
+				None => format!("/{}", rest)
+			};
+			if let Ok(uri) = Origin::parse_owned(uri) {
+				req.local_cache(|| PathLocation(Some(slug)));
+				req.set_uri(uri);
+			}
+		})))
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn prefixes_and_subdomains_name_locations()
+	{
+		assert_eq!(super::split_prefix("/locations/harbor/api/v1/tables"), Some(("harbor", "api/v1/tables")));
+		assert_eq!(super::split_prefix("/locations//api/v1/tables"), None);
+		assert_eq!(super::split_prefix("/api/v1/locations"), None);
+
+		assert_eq!(super::subdomain("harbor.example.com"), Some("harbor"));
+		assert_eq!(super::subdomain("harbor.localhost"), Some("harbor"));
+		assert_eq!(super::subdomain("example.com"), None);
+		assert_eq!(super::subdomain("localhost"), None);
+		assert_eq!(super::subdomain("192.168.1.10"), None);
+	}
+}
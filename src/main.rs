@@ -2,6 +2,27 @@ mod handlers;
 mod db;
 mod models;
 mod error_handler;
+mod auth;
+mod billing;
+mod receipt;
+mod kitchen;
+mod events;
+mod webhooks;
+mod idempotency;
+mod etag;
+mod pagination;
+mod openapi;
+mod graphql;
+mod locations;
+mod settings;
+mod money;
+mod i18n;
+mod guests;
+mod repository;
+mod service;
+mod timeouts;
+#[cfg(test)]
+mod testing;
 
 #[rocket::launch]
 fn rocket() -> _ {
@@ -1,48 +1,740 @@
-use rocket::serde::{Serialize, Deserialize};
+use rocket::serde::{Serialize, Deserialize, Deserializer};
+use rocket::serde::de::IgnoredAny;
 use serde_with::skip_serializing_none;
+use utoipa::ToSchema;
+use sqlx::Row;
+use crate::db::{self, DbRow};
+use crate::money::{Currency, Money};
 
-#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize, ToSchema, Default, sqlx::Type)]
+#[serde(crate="rocket::serde", rename_all="lowercase")]
+#[sqlx(type_name="text", rename_all="lowercase")]
+pub enum Station {
+    #[default]
+    Grill,
+    Fryer,
+    Bar,
+    Dessert
+}
+
+/// Fields set by the server are ignored in request bodies, like with
+/// `skip_deserializing`, but stay in the OpenAPI schema as read-only.
+fn server_set<'de, D: Deserializer<'de>, T: Default>(deserializer: D) -> Result<T, D::Error> {
+    IgnoredAny::deserialize(deserializer)?;
+    Ok(T::default())
+}
+
+/// One page of a collection. `next_cursor` is passed as `cursor` to get the
+/// next page and is missing on the last one.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[serde(crate="rocket::serde")]
+#[aliases(ItemPage = Page<Item>, SessionPage = Page<TableSession>, OrderPage = Page<Order>)]
+pub struct Page<T> {
+    pub data: Vec<T>,
+    pub next_cursor: Option<String>
+}
+
+/// The body of every error response, `{"status": "failed"}`, with a
+/// `message` in the language of the request's `Accept-Language` unless the
+/// error is internal.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[serde(crate="rocket::serde")]
+pub struct Failure {
+    pub status: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>
+}
+
+/// A restaurant. Each has its own menu, tables and sessions.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema, Default)]
+#[serde(crate="rocket::serde")]
+pub struct Location {
+    #[serde(default, deserialize_with = "server_set")]
+    #[schema(read_only)]
+    pub id: i64,
+    pub slug: String,
+    pub name: String
+}
+
+/// Sessions started and payments taken at a location over a period.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[serde(crate="rocket::serde")]
+pub struct LocationReport {
+    pub location_id: i64,
+    pub slug: String,
+    pub name: String,
+    pub sessions: u32,
+    pub revenue: u32,
+    pub tips: u32
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema, Default)]
 #[serde(crate="rocket::serde")]
 pub struct Item {
     pub id: i64,
     pub preparation_time: u32,
-    pub price_yen: u32,
+    #[serde(default)]
+    pub price: Money,
+    /// The price for clients from before `price`, only there for prices in
+    /// yen. Requests can give it instead of `price`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub price_yen: Option<u32>,
     pub name: String,
+    #[serde(default)]
+    pub station: Station,
+    #[serde(default, deserialize_with = "server_set")]
+    #[schema(read_only)]
+    pub location_id: i64
+}
+
+impl Item {
+    /// Takes `price_yen` as the price of a request without `price`.
+    pub fn with_legacy_price(mut self) -> Self {
+        if let (Some(price_yen), 0) = (self.price_yen, self.price.amount) {
+            self.price = Money::new(price_yen.into(), Currency::JPY);
+        }
+        self.price_yen = self.price.yen();
+        self
+    }
+}
+
+/// An item as stored, with its price in the `price` and `currency` columns.
+pub struct ItemRow {
+    pub id: i64,
+    pub preparation_time: u32,
+    pub price: i64,
+    pub currency: String,
+    pub name: String,
+    pub station: Station,
+    pub location_id: i64
+}
+
+impl TryFrom<ItemRow> for Item {
+    type Error = sqlx::Error;
+
+    fn try_from(row: ItemRow) -> Result<Self, sqlx::Error> {
+        let currency = Currency::parse(&row.currency)
+            .ok_or_else(|| sqlx::Error::Decode(format!("Invalid currency '{}'", row.currency).into()))?;
+        let price = Money::new(row.price, currency);
+        Ok(Item {
+            id: row.id,
+            preparation_time: row.preparation_time,
+            price,
+            price_yen: price.yen(),
+            name: row.name,
+            station: row.station,
+            location_id: row.location_id
+        })
+    }
+}
+
+// Rows are mapped by column name. Integers are read as the 64 bits both
+// databases store and checked to fit, as Postgres has no unsigned types.
+
+impl<'r> sqlx::FromRow<'r, DbRow> for ItemRow {
+    fn from_row(row: &'r DbRow) -> Result<Self, sqlx::Error> {
+        Ok(ItemRow {
+            id: row.try_get("id")?,
+            preparation_time: db::get_int(row, "preparation_time")?,
+            price: row.try_get("price")?,
+            currency: row.try_get("currency")?,
+            name: row.try_get("name")?,
+            station: row.try_get("station")?,
+            location_id: row.try_get("location_id")?
+        })
+    }
+}
+
+impl<'r> sqlx::FromRow<'r, DbRow> for Item {
+    fn from_row(row: &'r DbRow) -> Result<Self, sqlx::Error> {
+        Item::try_from(<ItemRow as sqlx::FromRow<'r, DbRow>>::from_row(row)?)
+    }
+}
+
+/// The name of an item in a locale, `ja`, `zh` or `ko`. Requests setting it
+/// give the locale in their path.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[serde(crate="rocket::serde")]
+pub struct ItemTranslation {
+    #[serde(default, deserialize_with = "server_set")]
+    #[schema(read_only)]
+    pub locale: String,
+    pub name: String
 }
 
 #[skip_serializing_none]
-#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema, Default)]
 #[serde(crate="rocket::serde")]
 pub struct TableSession {
-    #[serde(skip_deserializing)]
+    #[serde(default, deserialize_with = "server_set")]
+    #[schema(read_only)]
     pub id: i64,
-    #[serde(skip_deserializing)]
+    #[serde(default, deserialize_with = "server_set")]
+    #[schema(read_only)]
     pub table_nr: u8,
     pub customers: u8,
-    #[serde(skip_deserializing)]
+    #[serde(default, deserialize_with = "server_set")]
+    #[schema(read_only)]
     pub session_start: String,
-    #[serde(skip_deserializing)]
+    #[serde(default, deserialize_with = "server_set")]
+    #[schema(read_only)]
     pub session_end: String,
-    #[serde(skip_deserializing)]
-    pub active: bool
+    #[serde(default, deserialize_with = "server_set")]
+    #[schema(read_only)]
+    pub active: bool,
+    #[serde(default, deserialize_with = "server_set")]
+    #[schema(read_only)]
+    pub version: i64,
+    #[serde(default, deserialize_with = "server_set")]
+    #[schema(read_only)]
+    pub location_id: i64
+}
+
+impl<'r> sqlx::FromRow<'r, DbRow> for TableSession {
+    fn from_row(row: &'r DbRow) -> Result<Self, sqlx::Error> {
+        Ok(TableSession {
+            id: row.try_get("id")?,
+            table_nr: db::get_int(row, "table_nr")?,
+            customers: db::get_int(row, "customers")?,
+            session_start: row.try_get("session_start")?,
+            session_end: row.try_get("session_end")?,
+            active: row.try_get("active")?,
+            version: row.try_get("version")?,
+            location_id: row.try_get("location_id")?
+        })
+    }
+}
+
+/// Lets guests order to their table's session from their phones, e.g. from
+/// the QR code on the table. `expires_at` is in UTC.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[serde(crate="rocket::serde")]
+pub struct GuestToken {
+    pub token: String,
+    pub expires_at: String
+}
+
+/// A session as opened, with the token of its guests when staff opened it.
+#[skip_serializing_none]
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[serde(crate="rocket::serde")]
+pub struct OpenedSession {
+    #[serde(flatten)]
+    pub session: TableSession,
+    pub guest_token: Option<GuestToken>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, ToSchema, sqlx::Type)]
+#[serde(crate="rocket::serde", rename_all="lowercase")]
+#[sqlx(type_name="text", rename_all="lowercase")]
+pub enum GuestOrderStatus {
+    Pending,
+    Placed,
+    Rejected
+}
+
+/// An order guests placed with a table token. It is `pending` while it waits
+/// for staff to approve it, and has the `order_id` of the order it became
+/// once `placed`.
+#[skip_serializing_none]
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[serde(crate="rocket::serde")]
+pub struct GuestOrder {
+    pub id: i64,
+    pub table_session_id: i64,
+    pub order_items: Vec<OrderItem>,
+    pub status: GuestOrderStatus,
+    pub order_id: Option<i64>,
+    pub timestamp: String
 }
 
 #[skip_serializing_none]
-#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema, Default)]
 #[serde(crate="rocket::serde")]
 pub struct Order {
     pub id: Option<i64>,
-    #[serde(skip_deserializing)]
+    #[serde(default, deserialize_with = "server_set")]
+    #[schema(read_only)]
     pub table_session_id: i64,
-    #[serde(skip_deserializing)]
+    #[serde(default, deserialize_with = "server_set")]
+    #[schema(read_only)]
     pub timestamp: String,
+    #[serde(default, deserialize_with = "server_set")]
+    #[schema(read_only)]
+    pub staff_id: Option<i64>,
+    #[serde(default, deserialize_with = "server_set")]
+    #[schema(read_only)]
+    pub version: i64,
     pub order_items: Vec<OrderItem>
 }
 
+/// Rows of `Orders` by column name; the items are loaded separately.
+impl<'r> sqlx::FromRow<'r, DbRow> for Order {
+    fn from_row(row: &'r DbRow) -> Result<Self, sqlx::Error> {
+        Ok(Order {
+            id: row.try_get("id")?,
+            table_session_id: row.try_get("table_session_id")?,
+            timestamp: row.try_get("timestamp")?,
+            staff_id: row.try_get("staff_id")?,
+            version: row.try_get("version")?,
+            order_items: Vec::new()
+        })
+    }
+}
+
 #[skip_serializing_none]
-#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema, Default)]
 #[serde(crate="rocket::serde")]
 pub struct OrderItem {
     pub item_id: i64,
+    pub amount: u8,
+    pub course: Option<Course>,
+    #[serde(default, deserialize_with = "server_set")]
+    #[schema(read_only)]
+    pub held: bool
+}
+
+impl<'r> sqlx::FromRow<'r, DbRow> for OrderItem {
+    fn from_row(row: &'r DbRow) -> Result<Self, sqlx::Error> {
+        Ok(OrderItem {
+            item_id: row.try_get("item_id")?,
+            amount: db::get_int(row, "amount")?,
+            course: row.try_get("course")?,
+            held: row.try_get("held")?
+        })
+    }
+}
+
+/// Items ordered with a course are held until that course is fired; items
+/// without one go to the kitchen straight away.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize, ToSchema, sqlx::Type)]
+#[serde(crate="rocket::serde", rename_all="lowercase")]
+#[sqlx(type_name="text", rename_all="lowercase")]
+pub enum Course {
+    Starter,
+    Main,
+    Dessert
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize, ToSchema, sqlx::Type)]
+#[serde(crate="rocket::serde", rename_all="lowercase")]
+#[sqlx(type_name="text", rename_all="lowercase")]
+pub enum Role {
+    Server,
+    Manager
+}
+
+/// Staff work at one location; managers without one are owners and work at
+/// all of them.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[serde(crate="rocket::serde")]
+pub struct Staff {
+    pub id: i64,
+    pub name: String,
+    pub role: Role,
+    pub location_id: Option<i64>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, ToSchema, sqlx::Type)]
+#[serde(crate="rocket::serde", rename_all="lowercase")]
+#[sqlx(type_name="text", rename_all="lowercase")]
+pub enum DiscountKind {
+    Percent,
+    Fixed
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, ToSchema, sqlx::Type)]
+#[serde(crate="rocket::serde", rename_all="lowercase")]
+#[sqlx(type_name="text", rename_all="lowercase")]
+pub enum DiscountScope {
+    Line,
+    Bill
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[serde(crate="rocket::serde")]
+pub struct Discount {
+    #[serde(default, deserialize_with = "server_set")]
+    #[schema(read_only)]
+    pub id: i64,
+    pub name: String,
+    pub kind: DiscountKind,
+    pub value: u32,
+    pub scope: DiscountScope,
+    #[serde(default)]
+    pub stackable: bool,
+    #[serde(default)]
+    pub requires_manager: bool,
+    #[serde(default, deserialize_with = "server_set")]
+    #[schema(read_only)]
+    pub location_id: i64
+}
+
+/// Body of `POST /tables/<table_nr>/discounts`. Either references a predefined
+/// discount through `discount_id`, or describes a manual comp with `kind`,
+/// `value` and `scope`. Line-level discounts target an item within an order.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema, Default)]
+#[serde(crate="rocket::serde")]
+pub struct DiscountRequest {
+    pub discount_id: Option<i64>,
+    pub kind: Option<DiscountKind>,
+    pub value: Option<u32>,
+    pub scope: Option<DiscountScope>,
+    #[serde(default)]
+    pub stackable: bool,
+    pub order_id: Option<i64>,
+    pub item_id: Option<i64>,
+    pub reason: String
+}
+
+#[skip_serializing_none]
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[serde(crate="rocket::serde")]
+pub struct AppliedDiscount {
+    pub id: i64,
+    pub table_session_id: i64,
+    pub discount_id: Option<i64>,
+    pub order_id: Option<i64>,
+    pub item_id: Option<i64>,
+    pub name: String,
+    pub kind: DiscountKind,
+    pub value: u32,
+    pub scope: DiscountScope,
+    pub stackable: bool,
+    pub reason: String,
+    pub applied_by: i64,
+    pub timestamp: String
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema, Default)]
+#[serde(crate="rocket::serde")]
+pub struct BillLine {
+    pub order_id: i64,
+    pub item_id: i64,
+    pub name: String,
+    pub amount: u8,
+    pub price: Money,
+    /// `price` for clients from before there were currencies, for prices
+    /// in yen.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub price_yen: Option<u32>,
+    /// `amounts.line_total` and `amounts.discount` in minor units, for
+    /// clients from before there were currencies.
+    pub line_total: u32,
+    pub discount: u32,
+    #[serde(default)]
+    pub amounts: LineAmounts
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, ToSchema, Default)]
+#[serde(crate="rocket::serde")]
+pub struct LineAmounts {
+    pub line_total: Money,
+    pub discount: Money
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema, Default)]
+#[serde(crate="rocket::serde")]
+pub struct Bill {
+    pub table_session_id: i64,
+    /// The currency of the amounts of the bill, which are in its minor units.
+    #[schema(value_type = String)]
+    pub currency: Currency,
+    pub lines: Vec<BillLine>,
+    pub discounts: Vec<AppliedDiscount>,
+    /// The amounts below are the same as `amounts`, in minor units, for
+    /// clients from before there were currencies.
+    pub subtotal: u32,
+    pub line_discounts: u32,
+    pub bill_discounts: u32,
+    pub service_charge_percent: u32,
+    pub service_charge: u32,
+    pub tax_rate_percent: u32,
+    pub tax: u32,
+    pub total: u32,
+    pub paid: u32,
+    pub tips: u32,
+    pub balance_due: u32,
+    #[serde(default)]
+    pub amounts: BillAmounts
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, ToSchema, Default)]
+#[serde(crate="rocket::serde")]
+pub struct BillAmounts {
+    pub subtotal: Money,
+    pub line_discounts: Money,
+    pub bill_discounts: Money,
+    pub service_charge: Money,
+    pub tax: Money,
+    pub total: Money,
+    pub paid: Money,
+    pub tips: Money,
+    pub balance_due: Money
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema, Default)]
+#[serde(crate="rocket::serde")]
+pub struct Payment {
+    #[serde(default, deserialize_with = "server_set")]
+    #[schema(read_only)]
+    pub id: i64,
+    #[serde(default, deserialize_with = "server_set")]
+    #[schema(read_only)]
+    pub table_session_id: i64,
+    #[serde(default, deserialize_with = "server_set")]
+    #[schema(read_only)]
+    pub staff_id: i64,
+    pub amount: u32,
+    #[serde(default)]
+    pub tip: u32,
+    /// The currency of `amount` and `tip`, which has to be the bill's and is
+    /// when left out. Payments from before there were currencies have none.
+    #[serde(default)]
+    #[schema(value_type = Option<String>)]
+    pub currency: Option<Currency>,
+    pub method: String,
+    #[serde(default, deserialize_with = "server_set")]
+    #[schema(read_only)]
+    pub timestamp: String
+}
+
+#[skip_serializing_none]
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema, Default)]
+#[serde(crate="rocket::serde")]
+pub struct Shift {
+    pub id: i64,
+    pub staff_id: i64,
+    pub clock_in: String,
+    pub clock_out: Option<String>
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[serde(crate="rocket::serde")]
+pub struct TipShare {
+    pub staff_id: i64,
+    pub name: String,
+    pub role: Role,
+    pub hours: f64,
+    pub share: u32
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[serde(crate="rocket::serde")]
+pub struct TipReport {
+    pub from: String,
+    pub to: String,
+    pub total_tips: u32,
+    pub shares: Vec<TipShare>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, ToSchema, sqlx::Type)]
+#[serde(crate="rocket::serde", rename_all="snake_case")]
+#[sqlx(type_name="text", rename_all="snake_case")]
+pub enum SignalKind {
+    CallServer,
+    RequestBill,
+    NeedWater
+}
+
+/// A table asking for staff. `raised_by` is the staff member who raised it
+/// for the table, none when the guests did; it is open until acknowledged.
+#[skip_serializing_none]
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[serde(crate="rocket::serde")]
+pub struct Signal {
+    #[serde(default, deserialize_with = "server_set")]
+    #[schema(read_only)]
+    pub id: i64,
+    #[serde(default, deserialize_with = "server_set")]
+    #[schema(read_only)]
+    pub table_session_id: i64,
+    #[serde(default, deserialize_with = "server_set")]
+    #[schema(read_only)]
+    pub table_nr: u8,
+    pub kind: SignalKind,
+    #[serde(default, deserialize_with = "server_set")]
+    #[schema(read_only)]
+    pub raised_at: String,
+    #[serde(default, deserialize_with = "server_set")]
+    #[schema(read_only)]
+    pub raised_by: Option<i64>,
+    #[serde(default, deserialize_with = "server_set")]
+    #[schema(read_only)]
+    pub acknowledged_at: Option<String>,
+    #[serde(default, deserialize_with = "server_set")]
+    #[schema(read_only)]
+    pub acknowledged_by: Option<i64>
+}
+
+/// An active session on the floor, with the signals of its table still
+/// waiting for staff.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[serde(crate="rocket::serde")]
+pub struct FloorTable {
+    #[serde(flatten)]
+    pub session: TableSession,
+    pub signals: Vec<Signal>
+}
+
+/// How fast a staff member answered signals during a shift, in seconds from
+/// raising to acknowledging.
+#[skip_serializing_none]
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[serde(crate="rocket::serde")]
+pub struct SignalShift {
+    pub shift_id: i64,
+    pub staff_id: i64,
+    pub name: String,
+    pub clock_in: String,
+    pub clock_out: Option<String>,
+    pub acknowledged: u32,
+    pub average_response_seconds: u32,
+    pub longest_response_seconds: u32
+}
+
+/// Response times per shift, and the signals raised in the period that no
+/// one acknowledged.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[serde(crate="rocket::serde")]
+pub struct SignalReport {
+    pub from: String,
+    pub to: String,
+    pub unacknowledged: u32,
+    pub shifts: Vec<SignalShift>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[serde(crate="rocket::serde", rename_all="lowercase")]
+pub enum TicketKind {
+    New,
+    Reprint,
+    Fire
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[serde(crate="rocket::serde")]
+pub struct TicketLine {
+    pub name: String,
     pub amount: u8
-}
\ No newline at end of file
+}
+
+#[skip_serializing_none]
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[serde(crate="rocket::serde")]
+pub struct KitchenTicket {
+    pub kind: TicketKind,
+    pub station: Station,
+    pub course: Option<Course>,
+    pub table_nr: u8,
+    pub order_id: i64,
+    pub lines: Vec<TicketLine>
+}
+
+/// An item in the kitchen queue. `start_at` and `ready_at` suggest when to
+/// start and when the item lands, so every item of a course lands together;
+/// for held courses `fire_at` suggests when to fire them.
+#[skip_serializing_none]
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema, Default)]
+#[serde(crate="rocket::serde")]
+pub struct QueueItem {
+    pub table_session_id: i64,
+    pub table_nr: u8,
+    pub order_id: i64,
+    pub item_id: i64,
+    pub name: String,
+    pub station: Station,
+    pub course: Option<Course>,
+    pub amount: u8,
+    pub preparation_time: u32,
+    pub fired_at: Option<String>,
+    pub fire_at: Option<String>,
+    pub start_at: Option<String>,
+    pub ready_at: Option<String>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[serde(crate="rocket::serde", rename_all="lowercase")]
+pub enum ItemStatus {
+    Held,
+    Fired
+}
+
+/// What happened, tagged with its `type` when serialized.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[serde(crate="rocket::serde", tag="type", rename_all="snake_case")]
+pub enum EventData {
+    SessionOpened { table_session_id: i64, customers: u8 },
+    SessionClosed { table_session_id: i64 },
+    OrderCreated { order: Order },
+    OrderUpdated { order_id: i64 },
+    OrderVoided { order_id: i64 },
+    KitchenItemStatusChanged { order_id: i64, item_id: i64, station: Station, status: ItemStatus },
+    GuestOrderPending { guest_order: GuestOrder },
+    SignalRaised { signal: Signal },
+    SignalAcknowledged { signal: Signal }
+}
+
+/// An entry in the `GET /events` stream. `stations` lists the kitchen
+/// stations the event concerns, if any.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[serde(crate="rocket::serde")]
+pub struct Event {
+    pub id: u64,
+    pub location_id: i64,
+    pub table_nr: u8,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stations: Vec<Station>,
+    #[serde(flatten)]
+    pub data: EventData
+}
+
+/// An endpoint notified of events. `events` lists the event types it wants,
+/// or `*` for all of them. The secret signing its payloads is generated when
+/// none is given and only returned when the webhook is registered.
+#[skip_serializing_none]
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[serde(crate="rocket::serde")]
+pub struct Webhook {
+    #[serde(default, deserialize_with = "server_set")]
+    #[schema(read_only)]
+    pub id: i64,
+    pub url: String,
+    pub secret: Option<String>,
+    pub events: Vec<String>,
+    #[serde(default, deserialize_with = "server_set")]
+    #[schema(read_only)]
+    pub location_id: i64
+}
+
+#[skip_serializing_none]
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[serde(crate="rocket::serde")]
+pub struct WebhookDelivery {
+    pub id: i64,
+    pub webhook_id: i64,
+    /// The `X-Webhook-Id` header, the same for every attempt of a payload.
+    pub delivery_id: String,
+    pub event_id: i64,
+    pub event_type: String,
+    pub attempt: u32,
+    pub status_code: Option<u16>,
+    pub error: Option<String>,
+    pub succeeded: bool,
+    pub timestamp: String
+}
+
+/// A payload that could not be delivered after every retry.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[serde(crate="rocket::serde")]
+pub struct DeadLetter {
+    pub id: i64,
+    pub webhook_id: i64,
+    pub delivery_id: String,
+    pub event_id: i64,
+    pub event_type: String,
+    pub payload: String,
+    pub attempts: u32,
+    pub last_error: String,
+    pub timestamp: String
+}
@@ -0,0 +1,266 @@
+use std::fmt;
+use rocket::serde::{Deserialize, Deserializer, Serialize, Serializer};
+use rocket::serde::de::Error as _;
+use utoipa::ToSchema;
+use crate::i18n::{message, Message};
+
+/// The ISO 4217 codes of the currencies in circulation, in order. Precious
+/// metals, fund and testing codes aren't money a restaurant is paid in.
+const ISO_4217: [&str; 160] = [
+	"AED", "AFN", "ALL", "AMD", "ANG", "AOA", "ARS", "AUD", "AWG", "AZN",
+	"BAM", "BBD", "BDT", "BGN", "BHD", "BIF", "BMD", "BND", "BOB", "BRL",
+	"BSD", "BTN", "BWP", "BYN", "BZD", "CAD", "CDF", "CHF", "CLP", "CNY",
+	"COP", "CRC", "CUC", "CUP", "CVE", "CZK", "DJF", "DKK", "DOP", "DZD",
+	"EGP", "ERN", "ETB", "EUR", "FJD", "FKP", "GBP", "GEL", "GHS", "GIP",
+	"GMD", "GNF", "GTQ", "GYD", "HKD", "HNL", "HTG", "HUF", "IDR", "ILS",
+	"INR", "IQD", "IRR", "ISK", "JMD", "JOD", "JPY", "KES", "KGS", "KHR",
+	"KMF", "KPW", "KRW", "KWD", "KYD", "KZT", "LAK", "LBP", "LKR", "LRD",
+	"LSL", "LYD", "MAD", "MDL", "MGA", "MKD", "MMK", "MNT", "MOP", "MRU",
+	"MUR", "MVR", "MWK", "MXN", "MYR", "MZN", "NAD", "NGN", "NIO", "NOK",
+	"NPR", "NZD", "OMR", "PAB", "PEN", "PGK", "PHP", "PKR", "PLN", "PYG",
+	"QAR", "RON", "RSD", "RUB", "RWF", "SAR", "SBD", "SCR", "SDG", "SEK",
+	"SGD", "SHP", "SLE", "SLL", "SOS", "SRD", "SSP", "STN", "SVC", "SYP",
+	"SZL", "THB", "TJS", "TMT", "TND", "TOP", "TRY", "TTD", "TWD", "TZS",
+	"UAH", "UGX", "USD", "UYU", "UZS", "VED", "VES", "VND", "VUV", "WST",
+	"XAF", "XCD", "XCG", "XOF", "XPF", "YER", "ZAR", "ZMW", "ZWG", "ZWL",
+];
+
+/// How an amount between two minor units is rounded, the same way for
+/// negative amounts as for positive ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rounding {
+	/// Toward zero, as Japanese consumption tax usually is.
+	Down,
+	/// To the nearest minor unit, halves away from zero.
+	HalfUp
+}
+
+/// An ISO 4217 currency code, like `JPY`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Currency([u8; 3]);
+
+impl Currency {
+	pub const JPY: Currency = Currency(*b"JPY");
+
+	/// The currency of an ISO 4217 code in circulation.
+	pub fn parse(code: &str) -> Option<Currency> {
+		match code.as_bytes() {
+			&[a, b, c] if ISO_4217.binary_search(&code).is_ok() => Some(Currency([a, b, c])),
+			_ => None
+		}
+	}
+
+	pub fn code(&self) -> &str {
+		std::str::from_utf8(&self.0).expect("currency codes are ASCII")
+	}
+
+	/// Digits after the decimal point, so amounts are in yen and won but in
+	/// cents of euros and dollars.
+	pub fn minor_units(&self) -> u32 {
+		match self.code() {
+			"BIF" | "CLP" | "DJF" | "GNF" | "ISK" | "JPY" | "KMF" | "KRW" | "PYG" | "RWF" | "UGX" | "VND" | "VUV" | "XAF" | "XOF" | "XPF" => 0,
+			"BHD" | "IQD" | "JOD" | "KWD" | "LYD" | "OMR" | "TND" => 3,
+			_ => 2
+		}
+	}
+
+	/// How percentages of amounts, like tax and service charges, are rounded
+	/// to the minor unit.
+	pub fn rounding(&self) -> Rounding {
+		match self.code() {
+			"JPY" => Rounding::Down,
+			_ => Rounding::HalfUp
+		}
+	}
+}
+
+/// Amounts were in yen before there were currencies.
+impl Default for Currency {
+	fn default() -> Self {
+		Currency::JPY
+	}
+}
+
+impl fmt::Display for Currency {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(self.code())
+	}
+}
+
+impl Serialize for Currency {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.serialize_str(self.code())
+	}
+}
+
+impl<'de> Deserialize<'de> for Currency {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		let code = String::deserialize(deserializer)?;
+		Currency::parse(&code).ok_or_else(|| D::Error::custom(format!("invalid currency '{}', expected a code like JPY", code)))
+	}
+}
+
+/// Adding amounts in different currencies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CurrencyMismatch(pub Currency, pub Currency);
+
+impl CurrencyMismatch {
+	pub fn message(&self) -> Message {
+		message!("Unable to mix {} and {} amounts", self.0, self.1)
+	}
+}
+
+impl fmt::Display for CurrencyMismatch {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		self.message().fmt(f)
+	}
+}
+
+/// Arithmetic on amounts that has no result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoneyError {
+	/// The amounts are in different currencies.
+	Currency(CurrencyMismatch),
+	/// The result is beyond what an amount can hold.
+	Overflow(Currency)
+}
+
+impl From<CurrencyMismatch> for MoneyError {
+	fn from(mismatch: CurrencyMismatch) -> Self {
+		MoneyError::Currency(mismatch)
+	}
+}
+
+impl MoneyError {
+	pub fn message(&self) -> Message {
+		match self {
+			MoneyError::Currency(mismatch) => mismatch.message(),
+			MoneyError::Overflow(currency) => message!("An amount in {} is too large to calculate with", currency)
+		}
+	}
+}
+
+impl fmt::Display for MoneyError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		self.message().fmt(f)
+	}
+}
+
+/// An amount of money in the minor units of its currency, e.g.
+/// `{"amount": 1250, "currency": "EUR"}` for 12.50 euros and
+/// `{"amount": 1250, "currency": "JPY"}` for 1,250 yen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize, ToSchema)]
+#[serde(crate="rocket::serde")]
+pub struct Money {
+	pub amount: i64,
+	#[schema(value_type = String)]
+	pub currency: Currency
+}
+
+impl Money {
+	pub fn new(amount: i64, currency: Currency) -> Self {
+		Money { amount, currency }
+	}
+
+	pub fn checked_add(self, other: Money) -> Result<Money, MoneyError> {
+		self.same_currency(other)?;
+		self.amount.checked_add(other.amount).map(|amount| Money::new(amount, self.currency)).ok_or(MoneyError::Overflow(self.currency))
+	}
+
+	pub fn checked_sub(self, other: Money) -> Result<Money, MoneyError> {
+		self.same_currency(other)?;
+		self.amount.checked_sub(other.amount).map(|amount| Money::new(amount, self.currency)).ok_or(MoneyError::Overflow(self.currency))
+	}
+
+	fn same_currency(self, other: Money) -> Result<(), CurrencyMismatch> {
+		if self.currency == other.currency {
+			Ok(())
+		}
+		else {
+			Err(CurrencyMismatch(self.currency, other.currency))
+		}
+	}
+
+	pub fn times(self, factor: i64) -> Result<Money, MoneyError> {
+		self.amount.checked_mul(factor).map(|amount| Money::new(amount, self.currency)).ok_or(MoneyError::Overflow(self.currency))
+	}
+
+	/// `percent` of the amount in the minor unit of the currency (whole yen,
+	/// but cents of euros), rounded by the currency's `rounding`.
+	pub fn percent(self, percent: u32) -> Money {
+		let hundredths = u128::from(self.amount.unsigned_abs()) * u128::from(percent);
+		let rounded = match self.currency.rounding() {
+			Rounding::Down => hundredths / 100,
+			Rounding::HalfUp => (hundredths + 50) / 100
+		};
+		let rounded = i64::try_from(rounded).unwrap_or(i64::MAX);
+		Money::new(if self.amount < 0 { -rounded } else { rounded }, self.currency)
+	}
+
+	/// The amount in yen, for clients from before there were currencies.
+	pub fn yen(self) -> Option<u32> {
+		match self.currency {
+			Currency::JPY => self.to_u32(),
+			_ => None
+		}
+	}
+
+	/// The amount in minor units as the plain numbers of fields from before
+	/// there were currencies, unless it is negative or too large for them.
+	pub fn to_u32(self) -> Option<u32> {
+		u32::try_from(self.amount).ok()
+	}
+
+	/// The amount with thousands separators and the currency's decimals, e.g.
+	/// `1,250` yen or `-12.50` euros, without the currency.
+	pub fn formatted(self) -> String {
+		let scale = 10_u64.pow(self.currency.minor_units());
+		let digits = (self.amount.unsigned_abs() / scale).to_string();
+		let mut formatted = String::from(if self.amount < 0 { "-" } else { "" });
+		for (i, digit) in digits.chars().enumerate() {
+			if i > 0 && (digits.len() - i).is_multiple_of(3) {
+				formatted.push(',');
+			}
+			formatted.push(digit);
+		}
+		if scale > 1 {
+			let width = self.currency.minor_units() as usize;
+			formatted.push_str(&format!(".{:0width$}", self.amount.unsigned_abs() % scale, width = width));
+		}
+		formatted
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{Currency, CurrencyMismatch, Money, MoneyError};
+
+	#[test]
+	fn amounts_keep_their_currency()
+	{
+		let eur = Currency::parse("EUR").unwrap();
+		let yen = |amount| Money::new(amount, Currency::JPY);
+		assert_eq!(yen(1000).checked_add(yen(250)), Ok(yen(1250)));
+		assert_eq!(yen(1000).checked_add(Money::new(250, eur)), Err(MoneyError::Currency(CurrencyMismatch(Currency::JPY, eur))));
+		assert_eq!(yen(i64::MAX).checked_add(yen(1)), Err(MoneyError::Overflow(Currency::JPY)));
+		assert_eq!(yen(i64::MIN).checked_sub(yen(1)), Err(MoneyError::Overflow(Currency::JPY)));
+		assert_eq!(yen(i64::MAX / 2 + 1).times(2), Err(MoneyError::Overflow(Currency::JPY)));
+		assert_eq!(yen(1250).times(3), Ok(yen(3750)));
+		assert_eq!(Currency::parse("eur"), None);
+		assert_eq!(Currency::parse("EURO"), None);
+		assert_eq!(Currency::parse("ABC"), None);
+		assert_eq!(Currency::parse("XAU"), None);
+
+		assert_eq!(yen(1999).percent(10), yen(199));
+		assert_eq!(yen(-1999).percent(10), yen(-199));
+		assert_eq!(Money::new(1999, eur).percent(10), Money::new(200, eur));
+		assert_eq!(Money::new(1994, eur).percent(10), Money::new(199, eur));
+		assert_eq!(Money::new(-1995, eur).percent(10), Money::new(-200, eur));
+		assert_eq!(yen(-1548).formatted(), "-1,548");
+		assert_eq!(Money::new(123456, eur).formatted(), "1,234.56");
+		assert_eq!(Money::new(-5, eur).formatted(), "-0.05");
+		assert_eq!(Money::new(1500, Currency::parse("KWD").unwrap()).formatted(), "1.500");
+		assert_eq!((yen(500).yen(), Money::new(500, eur).yen(), yen(-500).yen()), (Some(500), None, None));
+		assert_eq!(Money::new(1 << 32, eur).to_u32(), None);
+	}
+}
@@ -0,0 +1,301 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use log::error;
+use rocket::fairing::AdHoc;
+use rocket::http::ContentType;
+use rocket::response::Redirect;
+use rocket::serde::json::Json;
+use rocket::State;
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+use utoipa_swagger_ui::Config;
+use crate::{billing, graphql, handlers, models, money, settings};
+
+/// The OpenAPI 3 document of every route in `handlers::stage()`, built from
+/// their `#[utoipa::path]` annotations and the models.
+#[derive(OpenApi)]
+#[openapi(
+	servers((url = "/api/v1")),
+	paths(
+		handlers::get_item,
+		handlers::get_items,
+		handlers::new_item,
+		handlers::update_item,
+		handlers::get_item_translations,
+		handlers::update_item_translation,
+		handlers::new_order,
+		handlers::get_orders,
+		handlers::get_order,
+		handlers::remove_order,
+		handlers::new_session,
+		handlers::get_active_sessions,
+		handlers::get_sessions,
+		handlers::get_active_session,
+		handlers::end_session,
+		handlers::remove_item,
+		handlers::get_discounts,
+		handlers::new_discount,
+		handlers::get_bill,
+		handlers::get_guest_token,
+		handlers::get_table_guest_orders,
+		handlers::approve_guest_order,
+		handlers::reject_guest_order,
+		handlers::get_guest_session,
+		handlers::get_guest_menu,
+		handlers::new_guest_order,
+		handlers::get_guest_orders,
+		handlers::get_guest_bill,
+		handlers::new_signal,
+		handlers::get_signals,
+		handlers::acknowledge_signal,
+		handlers::get_floor,
+		handlers::new_guest_signal,
+		handlers::get_guest_signals,
+		handlers::apply_discount,
+		handlers::remove_discount,
+		handlers::new_payment,
+		handlers::clock_in,
+		handlers::clock_out,
+		handlers::tip_report,
+		handlers::signal_report,
+		handlers::new_location,
+		handlers::location_report,
+		handlers::get_settings,
+		handlers::update_settings,
+		handlers::get_session,
+		handlers::get_session_orders,
+		handlers::get_session_order,
+		handlers::get_receipt,
+		handlers::reprint_tickets,
+		handlers::fire_order,
+		handlers::set_item_course,
+		handlers::fire_course,
+		handlers::hold_course,
+		handlers::get_kitchen_queue,
+		handlers::get_events,
+		handlers::new_webhook,
+		handlers::get_webhooks,
+		handlers::remove_webhook,
+		handlers::get_webhook_deliveries,
+		handlers::get_dead_letters,
+		handlers::retry_dead_letter,
+		graphql::graphql,
+		graphql::graphql_stream
+	),
+	components(schemas(
+		models::Failure,
+		models::Station,
+		models::Location,
+		models::LocationReport,
+		settings::RestaurantSettings,
+		settings::OpeningHours,
+		settings::Day,
+		billing::BillingConfig,
+		money::Money,
+		models::Item,
+		models::ItemTranslation,
+		models::ItemPage,
+		models::TableSession,
+		models::SessionPage,
+		models::Order,
+		models::OrderPage,
+		models::OrderItem,
+		models::GuestToken,
+		models::OpenedSession,
+		models::GuestOrderStatus,
+		models::GuestOrder,
+		models::Course,
+		models::Role,
+		models::DiscountKind,
+		models::DiscountScope,
+		models::Discount,
+		models::DiscountRequest,
+		models::AppliedDiscount,
+		models::BillLine,
+		models::LineAmounts,
+		models::Bill,
+		models::BillAmounts,
+		models::Payment,
+		models::Shift,
+		models::TipShare,
+		models::TipReport,
+		models::SignalKind,
+		models::Signal,
+		models::FloorTable,
+		models::SignalShift,
+		models::SignalReport,
+		models::TicketKind,
+		models::TicketLine,
+		models::KitchenTicket,
+		models::QueueItem,
+		models::ItemStatus,
+		models::EventData,
+		models::Event,
+		models::Webhook,
+		models::WebhookDelivery,
+		models::DeadLetter
+	)),
+	modifiers(&StaffToken)
+)]
+pub struct ApiDoc;
+
+/// Staff authenticate with `Authorization: Bearer <token>`, and guests the
+/// same way with the token of their table.
+struct StaffToken;
+
+impl Modify for StaffToken {
+	fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+		if let Some(components) = openapi.components.as_mut() {
+			components.add_security_scheme("staff_token", SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()));
+			components.add_security_scheme("table_token", SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()));
+		}
+	}
+}
+
+#[rocket::get("/openapi.json")]
+pub fn get_openapi(openapi: &State<utoipa::openapi::OpenApi>) -> Json<utoipa::openapi::OpenApi> {
+	Json(openapi.inner().clone())
+}
+
+/// The Swagger UI loads its files relative to its page.
+#[rocket::get("/docs")]
+pub fn get_docs_index() -> Redirect {
+	Redirect::to(format!("{}/docs/index.html", handlers::API_BASE))
+}
+
+/// The Swagger UI, bundled into the binary, showing `openapi.json`.
+#[rocket::get("/docs/<file..>", rank = 2)]
+pub fn get_docs(config: &State<Arc<Config<'static>>>, file: PathBuf) -> Option<(ContentType, Vec<u8>)> {
+	match utoipa_swagger_ui::serve(&file.to_string_lossy(), config.inner().clone()) {
+		Ok(file) => file.map(|file| (
+			ContentType::parse_flexible(&file.content_type).unwrap_or(ContentType::Binary),
+			file.bytes.into_owned()
+		)),
+		Err(e) => {
+			error!("Unable to serve API docs: {}", e);
+			None
+		}
+	}
+}
+
+pub fn stage() -> AdHoc {
+	AdHoc::on_ignite("OpenAPI", |rocket| async {
+		rocket.manage(ApiDoc::openapi())
+		.manage(Arc::new(Config::from(format!("{}/openapi.json", handlers::API_BASE))))
+		.mount(handlers::API_BASE, rocket::routes![get_openapi, get_docs_index, get_docs])
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::{BTreeMap, BTreeSet};
+	use rocket::serde::json::Value;
+	use utoipa::OpenApi;
+	use crate::handlers;
+
+	/// Statuses a route answers with because of a guard it takes, which its
+	/// annotation has to document.
+	const GUARD_STATUSES: [(&str, &[&str]); 5] = [
+		("etag::IfMatch", &["412", "428"]),
+		("auth::Manager", &["403"]),
+		("auth::Owner", &["403"]),
+		("idempotency::IdempotencyKey", &["409", "422"]),
+		("guests::Guest", &["401", "429"]),
+	];
+
+	/// Whether a handler's signature takes the guard, and not just a type
+	/// starting with its name.
+	fn takes(signature: &str, guard: &str) -> bool {
+		signature.match_indices(guard).any(|(i, _)| !signature[i + guard.len()..].starts_with(|c: char| c.is_alphanumeric() || c == '_'))
+	}
+
+	/// The modules holding the API's handlers.
+	const SOURCES: [&str; 2] = [include_str!("handlers.rs"), include_str!("graphql.rs")];
+
+	/// The signature and body of a handler.
+	fn handler_source(name: &str) -> &'static str {
+		let signature = format!("pub async fn {}(", name);
+		let (source, start) = SOURCES.iter()
+			.find_map(|source| source.find(&signature).map(|start| (source, start)))
+			.unwrap_or_else(|| panic!("handler {} not found", name));
+		let end = source[start..].find("\n}\n").map_or(source.len(), |end| start + end);
+		&source[start..end]
+	}
+
+	/// Fails when a route is added, removed or moved without updating its
+	/// `#[utoipa::path]` annotation and `ApiDoc`, or the other way around, or
+	/// when its documented statuses miss the ones its guards answer with.
+	#[rocket::async_test]
+	async fn spec_matches_routes()
+	{
+		let database = crate::testing::TestDatabase::new();
+		let rocket = crate::testing::rocket(&database, |rocket| rocket)
+		.ignite().await.expect("ignited rocket");
+		let routes: BTreeMap<(String, String), String> = rocket.routes()
+			.filter(|route| route.uri.base() == handlers::API_BASE)
+			.filter(|route| !route.name.as_deref().is_some_and(|name| name.starts_with("get_openapi") || name.starts_with("get_docs")))
+			.map(|route| {
+				let path = route.uri.path().to_string();
+				let path = path[handlers::API_BASE.len()..]
+					.split('/')
+					.map(|segment| match segment.strip_prefix('<').and_then(|segment| segment.strip_suffix('>')) {
+						Some(param) => format!("{{{}}}", param.trim_end_matches("..")),
+						None => String::from(segment),
+					})
+					.collect::<Vec<String>>()
+					.join("/");
+				((route.method.as_str().to_lowercase(), path), route.name.as_deref().unwrap_or_default().to_string())
+			})
+			.collect();
+
+		let spec = rocket::serde::json::to_value(super::ApiDoc::openapi()).unwrap();
+		let documented: BTreeSet<(String, String)> = spec["paths"].as_object().unwrap().iter()
+			.flat_map(|(path, item): (&String, &Value)| item.as_object().unwrap().keys()
+				.filter(|method| ["get", "post", "put", "delete", "patch"].contains(&method.as_str()))
+				.map(move |method| (method.clone(), path.clone())))
+			.collect();
+		let routed: BTreeSet<(String, String)> = routes.keys().cloned().collect();
+
+		assert_eq!(routed.difference(&documented).collect::<Vec<_>>(), Vec::<&(String, String)>::new(), "routes missing from the spec");
+		assert_eq!(documented.difference(&routed).collect::<Vec<_>>(), Vec::<&(String, String)>::new(), "spec paths without a route");
+
+		let mut undocumented = Vec::new();
+		for ((method, path), name) in &routes {
+			let handler = handler_source(name);
+			let signature = &handler[..handler.find('{').unwrap_or(handler.len())];
+			let responses = &spec["paths"][path][method]["responses"];
+			for (guard, statuses) in GUARD_STATUSES {
+				if takes(signature, guard) {
+					undocumented.extend(statuses.iter().filter(|status| responses[**status].is_null()).map(|status| format!("{} {} {}", method, path, status)));
+				}
+			}
+			// Paging through a list can be asked for wrongly.
+			if handler.contains("PageRequest::new") && responses["422"].is_null() {
+				undocumented.push(format!("{} {} 422", method, path));
+			}
+			// Answering `failed` is a 200 too.
+			if handler.contains("String::from(\"failed\")") && !responses["200"]["description"].as_str().unwrap_or_default().contains("`failed`") {
+				undocumented.push(format!("{} {} 200 `failed`", method, path));
+			}
+		}
+		assert_eq!(undocumented, Vec::<String>::new(), "statuses missing from the spec");
+	}
+
+	#[test]
+	fn spec_and_docs_are_served()
+	{
+		let client = crate::testing::client();
+
+		let response = client.get("/api/v1/openapi.json").dispatch();
+		let spec = response.into_json::<Value>().expect("OpenAPI document");
+		assert!(spec["openapi"].as_str().unwrap().starts_with("3."));
+		assert!(spec["components"]["schemas"]["Order"].is_object());
+
+		let response = client.get("/api/v1/docs").dispatch();
+		assert_eq!(response.headers().get_one("Location"), Some("/api/v1/docs/index.html"));
+		let response = client.get("/api/v1/docs/index.html").dispatch();
+		assert_eq!(response.content_type(), Some(rocket::http::ContentType::HTML));
+		let response = client.get("/api/v1/docs/swagger-initializer.js").dispatch();
+		assert!(response.into_string().unwrap().contains("/api/v1/openapi.json"));
+	}
+}
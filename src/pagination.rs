@@ -0,0 +1,208 @@
+use rocket::http::Header;
+use rocket::request::Request;
+use rocket::response::{self, Responder};
+use rocket::serde::json::Json;
+use rocket::serde::Serialize;
+use crate::i18n::{message, Message};
+use crate::models;
+
+/// Page size when a request gives no `limit`.
+pub const DEFAULT_LIMIT: u32 = 50;
+pub const MAX_LIMIT: u32 = 200;
+
+/// Sort columns holding integers. Cursors keep the sort value as text, so
+/// the value of a cursor sorting by one of them has to be a number.
+pub const INTEGER_COLUMNS: [&str; 4] = ["id", "preparation_time", "price", "customers"];
+
+/// A field a collection can be sorted by, and the column holding it. The
+/// first of a collection's fields is its default sort.
+pub type SortField = (&'static str, &'static str);
+
+/// Where a page starts and how the collection is ordered, from the `limit`,
+/// `cursor` and `sort` query parameters. `sort` names a field, prefixed with
+/// `-` to sort descending; rows with the same value are ordered by id.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageRequest {
+	pub limit: u32,
+	pub sort: String,
+	pub column: &'static str,
+	pub descending: bool,
+	/// The sort value and id of the last row on the previous page.
+	pub after: Option<(String, i64)>
+}
+
+/// Rows that can be paged through.
+pub trait Keyed {
+	fn key(&self) -> i64;
+	/// The value of a sort field, as it compares in the database.
+	fn sort_value(&self, field: &str) -> String;
+}
+
+impl PageRequest {
+	pub fn new(limit: Option<u32>, cursor: Option<&str>, sort: Option<&str>, fields: &[SortField]) -> Result<Self, Message> {
+		let sort = sort.unwrap_or(fields[0].0);
+		let (field, descending) = match sort.strip_prefix('-') {
+			Some(field) => (field, true),
+			None => (sort, false),
+		};
+		let column = match fields.iter().find(|(name, _)| *name == field) {
+			Some((_, column)) => *column,
+			None => return Err(message!("Unable to sort by '{}', expected one of: {}", field,
+				fields.iter().map(|(name, _)| *name).collect::<Vec<&str>>().join(", "))),
+		};
+		let after = match cursor {
+			Some(cursor) => match decode(cursor, sort) {
+				Some((value, id)) if !INTEGER_COLUMNS.contains(&column) || value.parse::<i64>().is_ok() => Some((value, id)),
+				_ => return Err(message!("Invalid cursor '{}' for sort '{}'", cursor, sort)),
+			},
+			None => None,
+		};
+		Ok(PageRequest {
+			limit: limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT),
+			sort: String::from(sort),
+			column,
+			descending,
+			after
+		})
+	}
+
+	/// The condition skipping rows up to the cursor, with the cursor's sort
+	/// value as parameter `$first` and its id as the next one. Empty on the
+	/// first page.
+	pub fn after_clause(&self, id_column: &str, first: usize) -> String {
+		let op = if self.descending { "<" } else { ">" };
+		match self.after {
+			Some(_) => format!("AND ({col} {op} ${value} OR ({col} = ${value} AND {id} {op} ${id_param}))", col = self.column, op = op, id = id_column, value = first, id_param = first + 1),
+			None => String::new(),
+		}
+	}
+
+	pub fn order_by(&self, id_column: &str) -> String {
+		let direction = if self.descending { "DESC" } else { "ASC" };
+		format!("{col} {dir}, {id} {dir}", col = self.column, dir = direction, id = id_column)
+	}
+
+	/// Rows to fetch: one more than the page holds, to tell if there is a
+	/// next page.
+	pub fn fetch_limit(&self) -> u32 {
+		self.limit + 1
+	}
+
+	/// Cuts the rows fetched with `fetch_limit` down to a page.
+	pub fn page<T: Keyed>(&self, mut rows: Vec<T>) -> models::Page<T> {
+		let next_cursor = if rows.len() > self.limit as usize {
+			rows.truncate(self.limit as usize);
+			let field = self.sort.trim_start_matches('-');
+			rows.last().map(|row| encode(&self.sort, &row.sort_value(field), row.key()))
+		} else {
+			None
+		};
+		models::Page { data: rows, next_cursor }
+	}
+}
+
+/// Cursors are opaque to clients: the sort they were made for, then the last
+/// row's id and sort value, hex encoded so they are safe in a URL.
+fn encode(sort: &str, value: &str, id: i64) -> String {
+	hex::encode(format!("{}\n{}\n{}", sort, id, value))
+}
+
+fn decode(cursor: &str, sort: &str) -> Option<(String, i64)> {
+	let cursor = String::from_utf8(hex::decode(cursor).ok()?).ok()?;
+	let mut parts = cursor.splitn(3, '\n');
+	if parts.next()? != sort {
+		return None;
+	}
+	let id = parts.next()?.parse().ok()?;
+	Some((String::from(parts.next()?), id))
+}
+
+/// A page of a collection, with a `Link` header pointing at the next one.
+pub struct Paginated<T>(pub models::Page<T>);
+
+impl<'r, T: Serialize> Responder<'r, 'static> for Paginated<T> {
+	fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+		let link = self.0.next_cursor.as_ref().map(|cursor| {
+			let mut query: Vec<String> = request.uri().query()
+				.map(|query| query.raw_segments()
+					.filter(|segment| !segment.as_str().starts_with("cursor="))
+					.map(|segment| segment.to_string())
+					.collect())
+				.unwrap_or_default();
+			query.push(format!("cursor={}", cursor));
+			format!("<{}?{}>; rel=\"next\"", request.uri().path(), query.join("&"))
+		});
+		let response = Json(self.0).respond_to(request)?;
+		match link {
+			Some(link) => rocket::Response::build_from(response).header(Header::new("Link", link)).ok(),
+			None => Ok(response),
+		}
+	}
+}
+
+impl Keyed for models::Item {
+	fn key(&self) -> i64 {
+		self.id
+	}
+
+	fn sort_value(&self, field: &str) -> String {
+		match field {
+			"name" => self.name.clone(),
+			"price" => self.price.amount.to_string(),
+			"preparation_time" => self.preparation_time.to_string(),
+			_ => self.id.to_string(),
+		}
+	}
+}
+
+impl Keyed for models::TableSession {
+	fn key(&self) -> i64 {
+		self.id
+	}
+
+	fn sort_value(&self, field: &str) -> String {
+		match field {
+			"session_start" => self.session_start.clone(),
+			"customers" => self.customers.to_string(),
+			_ => self.id.to_string(),
+		}
+	}
+}
+
+impl Keyed for models::Order {
+	fn key(&self) -> i64 {
+		self.id.unwrap_or_default()
+	}
+
+	fn sort_value(&self, field: &str) -> String {
+		match field {
+			"timestamp" => self.timestamp.clone(),
+			_ => self.key().to_string(),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::PageRequest;
+
+	const FIELDS: [super::SortField; 2] = [("id", "id"), ("price", "price")];
+
+	#[test]
+	fn cursors_continue_the_same_sort()
+	{
+		let page = PageRequest::new(Some(2), None, Some("-price"), &FIELDS).unwrap();
+		let items = (1..=3).map(|id| crate::models::Item { id, price: crate::money::Money::new(100 * id, Default::default()), ..Default::default() }).collect();
+		let page = page.page(items);
+		assert_eq!(page.data.len(), 2);
+		let cursor = page.next_cursor.expect("next cursor");
+
+		let next = PageRequest::new(Some(2), Some(&cursor), Some("-price"), &FIELDS).unwrap();
+		assert_eq!(next.after, Some((String::from("200"), 2)));
+		assert_eq!(next.after_clause("id", 4), "AND (price < $4 OR (price = $4 AND id < $5))");
+		assert!(PageRequest::new(Some(2), Some(&cursor), Some("price"), &FIELDS).is_err());
+		let forged = super::encode("-price", "cheap", 2);
+		assert!(PageRequest::new(Some(2), Some(&forged), Some("-price"), &FIELDS).is_err());
+		assert!(PageRequest::new(None, None, Some("name"), &FIELDS).is_err());
+	}
+}
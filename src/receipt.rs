@@ -0,0 +1,295 @@
+use std::io::{self, Write};
+use crate::models;
+use crate::money::{Currency, Money};
+
+const ESC: u8 = 0x1b;
+const GS: u8 = 0x1d;
+
+/// Receipt layouts served by `GET /sessions/<id>/receipt?format=...`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+	Text,
+	EscPos,
+	Html
+}
+
+impl Format {
+	pub fn parse(format: &str) -> Option<Format> {
+		match format {
+			"text" => Some(Format::Text),
+			"escpos" => Some(Format::EscPos),
+			"html" => Some(Format::Html),
+			_ => None,
+		}
+	}
+}
+
+/// Formats an amount of the bill with thousands separators and the decimals
+/// of its currency, e.g. `-1,548` yen.
+fn money(bill: &models::Bill, amount: i64) -> String {
+	Money::new(amount, bill.currency).formatted()
+}
+
+/// A row with `label` on the left and `value` right-aligned, truncating the
+/// label so the row never exceeds `width` characters.
+fn row(label: &str, value: &str, width: usize) -> String {
+	let room = width.saturating_sub(value.chars().count() + 1);
+	let label: String = label.chars().take(room).collect();
+	let padding = width - label.chars().count() - value.chars().count();
+	format!("{}{}{}", label, " ".repeat(padding), value)
+}
+
+fn center(text: &str, width: usize) -> String {
+	let text: String = text.chars().take(width).collect();
+	let padding = (width - text.chars().count()) / 2;
+	format!("{}{}", " ".repeat(padding), text)
+}
+
+/// The body of the receipt as rows of at most `width` characters, shared by
+/// the plain-text and ESC/POS layouts.
+fn body_rows(session: &models::TableSession, bill: &models::Bill, width: usize) -> Vec<String> {
+	let rule = "-".repeat(width);
+	let mut rows = vec![
+		row(&format!("Table #{}", session.table_nr), &format!("Session {}", session.id), width),
+		row("Opened", &session.session_start, width),
+		row("Closed", &session.session_end, width),
+		rule.clone(),
+	];
+	for line in &bill.lines {
+		rows.push(row(&format!("{} x {}", line.amount, line.name), &money(bill, line.amounts.line_total.amount), width));
+		if line.amounts.discount.amount > 0 {
+			rows.push(row("    Discount", &money(bill, -line.amounts.discount.amount), width));
+		}
+	}
+	rows.push(rule.clone());
+	rows.push(row("Subtotal", &money(bill, bill.amounts.subtotal.amount), width));
+	let discounts = bill.amounts.line_discounts.amount + bill.amounts.bill_discounts.amount;
+	if discounts > 0 {
+		rows.push(row("Discounts", &money(bill, -discounts), width));
+	}
+	for discount in bill.discounts.iter().filter(|d| d.scope == models::DiscountScope::Bill) {
+		rows.push(row(&format!("  {}", discount.name), "", width));
+	}
+	if bill.amounts.service_charge.amount > 0 {
+		rows.push(row(&format!("Service charge ({}%)", bill.service_charge_percent), &money(bill, bill.amounts.service_charge.amount), width));
+	}
+	rows.push(row(&format!("Tax ({}%)", bill.tax_rate_percent), &money(bill, bill.amounts.tax.amount), width));
+	rows.push(row("TOTAL", &money(bill, bill.amounts.total.amount), width));
+	rows.push(row("Paid", &money(bill, bill.amounts.paid.amount), width));
+	if bill.amounts.tips.amount > 0 {
+		rows.push(row("Tip", &money(bill, bill.amounts.tips.amount), width));
+	}
+	if bill.amounts.balance_due.amount > 0 {
+		rows.push(row("Balance due", &money(bill, bill.amounts.balance_due.amount), width));
+	}
+	rows.push(rule);
+	rows.push(row(&format!("All amounts in {}", bill.currency), "", width));
+	rows
+}
+
+/// Plain-text receipt for 42 or 48 column printers and terminals.
+pub fn render_text(session: &models::TableSession, bill: &models::Bill, width: usize) -> String {
+	let mut text = center("RECEIPT", width);
+	text.push('\n');
+	for row in body_rows(session, bill, width) {
+		text.push_str(row.trim_end());
+		text.push('\n');
+	}
+	text.push_str(&center("Thank you for dining with us", width));
+	text.push('\n');
+	text
+}
+
+/// Writes the receipt as an ESC/POS command stream, ending with a paper cut.
+/// Thermal printers only have an ASCII code page by default, so anything
+/// else is printed as `?`.
+pub fn write_escpos<W: Write>(session: &models::TableSession, bill: &models::Bill, width: usize, out: &mut W) -> io::Result<()> {
+	let ascii = |text: &str| -> Vec<u8> {
+		text.chars().map(|c| if c.is_ascii() { c as u8 } else { b'?' }).collect()
+	};
+	out.write_all(&[ESC, b'@'])?;
+	out.write_all(&[ESC, b'a', 1, ESC, b'E', 1, GS, b'!', 0x11])?;
+	out.write_all(b"RECEIPT\n")?;
+	out.write_all(&[GS, b'!', 0, ESC, b'E', 0, ESC, b'a', 0])?;
+	for row in body_rows(session, bill, width) {
+		out.write_all(&ascii(row.trim_end()))?;
+		out.write_all(b"\n")?;
+	}
+	out.write_all(&[ESC, b'a', 1])?;
+	out.write_all(b"Thank you for dining with us\n")?;
+	out.write_all(&[ESC, b'd', 4, GS, b'V', 66, 0])?;
+	out.flush()
+}
+
+pub fn render_escpos(session: &models::TableSession, bill: &models::Bill, width: usize) -> Vec<u8> {
+	let mut bytes = Vec::new();
+	write_escpos(session, bill, width, &mut bytes).expect("writing to a Vec cannot fail");
+	bytes
+}
+
+fn escape_html(text: &str) -> String {
+	text.replace('&', "&amp;")
+		.replace('<', "&lt;")
+		.replace('>', "&gt;")
+		.replace('"', "&quot;")
+		.replace('\'', "&#39;")
+}
+
+/// Printable HTML receipt, styled to fit a narrow receipt roll when printed
+/// from a browser.
+pub fn render_html(session: &models::TableSession, bill: &models::Bill) -> String {
+	let symbol = match bill.currency {
+		Currency::JPY => String::from("&yen;"),
+		currency => format!("{} ", currency)
+	};
+	let money_row = |label: &str, amount: i64| format!("<tr><td>{}</td><td class=\"amount\">{}{}</td></tr>\n", escape_html(label), symbol, money(bill, amount));
+	let mut rows = String::new();
+	for line in &bill.lines {
+		rows.push_str(&money_row(&format!("{} x {}", line.amount, line.name), line.amounts.line_total.amount));
+		if line.amounts.discount.amount > 0 {
+			rows.push_str(&money_row("Discount", -line.amounts.discount.amount));
+		}
+	}
+	let mut totals = money_row("Subtotal", bill.amounts.subtotal.amount);
+	let discounts = bill.amounts.line_discounts.amount + bill.amounts.bill_discounts.amount;
+	if discounts > 0 {
+		totals.push_str(&money_row("Discounts", -discounts));
+	}
+	if bill.amounts.service_charge.amount > 0 {
+		totals.push_str(&money_row(&format!("Service charge ({}%)", bill.service_charge_percent), bill.amounts.service_charge.amount));
+	}
+	totals.push_str(&money_row(&format!("Tax ({}%)", bill.tax_rate_percent), bill.amounts.tax.amount));
+	totals.push_str(&money_row("Total", bill.amounts.total.amount).replace("<tr>", "<tr class=\"total\">"));
+	totals.push_str(&money_row("Paid", bill.amounts.paid.amount));
+	if bill.amounts.tips.amount > 0 {
+		totals.push_str(&money_row("Tip", bill.amounts.tips.amount));
+	}
+	if bill.amounts.balance_due.amount > 0 {
+		totals.push_str(&money_row("Balance due", bill.amounts.balance_due.amount));
+	}
+
+	format!(r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Receipt - Table #{table_nr}</title>
+<style>
+body {{ font-family: monospace; max-width: 80mm; margin: 0 auto; }}
+h1 {{ text-align: center; font-size: 1.4em; }}
+table {{ width: 100%; border-collapse: collapse; }}
+td.amount {{ text-align: right; white-space: nowrap; }}
+tbody {{ border-top: 1px dashed black; border-bottom: 1px dashed black; }}
+tr.total {{ font-weight: bold; }}
+p {{ text-align: center; }}
+@media print {{ @page {{ margin: 0; }} }}
+</style>
+</head>
+<body>
+<h1>Receipt</h1>
+<table>
+<tr><td>Table #{table_nr}</td><td class="amount">Session {id}</td></tr>
+<tr><td>Opened</td><td class="amount">{start}</td></tr>
+<tr><td>Closed</td><td class="amount">{end}</td></tr>
+<tbody>
+{rows}</tbody>
+{totals}</table>
+<p>Thank you for dining with us</p>
+</body>
+</html>
+"#,
+		table_nr = session.table_nr,
+		id = session.id,
+		start = escape_html(&session.session_start),
+		end = escape_html(&session.session_end),
+		rows = rows,
+		totals = totals)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::Read;
+	use std::net::{TcpListener, TcpStream};
+	use super::models::{Bill, BillAmounts, BillLine, LineAmounts, TableSession};
+	use super::{Currency, Money};
+
+	fn yen(amount: i64) -> Money {
+		Money::new(amount, Currency::JPY)
+	}
+
+	fn receipt() -> (TableSession, Bill) {
+		let session = TableSession {
+			id: 7,
+			table_nr: 12,
+			customers: 2,
+			session_start: String::from("2022-05-16 18:00:00"),
+			session_end: String::from("2022-05-16 19:30:00"),
+			active: false,
+			version: 2,
+			location_id: 1
+		};
+		let bill = Bill {
+			table_session_id: 7,
+			lines: vec![BillLine {
+				order_id: 1,
+				item_id: 9,
+				name: String::from("Fish and cod crumble with an unreasonably long name"),
+				amount: 2,
+				price: Money::new(2893, Currency::JPY),
+				price_yen: Some(2893),
+				line_total: 5786,
+				discount: 0,
+				amounts: LineAmounts { line_total: yen(5786), discount: yen(0) }
+			}],
+			subtotal: 5786,
+			tax_rate_percent: 10,
+			tax: 578,
+			total: 6364,
+			paid: 6364,
+			amounts: BillAmounts { subtotal: yen(5786), tax: yen(578), total: yen(6364), paid: yen(6364), ..Default::default() },
+			..Default::default()
+		};
+		(session, bill)
+	}
+
+	#[test]
+	fn text_receipt_fits_width()
+	{
+		let (session, bill) = receipt();
+		for width in [42, 48] {
+			let text = super::render_text(&session, &bill, width);
+			assert!(text.lines().all(|line| line.chars().count() <= width));
+			assert!(text.contains("6,364"));
+			assert!(text.contains("All amounts in JPY"));
+		}
+	}
+
+	#[test]
+	fn escpos_receipt_can_be_sent_to_a_printer()
+	{
+		let (session, bill) = receipt();
+		let printer = TcpListener::bind("127.0.0.1:0").expect("printer stand-in");
+		let mut stream = TcpStream::connect(printer.local_addr().unwrap()).expect("connection");
+		super::write_escpos(&session, &bill, 42, &mut stream).expect("written receipt");
+		drop(stream);
+
+		let mut received = Vec::new();
+		printer.accept().unwrap().0.read_to_end(&mut received).unwrap();
+		assert_eq!(received, super::render_escpos(&session, &bill, 42));
+		assert!(received.starts_with(&[0x1b, b'@']));
+		assert!(received.ends_with(&[0x1d, b'V', 66, 0]));
+	}
+
+	#[test]
+	fn html_receipt_escapes_names()
+	{
+		let (session, mut bill) = receipt();
+		bill.lines[0].name = String::from("<b>Fish</b> & chips");
+		let html = super::render_html(&session, &bill);
+		assert!(html.contains("&lt;b&gt;Fish&lt;/b&gt; &amp; chips"));
+		assert!(html.contains("&yen;6,364"));
+
+		bill.currency = Currency::parse("EUR").unwrap();
+		let html = super::render_html(&session, &bill);
+		assert!(html.contains("EUR 63.64"));
+	}
+}
@@ -0,0 +1,799 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use rocket::fairing::AdHoc;
+use rocket_db_pools::{sqlx, Database};
+use crate::db;
+use crate::models;
+use crate::pagination::PageRequest;
+
+type Result<T> = std::result::Result<T, sqlx::Error>;
+
+/// The repository handlers and services use, managed by Rocket.
+pub type Repository = Arc<dyn RestaurantRepository>;
+
+/// Storage of the menu, table sessions, orders and what goes on their bills,
+/// discounts and payments included, along with the kitchen's courses and the
+/// webhooks events are posted to.
+/// Menus, tables, discounts and webhooks belong to a location; sessions and
+/// orders are found by id anywhere, callers check the session's location.
+#[rocket::async_trait]
+pub trait RestaurantRepository: Send + Sync {
+	async fn item(&self, location_id: i64, item_id: i64) -> Result<Option<models::Item>>;
+
+	async fn create_item(&self, location_id: i64, item: &models::Item) -> Result<models::Item>;
+
+	/// Changes an item of the location's menu, or returns `None` when the
+	/// menu has no such item.
+	async fn update_item(&self, location_id: i64, item_id: i64, item: &models::Item) -> Result<Option<models::Item>>;
+
+	async fn item_translations(&self, item_id: i64) -> Result<Vec<models::ItemTranslation>>;
+
+	/// Names an item in the translation's locale, replacing the name it had.
+	async fn save_item_translation(&self, item_id: i64, translation: &models::ItemTranslation) -> Result<()>;
+
+	/// A page of the menu, optionally within a price range.
+	async fn items(&self, location_id: i64, page: &PageRequest, min_price: Option<u32>, max_price: Option<u32>) -> Result<Vec<models::Item>>;
+
+	/// Names of the items in the first of `locales` each is translated into.
+	async fn item_names(&self, item_ids: &[i64], locales: &[&str]) -> Result<HashMap<i64, String>>;
+
+	async fn session(&self, table_session_id: i64) -> Result<Option<models::TableSession>>;
+
+	async fn active_session(&self, location_id: i64, table_nr: u8) -> Result<Option<models::TableSession>>;
+
+	async fn active_sessions(&self, location_id: i64) -> Result<Vec<models::TableSession>>;
+
+	/// A page of a table's sessions started from `start` and before `end`.
+	async fn sessions(&self, location_id: i64, table_nr: u8, page: &PageRequest, start: Option<&str>, end: Option<&str>) -> Result<Vec<models::TableSession>>;
+
+	/// Opens a session, or returns `None` when the table already has one.
+	async fn open_session(&self, location_id: i64, table_nr: u8, customers: u8) -> Result<Option<models::TableSession>>;
+
+	/// Ends a session if it is still at `version`.
+	async fn close_session(&self, table_session_id: i64, version: i64) -> Result<bool>;
+
+	/// Items of a course that was not fired yet are held.
+	async fn create_order(&self, table_session_id: i64, order_items: &[models::OrderItem], staff_id: Option<i64>) -> Result<models::Order>;
+
+	async fn order(&self, table_session_id: i64, order_id: i64) -> Result<Option<models::Order>>;
+
+	/// Deletes an order if it is still at `version`.
+	async fn delete_order(&self, table_session_id: i64, order_id: i64, version: i64) -> Result<bool>;
+
+	/// Removes an item from an order at `version`. `Some(false)` when the
+	/// item was not in the order, `None` when the order changed since.
+	async fn delete_order_item(&self, order_id: i64, item_id: i64, version: i64) -> Result<Option<bool>>;
+
+	/// A page of a session's orders, optionally only those with items held or
+	/// with all of them fired.
+	async fn orders(&self, table_session_id: i64, page: &PageRequest, status: Option<models::ItemStatus>) -> Result<Vec<models::Order>>;
+
+	/// The session's items for the kitchen, with their station and course.
+	async fn queue_items(&self, table_session_id: i64) -> Result<Vec<models::QueueItem>>;
+
+	/// The items for the kitchen at every open table of the location.
+	async fn kitchen_queue(&self, location_id: i64) -> Result<Vec<models::QueueItem>>;
+
+	/// Sends the held items of an order to the kitchen.
+	async fn fire_order(&self, order_id: i64) -> Result<()>;
+
+	/// Sends the held items of a course to the kitchen. Items of the course
+	/// ordered afterwards are no longer held.
+	async fn fire_course(&self, table_session_id: i64, course: models::Course) -> Result<()>;
+
+	/// Holds a course again, so items of it ordered from now on are held.
+	async fn hold_course(&self, table_session_id: i64, course: models::Course) -> Result<()>;
+
+	/// Moves a held item of an order at `version` to another course.
+	/// `Some(false)` when the order has no such held item, `None` when the
+	/// order changed since.
+	async fn update_item_course(&self, order_id: i64, item_id: i64, course: models::Course, version: i64) -> Result<Option<bool>>;
+
+	async fn bill_lines(&self, table_session_id: i64) -> Result<Vec<models::BillLine>>;
+
+	async fn discounts(&self, location_id: i64) -> Result<Vec<models::Discount>>;
+
+	async fn discount(&self, location_id: i64, discount_id: i64) -> Result<Option<models::Discount>>;
+
+	async fn create_discount(&self, location_id: i64, discount: &models::Discount) -> Result<models::Discount>;
+
+	async fn applied_discounts(&self, table_session_id: i64) -> Result<Vec<models::AppliedDiscount>>;
+
+	/// Applies a discount to the bill of its session.
+	async fn apply_discount(&self, discount: &models::AppliedDiscount) -> Result<models::AppliedDiscount>;
+
+	/// Takes a discount off a session's bill, or returns false when it was not
+	/// on it.
+	async fn remove_discount(&self, table_session_id: i64, applied_discount_id: i64) -> Result<bool>;
+
+	async fn payments(&self, table_session_id: i64) -> Result<Vec<models::Payment>>;
+
+	async fn create_payment(&self, table_session_id: i64, staff_id: i64, payment: &models::Payment) -> Result<models::Payment>;
+
+	/// Registers a webhook whose payloads are signed with `secret`.
+	async fn create_webhook(&self, location_id: i64, webhook: &models::Webhook, secret: &str) -> Result<models::Webhook>;
+
+	/// The location's active webhooks, without their secrets.
+	async fn webhooks(&self, location_id: i64) -> Result<Vec<models::Webhook>>;
+
+	/// An active webhook, with its secret.
+	async fn webhook(&self, webhook_id: i64) -> Result<Option<models::Webhook>>;
+
+	/// Deactivates one of the location's webhooks, or returns false when it
+	/// has no such active webhook.
+	async fn remove_webhook(&self, location_id: i64, webhook_id: i64) -> Result<bool>;
+
+	async fn webhook_deliveries(&self, location_id: i64, webhook_id: i64) -> Result<Vec<models::WebhookDelivery>>;
+
+	async fn dead_letters(&self, location_id: i64) -> Result<Vec<models::DeadLetter>>;
+
+	/// Removes a dead letter of one of the location's active webhooks, so it
+	/// can be delivered again.
+	async fn take_dead_letter(&self, location_id: i64, dead_letter_id: i64) -> Result<Option<models::DeadLetter>>;
+}
+
+/// The repository on the database, taking a connection from the pool for
+/// each call.
+pub struct DatabaseRepository(pub db::DbPool);
+
+#[rocket::async_trait]
+impl RestaurantRepository for DatabaseRepository {
+	async fn item(&self, location_id: i64, item_id: i64) -> Result<Option<models::Item>> {
+		db::get_item(&mut *self.0.acquire().await?, location_id, item_id).await
+	}
+
+	async fn create_item(&self, location_id: i64, item: &models::Item) -> Result<models::Item> {
+		db::create_item(&mut *self.0.acquire().await?, location_id, item).await?
+			.ok_or(sqlx::Error::RowNotFound)
+	}
+
+	async fn update_item(&self, location_id: i64, item_id: i64, item: &models::Item) -> Result<Option<models::Item>> {
+		db::update_item(&mut *self.0.acquire().await?, location_id, item_id, item).await
+	}
+
+	async fn item_translations(&self, item_id: i64) -> Result<Vec<models::ItemTranslation>> {
+		Ok(db::retrieve_item_translations(&mut *self.0.acquire().await?, item_id).await?.unwrap_or_default())
+	}
+
+	async fn save_item_translation(&self, item_id: i64, translation: &models::ItemTranslation) -> Result<()> {
+		db::save_item_translation(&mut *self.0.acquire().await?, item_id, translation).await
+	}
+
+	async fn items(&self, location_id: i64, page: &PageRequest, min_price: Option<u32>, max_price: Option<u32>) -> Result<Vec<models::Item>> {
+		Ok(db::get_items(&mut *self.0.acquire().await?, location_id, page, min_price, max_price).await?.unwrap_or_default())
+	}
+
+	async fn item_names(&self, item_ids: &[i64], locales: &[&str]) -> Result<HashMap<i64, String>> {
+		Ok(db::retrieve_item_names(&mut *self.0.acquire().await?, item_ids, locales).await?.unwrap_or_default())
+	}
+
+	async fn session(&self, table_session_id: i64) -> Result<Option<models::TableSession>> {
+		db::retrieve_table_session(&mut *self.0.acquire().await?, table_session_id).await
+	}
+
+	async fn active_session(&self, location_id: i64, table_nr: u8) -> Result<Option<models::TableSession>> {
+		db::retrieve_active_table_session(&mut *self.0.acquire().await?, location_id, table_nr).await
+	}
+
+	async fn active_sessions(&self, location_id: i64) -> Result<Vec<models::TableSession>> {
+		Ok(db::retrieve_active_table_sessions(&mut *self.0.acquire().await?, location_id).await?.unwrap_or_default())
+	}
+
+	async fn sessions(&self, location_id: i64, table_nr: u8, page: &PageRequest, start: Option<&str>, end: Option<&str>) -> Result<Vec<models::TableSession>> {
+		Ok(db::retrieve_table_sessions(&mut *self.0.acquire().await?, location_id, table_nr, page, start, end).await?.unwrap_or_default())
+	}
+
+	async fn open_session(&self, location_id: i64, table_nr: u8, customers: u8) -> Result<Option<models::TableSession>> {
+		let session = models::TableSession { customers, ..Default::default() };
+		match db::create_session(&mut *self.0.acquire().await?, location_id, table_nr, &session).await {
+			// Another request opened one first, the unique index catches it.
+			Err(e) if db::is_unique_violation(&e) => Ok(None),
+			Err(e) => Err(e),
+			Ok(session) => session.map(Some).ok_or(sqlx::Error::RowNotFound),
+		}
+	}
+
+	async fn close_session(&self, table_session_id: i64, version: i64) -> Result<bool> {
+		db::deactivate_table_session(&mut *self.0.acquire().await?, table_session_id, version).await
+	}
+
+	async fn create_order(&self, table_session_id: i64, order_items: &[models::OrderItem], staff_id: Option<i64>) -> Result<models::Order> {
+		let order = models::Order { order_items: order_items.to_vec(), ..Default::default() };
+		db::create_order(&mut *self.0.acquire().await?, table_session_id, &order, staff_id).await?
+			.ok_or(sqlx::Error::RowNotFound)
+	}
+
+	async fn order(&self, table_session_id: i64, order_id: i64) -> Result<Option<models::Order>> {
+		db::retrieve_order(&mut *self.0.acquire().await?, table_session_id, order_id).await
+	}
+
+	async fn delete_order(&self, table_session_id: i64, order_id: i64, version: i64) -> Result<bool> {
+		db::delete_order(&mut *self.0.acquire().await?, table_session_id, order_id, version).await
+	}
+
+	async fn delete_order_item(&self, order_id: i64, item_id: i64, version: i64) -> Result<Option<bool>> {
+		db::delete_item_from_order(&mut *self.0.acquire().await?, order_id, item_id, version).await
+	}
+
+	async fn orders(&self, table_session_id: i64, page: &PageRequest, status: Option<models::ItemStatus>) -> Result<Vec<models::Order>> {
+		Ok(db::retrieve_orders(&mut *self.0.acquire().await?, table_session_id, page, status).await?.unwrap_or_default())
+	}
+
+	async fn queue_items(&self, table_session_id: i64) -> Result<Vec<models::QueueItem>> {
+		Ok(db::retrieve_queue_items(&mut *self.0.acquire().await?, None, Some(table_session_id)).await?.unwrap_or_default())
+	}
+
+	async fn kitchen_queue(&self, location_id: i64) -> Result<Vec<models::QueueItem>> {
+		Ok(db::retrieve_queue_items(&mut *self.0.acquire().await?, Some(location_id), None).await?.unwrap_or_default())
+	}
+
+	async fn fire_order(&self, order_id: i64) -> Result<()> {
+		db::fire_order_items(&mut *self.0.acquire().await?, order_id).await?;
+		Ok(())
+	}
+
+	async fn fire_course(&self, table_session_id: i64, course: models::Course) -> Result<()> {
+		db::fire_course(&mut *self.0.acquire().await?, table_session_id, course).await?;
+		Ok(())
+	}
+
+	async fn hold_course(&self, table_session_id: i64, course: models::Course) -> Result<()> {
+		db::hold_course(&mut *self.0.acquire().await?, table_session_id, course).await?;
+		Ok(())
+	}
+
+	async fn update_item_course(&self, order_id: i64, item_id: i64, course: models::Course, version: i64) -> Result<Option<bool>> {
+		db::update_item_course(&mut *self.0.acquire().await?, order_id, item_id, course, version).await
+	}
+
+	async fn bill_lines(&self, table_session_id: i64) -> Result<Vec<models::BillLine>> {
+		Ok(db::retrieve_bill_lines(&mut *self.0.acquire().await?, table_session_id).await?.unwrap_or_default())
+	}
+
+	async fn discounts(&self, location_id: i64) -> Result<Vec<models::Discount>> {
+		Ok(db::get_discounts(&mut *self.0.acquire().await?, location_id).await?.unwrap_or_default())
+	}
+
+	async fn discount(&self, location_id: i64, discount_id: i64) -> Result<Option<models::Discount>> {
+		db::get_discount(&mut *self.0.acquire().await?, location_id, discount_id).await
+	}
+
+	async fn create_discount(&self, location_id: i64, discount: &models::Discount) -> Result<models::Discount> {
+		db::create_discount(&mut *self.0.acquire().await?, location_id, discount).await?
+			.ok_or(sqlx::Error::RowNotFound)
+	}
+
+	async fn applied_discounts(&self, table_session_id: i64) -> Result<Vec<models::AppliedDiscount>> {
+		Ok(db::retrieve_applied_discounts(&mut *self.0.acquire().await?, table_session_id).await?.unwrap_or_default())
+	}
+
+	async fn apply_discount(&self, discount: &models::AppliedDiscount) -> Result<models::AppliedDiscount> {
+		db::create_applied_discount(&mut *self.0.acquire().await?, discount).await?
+			.ok_or(sqlx::Error::RowNotFound)
+	}
+
+	async fn remove_discount(&self, table_session_id: i64, applied_discount_id: i64) -> Result<bool> {
+		db::delete_applied_discount(&mut *self.0.acquire().await?, table_session_id, applied_discount_id).await
+	}
+
+	async fn payments(&self, table_session_id: i64) -> Result<Vec<models::Payment>> {
+		Ok(db::retrieve_payments(&mut *self.0.acquire().await?, table_session_id).await?.unwrap_or_default())
+	}
+
+	async fn create_payment(&self, table_session_id: i64, staff_id: i64, payment: &models::Payment) -> Result<models::Payment> {
+		db::create_payment(&mut *self.0.acquire().await?, table_session_id, staff_id, payment).await?
+			.ok_or(sqlx::Error::RowNotFound)
+	}
+
+	async fn create_webhook(&self, location_id: i64, webhook: &models::Webhook, secret: &str) -> Result<models::Webhook> {
+		db::create_webhook(&mut *self.0.acquire().await?, location_id, webhook, secret).await?
+			.ok_or(sqlx::Error::RowNotFound)
+	}
+
+	async fn webhooks(&self, location_id: i64) -> Result<Vec<models::Webhook>> {
+		Ok(db::get_webhooks(&mut *self.0.acquire().await?, location_id).await?.unwrap_or_default())
+	}
+
+	async fn webhook(&self, webhook_id: i64) -> Result<Option<models::Webhook>> {
+		db::retrieve_webhook(&self.0, webhook_id).await
+	}
+
+	async fn remove_webhook(&self, location_id: i64, webhook_id: i64) -> Result<bool> {
+		db::deactivate_webhook(&mut *self.0.acquire().await?, location_id, webhook_id).await
+	}
+
+	async fn webhook_deliveries(&self, location_id: i64, webhook_id: i64) -> Result<Vec<models::WebhookDelivery>> {
+		Ok(db::retrieve_webhook_deliveries(&mut *self.0.acquire().await?, location_id, webhook_id).await?.unwrap_or_default())
+	}
+
+	async fn dead_letters(&self, location_id: i64) -> Result<Vec<models::DeadLetter>> {
+		Ok(db::retrieve_dead_letters(&mut *self.0.acquire().await?, location_id).await?.unwrap_or_default())
+	}
+
+	async fn take_dead_letter(&self, location_id: i64, dead_letter_id: i64) -> Result<Option<models::DeadLetter>> {
+		db::take_dead_letter(&self.0, location_id, dead_letter_id).await
+	}
+}
+
+/// Manages a `DatabaseRepository` on the database pool, unless a repository
+/// is already managed. Attached by `db::stage()` once the pool is ready.
+pub fn stage() -> AdHoc {
+	AdHoc::try_on_ignite("Repository", |rocket| async {
+		if rocket.state::<Repository>().is_some() {
+			return Ok(rocket);
+		}
+		let pool: db::DbPool = match db::Db::fetch(&rocket) {
+			Some(pool) => (**pool).clone(),
+			None => {
+				rocket::error!("The repository needs the database pool");
+				return Err(rocket);
+			}
+		};
+		let repository: Repository = Arc::new(DatabaseRepository(pool));
+		Ok(rocket.manage(repository))
+	})
+}
+
+/// A repository held in memory, so services can be tested without a
+/// database. Webhook deliveries and dead letters are logged by the delivery
+/// task on the database, so there are none here.
+#[cfg(test)]
+pub mod memory {
+	use std::cmp::Ordering;
+	use std::collections::HashMap;
+	use std::sync::Mutex;
+	use rocket_db_pools::sqlx;
+	use crate::models;
+	use crate::pagination::{Keyed, PageRequest};
+	use super::{RestaurantRepository, Result};
+
+	#[derive(Default)]
+	struct Tables {
+		items: Vec<models::Item>,
+		translations: Vec<(i64, models::ItemTranslation)>,
+		sessions: Vec<models::TableSession>,
+		orders: Vec<models::Order>,
+		fired_courses: Vec<(i64, models::Course)>,
+		discounts: Vec<models::Discount>,
+		applied_discounts: Vec<models::AppliedDiscount>,
+		payments: Vec<models::Payment>,
+		webhooks: Vec<(models::Webhook, bool)>
+	}
+
+	#[derive(Default)]
+	pub struct MemoryRepository(Mutex<Tables>);
+
+	impl MemoryRepository {
+		pub fn new(items: Vec<models::Item>) -> Self {
+			MemoryRepository(Mutex::new(Tables { items, ..Default::default() }))
+		}
+
+		pub fn add_translation(&self, item_id: i64, locale: &str, name: &str) {
+			let translation = models::ItemTranslation { locale: locale.to_string(), name: name.to_string() };
+			self.0.lock().unwrap().translations.push((item_id, translation));
+		}
+
+		pub fn add_payment(&self, payment: models::Payment) {
+			self.0.lock().unwrap().payments.push(payment);
+		}
+	}
+
+	impl Tables {
+		/// Bumps the version of the orders with a held item `fire` picks,
+		/// firing those items.
+		fn fire(&mut self, table_session_id: i64, fire: impl Fn(&models::Order, &models::OrderItem) -> bool) {
+			for order in self.orders.iter_mut().filter(|order| order.table_session_id == table_session_id) {
+				let held: Vec<i64> = order.order_items.iter().filter(|item| item.held && fire(order, item)).map(|item| item.item_id).collect();
+				if held.is_empty() {
+					continue;
+				}
+				for item in order.order_items.iter_mut().filter(|item| held.contains(&item.item_id)) {
+					item.held = false;
+				}
+				order.version += 1;
+			}
+		}
+
+		fn bump_session(&mut self, table_session_id: i64) {
+			if let Some(session) = self.sessions.iter_mut().find(|session| session.id == table_session_id) {
+				session.version += 1;
+			}
+		}
+	}
+
+	/// Sort values compare as numbers when both are, like columns do.
+	fn compare(a: &str, b: &str) -> Ordering {
+		match (a.parse::<f64>(), b.parse::<f64>()) {
+			(Ok(a), Ok(b)) => a.total_cmp(&b),
+			_ => a.cmp(b),
+		}
+	}
+
+	/// The rows `PageRequest` would fetch from the database.
+	fn select<T: Keyed>(page: &PageRequest, mut rows: Vec<T>) -> Vec<T> {
+		let field = page.sort.trim_start_matches('-');
+		let order = |a: &T, value: &str, id: i64| compare(&a.sort_value(field), value).then(a.key().cmp(&id));
+		rows.sort_by(|a, b| order(a, &b.sort_value(field), b.key()));
+		if page.descending {
+			rows.reverse();
+		}
+		if let Some((value, id)) = &page.after {
+			rows.retain(|row| match order(row, value, *id) {
+				Ordering::Less => page.descending,
+				Ordering::Greater => !page.descending,
+				Ordering::Equal => false,
+			});
+		}
+		rows.truncate(page.fetch_limit() as usize);
+		rows
+	}
+
+	fn now() -> String {
+		chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string()
+	}
+
+	#[rocket::async_trait]
+	impl RestaurantRepository for MemoryRepository {
+		async fn item(&self, location_id: i64, item_id: i64) -> Result<Option<models::Item>> {
+			Ok(self.0.lock().unwrap().items.iter().find(|item| item.id == item_id && item.location_id == location_id).cloned())
+		}
+
+		async fn create_item(&self, location_id: i64, item: &models::Item) -> Result<models::Item> {
+			let mut tables = self.0.lock().unwrap();
+			let id = tables.items.iter().map(|item| item.id).max().unwrap_or_default() + 1;
+			let item = models::Item { id, location_id, ..item.clone() };
+			tables.items.push(item.clone());
+			Ok(item)
+		}
+
+		async fn update_item(&self, location_id: i64, item_id: i64, item: &models::Item) -> Result<Option<models::Item>> {
+			let mut tables = self.0.lock().unwrap();
+			Ok(tables.items.iter_mut().find(|item| item.id == item_id && item.location_id == location_id).map(|stored| {
+				*stored = models::Item { id: item_id, location_id, ..item.clone() };
+				stored.clone()
+			}))
+		}
+
+		async fn item_translations(&self, item_id: i64) -> Result<Vec<models::ItemTranslation>> {
+			let mut translations: Vec<models::ItemTranslation> = self.0.lock().unwrap().translations.iter()
+				.filter(|(id, _)| *id == item_id)
+				.map(|(_, translation)| translation.clone())
+				.collect();
+			translations.sort_by(|a, b| a.locale.cmp(&b.locale));
+			Ok(translations)
+		}
+
+		async fn save_item_translation(&self, item_id: i64, translation: &models::ItemTranslation) -> Result<()> {
+			let mut tables = self.0.lock().unwrap();
+			tables.translations.retain(|(id, saved)| *id != item_id || saved.locale != translation.locale);
+			tables.translations.push((item_id, translation.clone()));
+			Ok(())
+		}
+
+		async fn items(&self, location_id: i64, page: &PageRequest, min_price: Option<u32>, max_price: Option<u32>) -> Result<Vec<models::Item>> {
+			let items = self.0.lock().unwrap().items.iter()
+				.filter(|item| item.location_id == location_id)
+				.filter(|item| min_price.is_none_or(|min_price| item.price.amount >= i64::from(min_price)))
+				.filter(|item| max_price.is_none_or(|max_price| item.price.amount <= i64::from(max_price)))
+				.cloned()
+				.collect();
+			Ok(select(page, items))
+		}
+
+		async fn item_names(&self, item_ids: &[i64], locales: &[&str]) -> Result<HashMap<i64, String>> {
+			let tables = self.0.lock().unwrap();
+			Ok(item_ids.iter()
+				.filter_map(|item_id| locales.iter()
+					.find_map(|locale| tables.translations.iter().find(|(id, translation)| id == item_id && translation.locale == *locale))
+					.map(|(_, translation)| (*item_id, translation.name.clone())))
+				.collect())
+		}
+
+		async fn session(&self, table_session_id: i64) -> Result<Option<models::TableSession>> {
+			Ok(self.0.lock().unwrap().sessions.iter().find(|session| session.id == table_session_id).cloned())
+		}
+
+		async fn active_session(&self, location_id: i64, table_nr: u8) -> Result<Option<models::TableSession>> {
+			Ok(self.0.lock().unwrap().sessions.iter().find(|session| session.location_id == location_id && session.table_nr == table_nr && session.active).cloned())
+		}
+
+		async fn active_sessions(&self, location_id: i64) -> Result<Vec<models::TableSession>> {
+			Ok(self.0.lock().unwrap().sessions.iter().filter(|session| session.location_id == location_id && session.active).cloned().collect())
+		}
+
+		async fn sessions(&self, location_id: i64, table_nr: u8, page: &PageRequest, start: Option<&str>, end: Option<&str>) -> Result<Vec<models::TableSession>> {
+			let sessions = self.0.lock().unwrap().sessions.iter()
+				.filter(|session| session.location_id == location_id && session.table_nr == table_nr)
+				.filter(|session| start.is_none_or(|start| session.session_start.as_str() >= start))
+				.filter(|session| end.is_none_or(|end| session.session_start.as_str() < end))
+				.cloned()
+				.collect();
+			Ok(select(page, sessions))
+		}
+
+		async fn open_session(&self, location_id: i64, table_nr: u8, customers: u8) -> Result<Option<models::TableSession>> {
+			let mut tables = self.0.lock().unwrap();
+			if tables.sessions.iter().any(|session| session.location_id == location_id && session.table_nr == table_nr && session.active) {
+				return Ok(None);
+			}
+			let session = models::TableSession {
+				id: tables.sessions.len() as i64 + 1,
+				table_nr,
+				customers,
+				session_start: now(),
+				session_end: String::new(),
+				active: true,
+				version: 1,
+				location_id
+			};
+			tables.sessions.push(session.clone());
+			Ok(Some(session))
+		}
+
+		async fn close_session(&self, table_session_id: i64, version: i64) -> Result<bool> {
+			let mut tables = self.0.lock().unwrap();
+			match tables.sessions.iter_mut().find(|session| session.id == table_session_id && session.active && session.version == version) {
+				Some(session) => {
+					session.active = false;
+					session.session_end = now();
+					session.version += 1;
+					Ok(true)
+				},
+				None => Ok(false),
+			}
+		}
+
+		async fn create_order(&self, table_session_id: i64, order_items: &[models::OrderItem], staff_id: Option<i64>) -> Result<models::Order> {
+			let mut tables = self.0.lock().unwrap();
+			let order = models::Order {
+				id: Some(tables.orders.len() as i64 + 1),
+				table_session_id,
+				timestamp: now(),
+				staff_id,
+				version: 1,
+				order_items: order_items.iter()
+					.map(|item| models::OrderItem {
+						held: item.course.is_some_and(|course| !tables.fired_courses.contains(&(table_session_id, course))),
+						..item.clone()
+					})
+					.collect()
+			};
+			tables.orders.push(order.clone());
+			Ok(order)
+		}
+
+		async fn order(&self, table_session_id: i64, order_id: i64) -> Result<Option<models::Order>> {
+			Ok(self.0.lock().unwrap().orders.iter()
+				.find(|order| order.table_session_id == table_session_id && order.id == Some(order_id))
+				.cloned())
+		}
+
+		async fn delete_order(&self, table_session_id: i64, order_id: i64, version: i64) -> Result<bool> {
+			let mut tables = self.0.lock().unwrap();
+			let before = tables.orders.len();
+			tables.orders.retain(|order| !(order.table_session_id == table_session_id && order.id == Some(order_id) && order.version == version));
+			Ok(tables.orders.len() < before)
+		}
+
+		async fn delete_order_item(&self, order_id: i64, item_id: i64, version: i64) -> Result<Option<bool>> {
+			let mut tables = self.0.lock().unwrap();
+			let order = match tables.orders.iter_mut().find(|order| order.id == Some(order_id)) {
+				Some(order) if order.order_items.iter().any(|item| item.item_id == item_id) => order,
+				_ => return Ok(Some(false)),
+			};
+			if order.version != version {
+				return Ok(None);
+			}
+			order.order_items.retain(|item| item.item_id != item_id);
+			order.version += 1;
+			Ok(Some(true))
+		}
+
+		async fn orders(&self, table_session_id: i64, page: &PageRequest, status: Option<models::ItemStatus>) -> Result<Vec<models::Order>> {
+			let held = status.map(|status| status == models::ItemStatus::Held);
+			let orders = self.0.lock().unwrap().orders.iter()
+				.filter(|order| order.table_session_id == table_session_id)
+				.filter(|order| held.is_none_or(|held| order.order_items.iter().any(|item| item.held) == held))
+				.cloned()
+				.collect();
+			Ok(select(page, orders))
+		}
+
+		async fn queue_items(&self, table_session_id: i64) -> Result<Vec<models::QueueItem>> {
+			let tables = self.0.lock().unwrap();
+			let table_nr = match tables.sessions.iter().find(|session| session.id == table_session_id) {
+				Some(session) => session.table_nr,
+				None => return Err(sqlx::Error::RowNotFound),
+			};
+			Ok(tables.orders.iter()
+				.filter(|order| order.table_session_id == table_session_id)
+				.flat_map(|order| order.order_items.iter().map(move |item| (order, item)))
+				.filter_map(|(order, order_item)| tables.items.iter().find(|item| item.id == order_item.item_id).map(|item| models::QueueItem {
+					table_session_id,
+					table_nr,
+					order_id: order.id.unwrap_or_default(),
+					item_id: item.id,
+					name: item.name.clone(),
+					station: item.station,
+					course: order_item.course,
+					amount: order_item.amount,
+					preparation_time: item.preparation_time,
+					fired_at: (!order_item.held).then(|| order.timestamp.clone()),
+					..Default::default()
+				}))
+				.collect())
+		}
+
+		async fn kitchen_queue(&self, location_id: i64) -> Result<Vec<models::QueueItem>> {
+			let table_session_ids: Vec<i64> = self.0.lock().unwrap().sessions.iter()
+				.filter(|session| session.location_id == location_id && session.active)
+				.map(|session| session.id)
+				.collect();
+			let mut items = Vec::new();
+			for table_session_id in table_session_ids {
+				items.extend(self.queue_items(table_session_id).await?);
+			}
+			Ok(items)
+		}
+
+		async fn fire_order(&self, order_id: i64) -> Result<()> {
+			let mut tables = self.0.lock().unwrap();
+			if let Some(table_session_id) = tables.orders.iter().find(|order| order.id == Some(order_id)).map(|order| order.table_session_id) {
+				tables.fire(table_session_id, |order, _| order.id == Some(order_id));
+			}
+			Ok(())
+		}
+
+		async fn fire_course(&self, table_session_id: i64, course: models::Course) -> Result<()> {
+			let mut tables = self.0.lock().unwrap();
+			if !tables.fired_courses.contains(&(table_session_id, course)) {
+				tables.fired_courses.push((table_session_id, course));
+			}
+			tables.fire(table_session_id, |_, item| item.course == Some(course));
+			Ok(())
+		}
+
+		async fn hold_course(&self, table_session_id: i64, course: models::Course) -> Result<()> {
+			self.0.lock().unwrap().fired_courses.retain(|fired| *fired != (table_session_id, course));
+			Ok(())
+		}
+
+		async fn update_item_course(&self, order_id: i64, item_id: i64, course: models::Course, version: i64) -> Result<Option<bool>> {
+			let mut tables = self.0.lock().unwrap();
+			let order = match tables.orders.iter_mut().find(|order| order.id == Some(order_id)) {
+				Some(order) if order.order_items.iter().any(|item| item.item_id == item_id && item.held) => order,
+				_ => return Ok(Some(false)),
+			};
+			if order.version != version {
+				return Ok(None);
+			}
+			for item in order.order_items.iter_mut().filter(|item| item.item_id == item_id && item.held) {
+				item.course = Some(course);
+			}
+			order.version += 1;
+			Ok(Some(true))
+		}
+
+		async fn bill_lines(&self, table_session_id: i64) -> Result<Vec<models::BillLine>> {
+			let tables = self.0.lock().unwrap();
+			Ok(tables.orders.iter()
+				.filter(|order| order.table_session_id == table_session_id)
+				.flat_map(|order| order.order_items.iter().map(move |item| (order, item)))
+				.filter_map(|(order, order_item)| tables.items.iter().find(|item| item.id == order_item.item_id).map(|item| models::BillLine {
+					order_id: order.id.unwrap_or_default(),
+					item_id: item.id,
+					name: item.name.clone(),
+					amount: order_item.amount,
+					price: item.price,
+					price_yen: item.price_yen,
+					..Default::default()
+				}))
+				.collect())
+		}
+
+		async fn discounts(&self, location_id: i64) -> Result<Vec<models::Discount>> {
+			Ok(self.0.lock().unwrap().discounts.iter().filter(|discount| discount.location_id == location_id).cloned().collect())
+		}
+
+		async fn discount(&self, location_id: i64, discount_id: i64) -> Result<Option<models::Discount>> {
+			Ok(self.0.lock().unwrap().discounts.iter().find(|discount| discount.id == discount_id && discount.location_id == location_id).cloned())
+		}
+
+		async fn create_discount(&self, location_id: i64, discount: &models::Discount) -> Result<models::Discount> {
+			let mut tables = self.0.lock().unwrap();
+			let discount = models::Discount { id: tables.discounts.len() as i64 + 1, location_id, ..discount.clone() };
+			tables.discounts.push(discount.clone());
+			Ok(discount)
+		}
+
+		async fn applied_discounts(&self, table_session_id: i64) -> Result<Vec<models::AppliedDiscount>> {
+			Ok(self.0.lock().unwrap().applied_discounts.iter().filter(|discount| discount.table_session_id == table_session_id).cloned().collect())
+		}
+
+		async fn apply_discount(&self, discount: &models::AppliedDiscount) -> Result<models::AppliedDiscount> {
+			let mut tables = self.0.lock().unwrap();
+			let id = tables.applied_discounts.iter().map(|discount| discount.id).max().unwrap_or_default() + 1;
+			let discount = models::AppliedDiscount { id, timestamp: now(), ..discount.clone() };
+			tables.applied_discounts.push(discount.clone());
+			tables.bump_session(discount.table_session_id);
+			Ok(discount)
+		}
+
+		async fn remove_discount(&self, table_session_id: i64, applied_discount_id: i64) -> Result<bool> {
+			let mut tables = self.0.lock().unwrap();
+			let before = tables.applied_discounts.len();
+			tables.applied_discounts.retain(|discount| !(discount.id == applied_discount_id && discount.table_session_id == table_session_id));
+			if tables.applied_discounts.len() == before {
+				return Ok(false);
+			}
+			tables.bump_session(table_session_id);
+			Ok(true)
+		}
+
+		async fn payments(&self, table_session_id: i64) -> Result<Vec<models::Payment>> {
+			Ok(self.0.lock().unwrap().payments.iter().filter(|payment| payment.table_session_id == table_session_id).cloned().collect())
+		}
+
+		async fn create_payment(&self, table_session_id: i64, staff_id: i64, payment: &models::Payment) -> Result<models::Payment> {
+			let mut tables = self.0.lock().unwrap();
+			let payment = models::Payment {
+				id: tables.payments.len() as i64 + 1,
+				table_session_id,
+				staff_id,
+				timestamp: now(),
+				..payment.clone()
+			};
+			tables.payments.push(payment.clone());
+			tables.bump_session(table_session_id);
+			Ok(payment)
+		}
+
+		async fn create_webhook(&self, location_id: i64, webhook: &models::Webhook, secret: &str) -> Result<models::Webhook> {
+			let mut tables = self.0.lock().unwrap();
+			let webhook = models::Webhook {
+				id: tables.webhooks.len() as i64 + 1,
+				secret: Some(secret.to_string()),
+				location_id,
+				..webhook.clone()
+			};
+			tables.webhooks.push((webhook.clone(), true));
+			Ok(webhook)
+		}
+
+		async fn webhooks(&self, location_id: i64) -> Result<Vec<models::Webhook>> {
+			Ok(self.0.lock().unwrap().webhooks.iter()
+				.filter(|(webhook, active)| *active && webhook.location_id == location_id)
+				.map(|(webhook, _)| models::Webhook { secret: None, ..webhook.clone() })
+				.collect())
+		}
+
+		async fn webhook(&self, webhook_id: i64) -> Result<Option<models::Webhook>> {
+			Ok(self.0.lock().unwrap().webhooks.iter()
+				.find(|(webhook, active)| *active && webhook.id == webhook_id)
+				.map(|(webhook, _)| webhook.clone()))
+		}
+
+		async fn remove_webhook(&self, location_id: i64, webhook_id: i64) -> Result<bool> {
+			let mut tables = self.0.lock().unwrap();
+			match tables.webhooks.iter_mut().find(|(webhook, active)| *active && webhook.id == webhook_id && webhook.location_id == location_id) {
+				Some((_, active)) => {
+					*active = false;
+					Ok(true)
+				},
+				None => Ok(false),
+			}
+		}
+
+		async fn webhook_deliveries(&self, _location_id: i64, _webhook_id: i64) -> Result<Vec<models::WebhookDelivery>> {
+			Ok(Vec::new())
+		}
+
+		async fn dead_letters(&self, _location_id: i64) -> Result<Vec<models::DeadLetter>> {
+			Ok(Vec::new())
+		}
+
+		async fn take_dead_letter(&self, _location_id: i64, _dead_letter_id: i64) -> Result<Option<models::DeadLetter>> {
+			Ok(None)
+		}
+	}
+}
@@ -0,0 +1,249 @@
+use crate::billing;
+use crate::error_handler::Error;
+use crate::i18n::{message, Locale};
+use crate::models;
+use crate::pagination::PageRequest;
+use crate::repository::RestaurantRepository;
+use crate::settings::RestaurantSettings;
+
+type Result<T> = std::result::Result<T, Error>;
+
+// What the handlers do with the menu, sessions, orders and bills of a
+// location, on any repository. Handlers add what is about HTTP: idempotency
+// keys, ETags, events and kitchen tickets.
+
+/// Names the items in the locale, or its fallback, where they are translated.
+async fn translated(repository: &dyn RestaurantRepository, mut items: Vec<models::Item>, locale: Locale) -> Result<Vec<models::Item>> {
+	let item_ids: Vec<i64> = items.iter().map(|item| item.id).collect();
+	let mut names = repository.item_names(&item_ids, &locale.preferred()).await?;
+	for item in &mut items {
+		if let Some(name) = names.remove(&item.id) {
+			item.name = name;
+		}
+	}
+	Ok(items)
+}
+
+pub async fn item(repository: &dyn RestaurantRepository, location_id: i64, item_id: i64, locale: Locale) -> Result<models::Item> {
+	match repository.item(location_id, item_id).await? {
+		Some(item) => Ok(translated(repository, vec![item], locale).await?.remove(0)),
+		None => Err(Error::Api{
+			msg: message!("Unable to get item with ID {}", item_id)
+		})
+	}
+}
+
+/// A page of the menu in the locale. Pages are sorted by the names the items
+/// have untranslated.
+pub async fn items(repository: &dyn RestaurantRepository, location_id: i64, page: &PageRequest, min_price: Option<u32>, max_price: Option<u32>, locale: Locale) -> Result<models::Page<models::Item>> {
+	let mut items = page.page(repository.items(location_id, page, min_price, max_price).await?);
+	items.data = translated(repository, items.data, locale).await?;
+	Ok(items)
+}
+
+pub async fn active_session(repository: &dyn RestaurantRepository, location_id: i64, table_nr: u8) -> Result<models::TableSession> {
+	match repository.active_session(location_id, table_nr).await? {
+		Some(session) => Ok(session),
+		None => Err(Error::Api{
+			msg: message!("No active session for table #{}", table_nr)
+		})
+	}
+}
+
+pub async fn active_sessions(repository: &dyn RestaurantRepository, location_id: i64) -> Result<Vec<models::TableSession>> {
+	Ok(repository.active_sessions(location_id).await?)
+}
+
+/// A session by id, as long as it is one of the location's.
+pub async fn session(repository: &dyn RestaurantRepository, location_id: i64, table_session_id: i64) -> Result<models::TableSession> {
+	match repository.session(table_session_id).await? {
+		Some(session) if session.location_id == location_id => Ok(session),
+		_ => Err(Error::Api{
+			msg: message!("Unable to get session with ID {}", table_session_id)
+		})
+	}
+}
+
+/// A session of the location by id. Staff see the active ones, managers
+/// closed ones too.
+pub async fn readable_session(repository: &dyn RestaurantRepository, location_id: i64, staff: &models::Staff, table_session_id: i64) -> Result<models::TableSession> {
+	match session(repository, location_id, table_session_id).await? {
+		session if !session.active && staff.role < models::Role::Manager => Err(Error::Forbidden{
+			msg: message!("Session {} is closed, looking it up requires a manager", table_session_id)
+		}),
+		session => Ok(session)
+	}
+}
+
+/// A page of a table's sessions started `from` and `to` the given days at
+/// the restaurant, both included.
+#[allow(clippy::too_many_arguments)]
+pub async fn sessions(repository: &dyn RestaurantRepository, settings: &RestaurantSettings, location_id: i64, table_nr: u8, page: &PageRequest, from: Option<chrono::NaiveDate>, to: Option<chrono::NaiveDate>) -> Result<models::Page<models::TableSession>> {
+	let start = from.map(|from| settings.day_start(from));
+	let end = to.map(|to| settings.day_start(to + chrono::Duration::days(1)));
+	Ok(page.page(repository.sessions(location_id, table_nr, page, start.as_deref(), end.as_deref()).await?))
+}
+
+pub async fn open_session(repository: &dyn RestaurantRepository, location_id: i64, table_nr: u8, customers: u8) -> Result<models::TableSession> {
+	let conflict = || Error::Conflict{
+		msg: message!("Active session for table #{} already exists", table_nr)
+	};
+	if repository.active_session(location_id, table_nr).await?.is_some() {
+		return Err(conflict());
+	}
+	repository.open_session(location_id, table_nr, customers).await?.ok_or_else(conflict)
+}
+
+/// Ends the session if it is still at `version`.
+pub async fn close_session(repository: &dyn RestaurantRepository, session: &models::TableSession, version: i64) -> Result<()> {
+	if repository.close_session(session.id, version).await? {
+		Ok(())
+	}
+	else {
+		Err(Error::PreconditionFailed{
+			msg: message!("Session {} was changed by someone else", session.id)
+		})
+	}
+}
+
+/// Orders for the table's active session, from the location's menu.
+pub async fn place_order(repository: &dyn RestaurantRepository, location_id: i64, table_nr: u8, order_items: &[models::OrderItem], staff_id: Option<i64>) -> Result<(models::TableSession, models::Order)> {
+	let session = active_session(repository, location_id, table_nr).await?;
+	for order_item in order_items {
+		if repository.item(location_id, order_item.item_id).await?.is_none() {
+			return Err(Error::Api{
+				msg: message!("Item {} is not on the menu here", order_item.item_id)
+			});
+		}
+	}
+	let order = repository.create_order(session.id, order_items, staff_id).await?;
+	Ok((session, order))
+}
+
+pub async fn order(repository: &dyn RestaurantRepository, table_session_id: i64, order_id: i64) -> Result<models::Order> {
+	match repository.order(table_session_id, order_id).await? {
+		Some(order) => Ok(order),
+		None => Err(Error::Api{
+			msg: message!("Unable to get order with ID {}", order_id)
+		})
+	}
+}
+
+pub async fn orders(repository: &dyn RestaurantRepository, table_session_id: i64, page: &PageRequest, status: Option<models::ItemStatus>) -> Result<models::Page<models::Order>> {
+	Ok(page.page(repository.orders(table_session_id, page, status).await?))
+}
+
+pub async fn bill(repository: &dyn RestaurantRepository, config: &billing::BillingConfig, session: &models::TableSession) -> Result<models::Bill> {
+	let lines = repository.bill_lines(session.id).await?;
+	let discounts = repository.applied_discounts(session.id).await?;
+	let payments = repository.payments(session.id).await?;
+	billing::calculate_bill(session, lines, discounts, &payments, config).map_err(|e| Error::Unprocessable{
+		msg: e.message()
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::billing::BillingConfig;
+	use crate::error_handler::Error;
+	use crate::i18n::Locale;
+	use crate::models;
+	use crate::money::{Currency, Money};
+	use crate::pagination::PageRequest;
+	use crate::repository::memory::MemoryRepository;
+
+	fn menu() -> MemoryRepository {
+		MemoryRepository::new((1..=4).map(|id| models::Item {
+			id,
+			location_id: if id == 4 { 2 } else { 1 },
+			name: format!("Item {}", id),
+			price: Money::new(500 * id, Currency::JPY),
+			preparation_time: 300,
+			..Default::default()
+		}).collect())
+	}
+
+	#[rocket::async_test]
+	async fn sessions_orders_and_bills()
+	{
+		let repository = menu();
+		let session = super::open_session(&repository, 1, 5, 2).await.unwrap();
+		assert!(matches!(super::open_session(&repository, 1, 5, 2).await, Err(Error::Conflict{ .. })));
+
+		let items = [
+			models::OrderItem { item_id: 1, amount: 2, ..Default::default() },
+			models::OrderItem { item_id: 3, amount: 1, course: Some(models::Course::Dessert), ..Default::default() }
+		];
+		let (_, order) = super::place_order(&repository, 1, 5, &items, None).await.unwrap();
+		assert!(order.order_items[1].held);
+		let page = PageRequest::new(None, None, None, &[("id", "id")]).unwrap();
+		let held = super::orders(&repository, session.id, &page, Some(models::ItemStatus::Held)).await.unwrap();
+		assert_eq!(held.data.len(), 1);
+		assert!(matches!(super::place_order(&repository, 1, 6, &items, None).await, Err(Error::Api{ .. })));
+
+		repository.add_payment(models::Payment { table_session_id: session.id, amount: 1000, ..Default::default() });
+		let bill = super::bill(&repository, &BillingConfig::default(), &session).await.unwrap();
+		assert_eq!(bill.subtotal, 2 * 500 + 1500);
+		assert_eq!(bill.paid, 1000);
+		assert_eq!(bill.balance_due, bill.total - 1000);
+
+		assert!(matches!(super::close_session(&repository, &session, session.version + 1).await, Err(Error::PreconditionFailed{ .. })));
+		super::close_session(&repository, &session, session.version).await.unwrap();
+		assert!(super::active_sessions(&repository, 1).await.unwrap().is_empty());
+		let server = models::Staff { id: 1, name: String::from("Server"), role: models::Role::Server, location_id: Some(1) };
+		assert!(matches!(super::readable_session(&repository, 1, &server, session.id).await, Err(Error::Forbidden{ .. })));
+	}
+
+	#[rocket::async_test]
+	async fn locations_keep_their_tables_and_menus()
+	{
+		let repository = menu();
+		let session = super::open_session(&repository, 1, 5, 2).await.unwrap();
+		let other = super::open_session(&repository, 2, 5, 3).await.unwrap();
+		assert_ne!(session.id, other.id);
+		assert_eq!(super::active_sessions(&repository, 2).await.unwrap().len(), 1);
+
+		let item = |item_id| [models::OrderItem { item_id, amount: 1, ..Default::default() }];
+		assert!(matches!(super::place_order(&repository, 2, 5, &item(1), None).await, Err(Error::Api{ .. })));
+		let (placed_at, _) = super::place_order(&repository, 2, 5, &item(4), None).await.unwrap();
+		assert_eq!(placed_at.id, other.id);
+		assert!(matches!(super::place_order(&repository, 1, 5, &item(4), None).await, Err(Error::Api{ .. })));
+
+		let manager = models::Staff { id: 2, name: String::from("Manager"), role: models::Role::Manager, location_id: None };
+		assert!(matches!(super::readable_session(&repository, 2, &manager, session.id).await, Err(Error::Api{ .. })));
+		assert!(super::readable_session(&repository, 2, &manager, other.id).await.is_ok());
+	}
+
+	#[rocket::async_test]
+	async fn menu_pages_follow_the_sort()
+	{
+		let repository = menu();
+		let fields = [("id", "id"), ("price", "price")];
+		let english = Locale::negotiate(None, "en");
+		let page = PageRequest::new(Some(2), None, Some("-price"), &fields).unwrap();
+		let first = super::items(&repository, 1, &page, None, None, english).await.unwrap();
+		assert_eq!(first.data.iter().map(|item| item.id).collect::<Vec<i64>>(), vec![3, 2]);
+		let page = PageRequest::new(Some(2), first.next_cursor.as_deref(), Some("-price"), &fields).unwrap();
+		let second = super::items(&repository, 1, &page, None, None, english).await.unwrap();
+		assert_eq!(second.data.iter().map(|item| item.id).collect::<Vec<i64>>(), vec![1]);
+		assert!(second.next_cursor.is_none());
+	}
+
+	#[rocket::async_test]
+	async fn menus_are_read_in_the_guests_language()
+	{
+		let repository = menu();
+		repository.add_translation(1, "ja", "寿司");
+		repository.add_translation(2, "ko", "김밥");
+		let page = PageRequest::new(None, None, None, &[("id", "id")]).unwrap();
+		let mut names = Vec::new();
+		for (accept_language, default) in [(None, "en"), (Some("ja-JP"), "en"), (Some("ko"), "ja")] {
+			let items = super::items(&repository, 1, &page, None, None, Locale::negotiate(accept_language, default)).await.unwrap();
+			names.push(items.data.into_iter().map(|item| item.name).collect::<Vec<String>>());
+		}
+		assert_eq!(names[0], ["Item 1", "Item 2", "Item 3"]);
+		assert_eq!(names[1], ["寿司", "Item 2", "Item 3"]);
+		assert_eq!(names[2], ["寿司", "김밥", "Item 3"]);
+		assert_eq!(super::item(&repository, 1, 2, Locale::negotiate(Some("ko"), "en")).await.unwrap().name, "김밥");
+	}
+}
@@ -0,0 +1,304 @@
+use std::sync::{Arc, RwLock};
+use chrono::{DateTime, Datelike, NaiveDate, NaiveTime, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+use rocket::fairing::AdHoc;
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::Database;
+use utoipa::ToSchema;
+use crate::billing::BillingConfig;
+use crate::db;
+use crate::i18n::{self, message, Message};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[serde(crate="rocket::serde", rename_all = "lowercase")]
+pub enum Day {
+	Monday,
+	Tuesday,
+	Wednesday,
+	Thursday,
+	Friday,
+	Saturday,
+	Sunday
+}
+
+impl Day {
+	fn weekday(self) -> Weekday {
+		match self {
+			Day::Monday => Weekday::Mon,
+			Day::Tuesday => Weekday::Tue,
+			Day::Wednesday => Weekday::Wed,
+			Day::Thursday => Weekday::Thu,
+			Day::Friday => Weekday::Fri,
+			Day::Saturday => Weekday::Sat,
+			Day::Sunday => Weekday::Sun
+		}
+	}
+}
+
+/// When the restaurant is open on a day, as `HH:MM` in its timezone. A
+/// `close` before `open` is past midnight.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[serde(crate="rocket::serde")]
+pub struct OpeningHours {
+	pub day: Day,
+	pub open: String,
+	pub close: String
+}
+
+/// Restaurant settings read from `Rocket.toml` or `ROCKET_` environment
+/// variables, e.g. `timezone = "Asia/Tokyo"` or `ROCKET_TIMEZONE=Asia/Tokyo`.
+/// The billing settings (`currency`, `tax_rate_percent`, ...) sit next to the
+/// others. Sessions without orders, payments or signals for
+/// `session_timeout_minutes` are closed, unless it is 0 (see `timeouts`).
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[serde(crate="rocket::serde")]
+pub struct RestaurantSettings {
+	#[serde(default = "default_restaurant_name")]
+	pub restaurant_name: String,
+	/// An IANA timezone like `Asia/Tokyo`.
+	#[serde(default = "default_timezone")]
+	pub timezone: String,
+	/// Days without opening hours are closed; no opening hours at all means
+	/// always open.
+	#[serde(default)]
+	pub opening_hours: Vec<OpeningHours>,
+	#[serde(default = "default_session_timeout_minutes")]
+	pub session_timeout_minutes: u32,
+	/// The locale of guests whose `Accept-Language` has none of ours: `en`,
+	/// `ja`, `zh` or `ko`.
+	#[serde(default = "default_locale")]
+	pub default_locale: String,
+	#[serde(flatten)]
+	pub billing: BillingConfig
+}
+
+fn default_restaurant_name() -> String {
+	String::from("Restaurant")
+}
+
+fn default_timezone() -> String {
+	String::from("Asia/Tokyo")
+}
+
+fn default_session_timeout_minutes() -> u32 {
+	240
+}
+
+fn default_locale() -> String {
+	String::from("en")
+}
+
+fn parse_time(time: &str) -> Option<NaiveTime> {
+	NaiveTime::parse_from_str(time, "%H:%M").ok()
+}
+
+impl RestaurantSettings {
+	/// Checks what the types don't, naming the first invalid setting.
+	pub fn validate(&self) -> Result<(), Message> {
+		if self.restaurant_name.trim().is_empty() {
+			return Err(message!("restaurant_name can't be empty"));
+		}
+		if self.timezone.parse::<chrono_tz::Tz>().is_err() {
+			return Err(message!("Unknown timezone '{}'", self.timezone));
+		}
+		if !i18n::LOCALES.contains(&self.default_locale.as_str()) {
+			return Err(message!("Unsupported default_locale '{}', expected one of: {}", self.default_locale, i18n::LOCALES.join(", ")));
+		}
+		if self.billing.tax_rate_percent > 100 || self.billing.service_charge_percent > 100 {
+			return Err(message!("tax_rate_percent and service_charge_percent can't be over 100"));
+		}
+		if self.billing.service_charge_min_customers == 0 {
+			return Err(message!("service_charge_min_customers has to be at least 1"));
+		}
+		for hours in &self.opening_hours {
+			match (parse_time(&hours.open), parse_time(&hours.close)) {
+				(Some(open), Some(close)) if open != close => {},
+				_ => return Err(message!("Invalid opening hours {}-{} on {}, expected HH:MM to a different HH:MM", hours.open, hours.close, format!("{:?}", hours.day)))
+			}
+		}
+		Ok(())
+	}
+
+	/// The timezone, which `validate` made sure of.
+	pub fn tz(&self) -> Tz {
+		self.timezone.parse().unwrap_or(Tz::UTC)
+	}
+
+	/// The day it is at the restaurant.
+	pub fn today(&self) -> NaiveDate {
+		Utc::now().with_timezone(&self.tz()).date_naive()
+	}
+
+	/// When a day starts at the restaurant, as the database stores times: in
+	/// UTC. Reports and session histories run from the start of their first
+	/// day to the start of the day after their last.
+	pub fn day_start(&self, date: NaiveDate) -> String {
+		let midnight = date.and_time(NaiveTime::MIN);
+		// Days switching to daylight saving time at midnight start at 1:00.
+		let start = [0, 1].into_iter()
+			.find_map(|hour| self.tz().from_local_datetime(&(midnight + chrono::Duration::hours(hour))).earliest())
+			.map_or_else(|| Utc.from_utc_datetime(&midnight), |start| start.with_timezone(&Utc));
+		db::timestamp(start)
+	}
+
+	/// Whether orders are taken at `now`: always without opening hours, else
+	/// within the hours of the day, or of the day before when they run past
+	/// midnight.
+	pub fn is_open(&self, now: DateTime<Utc>) -> bool {
+		if self.opening_hours.is_empty() {
+			return true;
+		}
+		let local = now.with_timezone(&self.tz());
+		let (today, time) = (local.weekday(), local.time());
+		self.opening_hours.iter().any(|hours| {
+			let (open, close) = match (parse_time(&hours.open), parse_time(&hours.close)) {
+				(Some(open), Some(close)) => (open, close),
+				_ => return false
+			};
+			let day = hours.day.weekday();
+			if open < close {
+				day == today && open <= time && time < close
+			}
+			else {
+				(day == today && open <= time) || (day.succ() == today && time < close)
+			}
+		})
+	}
+}
+
+/// The settings `saved` changes from `configured`, by name.
+fn overridden(configured: &RestaurantSettings, saved: &RestaurantSettings) -> Vec<String> {
+	let (configured, saved) = match (rocket::serde::json::to_value(configured), rocket::serde::json::to_value(saved)) {
+		(Ok(rocket::serde::json::Value::Object(configured)), Ok(rocket::serde::json::Value::Object(saved))) => (configured, saved),
+		_ => return Vec::new()
+	};
+	saved.into_iter()
+		.filter(|(name, value)| configured.get(name) != Some(value))
+		.map(|(name, _)| name)
+		.collect()
+}
+
+/// Managed state with the current settings. Clones share them, so changes
+/// made through the API are seen everywhere.
+#[derive(Clone)]
+pub struct Settings(Arc<RwLock<RestaurantSettings>>);
+
+impl Settings {
+	pub fn new(settings: RestaurantSettings) -> Self {
+		Settings(Arc::new(RwLock::new(settings)))
+	}
+
+	pub fn current(&self) -> RestaurantSettings {
+		self.0.read().expect("settings lock").clone()
+	}
+
+	pub fn billing(&self) -> BillingConfig {
+		self.0.read().expect("settings lock").billing.clone()
+	}
+
+	pub fn replace(&self, settings: RestaurantSettings) {
+		*self.0.write().expect("settings lock") = settings;
+	}
+}
+
+/// Reads the settings from the configuration, or those saved through the
+/// API when there are any, and stops the launch when they are invalid.
+pub fn stage() -> AdHoc {
+	AdHoc::try_on_ignite("Settings", |rocket| async {
+		let mut settings = match rocket.figment().extract::<RestaurantSettings>() {
+			Ok(settings) => settings,
+			Err(e) => {
+				rocket::error!("Invalid settings: {}", e);
+				return Err(rocket);
+			}
+		};
+		if let Some(pool) = db::Db::fetch(&rocket) {
+			let saved = match db::retrieve_settings(pool).await {
+				Ok(saved) => saved,
+				Err(e) => {
+					rocket::error!("Unable to read the saved settings: {}", e);
+					return Err(rocket);
+				}
+			};
+			if let Some(saved) = saved {
+				let saved: RestaurantSettings = match rocket::serde::json::from_str(&saved) {
+					Ok(saved) => saved,
+					Err(e) => {
+						rocket::error!("Invalid saved settings: {}", e);
+						return Err(rocket);
+					}
+				};
+				let overridden = overridden(&settings, &saved);
+				if !overridden.is_empty() {
+					rocket::warn!("Settings saved with PUT /settings override the configuration of: {}", overridden.join(", "));
+				}
+				settings = saved;
+			}
+		}
+		if let Err(e) = settings.validate() {
+			rocket::error!("Invalid settings: {}", e);
+			return Err(rocket);
+		}
+		Ok(rocket.manage(Settings::new(settings)))
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use rocket::figment::Figment;
+	use rocket::figment::providers::{Format, Toml};
+	use chrono::{NaiveDate, TimeZone, Utc};
+	use super::{Day, RestaurantSettings};
+
+	#[test]
+	fn settings_are_read_and_validated()
+	{
+		let figment = Figment::from(Toml::string(r#"
+			restaurant_name = "Harbor Grill"
+			currency = "EUR"
+			tax_rate_percent = 8
+			opening_hours = [{ day = "friday", open = "17:00", close = "01:00" }]
+		"#));
+		let settings: RestaurantSettings = figment.extract().expect("settings");
+		assert_eq!(settings.restaurant_name, "Harbor Grill");
+		assert_eq!(settings.timezone, "Asia/Tokyo");
+		assert_eq!(settings.billing.tax_rate_percent, 8);
+		assert_eq!(settings.billing.service_charge_min_customers, 8);
+		assert_eq!(settings.opening_hours[0].day, Day::Friday);
+		assert_eq!(settings.billing.currency.code(), "EUR");
+		assert!(settings.validate().is_ok());
+		assert!(figment.merge(("currency", "yen")).extract::<RestaurantSettings>().is_err());
+
+		let invalid = [
+			RestaurantSettings { timezone: String::from("Mars/Olympus"), ..settings.clone() },
+			RestaurantSettings { restaurant_name: String::from(" "), ..settings.clone() },
+		];
+		assert!(invalid.iter().all(|settings| settings.validate().is_err()));
+		let mut late = settings.clone();
+		late.opening_hours[0].close = String::from("25:00");
+		assert!(late.validate().is_err());
+		let mut taxed = settings;
+		taxed.billing.tax_rate_percent = 101;
+		assert!(taxed.validate().is_err());
+	}
+
+	#[test]
+	fn hours_and_days_are_local()
+	{
+		let figment = Figment::from(Toml::string(r#"
+			opening_hours = [{ day = "friday", open = "17:00", close = "01:00" }]
+		"#));
+		let settings: RestaurantSettings = figment.extract().expect("settings");
+		let at = |hour| Utc.with_ymd_and_hms(2026, 10, 16, hour, 0, 0).unwrap();
+		// Friday 12:00, 18:00 and midnight, Saturday 2:00 in Tokyo.
+		assert!(!settings.is_open(at(3)));
+		assert!(settings.is_open(at(9)));
+		assert!(settings.is_open(at(15)));
+		assert!(!settings.is_open(at(17)));
+		assert!(RestaurantSettings { opening_hours: Vec::new(), ..settings.clone() }.is_open(at(3)));
+
+		assert_eq!(settings.day_start(NaiveDate::from_ymd_opt(2026, 10, 16).unwrap()), "2026-10-15 15:00:00");
+		let renamed = RestaurantSettings { restaurant_name: String::from("Harbor Grill"), ..settings.clone() };
+		assert_eq!(super::overridden(&settings, &renamed), vec![String::from("restaurant_name")]);
+	}
+}
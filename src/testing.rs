@@ -0,0 +1,191 @@
+use std::ops::Deref;
+use rocket::figment::Figment;
+use rocket::http::{ContentType, Header};
+use rocket::local::{asynchronous, blocking};
+use rocket::serde::json::Value;
+use rocket::{Build, Rocket};
+#[cfg(feature = "postgres")]
+use rocket_db_pools::sqlx;
+use crate::{db, handlers, models};
+
+// Test harness: every test gets a database of its own, migrated and seeded
+// with the menu, discounts and staff of the migrations when its Rocket
+// ignites, so tests can run in parallel and in any order.
+
+/// Tokens of the staff the migrations seed.
+pub const SERVER_TOKEN: &str = "dev-server-token";
+pub const MANAGER_TOKEN: &str = "dev-manager-token";
+pub const OWNER_TOKEN: &str = "dev-owner-token";
+
+pub fn server() -> Header<'static> {
+	Header::new("Authorization", format!("Bearer {}", SERVER_TOKEN))
+}
+
+pub fn manager() -> Header<'static> {
+	Header::new("Authorization", format!("Bearer {}", MANAGER_TOKEN))
+}
+
+pub fn owner() -> Header<'static> {
+	Header::new("Authorization", format!("Bearer {}", OWNER_TOKEN))
+}
+
+/// Sends a request to another location than the default one.
+pub fn at(slug: &str) -> Header<'static> {
+	Header::new("X-Location", slug.to_string())
+}
+
+/// A database for one test, removed again when dropped: a temporary file on
+/// SQLite, a database next to the configured one on Postgres.
+pub struct TestDatabase {
+	name: String,
+	url: String
+}
+
+impl TestDatabase {
+	#[cfg(not(feature = "postgres"))]
+	pub fn new() -> Self {
+		let name = format!("order_test_{:016x}.sqlite", rand::random::<u64>());
+		let path = std::env::temp_dir().join(&name);
+		let url = format!("sqlite://{}?mode=rwc", path.display());
+		TestDatabase { name: path.display().to_string(), url }
+	}
+
+	#[cfg(feature = "postgres")]
+	pub fn new() -> Self {
+		let name = format!("order_test_{:016x}", rand::random::<u64>());
+		let configured = configured_url();
+		let url = format!("{}/{}", &configured[..configured.rfind('/').expect("database URL with a path")], name);
+		let create = format!("CREATE DATABASE {}", name);
+		blocking_query(configured, create);
+		TestDatabase { name, url }
+	}
+
+	pub fn url(&self) -> &str {
+		&self.url
+	}
+
+	/// The test configuration, pointing `order_db` at this database. Kitchen
+	/// tickets stay in memory instead of going to `kitchen_tickets` in the
+	/// working directory.
+	pub fn figment(&self) -> Figment {
+		rocket::Config::figment()
+		.merge(("databases.order_db.url", &self.url))
+		.merge(("kitchen.sink", "memory"))
+	}
+}
+
+impl Drop for TestDatabase {
+	#[cfg(not(feature = "postgres"))]
+	fn drop(&mut self) {
+		for suffix in ["", "-wal", "-shm"] {
+			let _ = std::fs::remove_file(format!("{}{}", self.name, suffix));
+		}
+	}
+
+	#[cfg(feature = "postgres")]
+	fn drop(&mut self) {
+		blocking_query(configured_url(), format!("DROP DATABASE IF EXISTS {} WITH (FORCE)", self.name));
+	}
+}
+
+#[cfg(feature = "postgres")]
+fn configured_url() -> String {
+	rocket::Config::figment().extract_inner("databases.order_db.url").expect("databases.order_db.url")
+}
+
+/// Runs a statement on its own thread and runtime, as tests creating and
+/// dropping databases may already be running on one.
+#[cfg(feature = "postgres")]
+fn blocking_query(url: String, statement: String) {
+	std::thread::spawn(move || {
+		let runtime = rocket::tokio::runtime::Builder::new_current_thread().enable_all().build().expect("runtime");
+		runtime.block_on(async {
+			let pool = db::DbPool::connect(&url).await.expect("test database server");
+			sqlx::query(&statement).execute(&pool).await.expect("test database");
+			pool.close().await;
+		})
+	}).join().expect("test database");
+}
+
+/// The server on a database of its own, with `customize` applied first to
+/// manage state or change the configuration.
+pub fn rocket(database: &TestDatabase, customize: impl FnOnce(Rocket<Build>) -> Rocket<Build>) -> Rocket<Build> {
+	customize(rocket::custom(database.figment()))
+		.attach(handlers::stage())
+		.attach(db::stage())
+}
+
+/// A client holding on to its test database for as long as it is used.
+pub struct TestClient<C> {
+	client: C,
+	_database: TestDatabase
+}
+
+impl<C> Deref for TestClient<C> {
+	type Target = C;
+
+	fn deref(&self) -> &C {
+		&self.client
+	}
+}
+
+pub fn client() -> TestClient<blocking::Client> {
+	client_with(|rocket| rocket)
+}
+
+pub fn client_with(customize: impl FnOnce(Rocket<Build>) -> Rocket<Build>) -> TestClient<blocking::Client> {
+	let database = TestDatabase::new();
+	let client = blocking::Client::tracked(rocket(&database, customize)).expect("valid rocket instance");
+	TestClient { client, _database: database }
+}
+
+pub async fn async_client() -> TestClient<asynchronous::Client> {
+	let database = TestDatabase::new();
+	let client = asynchronous::Client::tracked(rocket(&database, |rocket| rocket)).await.expect("valid rocket instance");
+	TestClient { client, _database: database }
+}
+
+/// Opens a session for a table, returning its id.
+pub fn open_session(client: &blocking::Client, table_nr: u8, customers: u8) -> i64 {
+	let session = client.post(format!("/api/v1/tables/{}", table_nr))
+		.header(ContentType::JSON)
+		.body(format!(r#"{{"customers":{}}}"#, customers))
+		.dispatch()
+		.into_json::<Value>()
+		.expect("TableSession");
+	session["id"].as_i64().expect("session id")
+}
+
+/// Orders `(item_id, amount)` pairs for a table's active session.
+pub fn place_order(client: &blocking::Client, table_nr: u8, items: &[(i64, u8)]) -> models::Order {
+	let order_items: Vec<String> = items.iter()
+		.map(|(item_id, amount)| format!(r#"{{"item_id":{},"amount":{}}}"#, item_id, amount))
+		.collect();
+	client.post(format!("/api/v1/tables/{}/orders", table_nr))
+		.header(ContentType::JSON)
+		.body(format!(r#"{{"order_items":[{}]}}"#, order_items.join(",")))
+		.dispatch()
+		.into_json()
+		.expect("Order")
+}
+
+#[cfg(test)]
+mod tests {
+	use rocket::http::Status;
+
+	#[test]
+	fn databases_are_isolated()
+	{
+		let (first, second) = (super::client(), super::client());
+		let session_id = super::open_session(&first, 1, 2);
+		super::open_session(&second, 1, 4);
+		let order = super::place_order(&first, 1, &[(1, 2)]);
+		assert_eq!(order.order_items.len(), 1);
+
+		let response = second.get("/api/v1/tables/1/orders").dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		assert!(response.into_json::<crate::models::Page<crate::models::Order>>().expect("Orders").data.is_empty());
+		let response = first.get(format!("/api/v1/sessions/{}/orders", session_id)).header(super::manager()).dispatch();
+		assert_eq!(response.into_json::<crate::models::Page<crate::models::Order>>().expect("Orders").data.len(), 1);
+	}
+}
@@ -0,0 +1,83 @@
+use std::time::Duration;
+use chrono::Utc;
+use log::{error, info};
+use rocket::fairing::AdHoc;
+use rocket::tokio::time::interval;
+use rocket_db_pools::Database;
+use crate::{db, events, models, settings};
+
+/// How often idle sessions are looked for.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Closes the sessions idle for longer than `session_timeout_minutes`, and
+/// tells subscribers as if staff had ended them. Returns how many there were.
+pub async fn close_idle_sessions(pool: &db::DbPool, events: &events::Events, timeout_minutes: u32) -> Result<usize, rocket_db_pools::sqlx::Error> {
+	if timeout_minutes == 0 {
+		return Ok(0);
+	}
+	let cutoff = db::timestamp(Utc::now() - chrono::Duration::minutes(timeout_minutes.into()));
+	let closed = db::close_idle_sessions(pool, &cutoff).await?;
+	for (table_session_id, table_nr, location_id) in &closed {
+		info!("Closed idle session {} of table #{}", table_session_id, table_nr);
+		events.publish(*location_id, *table_nr, Vec::new(), models::EventData::SessionClosed { table_session_id: *table_session_id });
+	}
+	Ok(closed.len())
+}
+
+/// Sweeps for idle sessions every minute once the server has launched.
+pub fn stage() -> AdHoc {
+	AdHoc::on_liftoff("Session timeouts", |rocket| Box::pin(async move {
+		let (settings, pool, events) = match (rocket.state::<settings::Settings>(), db::Db::fetch(rocket), rocket.state::<events::Events>()) {
+			(Some(settings), Some(pool), Some(events)) => (settings.clone(), (**pool).clone(), events.clone()),
+			_ => {
+				error!("Session timeouts need the settings, database and events to be staged");
+				return;
+			}
+		};
+		let mut shutdown = rocket.shutdown();
+		rocket::tokio::spawn(async move {
+			let mut ticks = interval(SWEEP_INTERVAL);
+			loop {
+				rocket::tokio::select! {
+					_ = ticks.tick() => {
+						if let Err(e) = close_idle_sessions(&pool, &events, settings.current().session_timeout_minutes).await {
+							error!("Unable to close idle sessions: {}", e);
+						}
+					},
+					_ = &mut shutdown => break,
+				}
+			}
+		});
+	}))
+}
+
+#[cfg(test)]
+mod tests {
+	use chrono::Utc;
+	use rocket::http::ContentType;
+	use rocket::serde::json::Value;
+	use rocket_db_pools::{sqlx, Database};
+	use crate::{db, events};
+
+	#[rocket::async_test]
+	async fn idle_sessions_are_closed()
+	{
+		let client = crate::testing::async_client().await;
+		for table_nr in [1, 2] {
+			client.post(format!("/api/v1/tables/{}", table_nr)).header(ContentType::JSON).body(r#"{"customers":2}"#).dispatch().await;
+		}
+		let pool = db::Db::fetch(client.rocket()).expect("database");
+		let started = db::timestamp(Utc::now() - chrono::Duration::hours(5));
+		sqlx::query("UPDATE TableSessions SET session_start = $1").bind(&started).execute(&**pool).await.unwrap();
+		client.post("/api/v1/tables/2/orders").header(ContentType::JSON).body(r#"{"order_items":[{"item_id":1,"amount":1}]}"#).dispatch().await;
+
+		let events = client.rocket().state::<events::Events>().expect("events");
+		let (_, mut receiver) = events.subscribe(None);
+		assert_eq!(super::close_idle_sessions(pool, events, 0).await.unwrap(), 0);
+		assert_eq!(super::close_idle_sessions(pool, events, 240).await.unwrap(), 1);
+		assert_eq!(receiver.try_recv().unwrap().table_nr, 1);
+
+		let active: Vec<Value> = client.get("/api/v1/tables").dispatch().await.into_json().await.expect("TableSessions");
+		assert_eq!(active.iter().map(|session| session["table_nr"].as_u64().unwrap()).collect::<Vec<u64>>(), vec![2]);
+	}
+}
@@ -0,0 +1,280 @@
+use std::time::Duration;
+use hmac::{Hmac, Mac};
+use hyper::client::HttpConnector;
+use hyper::{Body, Client, Request, Uri};
+use hyper_rustls::HttpsConnector;
+use log::{error, warn};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use rocket::fairing::AdHoc;
+use rocket::serde::Deserialize;
+use rocket::tokio::sync::broadcast::error::RecvError;
+use rocket::tokio::time::{sleep, timeout};
+use rocket_db_pools::Database;
+use sha2::Sha256;
+use crate::i18n::{message, Message};
+use crate::{db, events, models};
+
+/// Event types webhooks can subscribe to, as in the `type` of `GET /events`.
+pub const EVENT_TYPES: [&str; 9] = [
+	"session_opened",
+	"session_closed",
+	"order_created",
+	"order_updated",
+	"order_voided",
+	"kitchen_item_status_changed",
+	"guest_order_pending",
+	"signal_raised",
+	"signal_acknowledged",
+];
+
+/// The `[default.webhooks]` table of `Rocket.toml`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(crate="rocket::serde", default)]
+pub struct WebhookConfig {
+	/// Deliveries tried before a payload goes to the dead letters.
+	pub max_attempts: u32,
+	/// Wait before the first retry, doubled for every retry after it.
+	pub retry_base_ms: u64,
+	pub timeout_secs: u64
+}
+
+impl Default for WebhookConfig {
+	fn default() -> Self {
+		WebhookConfig { max_attempts: 5, retry_base_ms: 1000, timeout_secs: 10 }
+	}
+}
+
+impl WebhookConfig {
+	/// How long to wait after a failed `attempt` (counting from 1).
+	pub fn backoff(&self, attempt: u32) -> Duration {
+		Duration::from_millis(self.retry_base_ms.saturating_mul(1 << attempt.saturating_sub(1).min(16)))
+	}
+}
+
+/// Managed state delivering webhook payloads.
+#[derive(Clone)]
+pub struct Webhooks {
+	config: WebhookConfig,
+	client: Client<HttpsConnector<HttpConnector>>
+}
+
+/// Secrets are generated for webhooks registered without one.
+pub fn generate_secret() -> String {
+	rand::thread_rng().sample_iter(&Alphanumeric).take(32).map(char::from).collect()
+}
+
+/// Identifies a payload to its receiver across retries, as `X-Webhook-Id`.
+pub fn generate_delivery_id() -> String {
+	hex::encode(rand::random::<[u8; 16]>())
+}
+
+/// Webhook URLs are `https`, checked against the Mozilla root certificates,
+/// or plain `http` for receivers on a trusted network.
+pub fn validate(webhook: &models::Webhook) -> Result<(), Message> {
+	match webhook.url.parse::<Uri>() {
+		Ok(uri) if matches!(uri.scheme_str(), Some("http" | "https")) && uri.host().is_some() => (),
+		_ => return Err(message!("Webhook URL '{}' must be an http:// or https:// URL", webhook.url)),
+	}
+	if webhook.events.is_empty() {
+		return Err(message!("A webhook must subscribe to at least one event"));
+	}
+	match webhook.events.iter().find(|event| *event != "*" && !EVENT_TYPES.contains(&event.as_str())) {
+		Some(event) => Err(message!("Unknown event type '{}'", event)),
+		None => Ok(()),
+	}
+}
+
+/// The `X-Webhook-Signature` header: `sha256=` and the hex HMAC-SHA256 of the
+/// body, keyed with the webhook's secret.
+pub fn signature(secret: &str, body: &str) -> String {
+	let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+	mac.update(body.as_bytes());
+	format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+impl Webhooks {
+	pub fn new(config: WebhookConfig) -> Self {
+		Webhooks { config, client: Client::builder().build(HttpsConnector::with_webpki_roots()) }
+	}
+
+	async fn post(&self, webhook: &models::Webhook, delivery_id: &str, event_type: &str, payload: &str) -> Result<u16, (Option<u16>, String)> {
+		let request = Request::post(webhook.url.as_str())
+			.header("Content-Type", "application/json")
+			.header("User-Agent", "rust_restaurant_api")
+			.header("X-Webhook-Event", event_type)
+			.header("X-Webhook-Id", delivery_id)
+			.header("X-Webhook-Signature", signature(webhook.secret.as_deref().unwrap_or_default(), payload))
+			.body(Body::from(payload.to_string()))
+			.map_err(|e| (None, e.to_string()))?;
+		match timeout(Duration::from_secs(self.config.timeout_secs), self.client.request(request)).await {
+			Ok(Ok(response)) if response.status().is_success() => Ok(response.status().as_u16()),
+			Ok(Ok(response)) => Err((Some(response.status().as_u16()), format!("Receiver responded {}", response.status()))),
+			Ok(Err(e)) => Err((None, e.to_string())),
+			Err(_) => Err((None, String::from("Timed out"))),
+		}
+	}
+
+	/// Posts the payload until the receiver accepts it, waiting longer after
+	/// each failure. Every attempt is logged; when all of them fail the payload
+	/// is kept as a dead letter.
+	pub async fn deliver(&self, pool: &db::DbPool, webhook: &models::Webhook, delivery_id: &str, event_id: i64, event_type: &str, payload: &str) {
+		let mut last_error = String::new();
+		for attempt in 1..=self.config.max_attempts.max(1) {
+			let result = self.post(webhook, delivery_id, event_type, payload).await;
+			let delivery = models::WebhookDelivery {
+				id: 0,
+				webhook_id: webhook.id,
+				delivery_id: String::from(delivery_id),
+				event_id,
+				event_type: String::from(event_type),
+				attempt,
+				status_code: match &result {
+					Ok(status_code) => Some(*status_code),
+					Err((status_code, _)) => *status_code,
+				},
+				error: result.as_ref().err().map(|(_, e)| e.clone()),
+				succeeded: result.is_ok(),
+				timestamp: String::new()
+			};
+			if let Err(e) = db::create_webhook_delivery(pool, &delivery).await {
+				error!("Unable to log webhook delivery: {}", e);
+			}
+			match result {
+				Ok(_) => return,
+				Err((_, e)) => {
+					warn!("Webhook {} delivery {} of event {} failed: {}", webhook.id, attempt, event_id, e);
+					last_error = e;
+				}
+			}
+			if attempt < self.config.max_attempts {
+				sleep(self.config.backoff(attempt)).await;
+			}
+		}
+		let dead_letter = models::DeadLetter {
+			id: 0,
+			webhook_id: webhook.id,
+			delivery_id: String::from(delivery_id),
+			event_id,
+			event_type: String::from(event_type),
+			payload: String::from(payload),
+			attempts: self.config.max_attempts.max(1),
+			last_error,
+			timestamp: String::new()
+		};
+		if let Err(e) = db::create_dead_letter(pool, &dead_letter).await {
+			error!("Unable to keep undelivered webhook payload: {}", e);
+		}
+	}
+
+	async fn notify(&self, pool: &db::DbPool, event: models::Event) {
+		let payload = match rocket::serde::json::to_string(&event) {
+			Ok(payload) => payload,
+			Err(e) => {
+				error!("Unable to serialize event {}: {}", event.id, e);
+				return;
+			}
+		};
+		let event_type = rocket::serde::json::to_value(&event.data).ok()
+			.and_then(|data| data["type"].as_str().map(String::from))
+			.unwrap_or_default();
+		let webhooks = match db::retrieve_subscribed_webhooks(pool, event.location_id, &event_type).await {
+			Ok(webhooks) => webhooks.unwrap_or_default(),
+			Err(e) => {
+				error!("Unable to get webhooks for event {}: {}", event.id, e);
+				return;
+			}
+		};
+		for webhook in webhooks {
+			let (this, pool, event_type, payload) = (self.clone(), pool.clone(), event_type.clone(), payload.clone());
+			rocket::tokio::spawn(async move {
+				this.deliver(&pool, &webhook, &generate_delivery_id(), event.id as i64, &event_type, &payload).await;
+			});
+		}
+	}
+}
+
+/// Notifies webhooks of every event published from the handlers, once the
+/// server has launched.
+pub fn stage() -> AdHoc {
+	AdHoc::on_ignite("Webhooks", |rocket| async {
+		let config = rocket.figment().extract_inner::<WebhookConfig>("webhooks").unwrap_or_default();
+		rocket.manage(Webhooks::new(config))
+		.attach(AdHoc::on_liftoff("Webhook deliveries", |rocket| Box::pin(async move {
+			let (webhooks, pool, events) = match (rocket.state::<Webhooks>(), db::Db::fetch(rocket), rocket.state::<events::Events>()) {
+				(Some(webhooks), Some(pool), Some(events)) => (webhooks.clone(), (**pool).clone(), events),
+				_ => {
+					error!("Webhooks need the database and events to be staged");
+					return;
+				}
+			};
+			let events = events.clone();
+			let (subscribed, mut receiver) = events.subscribe(None);
+			let mut shutdown = rocket.shutdown();
+			rocket::tokio::spawn(async move {
+				let mut last_event_id = subscribed.latest;
+				loop {
+					rocket::tokio::select! {
+						event = receiver.recv() => match event {
+							Ok(event) => {
+								last_event_id = event.id;
+								webhooks.notify(&pool, event).await;
+							},
+							// Deliveries fell behind, so the events they missed are
+							// taken from the history instead.
+							Err(RecvError::Lagged(lagged)) => {
+								let (missed, resubscribed) = events.subscribe(Some(last_event_id));
+								if missed.reset {
+									error!("Webhooks missed events after {} that are no longer kept", last_event_id);
+								}
+								else {
+									warn!("Webhooks fell {} events behind, catching up", lagged);
+								}
+								receiver = resubscribed;
+								for event in missed.events {
+									last_event_id = event.id;
+									webhooks.notify(&pool, event).await;
+								}
+							},
+							Err(RecvError::Closed) => break,
+						},
+						_ = &mut shutdown => break,
+					}
+				}
+			});
+		})))
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+	use super::WebhookConfig;
+	use crate::models::Webhook;
+
+	#[test]
+	fn backoff_doubles()
+	{
+		let config = WebhookConfig { retry_base_ms: 500, ..Default::default() };
+		assert_eq!(config.backoff(1), Duration::from_millis(500));
+		assert_eq!(config.backoff(3), Duration::from_millis(2000));
+	}
+
+	#[test]
+	fn webhooks_are_validated()
+	{
+		let webhook = |url: &str, events: &[&str]| Webhook {
+			id: 0,
+			url: String::from(url),
+			secret: None,
+			events: events.iter().map(|event| String::from(*event)).collect(),
+			location_id: 1
+		};
+		assert!(super::validate(&webhook("http://localhost:8080/hook", &["order_created"])).is_ok());
+		assert!(super::validate(&webhook("http://localhost:8080/hook", &["*"])).is_ok());
+		assert!(super::validate(&webhook("https://loyalty.example.com/hook", &["order_created"])).is_ok());
+		assert!(super::validate(&webhook("ftp://localhost/hook", &["order_created"])).is_err());
+		assert!(super::validate(&webhook("http://localhost:8080/hook", &["order_eaten"])).is_err());
+		assert!(super::validate(&webhook("http://localhost:8080/hook", &[])).is_err());
+	}
+}